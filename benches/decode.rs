@@ -0,0 +1,80 @@
+//! Decoding JPEGs in full versus at a reduced scale.
+//!
+//! Runs on every JPEG in the directory `SUCCOTASH_BENCH_DIR` points to,
+//! or on a generated photo-sized JPEG if it isn't set:
+//!
+//! ```console
+//! $ SUCCOTASH_BENCH_DIR=~/Pictures cargo bench --bench decode
+//! ```
+//!
+//! Without `--bench` (e.g. under `cargo test`), every image is decoded once.
+
+use libsuccotash::analyze::img::decode_jpeg_scaled;
+use std::time::{Duration, Instant};
+
+/// Times decoding every image, in seconds per image.
+fn time(images: &[Vec<u8>], rounds: u32, decode: impl Fn(&[u8])) -> f64 {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        for image in images {
+            decode(image);
+        }
+    }
+    let elapsed: Duration = start.elapsed();
+    elapsed.as_secs_f64() / f64::from(rounds) / images.len() as f64
+}
+
+/// JPEGs in a directory, not recursively.
+fn read_dir(dir: &str) -> Vec<Vec<u8>> {
+    std::fs::read_dir(dir)
+        .expect("SUCCOTASH_BENCH_DIR is a directory")
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+                .unwrap_or(false)
+        })
+        .filter_map(|path| std::fs::read(path).ok())
+        .collect()
+}
+
+/// A 24 megapixel JPEG with some detail in it.
+fn generate() -> Vec<u8> {
+    let photo = image::RgbImage::from_fn(6000, 4000, |x, y| {
+        let level = ((x ^ y) % 256) as u8;
+        image::Rgb([level, (x / 24) as u8, (y / 16) as u8])
+    });
+    let mut jpeg = Vec::new();
+    image::DynamicImage::ImageRgb8(photo)
+        .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90))
+        .unwrap();
+    jpeg
+}
+
+fn main() {
+    let bench = std::env::args().any(|arg| arg == "--bench");
+    let images = match std::env::var("SUCCOTASH_BENCH_DIR") {
+        Ok(dir) => read_dir(&dir),
+        Err(_) => vec![generate()],
+    };
+    if images.is_empty() {
+        eprintln!("No JPEGs to decode");
+        return;
+    }
+    let rounds = if bench { 5 } else { 1 };
+
+    let full = time(&images, rounds, |data| {
+        image::load_from_memory_with_format(data, image::ImageFormat::Jpeg).unwrap();
+    });
+    let scaled = time(&images, rounds, |data| {
+        decode_jpeg_scaled(data).unwrap();
+    });
+    println!(
+        "{} JPEGs: full {:.1} ms, scaled {:.1} ms per image, {:.1}x faster",
+        images.len(),
+        full * 1000.0,
+        scaled * 1000.0,
+        full / scaled
+    );
+}
@@ -0,0 +1,85 @@
+//! Finding features on one thread versus on every core.
+//!
+//! Runs on the files `SUCCOTASH_BENCH_DIR` points to, not recursively,
+//! or on generated JPEGs if it isn't set:
+//!
+//! ```console
+//! $ SUCCOTASH_BENCH_DIR=~/Pictures cargo bench --bench parallel
+//! ```
+//!
+//! Without `--bench` (e.g. under `cargo test`), only one thread count is run.
+
+use async_std::path::PathBuf;
+use libsuccotash::analyze::parallel;
+use libsuccotash::analyze::progress::{Progress, Stage};
+use std::time::Instant;
+
+/// Files in a directory, not recursively.
+fn read_dir(dir: &str) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .expect("SUCCOTASH_BENCH_DIR is a directory")
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Photo-sized JPEGs in a temporary directory.
+fn generate(dir: &std::path::Path, count: u32) -> Vec<PathBuf> {
+    std::fs::create_dir_all(dir).unwrap();
+    (0..count)
+        .map(|index| {
+            let photo = image::RgbImage::from_fn(3000, 2000, |x, y| {
+                let level = ((x ^ y).wrapping_add(index * 37) % 256) as u8;
+                image::Rgb([level, (x / 12) as u8, (y / 8) as u8])
+            });
+            let path = dir.join(format!("{}.jpg", index));
+            photo.save(&path).unwrap();
+            PathBuf::from(path)
+        })
+        .collect()
+}
+
+fn main() {
+    let bench = std::env::args().any(|arg| arg == "--bench");
+    let generated = std::env::temp_dir().join(format!("succotash-bench-{}", std::process::id()));
+    let paths = match std::env::var("SUCCOTASH_BENCH_DIR") {
+        Ok(dir) => read_dir(&dir),
+        Err(_) => generate(&generated, if bench { 32 } else { 2 }),
+    };
+
+    let cores = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut thread_counts = vec![1];
+    if bench {
+        thread_counts.extend((1..).map(|i| 1 << i).take_while(|&n| n < cores));
+        thread_counts.push(cores);
+        thread_counts.dedup();
+    }
+
+    let mut single = None;
+    for threads in thread_counts {
+        let started = Instant::now();
+        let outcomes = async_std::task::block_on(parallel::find_all(
+            paths.clone(),
+            threads,
+            Default::default(),
+            None,
+            &Default::default(),
+            Progress::default().stage(Stage::Features, None),
+            Default::default(),
+        ))
+        .unwrap();
+        let elapsed = started.elapsed().as_secs_f64();
+        let single = *single.get_or_insert(elapsed);
+        println!(
+            "{} files on {} threads: {:.2} s, {:.1}x of one thread",
+            outcomes.len(),
+            threads,
+            elapsed,
+            single / elapsed
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(&generated);
+}
@@ -0,0 +1,102 @@
+//! Finding candidates for similar images by brute force, in a VP-tree, in bands
+//! and in an HNSW graph.
+//!
+//! Runs [`group::candidates`] on pseudo-random hashes of datasets of growing
+//! size, so that the [`Search`] that suits a library can be picked:
+//!
+//! ```console
+//! $ cargo bench --bench search
+//! ```
+//!
+//! Without `--bench` (e.g. under `cargo test`), only the smallest dataset is searched.
+
+use async_std::path::PathBuf;
+use libsuccotash::analyze::cancel::CancelToken;
+use libsuccotash::analyze::features::{set::FeatureSet, ImgFeatures};
+use libsuccotash::analyze::group;
+use libsuccotash::analyze::img::Img;
+use libsuccotash::analyze::index::{bands, Search};
+use std::time::Instant;
+
+/// Pseudo-random hashes, in clusters of near-duplicates like a real library has.
+fn generate(count: usize) -> Vec<u64> {
+    let mut state = 0x2545f4914f6cdd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut hashes = Vec::with_capacity(count);
+    while hashes.len() < count {
+        let original = next();
+        hashes.push(original);
+        if next() % 4 == 0 {
+            // A near-duplicate, a few bits off.
+            hashes.push(original ^ (1 << (next() % 64)) ^ (1 << (next() % 64)));
+        }
+    }
+    hashes.truncate(count);
+    hashes
+}
+
+/// Images with the hashes and otherwise the same features.
+fn imgs(hashes: &[u64]) -> Vec<Img<PathBuf>> {
+    hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| Img {
+            path: PathBuf::from(format!("/{}.jpg", i)),
+            features: ImgFeatures::decode(&format!("{:016x} 0 0 0.5 0.2", hash)).unwrap(),
+        })
+        .collect()
+}
+
+/// Candidates found with a search, and the seconds it took.
+fn time(imgs: &[Img<PathBuf>], search: Search) -> (usize, f64) {
+    let start = Instant::now();
+    let pairs = group::candidates(imgs, FeatureSet::standard(), search, &CancelToken::new());
+    (pairs.len(), start.elapsed().as_secs_f64())
+}
+
+fn main() {
+    let bench = std::env::args().any(|arg| arg == "--bench");
+    let sizes: &[usize] = if bench {
+        &[1_000, 5_000, 20_000]
+    } else {
+        &[1_000]
+    };
+
+    for &size in sizes {
+        let imgs = imgs(&generate(size));
+        let (brute_pairs, brute_time) = time(&imgs, Search::Brute);
+        println!(
+            "{} images, {} pairs: brute force {:.1} ms",
+            size,
+            brute_pairs,
+            brute_time * 1000.
+        );
+        for search in [Search::VpTree, Search::Bands(bands::DEFAULT_BANDS)] {
+            let (pairs, time) = time(&imgs, search);
+            assert_eq!(pairs, brute_pairs, "{} finds the same pairs", search.name());
+            println!(
+                "    {} {:.1} ms, {:.2}x faster",
+                search.name(),
+                time * 1000.,
+                brute_time / time
+            );
+        }
+        // The graph is approximate, it may miss a few pairs.
+        let (pairs, time) = time(&imgs, Search::Hnsw);
+        assert!(
+            pairs <= brute_pairs,
+            "hnsw finds no pairs brute force doesn't"
+        );
+        println!(
+            "    hnsw {:.1} ms, {:.2}x faster, {} of the pairs",
+            time * 1000.,
+            brute_time / time,
+            pairs
+        );
+    }
+}
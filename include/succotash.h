@@ -0,0 +1,49 @@
+/*
+ * C interface of succotash, see src/ffi.rs.
+ *
+ * Build the library with the `ffi` feature:
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * Hashes are the same as `succotash hash` prints with default options.
+ *
+ * Generated with cbindgen, regenerate it with
+ *
+ *     SUCCOTASH_UPDATE_HEADER=1 cargo test --features ffi --test ffi
+ */
+
+#ifndef SUCCOTASH_H
+#define SUCCOTASH_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The hash was found.
+#define SUCCOTASH_OK 0
+
+// A pointer was null or the size of a buffer was wrong.
+#define SUCCOTASH_INVALID_ARGUMENT 1
+
+// The image couldn't be decoded or hashed.
+#define SUCCOTASH_FAILED 2
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+extern const uint32_t SUCCOTASH_MAX_DISTANCE;
+
+// Hash an image given as pixels, 3 bytes per pixel in RGB order, row after row.
+int succotash_hash_rgb8(const uint8_t *pixels, uint32_t width, uint32_t height, uint64_t *hash);
+
+// Hash an image given as contents of its file, e.g. a JPEG.
+int succotash_hash_file_contents(const uint8_t *data, size_t len, uint64_t *hash);
+
+// How many bits two hashes differ in.
+uint32_t succotash_distance(uint64_t a, uint64_t b);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* SUCCOTASH_H */
@@ -0,0 +1,430 @@
+//! Acting on a [`Plan`], rather than only writing it down.
+//!
+//! An [`Action`] is applied to every file of a group planned for deletion,
+//! against the files of the group that are kept. Actions are careful:
+//! a file they can't act on safely is left alone and reported as skipped,
+//! with the reason, see [`Outcome`]. Links can't cross filesystems, so files
+//! whose kept files are all on other filesystems are left alone and reported
+//! as such, see [`Outcome::Elsewhere`]. Deleted files can go to the trash of
+//! the system instead of being gone for good, see [`ActionOptions::trash`],
+//! and everything done can be recorded in a [`Journal`] to be undone.
+
+use super::group::Group;
+use super::plan::Plan;
+use crate::journal::{Journal, Op};
+use async_std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How many bytes of two files are compared at a time.
+const CHUNK: usize = 1 << 16;
+
+/// What to do with files planned for deletion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// Replace a file with a reflink to a kept file, a copy sharing its
+    /// blocks until either is changed, on filesystems that have them,
+    /// e.g. btrfs and XFS. Only files that are the same byte for byte as
+    /// a kept file on the same filesystem are replaced, keeping their
+    /// permissions, owner and modification time. Linux only.
+    Reflink,
+    /// Replace a file with a hard link to a kept file, another name for
+    /// the same data. Only files that are the same byte for byte as a kept
+    /// file on the same filesystem are replaced, and they take the
+    /// permissions, owner and modification time of the kept file.
+    Hardlink,
+    /// Delete a file, as long as a kept file of its group, other than
+    /// the file itself, is still there.
+    Delete,
+}
+
+impl Action {
+    /// Name of the action, as `--action` takes it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Reflink => "reflink",
+            Self::Hardlink => "hardlink",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reflink" => Ok(Self::Reflink),
+            "hardlink" => Ok(Self::Hardlink),
+            "delete" => Ok(Self::Delete),
+            _ => Err(format!("unknown action '{}'", s)),
+        }
+    }
+}
+
+/// How actions are done.
+#[derive(Clone, Debug, Default)]
+pub struct ActionOptions {
+    /// Whether [deleted](Action::Delete) files are moved to the trash
+    /// (the recycle bin on Windows), where they can be restored from,
+    /// rather than deleted for good.
+    pub trash: bool,
+    /// Where to record what is done to be undone, see [`journal`](crate::journal).
+    pub journal: Option<std::path::PathBuf>,
+}
+
+/// What an action did to a file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// Done, against this kept file.
+    Done(PathBuf),
+    /// The file was left alone, for this reason.
+    Skipped(String),
+    /// The file was left alone, as it is on another filesystem than this
+    /// kept file, and than every other kept file of its groups.
+    Elsewhere(PathBuf),
+    /// The action failed, the file is as it was.
+    Failed(String),
+}
+
+/// What an action did, see [`apply`].
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Files planned for deletion and what was done to them, ordered by path.
+    pub outcomes: Vec<(PathBuf, Outcome)>,
+    /// Bytes of the files the action was done to, which they no longer take.
+    /// Files moved to the trash take them until it is emptied.
+    pub reclaimed: u64,
+}
+
+impl Report {
+    /// How many files the action was done to.
+    pub fn done(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Done(_)))
+    }
+
+    /// How many files the action failed on.
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Failed(_)))
+    }
+
+    /// How many files were left alone for being on other filesystems than their kept files.
+    pub fn elsewhere(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Elsewhere(_)))
+    }
+
+    fn count(&self, f: impl Fn(&Outcome) -> bool) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| f(outcome))
+            .count()
+    }
+}
+
+/// Files planned for deletion, with the kept files of the groups they are in.
+fn targets(groups: &[Group], plan: &Plan) -> BTreeMap<PathBuf, Vec<PathBuf>> {
+    let mut targets: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for group in groups {
+        let files = group.files();
+        let kept: Vec<PathBuf> = files
+            .iter()
+            .filter(|img| plan.keep.binary_search(&img.path).is_ok())
+            .map(|img| img.path.clone())
+            .collect();
+        for img in files {
+            if plan.delete.binary_search(&img.path).is_ok() {
+                targets
+                    .entry(img.path.clone())
+                    .or_default()
+                    .extend(kept.iter().cloned());
+            }
+        }
+    }
+    targets
+}
+
+/// Apply an action to the files a plan deletes.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+///
+/// # Arguments
+///
+/// * `action` - what to do.
+/// * `groups` - groups of similar images the plan was made for.
+/// * `plan` - what to keep and what to delete, see [`Plan`].
+/// * `options` - how to do it.
+pub fn apply(action: Action, groups: &[Group], plan: &Plan, options: &ActionOptions) -> Report {
+    let mut report = Report::default();
+    let mut journal = Journal::new(options.journal.clone());
+    for (path, kept) in targets(groups, plan) {
+        let size = std::fs::symlink_metadata(&path).map_or(0, |m| m.len());
+        let outcome = match action {
+            Action::Reflink | Action::Hardlink => {
+                link_to_any(action, path.as_ref(), &kept, &mut journal)
+            }
+            Action::Delete => delete(path.as_ref(), &kept, options.trash, &mut journal),
+        };
+        match &outcome {
+            Outcome::Done(source) => {
+                debug!(
+                    "Applied '{}' to '{}', keeping '{}'",
+                    action.name(),
+                    path.to_string_lossy(),
+                    source.to_string_lossy()
+                );
+                if !(action == Action::Delete && options.trash) {
+                    report.reclaimed += size;
+                }
+            }
+            Outcome::Skipped(reason) => {
+                debug!("Left '{}' alone: {}", path.to_string_lossy(), reason)
+            }
+            Outcome::Elsewhere(source) => debug!(
+                "Left '{}' alone: it is on another filesystem than '{}'",
+                path.to_string_lossy(),
+                source.to_string_lossy()
+            ),
+            Outcome::Failed(e) => error!("Couldn't act on '{}': {}", path.to_string_lossy(), e),
+        }
+        report.outcomes.push((path, outcome));
+    }
+    journal.close();
+    report
+}
+
+/// Whether two files have the same contents, read side by side.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::action;
+/// let dir = std::env::temp_dir().join("succotash-action-identical");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("a"), b"same").unwrap();
+/// std::fs::write(dir.join("b"), b"same").unwrap();
+/// std::fs::write(dir.join("c"), b"sane").unwrap();
+/// assert!(action::identical(&dir.join("a"), &dir.join("b")).unwrap());
+/// assert!(!action::identical(&dir.join("a"), &dir.join("c")).unwrap());
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn identical(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut chunk_a, mut chunk_b) = (vec![0; CHUNK], vec![0; CHUNK]);
+    loop {
+        let read = a.read(&mut chunk_a)?;
+        if read == 0 {
+            // Sizes are the same, unless one has changed meanwhile.
+            return Ok(b.read(&mut chunk_b[..1])? == 0);
+        }
+        b.read_exact(&mut chunk_b[..read])?;
+        if chunk_a[..read] != chunk_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// The filesystem a file is on, its device ID, if the system tells.
+#[cfg(unix)]
+pub fn device(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+/// The filesystem a file is on, its device ID, if the system tells.
+#[cfg(not(unix))]
+pub fn device(_: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Whether two files are on the same filesystem, and whether they are the same file.
+///
+/// Files are on the same filesystem unless their devices are known to differ.
+#[cfg(unix)]
+fn placement(a: &std::fs::Metadata, b: &std::fs::Metadata) -> (bool, bool) {
+    use std::os::unix::fs::MetadataExt;
+    let same_filesystem = device(a) == device(b);
+    (same_filesystem, same_filesystem && a.ino() == b.ino())
+}
+
+/// Whether two files are on the same filesystem, and whether they are the same file.
+///
+/// Files are on the same filesystem unless their devices are known to differ.
+#[cfg(not(unix))]
+fn placement(_: &std::fs::Metadata, _: &std::fs::Metadata) -> (bool, bool) {
+    (true, false)
+}
+
+/// Replace a file with a reflink or a hard link, as `action` says, to the
+/// first of `kept` on its filesystem that is the same byte for byte.
+///
+/// Kept files on other filesystems are passed over, and if there is no
+/// other, the file is left alone, see [`Outcome::Elsewhere`].
+fn link_to_any(action: Action, path: &Path, kept: &[PathBuf], journal: &mut Journal) -> Outcome {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    if !metadata.is_file() {
+        return Outcome::Skipped("is not a regular file".to_owned());
+    }
+    let mut elsewhere = None;
+    let mut reason = None;
+    for source in kept {
+        let source_metadata = match std::fs::metadata(source) {
+            Ok(metadata) => metadata,
+            Err(e) => return Outcome::Failed(format!("'{}' {}", source.to_string_lossy(), e)),
+        };
+        let (same_filesystem, same_file) = placement(&metadata, &source_metadata);
+        if !same_filesystem {
+            elsewhere.get_or_insert(source);
+            continue;
+        }
+        if same_file {
+            reason = Some(format!(
+                "is the same file as '{}'",
+                source.to_string_lossy()
+            ));
+            continue;
+        }
+        match identical(source.as_ref(), path) {
+            Ok(true) => {}
+            Ok(false) => {
+                reason = Some(format!("is not the same as '{}'", source.to_string_lossy()));
+                continue;
+            }
+            Err(e) => return Outcome::Failed(e.to_string()),
+        }
+        let op = match action {
+            Action::Hardlink => Op::Hardlink,
+            _ => Op::Reflink,
+        };
+        if let Err(e) = journal.record(op, path, source.as_ref()) {
+            return Outcome::Failed(format!("couldn't write the journal: {}", e));
+        }
+        let linked = match action {
+            Action::Hardlink => hardlink(source.as_ref(), path),
+            _ => reflink(source.as_ref(), path),
+        };
+        return match linked {
+            Ok(()) => Outcome::Done(source.clone()),
+            // Another mount of the same filesystem, which links can't cross either.
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                Outcome::Elsewhere(source.clone())
+            }
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                Outcome::Skipped("the filesystem doesn't support reflinks".to_owned())
+            }
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+    }
+    match (reason, elsewhere) {
+        (Some(reason), _) => Outcome::Skipped(reason),
+        (None, Some(source)) => Outcome::Elsewhere(source.clone()),
+        (None, None) => Outcome::Skipped("has no kept file in its groups".to_owned()),
+    }
+}
+
+/// Delete a file, or move it to the trash, if the first of `kept`
+/// that is still there is another file.
+fn delete(path: &Path, kept: &[PathBuf], trash: bool, journal: &mut Journal) -> Outcome {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    let Some((source, source_metadata)) = kept
+        .iter()
+        .find_map(|kept| Some((kept, std::fs::metadata(kept).ok()?)))
+    else {
+        return Outcome::Skipped("no kept file of its groups is there anymore".to_owned());
+    };
+    // E.g. the same file by another name on a case-insensitive filesystem.
+    if placement(&metadata, &source_metadata).1 {
+        let source = source.to_string_lossy();
+        return Outcome::Skipped(format!("is the same file as '{}'", source));
+    }
+    let op = if trash { Op::Trash } else { Op::Delete };
+    if let Err(e) = journal.record(op, path, source.as_ref()) {
+        return Outcome::Failed(format!("couldn't write the journal: {}", e));
+    }
+    let deleted = match trash {
+        true => trash::delete(path).map_err(|e| e.to_string()),
+        false => std::fs::remove_file(path).map_err(|e| e.to_string()),
+    };
+    match deleted {
+        Ok(()) => Outcome::Done(source.clone()),
+        Err(e) => Outcome::Failed(e),
+    }
+}
+
+/// Replace `target` with a reflink to `source`, keeping permissions,
+/// owner and modification time of `target`.
+///
+/// The reflink is made next to `target` and renamed over it, so `target`
+/// is never half replaced. Fails with [`io::ErrorKind::Unsupported`] if
+/// the filesystem has no reflinks, and with [`io::ErrorKind::CrossesDevices`]
+/// if the files are on different filesystems.
+#[cfg(target_os = "linux")]
+pub fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    let metadata = std::fs::metadata(target)?;
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = target.with_file_name(format!(".{}.succotash-reflink", name));
+    let from = File::open(source)?;
+    let to = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temporary)?;
+    let finished = (|| {
+        // SAFETY: both descriptors are open for as long as the call takes.
+        if unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) } != 0 {
+            let e = io::Error::last_os_error();
+            return match e.raw_os_error() {
+                Some(libc::EOPNOTSUPP | libc::ENOTTY | libc::EINVAL) => {
+                    Err(io::ErrorKind::Unsupported.into())
+                }
+                Some(libc::EXDEV) => Err(io::ErrorKind::CrossesDevices.into()),
+                _ => Err(e),
+            };
+        }
+        let created = to.metadata()?;
+        if (created.uid(), created.gid()) != (metadata.uid(), metadata.gid()) {
+            std::os::unix::fs::fchown(&to, Some(metadata.uid()), Some(metadata.gid()))?;
+        }
+        to.set_permissions(metadata.permissions())?;
+        to.set_modified(metadata.modified()?)?;
+        std::fs::rename(&temporary, target)
+    })();
+    if finished.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    finished
+}
+
+/// Replace `target` with a reflink to `source`, Linux only.
+#[cfg(not(target_os = "linux"))]
+pub fn reflink(_: &Path, _: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Replace `target` with a hard link to `source`.
+///
+/// The link is made next to `target` and renamed over it, so `target` is
+/// never half replaced. Fails with [`io::ErrorKind::CrossesDevices`] if the
+/// files are on different filesystems.
+pub fn hardlink(source: &Path, target: &Path) -> io::Result<()> {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = target.with_file_name(format!(".{}.succotash-hardlink", name));
+    std::fs::hard_link(source, &temporary)?;
+    let renamed = std::fs::rename(&temporary, target);
+    if renamed.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    renamed
+}
@@ -0,0 +1,125 @@
+//! Images inside ZIP and TAR archives.
+//!
+//! Only available with the `archives` feature, and only walked into with
+//! [`WalkOptions::archives`](super::walker::WalkOptions::archives). An image
+//! inside an archive gets a virtual path: the path of the archive, [`SEPARATOR`]
+//! and the path inside it, e.g. `comics/issue-1.cbz!/pages/001.png`. Such paths
+//! are analyzed, saved to the index and reported like any other, but their
+//! contents are read from the archive into memory, see [`read`]. Nothing is
+//! ever written into an archive, e.g. sidecars of images inside them.
+
+use async_std::path::{Path, PathBuf};
+use std::io::Read;
+
+/// File extensions of supported archives, lowercase. CBZ is a ZIP of comic pages.
+pub const EXTENSIONS: &[&str] = &["zip", "cbz", "tar"];
+
+/// Separates the path of an archive from the path inside it.
+pub const SEPARATOR: &str = "!/";
+
+/// Whether a file looks like an archive, judging by its extension.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::archive;
+/// assert!(archive::is_archive("/home/user/Comics/issue-1.CBZ".as_ref()));
+/// assert!(!archive::is_archive("/home/user/Pictures/cat.png".as_ref()));
+/// ```
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Virtual path of an entry of an archive.
+pub fn join(archive: &Path, entry: &str) -> PathBuf {
+    format!("{}{}{}", archive.to_string_lossy(), SEPARATOR, entry).into()
+}
+
+/// The archive and the path inside it of a virtual path, `None` for other paths.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::archive;
+/// let path = archive::join("/home/user/Comics/issue-1.cbz".as_ref(), "pages/001.png");
+/// assert_eq!(path.to_string_lossy(), "/home/user/Comics/issue-1.cbz!/pages/001.png");
+/// let (archive, entry) = archive::split(&path).unwrap();
+/// assert_eq!(archive, async_std::path::PathBuf::from("/home/user/Comics/issue-1.cbz"));
+/// assert_eq!(entry, "pages/001.png");
+/// assert_eq!(archive::split("/home/user/Pictures/wow!/cat.png".as_ref()), None);
+/// ```
+pub fn split(path: &Path) -> Option<(PathBuf, String)> {
+    let path = path.to_str()?;
+    // A directory may be named with a `!` too, the archive is the first one with an extension of one.
+    path.match_indices(SEPARATOR).find_map(|(i, _)| {
+        let archive = Path::new(&path[..i]);
+        is_archive(archive).then(|| (archive.into(), path[i + SEPARATOR.len()..].to_owned()))
+    })
+}
+
+/// Whether a path is of an image inside an archive, see [`split`].
+pub fn is_entry(path: &Path) -> bool {
+    split(path).is_some()
+}
+
+/// Whether an entry looks like an image, judging by its extension.
+fn is_image(entry: &str) -> bool {
+    image::ImageFormat::from_path(entry).is_ok()
+}
+
+/// Whether an archive is a TAR, rather than a ZIP.
+fn is_tar(archive: &Path) -> bool {
+    archive
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("tar"))
+}
+
+/// Paths inside an archive of the images in it, sorted.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+pub fn entries(archive: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(archive)?;
+    let mut entries = Vec::new();
+    if is_tar(archive) {
+        for entry in tar::Archive::new(file).entries()? {
+            let entry = entry?;
+            if entry.header().entry_type().is_file() {
+                entries.push(entry.path()?.to_string_lossy().into_owned());
+            }
+        }
+    } else {
+        let zip = zip::ZipArchive::new(file)?;
+        entries.extend(zip.file_names().map(str::to_owned));
+    }
+    entries.retain(|entry| !entry.ends_with('/') && is_image(entry));
+    entries.sort();
+    Ok(entries)
+}
+
+/// Read an entry of an archive into memory.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+///
+/// # Arguments
+///
+/// * `archive` - the archive.
+/// * `entry` - the path of the entry inside it, as [`entries`] gives it.
+pub fn read(archive: &Path, entry: &str) -> std::io::Result<Vec<u8>> {
+    let file = std::fs::File::open(archive)?;
+    let mut data = Vec::new();
+    if is_tar(archive) {
+        for tar_entry in tar::Archive::new(file).entries()? {
+            let mut tar_entry = tar_entry?;
+            if tar_entry.path()?.to_string_lossy() == entry {
+                tar_entry.read_to_end(&mut data)?;
+                return Ok(data);
+            }
+        }
+        return Err(std::io::ErrorKind::NotFound.into());
+    }
+    let mut zip = zip::ZipArchive::new(file)?;
+    zip.by_name(entry)?.read_to_end(&mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,66 @@
+//! Synchronous equivalents of the library API, with the `blocking` feature.
+//!
+//! Every function blocks the calling thread until its async counterpart is done,
+//! so programs that don't use async, e.g. GUI apps, can call the library
+//! without running an executor of their own.
+
+use super::index::query::Match;
+use super::walker::Source;
+use super::AnalyzeOptions;
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use async_std::task::block_on;
+
+/// Run the analysis on the given files, do not propagate errors, see [`analyze::run`](super::run).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::{blocking, walker::Source};
+/// let source = Source::Walk(vec!["/home/user/Pictures".to_owned()]);
+/// let status = blocking::run(source, Default::default());
+/// ```
+pub fn run(source: Source, options: AnalyzeOptions) -> Status {
+    block_on(super::run(source, options))
+}
+
+/// Save features of files in directories to an index, do not propagate errors,
+/// see [`index::build`](super::index::build).
+pub fn build(dirs: Vec<String>, options: AnalyzeOptions) -> Status {
+    block_on(super::index::build(dirs, options))
+}
+
+/// Find files in an index with images similar to those in a file,
+/// see [`index::try_query`](super::index::try_query).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::blocking;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let found = blocking::query(
+///     "/home/user/Downloads/sunset.jpg".into(),
+///     "/home/user/photos.index",
+///     &Default::default(),
+/// )?;
+/// for found in found {
+///     println!("{}\t{}", found.distance, found.path.to_string_lossy());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn query(
+    image: PathBuf,
+    index: &str,
+    options: &AnalyzeOptions,
+) -> Result<Vec<Match>, Box<dyn std::error::Error>> {
+    block_on(super::index::try_query(image, index, options))
+}
+
+/// Features of every image in a file, see [`index::features_of`](super::index::features_of).
+pub fn features_of(
+    image: &Path,
+    options: &AnalyzeOptions,
+) -> Result<Vec<super::features::ImgFeatures>, Box<dyn std::error::Error>> {
+    block_on(super::index::features_of(image.to_path_buf(), options))
+}
@@ -0,0 +1,81 @@
+//! Bursts of photos and the best shot of each.
+//!
+//! A camera in burst mode takes photos a fraction of a second apart, and
+//! usually one of them is worth keeping. With [`AnalyzeOptions::bursts`](super::AnalyzeOptions::bursts),
+//! photos are grouped into bursts by the time they were taken instead of
+//! being compared with all others, see [`group::bursts`](super::group::bursts),
+//! and photos of every burst are [ranked](rank): those well exposed first,
+//! the sharpest of them first. The best one is suggested to be kept.
+
+use super::group::Group;
+use super::img::Img;
+use async_std::path::PathBuf;
+use std::time::Duration;
+
+/// How long after the previous one a photo of a burst can be taken by default.
+pub const WINDOW: Duration = Duration::from_secs(2);
+
+/// How far from the previous one a photo of a burst can be taken, in meters,
+/// if both photos record where they were taken.
+pub const MAX_METERS: f64 = 100.;
+
+/// Photos darker than this on average are underexposed.
+pub const MIN_BRIGHTNESS: f64 = 0.15;
+
+/// Photos brighter than this on average are overexposed.
+pub const MAX_BRIGHTNESS: f64 = 0.85;
+
+/// Whether a photo is neither too dark nor too bright.
+pub fn well_exposed(img: &Img<PathBuf>) -> bool {
+    let brightness = img.features.brightness.mean();
+    (MIN_BRIGHTNESS..=MAX_BRIGHTNESS).contains(&brightness)
+}
+
+/// Photos of a burst, the best first.
+///
+/// Well exposed photos come before the rest, and sharper ones before blurrier
+/// ones. Photos equally good stay in the order of their paths.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{burst, features::ImgFeatures, group::Group, img::Img};
+/// let img = |path: &str, features| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode(features).unwrap(),
+/// };
+/// let group = Group {
+///     imgs: vec![
+///         img("/1.jpg", "00000000000000ff 0 40 0.5 0.2"),
+///         img("/2.jpg", "00000000000000ff 0 90 0.95 0.2"),
+///         img("/3.jpg", "00000000000000ff 0 70 0.5 0.2"),
+///     ],
+///     score: None,
+///     explanations: Vec::new(),
+/// };
+/// let ranked: Vec<String> = burst::rank(&group)
+///     .iter()
+///     .map(|img| img.path.to_string_lossy().into_owned())
+///     .collect();
+/// // The sharpest photo is overexposed.
+/// assert_eq!(ranked, vec!["/3.jpg", "/1.jpg", "/2.jpg"]);
+/// assert_eq!(burst::keeper(&group).path, async_std::path::PathBuf::from("/3.jpg"));
+/// ```
+pub fn rank(group: &Group) -> Vec<&Img<PathBuf>> {
+    let mut ranked = group.files();
+    ranked.sort_by(|a, b| {
+        well_exposed(b).cmp(&well_exposed(a)).then_with(|| {
+            let (a, b) = (
+                a.features.sharpness.variance(),
+                b.features.sharpness.variance(),
+            );
+            b.total_cmp(&a)
+        })
+    });
+    ranked
+}
+
+/// The photo of a burst to keep, the best one, see [`rank`].
+pub fn keeper(group: &Group) -> &Img<PathBuf> {
+    rank(group)[0]
+}
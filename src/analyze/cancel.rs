@@ -0,0 +1,48 @@
+//! Stopping an analysis early.
+//!
+//! Every stage of a cancelled analysis checks the token between steps: walking
+//! stops at the next directory, finding features at the next file and comparing
+//! at the next image. What was done is kept: the results are written and the
+//! [checkpoint](super::index::checkpoint) is synced, so that the run can be resumed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag shared by whoever cancels an analysis and the analysis itself.
+///
+/// Clones share the flag, cancelling one cancels them all.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::cancel::CancelToken;
+/// let token = CancelToken::new();
+/// let handle = token.clone();
+/// assert!(!token.is_cancelled());
+/// handle.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// A token that isn't cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the analysis to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the analysis was asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// The flag itself, e.g. to be set by a signal handler.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.0.clone()
+    }
+}
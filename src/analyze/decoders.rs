@@ -0,0 +1,136 @@
+//! Formats that not every build can decode.
+//!
+//! RAW files are only decoded with the `raw` feature and videos with the
+//! `video` one, GIF, TIFF and WebP with features of their own (on by default),
+//! and HEIC isn't decoded at all yet. Files in formats whose
+//! decoder isn't in this build are recognized by their extension and skipped
+//! before they are opened, with a reason that tells which feature would help,
+//! rather than failing with a generic decode error.
+
+use async_std::path::{Path, PathBuf};
+
+/// A decoder of a format that a build may lack.
+#[derive(Debug, PartialEq)]
+pub struct Decoder {
+    /// Name of the format.
+    pub format: &'static str,
+    /// File extensions of the format, lowercase.
+    pub extensions: &'static [&'static str],
+    /// The feature that enables the decoder, `None` if there is no decoder yet.
+    pub feature: Option<&'static str>,
+    /// Whether the decoder is in this build.
+    pub compiled: bool,
+}
+
+/// Camera RAW files, see [`raw`](super::raw).
+pub const RAW: Decoder = Decoder {
+    format: "RAW",
+    extensions: &["cr2", "nef", "arw", "dng"],
+    feature: Some("raw"),
+    compiled: cfg!(feature = "raw"),
+};
+
+/// Videos, see [`video`](super::video).
+pub const VIDEO: Decoder = Decoder {
+    format: "video",
+    extensions: &["mp4", "m4v", "mov", "avi", "mkv", "webm", "3gp"],
+    feature: Some("video"),
+    compiled: cfg!(feature = "video"),
+};
+
+/// GIF images, the first frame of animated ones.
+pub const GIF: Decoder = Decoder {
+    format: "GIF",
+    extensions: &["gif"],
+    feature: Some("gif"),
+    compiled: cfg!(feature = "gif"),
+};
+
+/// TIFF images.
+pub const TIFF: Decoder = Decoder {
+    format: "TIFF",
+    extensions: &["tif", "tiff"],
+    feature: Some("tiff"),
+    compiled: cfg!(feature = "tiff"),
+};
+
+/// WebP images.
+pub const WEBP: Decoder = Decoder {
+    format: "WebP",
+    extensions: &["webp"],
+    feature: Some("webp"),
+    compiled: cfg!(feature = "webp"),
+};
+
+/// HEIC and HEIF images, e.g. from iPhones.
+pub const HEIC: Decoder = Decoder {
+    format: "HEIC",
+    extensions: &["heic", "heif"],
+    feature: None,
+    compiled: false,
+};
+
+/// All decoders a build may lack.
+pub static OPTIONAL: [&Decoder; 6] = [&RAW, &VIDEO, &GIF, &TIFF, &WEBP, &HEIC];
+
+impl Decoder {
+    /// Whether a file is in this format, judging by its extension.
+    pub fn matches(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.extensions.contains(&e.to_ascii_lowercase().as_str()))
+    }
+
+    /// Why files in this format are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::decoders;
+    /// assert_eq!(decoders::RAW.reason(), "unsupported format (enable feature 'raw')");
+    /// assert_eq!(decoders::HEIC.reason(), "unsupported format (no decoder for HEIC yet)");
+    /// ```
+    pub fn reason(&self) -> String {
+        match self.feature {
+            Some(feature) => format!("unsupported format (enable feature '{}')", feature),
+            None => format!("unsupported format (no decoder for {} yet)", self.format),
+        }
+    }
+}
+
+/// The decoder a file needs, if this build lacks it.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::decoders;
+/// assert_eq!(decoders::missing("IMG_0001.HEIC".as_ref()), Some(&decoders::HEIC));
+/// assert_eq!(decoders::missing("IMG_0001.JPG".as_ref()), None);
+/// ```
+pub fn missing(path: &Path) -> Option<&'static Decoder> {
+    OPTIONAL
+        .iter()
+        .copied()
+        .find(|decoder| !decoder.compiled && decoder.matches(path))
+}
+
+/// How many files were skipped for every missing decoder, in the order of [`OPTIONAL`].
+///
+/// Decoders no file needed are left out.
+///
+/// # Arguments
+///
+/// * `unsupported` - skipped files and the decoders they need.
+pub fn counts(unsupported: &[(PathBuf, &'static Decoder)]) -> Vec<(&'static Decoder, usize)> {
+    OPTIONAL
+        .iter()
+        .map(|&decoder| {
+            let count = unsupported
+                .iter()
+                .filter(|(_, needed)| *needed == decoder)
+                .count();
+            (decoder, count)
+        })
+        .filter(|&(_, count)| count > 0)
+        .collect()
+}
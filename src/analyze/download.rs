@@ -0,0 +1,75 @@
+//! Images on the web, given by their URLs.
+//!
+//! Only available with the `urls` feature. An `http://` or `https://` URL
+//! can stand for a path of an image, e.g. one queried with
+//! [`index::query`](super::index::query): it is downloaded into memory,
+//! see [`get`], and decoded like the contents of a file.
+
+use async_std::path::Path;
+use std::io::{self, Read};
+use std::time::Duration;
+
+/// Schemes of URLs that are downloaded.
+pub const SCHEMES: &[&str] = &["http://", "https://"];
+
+/// Downloads larger than this many bytes fail, so that a wrong URL can't fill the memory.
+pub const MAX_SIZE: u64 = 64 << 20;
+
+/// How long a download can take.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Whether a path is a URL to download.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::download;
+/// assert!(download::is_url("https://example.com/sunset.jpg".as_ref()));
+/// assert!(!download::is_url("/home/user/Downloads/sunset.jpg".as_ref()));
+/// ```
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| SCHEMES.iter().any(|scheme| path.starts_with(scheme)))
+}
+
+/// Download a URL into memory, at most [`MAX_SIZE`] bytes of it.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+pub fn get(url: &Path) -> io::Result<Vec<u8>> {
+    let url = url.to_string_lossy();
+    debug!("Downloading '{}'", url);
+    let too_large = || {
+        let max = crate::units::format_size(MAX_SIZE);
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("is larger than {}", max),
+        )
+    };
+    let response = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .build()
+        .get(&url)
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(404, _) => io::ErrorKind::NotFound.into(),
+            ureq::Error::Status(status, _) => {
+                io::Error::other(format!("server responded with status {}", status))
+            }
+            ureq::Error::Transport(e) => io::Error::other(e.to_string()),
+        })?;
+    let length = response
+        .header("content-length")
+        .and_then(|l| l.parse::<u64>().ok());
+    if length.is_some_and(|length| length > MAX_SIZE) {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_SIZE + 1)
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_SIZE {
+        return Err(too_large());
+    }
+    Ok(data)
+}
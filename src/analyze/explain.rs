@@ -0,0 +1,195 @@
+//! Explaining why images were found similar.
+//!
+//! With [`AnalyzeOptions::explain`](super::AnalyzeOptions::explain), every pair
+//! of images that joined a group comes with its distances in all features it
+//! was compared by, the thresholds in effect and what made it a match, so that
+//! thresholds can be tuned and decisions checked before anything is deleted.
+
+use super::features::set::FeatureSet;
+use super::group::Group;
+use super::img::Img;
+use super::verify;
+use async_std::path::PathBuf;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Distance between two images in one feature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distance {
+    /// Name of the feature.
+    pub name: &'static str,
+    /// How far apart the images are in it.
+    pub distance: f64,
+    /// How far apart similar images can be in it.
+    pub max_distance: f64,
+}
+
+/// What made two images a match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+    /// They are close enough in all features, see [`FeatureSet::similar`].
+    Features,
+    /// One contains the other, see [`group::contained`](super::group::contained).
+    Contained {
+        /// Part of tiles of one image found in the other.
+        part: f64,
+        /// Part of tiles that has to be found.
+        min_part: f64,
+    },
+    /// They are photos of a burst, see [`group::bursts`](super::group::bursts).
+    Burst {
+        /// How long after one the other was taken.
+        apart: Duration,
+        /// How long after one the other can be taken.
+        window: Duration,
+    },
+}
+
+/// Why two images are in one group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation {
+    /// Path of one image.
+    pub a: PathBuf,
+    /// Path of the other image, contained in `a` if it is [`Trigger::Contained`].
+    pub b: PathBuf,
+    /// What made them a match.
+    pub trigger: Trigger,
+    /// Distances in all features they were compared by.
+    pub distances: Vec<Distance>,
+    /// How the match was verified by pixels, and the cutoff it passed.
+    pub verify: Option<(verify::Method, f64)>,
+}
+
+impl Explanation {
+    /// Explain a pair of images.
+    ///
+    /// # Arguments
+    ///
+    /// * `a`, `b` - the images.
+    /// * `trigger` - what made them a match.
+    /// * `features` - what they were compared by.
+    /// * `verify` - how the match was verified, and the cutoff.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{explain::{Explanation, Trigger}, features::{set::FeatureSet, ImgFeatures}, img::Img};
+    /// let img = |path: &str, features| Img {
+    ///     path: async_std::path::PathBuf::from(path),
+    ///     features: ImgFeatures::decode(features).unwrap(),
+    /// };
+    /// let a = img("/a.jpg", "00000000000000ff 0 0 0.5 0.2");
+    /// let b = img("/b.jpg", "00000000000000fc 0 0 0.5 0.2");
+    /// let explanation = Explanation::new(&a, &b, Trigger::Features, FeatureSet::standard(), None);
+    /// assert_eq!(explanation.distances[0].distance, 2.);
+    /// assert_eq!(explanation.closest().unwrap().name, "lshash");
+    /// assert_eq!(
+    ///     explanation.describe(),
+    ///     "lshash 2 of 6, brightness 0 of 0.25, contrast 0 of 0.15; all within thresholds, lshash the nearest to its own"
+    /// );
+    /// ```
+    pub fn new(
+        a: &Img<PathBuf>,
+        b: &Img<PathBuf>,
+        trigger: Trigger,
+        features: &FeatureSet,
+        verify: Option<(verify::Method, f64)>,
+    ) -> Self {
+        let distances = features
+            .iter()
+            .filter_map(|feature| {
+                let name = feature.name();
+                let (value_a, value_b) = (a.features.value(name)?, b.features.value(name)?);
+                Some(Distance {
+                    name,
+                    distance: feature.distance(&value_a, &value_b),
+                    max_distance: features.max_distance(name)?,
+                })
+            })
+            .collect();
+        Self {
+            a: a.path.clone(),
+            b: b.path.clone(),
+            trigger,
+            distances,
+            verify,
+        }
+    }
+
+    /// The feature closest to its threshold, the first to break the match if it were stricter.
+    pub fn closest(&self) -> Option<&Distance> {
+        let part = |distance: &Distance| match distance.max_distance {
+            max if max > 0. => distance.distance / max,
+            _ => 0.,
+        };
+        self.distances.iter().max_by(|a, b| {
+            part(a)
+                .partial_cmp(&part(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+
+    /// The explanation as a line of text, for people.
+    pub fn describe(&self) -> String {
+        let distances: Vec<String> = self
+            .distances
+            .iter()
+            .map(|d| format!("{} {} of {}", d.name, round(d.distance), d.max_distance))
+            .collect();
+        let trigger = match (self.trigger, self.closest()) {
+            (Trigger::Features, Some(closest)) => {
+                format!(
+                    "all within thresholds, {} the nearest to its own",
+                    closest.name
+                )
+            }
+            (Trigger::Features, None) => "all within thresholds".to_owned(),
+            (Trigger::Contained { part, min_part }, _) => format!(
+                "contains {:.0}% of tiles, at least {:.0}% needed",
+                part * 100.,
+                min_part * 100.
+            ),
+            (Trigger::Burst { apart, window }, _) => format!(
+                "taken {}s apart in a burst, at most {}s allowed",
+                round(apart.as_secs_f64()),
+                round(window.as_secs_f64())
+            ),
+        };
+        let mut description = format!("{}; {}", distances.join(", "), trigger);
+        if let Some((method, cutoff)) = self.verify {
+            description += &format!("; verified by {} of at least {}", method.name(), cutoff);
+        }
+        description
+    }
+}
+
+/// Round a distance for people, to 4 decimal places.
+fn round(distance: f64) -> f64 {
+    (distance * 1e4).round() / 1e4
+}
+
+/// Give every explanation to the group its images are in.
+///
+/// # Arguments
+///
+/// * `groups` - groups of similar images.
+/// * `explanations` - why pairs of images are similar, pairs of images
+///   that aren't in any group are dropped.
+pub fn attach(groups: &mut [Group], explanations: Vec<Explanation>) {
+    let mut group_of = HashMap::new();
+    for (i, group) in groups.iter().enumerate() {
+        for img in &group.imgs {
+            group_of.insert(img.path.clone(), i);
+        }
+    }
+    for explanation in explanations {
+        if let Some(&i) = group_of.get(&explanation.a) {
+            groups[i].explanations.push(explanation);
+        }
+    }
+    for group in groups {
+        group
+            .explanations
+            .sort_by(|x, y| (&x.a, &x.b).cmp(&(&y.a, &y.b)));
+    }
+}
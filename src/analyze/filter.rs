@@ -0,0 +1,84 @@
+//! Filters of images by their features, see `--filter`.
+//!
+//! Images whose features don't match every filter are left out of the
+//! analysis after their features are found, so only images that match are
+//! compared and reported. A filter is `key=value`, and a value can be several
+//! ones separated by commas, any of which matches, e.g. `hue=blue,cyan`.
+
+use super::features::hue::HueName;
+use super::features::ImgFeatures;
+
+/// A filter of images by a feature.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// Images with their hue named any of these, see [`HueName`].
+    Hue(Vec<HueName>),
+}
+
+impl Filter {
+    /// Whether an image matches the filter.
+    pub fn matches(&self, features: &ImgFeatures) -> bool {
+        match self {
+            Self::Hue(names) => names.contains(&features.hue.name()),
+        }
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("filter '{}' isn't like 'key=value'", s))?;
+        match key {
+            "hue" => Ok(Self::Hue(
+                value
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("{} in filter '{}'", e, s))?,
+            )),
+            _ => Err(format!("unknown key '{}' in filter '{}'", key, s)),
+        }
+    }
+}
+
+/// Filters an image has to match all of, none by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filters(Vec<Filter>);
+
+impl Filters {
+    /// Filters from `key=value` strings, e.g. `hue=blue`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{features::ImgFeatures, filter::Filters};
+    /// let features = ImgFeatures::decode("00000000000000ff 210 0 0.5 0.2").unwrap();
+    /// assert!(Filters::parse(["hue=blue"]).unwrap().matches(&features));
+    /// assert!(Filters::parse(["hue=red,blue"]).unwrap().matches(&features));
+    /// assert!(!Filters::parse(["hue=red"]).unwrap().matches(&features));
+    /// assert!(Filters::default().matches(&features));
+    /// assert!(Filters::parse(["hue"]).is_err());
+    /// assert!(Filters::parse(["hue=teal"]).is_err());
+    /// assert!(Filters::parse(["smell=blue"]).is_err());
+    /// ```
+    pub fn parse<'a>(filters: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        filters
+            .into_iter()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// Whether there are no filters, and every image matches.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether an image matches all filters.
+    pub fn matches(&self, features: &ImgFeatures) -> bool {
+        self.0.iter().all(|filter| filter.matches(features))
+    }
+}
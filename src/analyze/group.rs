@@ -0,0 +1,326 @@
+//! Grouping similar images.
+//!
+//! Two images are similar if they are close enough in all features of a
+//! [`FeatureSet`], by default their [`LsHash`](super::features::lshash::LsHash)es,
+//! brightness and contrast. Similarity is transitive here: if A is similar to B
+//! and B to C, all three end up in one group.
+
+use super::cancel::CancelToken;
+use super::features::set::FeatureSet;
+use super::img::Img;
+use super::index::Search;
+use async_std::path::PathBuf;
+use std::time::Duration;
+
+/// Images whose hashes differ in at most this many bits are similar by default.
+pub const MAX_DISTANCE: u32 = crate::features::lshash::MAX_DISTANCE;
+
+/// An image is contained in another if at least this part of its tiles is found in it.
+pub const MIN_CONTAINED: f64 = 0.75;
+
+/// A group of similar images.
+pub struct Group {
+    /// Images of the group, ordered by path.
+    pub imgs: Vec<Img<PathBuf>>,
+    /// How similar the images are, if they were [scored](super::score::sort).
+    pub score: Option<f64>,
+    /// Why pairs of its images are similar, if they were [explained](super::explain).
+    pub explanations: Vec<super::explain::Explanation>,
+}
+
+impl Group {
+    /// Hamming distance between hashes of the first image of the group and `img`.
+    pub fn distance(&self, img: &Img<PathBuf>) -> u32 {
+        self.imgs[0].features.lshash.distance(&img.features.lshash)
+    }
+
+    /// The first image of every file of the group, e.g. of a video with many keyframes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{features::ImgFeatures, group::Group, img::Img};
+    /// let img = |path: &str| Img {
+    ///     path: async_std::path::PathBuf::from(path),
+    ///     features: ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2").unwrap(),
+    /// };
+    /// let group = Group {
+    ///     imgs: vec![img("/a.jpg"), img("/b.mp4"), img("/b.mp4")],
+    ///     score: None,
+    ///     explanations: Vec::new(),
+    /// };
+    /// assert_eq!(group.files().len(), 2);
+    /// ```
+    pub fn files(&self) -> Vec<&Img<PathBuf>> {
+        let mut files: Vec<&Img<PathBuf>> = self.imgs.iter().collect();
+        // Images are ordered by path, so those of one file are next to each other.
+        files.dedup_by(|a, b| a.path == b.path);
+        files
+    }
+}
+
+/// Find the root of a set, compressing the path to it.
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Find pairs of similar images, as indices into `imgs`.
+///
+/// # Arguments
+///
+/// * `imgs` - images to compare.
+/// * `features` - what features of similar images are close enough.
+/// * `search` - how to find images to compare. Searching by hashes needs
+///   them to be in `features`, otherwise every image is compared.
+/// * `cancel` - stops comparing, the pairs found so far are returned.
+pub fn candidates(
+    imgs: &[Img<PathBuf>],
+    features: &FeatureSet,
+    search: Search,
+    cancel: &CancelToken,
+) -> Vec<(usize, usize)> {
+    let radius = features.max_distance("lshash");
+    let near = match radius {
+        Some(_) => {
+            let hashes: Vec<u64> = imgs.iter().map(|img| img.features.lshash.bits()).collect();
+            search.build(&hashes)
+        }
+        None if search == Search::Brute => None,
+        None => {
+            warn!(
+                "Hashes aren't compared, so all images are compared instead of a {} search",
+                search.name()
+            );
+            None
+        }
+    };
+    if let (Search::Bands(count), Some(radius)) = (search, radius) {
+        if radius >= count as f64 {
+            warn!(
+                "Hashes {} bits apart may share none of {} bands and not be compared",
+                radius, count
+            );
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for (i, a) in imgs.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let others: Vec<usize> = match (&near, radius) {
+            (Some(near), Some(radius)) => {
+                let mut others = near.near(a.features.lshash.bits(), radius.max(0.) as u32);
+                others.retain(|&j| j > i);
+                others.sort_unstable();
+                others
+            }
+            _ => (i + 1..imgs.len()).collect(),
+        };
+        for j in others {
+            let b = &imgs[j];
+            if a.features.comparable(&b.features) && features.similar(&a.features, &b.features) {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+/// Find images contained in other images, e.g. crops, as indices into `imgs`.
+///
+/// Returns `(inner, outer, part)` for every image contained in another,
+/// where `part` is the part of tiles of `inner` found in `outer`, see
+/// [`Tiles::contained_in`](super::features::tiles::Tiles::contained_in).
+/// Images without tiles are skipped. A pair of images contained in each
+/// other is only returned once.
+///
+/// # Arguments
+///
+/// * `imgs` - images to look through.
+/// * `min_part` - how much of an image has to be found in another.
+/// * `cancel` - stops looking, the images found so far are returned.
+pub fn contained(
+    imgs: &[Img<PathBuf>],
+    min_part: f64,
+    cancel: &CancelToken,
+) -> Vec<(usize, usize, f64)> {
+    let mut found = Vec::new();
+    for (i, a) in imgs.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        for (j, b) in imgs.iter().enumerate().skip(i + 1) {
+            let (tiles_a, tiles_b) = match (&a.features.tiles, &b.features.tiles) {
+                (Some(tiles_a), Some(tiles_b)) if a.path != b.path => (tiles_a, tiles_b),
+                _ => continue,
+            };
+            let (a_in_b, b_in_a) = (tiles_a.contained_in(tiles_b), tiles_b.contained_in(tiles_a));
+            if a_in_b >= min_part && a_in_b >= b_in_a {
+                found.push((i, j, a_in_b));
+            } else if b_in_a >= min_part {
+                found.push((j, i, b_in_a));
+            }
+        }
+    }
+    found
+}
+
+/// Find pairs of photos of a [burst](super::burst), as indices into `imgs`.
+///
+/// Photos are clustered by the time they were taken, see [`ImgFeatures::exif`](super::features::ImgFeatures::exif):
+/// a photo is in the burst of the one before it if the same camera took it at most
+/// `window` later, no farther than [`MAX_METERS`](super::burst::MAX_METERS) away if
+/// both record where, and their hashes are close. Returns `(earlier, later, apart)`
+/// for every photo and the one before it in its burst, [`group_pairs`] turns them
+/// into bursts. Images without the time they were taken are skipped, and so are
+/// keyframes of one file.
+///
+/// # Arguments
+///
+/// * `imgs` - images to look through.
+/// * `window` - how long after the previous one a photo of a burst can be taken.
+/// * `max_distance` - how many bits hashes of photos of a burst can differ in.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{features::ImgFeatures, group, img::Img};
+/// # use std::time::Duration;
+/// let img = |path: &str, hash, taken, gps| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode(&format!(
+///         "{} 0 0 0.5 0.2 exif.taken={} exif.camera=Canon exif.gps={}",
+///         hash, taken, gps
+///     ))
+///     .unwrap(),
+/// };
+/// let imgs = vec![
+///     img("/1.jpg", "00000000000000ff", 0., "51.5,-0.1"),
+///     img("/2.jpg", "00000000000001ff", 0.3, "51.5,-0.1"),
+///     img("/3.jpg", "00000000000003ff", 0.6, "51.6,-0.1"),
+///     img("/4.jpg", "00000000000003ff", 60.6, "51.6,-0.1"),
+///     img("/5.jpg", "ffffffffffffff00", 60.9, "51.6,-0.1"),
+/// ];
+/// // The third photo is miles away, the fourth is a minute later,
+/// // the fifth is of something else.
+/// let pairs = group::bursts(&imgs, Duration::from_secs(2), group::MAX_DISTANCE);
+/// let pairs: Vec<(usize, usize)> = pairs.iter().map(|&(i, j, _)| (i, j)).collect();
+/// assert_eq!(pairs, vec![(0, 1)]);
+/// ```
+pub fn bursts(
+    imgs: &[Img<PathBuf>],
+    window: Duration,
+    max_distance: u32,
+) -> Vec<(usize, usize, Duration)> {
+    let mut taken: Vec<(&Option<String>, f64, usize)> = imgs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, img)| {
+            let exif = img.features.exif.as_ref()?;
+            Some((&exif.camera, exif.taken?, i))
+        })
+        .collect();
+    taken.sort_by(|a, b| a.0.cmp(b.0).then(a.1.total_cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut pairs = Vec::new();
+    for pair in taken.windows(2) {
+        let ((camera_a, _, i), (camera_b, _, j)) = (pair[0], pair[1]);
+        let (a, b) = (&imgs[i], &imgs[j]);
+        let (exif_a, exif_b) = (a.features.exif.as_ref(), b.features.exif.as_ref());
+        let apart = match exif_a.zip(exif_b).and_then(|(a, b)| a.apart(b)) {
+            Some(apart) if camera_a == camera_b && apart <= window => apart,
+            _ => continue,
+        };
+        let meters = exif_a.zip(exif_b).and_then(|(a, b)| a.meters(b));
+        if meters.is_some_and(|meters| meters > super::burst::MAX_METERS) {
+            continue;
+        }
+        if a.path != b.path && a.features.lshash.distance(&b.features.lshash) <= max_distance {
+            pairs.push((i, j, apart));
+        }
+    }
+    pairs
+}
+
+/// Group images similar in the [standard](FeatureSet::standard) features.
+///
+/// See [`group_pairs`] for what groups are returned.
+///
+/// # Arguments
+///
+/// * `imgs` - images to group.
+/// * `max_distance` - how many bits hashes of similar images can differ in.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{group, img::Img, features::ImgFeatures};
+/// let img = |path: &str, features| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode(features).unwrap(),
+/// };
+/// let imgs = vec![
+///     img("/a.jpg", "00000000000000ff 0 0 0.5 0.2"),
+///     img("/b.jpg", "00000000000000fe 0 0 0.5 0.2"),
+///     img("/c.jpg", "ffffffffffffff00 0 0 0.5 0.2"),
+/// ];
+/// let groups = group::group(imgs, group::MAX_DISTANCE);
+/// assert_eq!(groups.len(), 1);
+/// assert_eq!(groups[0].imgs.len(), 2);
+/// ```
+pub fn group(imgs: Vec<Img<PathBuf>>, max_distance: u32) -> Vec<Group> {
+    let features = FeatureSet::standard()
+        .clone()
+        .with_max_distance("lshash", f64::from(max_distance));
+    let pairs = candidates(&imgs, &features, Search::default(), &CancelToken::new());
+    group_pairs(imgs, &pairs)
+}
+
+/// Group images connected by pairs of similar images.
+///
+/// Only groups with images from more than one file are returned,
+/// so keyframes of a single video don't make a group on their own.
+/// Groups are ordered by paths of their images, the first one first, and aren't scored yet.
+///
+/// # Arguments
+///
+/// * `imgs` - images to group.
+/// * `pairs` - pairs of similar images, as indices into `imgs`,
+///   e.g. [`candidates`] that passed [verification](super::verify).
+pub fn group_pairs(imgs: Vec<Img<PathBuf>>, pairs: &[(usize, usize)]) -> Vec<Group> {
+    let mut parents: Vec<usize> = (0..imgs.len()).collect();
+    for &(i, j) in pairs {
+        let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+        parents[root_i] = root_j;
+    }
+
+    let mut sets = std::collections::BTreeMap::<usize, Vec<Img<PathBuf>>>::new();
+    for (i, img) in imgs.into_iter().enumerate() {
+        let root = find(&mut parents, i);
+        sets.entry(root).or_default().push(img);
+    }
+
+    let mut groups: Vec<Group> = sets
+        .into_values()
+        .filter(|imgs| imgs.iter().any(|img| img.path != imgs[0].path))
+        .map(|mut imgs| {
+            imgs.sort_by(|a, b| a.path.cmp(&b.path));
+            Group {
+                imgs,
+                score: None,
+                explanations: Vec::new(),
+            }
+        })
+        .collect();
+    // Groups of keyframes of one video can start with the same path, the rest tells them apart.
+    groups.sort_by(|a, b| {
+        let paths_b = b.imgs.iter().map(|img| &img.path);
+        a.imgs.iter().map(|img| &img.path).cmp(paths_b)
+    });
+    groups
+}
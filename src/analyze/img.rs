@@ -1,13 +1,130 @@
 //! Implementation of internally-used image structures.
 
-use super::features;
+use super::{features, io};
+
+/// What the header of an image tells, without decoding the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Probe {
+    /// Format of the image, `None` if not recognized.
+    pub format: Option<image::ImageFormat>,
+    /// Width and height of the image, `None` if they couldn't be read.
+    pub dimensions: Option<(u32, u32)>,
+}
+
+impl Probe {
+    /// Probe contents of an image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::img::Probe;
+    /// let mut png = Vec::new();
+    /// image::DynamicImage::new_rgb8(4, 3)
+    ///     .write_to(&mut png, image::ImageOutputFormat::Png)
+    ///     .unwrap();
+    /// let probe = Probe::of_bytes(&png);
+    /// assert_eq!(probe.format, Some(image::ImageFormat::Png));
+    /// assert_eq!(probe.dimensions, Some((4, 3)));
+    /// assert_eq!(Probe::of_bytes(b"not an image").format, None);
+    /// ```
+    pub fn of_bytes(data: &[u8]) -> Self {
+        Self::of_reader(image::io::Reader::new(std::io::Cursor::new(data)))
+    }
+
+    /// Probe an image in a file, see [`probe`] for the asynchronous version.
+    pub fn of_file(path: &std::path::Path) -> std::io::Result<Self> {
+        Ok(Self::of_reader(image::io::Reader::open(path)?))
+    }
+
+    fn of_reader<R: std::io::BufRead + std::io::Seek>(reader: image::io::Reader<R>) -> Self {
+        match reader.with_guessed_format() {
+            Ok(reader) => Self {
+                format: reader.format(),
+                dimensions: reader.into_dimensions().ok(),
+            },
+            Err(_) => Self {
+                format: None,
+                dimensions: None,
+            },
+        }
+    }
+}
+
+/// Probe an image, reading only as much of the file as its header takes.
+///
+/// # Arguments
+///
+/// * `path` - where the image is.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::img;
+/// # async_std::task::block_on(async {
+/// let probe = img::probe("/home/user/Pictures/wallpaper.png").await?;
+/// if let Some((width, height)) = probe.dimensions {
+///     println!("{}x{}", width, height);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// # });
+/// ```
+pub async fn probe(path: impl AsRef<std::path::Path>) -> Result<Probe, Box<dyn std::error::Error>> {
+    let path = path.as_ref().to_path_buf();
+    Ok(io::unblock(move || Probe::of_file(&path)).await?)
+}
+
+/// JPEGs are decoded at the smallest scale that still covers this many pixels
+/// on one side. It is the largest size features are computed at, so they
+/// don't change, see [`features::sharpness::SIZE`].
+pub const JPEG_DECODE_SIZE: u16 = features::sharpness::SIZE as u16;
+
+/// Decode a JPEG with its DCT scaled down, to 1/2, 1/4 or 1/8 of its size,
+/// which is much faster than decoding it in full and resizing afterwards.
+///
+/// Returns `None` for CMYK JPEGs, which should be decoded in full.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::img::decode_jpeg_scaled;
+/// # use image::GenericImageView;
+/// let mut jpeg = Vec::new();
+/// image::DynamicImage::new_rgb8(4096, 2048)
+///     .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90))
+///     .unwrap();
+/// let decoded = decode_jpeg_scaled(&jpeg).unwrap().unwrap();
+/// assert_eq!(decoded.dimensions(), (1024, 512));
+/// ```
+pub fn decode_jpeg_scaled(
+    data: &[u8],
+) -> Result<Option<image::DynamicImage>, Box<dyn std::error::Error>> {
+    let mut decoder = jpeg_decoder::Decoder::new(std::io::Cursor::new(data));
+    decoder.read_info()?;
+    let (width, height) = decoder.scale(JPEG_DECODE_SIZE, JPEG_DECODE_SIZE)?;
+    let (width, height) = (u32::from(width), u32::from(height));
+    let pixels = decoder.decode()?;
+    let info = decoder.info().ok_or("no JPEG header")?;
+
+    let data = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => {
+            image::GrayImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageLuma8)
+        }
+        jpeg_decoder::PixelFormat::RGB24 => {
+            image::RgbImage::from_raw(width, height, pixels).map(image::DynamicImage::ImageRgb8)
+        }
+        jpeg_decoder::PixelFormat::CMYK32 => return Ok(None),
+    };
+    Ok(Some(
+        data.ok_or("JPEG decoded to a wrong number of pixels")?,
+    ))
+}
 
 /// Image - path to it and its contents.
 ///
 /// Convert to [`Img`] to make useful.
 pub struct ImgRaw<P>
 where
-    P: AsRef<async_std::path::Path>,
+    P: AsRef<std::path::Path>,
 {
     /// Path to where the image was loaded from
     pub path: P,
@@ -18,7 +135,7 @@ where
 
 impl<P> ImgRaw<P>
 where
-    P: AsRef<async_std::path::Path>,
+    P: AsRef<std::path::Path>,
 {
     /// Load an image from a given path.
     ///
@@ -36,12 +153,111 @@ where
     /// # }
     /// ```
     pub async fn load(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        let data_raw = async_std::fs::read(path.as_ref()).await?;
-        let data = image::io::Reader::new(std::io::Cursor::new(data_raw))
-            .with_guessed_format()?
-            .decode()?;
+        let data_raw = io::read(path.as_ref()).await?;
+        Self::decode(path, &data_raw)
+    }
+
+    /// Load an image from a given path, blocking the thread until it is loaded.
+    ///
+    /// The same as [`ImgRaw::load`], for programs that don't use async,
+    /// only with the `blocking` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path where to load the image from.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use libsuccotash::analyze::img::ImgRaw;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let wallpaper = ImgRaw::load_sync("/home/user/Pictures/wallpaper.png")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "blocking")]
+    pub fn load_sync(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let data_raw = std::fs::read(path.as_ref())?;
+        Self::decode(path, &data_raw)
+    }
+
+    /// Decode an image from contents of its file.
+    ///
+    /// Unlike [`ImgRaw::load`], doesn't do any IO, so that it can run on any thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path where the contents were read from.
+    /// * `data_raw` - Contents of the file.
+    pub fn decode(path: P, data_raw: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        #[cfg(feature = "raw")]
+        if super::raw::is_raw(path.as_ref().into()) {
+            let data = super::raw::preview(data_raw).ok_or("no embedded preview in RAW file")?;
+            return Ok(Self { path, data });
+        }
+
+        let probe = Probe::of_bytes(data_raw);
+        let format = probe.format.ok_or("unknown image format")?;
+        if let Some((width, height)) = probe.dimensions {
+            trace!(
+                "'{}' is a {}x{} {:?} image",
+                path.as_ref().to_string_lossy(),
+                width,
+                height,
+                format
+            );
+        }
+        let data = match format {
+            image::ImageFormat::Jpeg => match decode_jpeg_scaled(data_raw)? {
+                Some(data) => data,
+                None => image::load_from_memory_with_format(data_raw, format)?,
+            },
+            _ => image::load_from_memory_with_format(data_raw, format)?,
+        };
         Ok(Self { path, data })
     }
+
+    /// Load all images from a given path.
+    ///
+    /// Most files have exactly one image in them, but videos
+    /// (with the `video` feature) give a few keyframes.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path where to load the images from.
+    pub async fn load_all(path: P) -> Result<Vec<Self>, Box<dyn std::error::Error>>
+    where
+        P: Clone,
+    {
+        #[cfg(feature = "video")]
+        if super::video::is_video(path.as_ref().into()) {
+            let keyframes = super::video::keyframes(path.as_ref().into()).await?;
+            return Ok(keyframes
+                .into_iter()
+                .map(|frame| Self {
+                    path: path.clone(),
+                    data: image::DynamicImage::ImageRgb8(frame),
+                })
+                .collect());
+        }
+
+        Ok(vec![Self::load(path).await?])
+    }
+
+    /// Load all images from a given path, blocking the thread until they are loaded.
+    ///
+    /// The same as [`ImgRaw::load_all`], only with the `blocking` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path where to load the images from.
+    #[cfg(feature = "blocking")]
+    pub fn load_all_sync(path: P) -> Result<Vec<Self>, Box<dyn std::error::Error>>
+    where
+        P: Clone,
+    {
+        async_std::task::block_on(Self::load_all(path))
+    }
 }
 
 /// An image and its features.
@@ -51,7 +267,7 @@ where
 /// Use From/Into to convert [`Img`] into this.
 pub struct Img<P>
 where
-    P: AsRef<async_std::path::Path>,
+    P: AsRef<std::path::Path>,
 {
     /// The original image we find features of.
     pub path: P,
@@ -62,7 +278,7 @@ where
 
 impl<P> From<ImgRaw<P>> for Img<P>
 where
-    P: AsRef<async_std::path::Path>,
+    P: AsRef<std::path::Path>,
 {
     fn from(original: ImgRaw<P>) -> Img<P> {
         Img {
@@ -0,0 +1,96 @@
+//! Multi-index hashing: buckets of hashes by bands of their bits.
+//!
+//! A hash is split into a number of bands, and every band has buckets of
+//! hashes by the value of their bits in it. Hashes that differ in fewer bits
+//! than there are bands have at least one band the same, so only hashes that
+//! share a bucket with the query have to be compared. More bands find hashes
+//! that are farther apart, but put more of them into each bucket.
+
+use super::Near;
+use std::collections::HashMap;
+
+/// How many bands hashes are split into by default.
+pub const DEFAULT_BANDS: usize = 8;
+
+/// Hashes in buckets by bands of their bits.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::{bands::Bands, Near};
+/// let hashes = [0x0000_0000_0000_0000u64, 0x0000_0000_0000_0003, 0xffff_ffff_0000_0000];
+/// let bands = Bands::new(&hashes, 4);
+/// let mut found = bands.near(0, 3);
+/// found.sort_unstable();
+/// assert_eq!(found, vec![0, 1]);
+/// ```
+pub struct Bands {
+    hashes: Vec<u64>,
+    /// Bit ranges of the bands, as masks.
+    masks: Vec<u64>,
+    /// For every band, indices of hashes by their bits in it.
+    buckets: Vec<HashMap<u64, Vec<usize>>>,
+}
+
+impl Bands {
+    /// Put hashes into buckets, their positions in `hashes` are their indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashes` - what to put into buckets.
+    /// * `count` - how many bands to split hashes into, from 1 to 64.
+    ///   Bands are as wide as they can be, the last ones are one bit narrower
+    ///   if 64 isn't divisible by `count`.
+    pub fn new(hashes: &[u64], count: usize) -> Self {
+        let count = count.clamp(1, 64);
+        let mut masks = Vec::with_capacity(count);
+        let mut start = 0;
+        for band in 0..count {
+            let width = 64 / count + usize::from(band < 64 % count);
+            let mask = match width {
+                64 => u64::MAX,
+                _ => ((1u64 << width) - 1) << start,
+            };
+            masks.push(mask);
+            start += width;
+        }
+
+        let mut buckets = vec![HashMap::<u64, Vec<usize>>::new(); count];
+        for (index, hash) in hashes.iter().enumerate() {
+            for (band, mask) in masks.iter().enumerate() {
+                buckets[band].entry(hash & mask).or_default().push(index);
+            }
+        }
+        Self {
+            hashes: hashes.to_vec(),
+            masks,
+            buckets,
+        }
+    }
+
+    /// How many bands hashes are split into.
+    pub fn count(&self) -> usize {
+        self.masks.len()
+    }
+}
+
+impl Near for Bands {
+    /// Hashes that share a bucket with `query` and are at most `radius` bits away.
+    ///
+    /// Hashes that are `radius` bits away may share no band with `query`
+    /// if there are no more bands than that, they aren't found then.
+    fn near(&self, query: u64, radius: u32) -> Vec<usize> {
+        let mut found: Vec<usize> = self
+            .masks
+            .iter()
+            .zip(&self.buckets)
+            .filter_map(|(mask, buckets)| buckets.get(&(query & mask)))
+            .flatten()
+            .copied()
+            .filter(|&index| (self.hashes[index] ^ query).count_ones() <= radius)
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
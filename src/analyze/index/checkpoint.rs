@@ -0,0 +1,249 @@
+//! Saving features to an index while they are found.
+//!
+//! Features of every analyzed file are put into a [`Store`] as soon as they
+//! come back, and the store is synced to disk every [`SYNC_INTERVAL`], so that
+//! an interrupted run, e.g. by a power loss, can be resumed: files that are in
+//! the store already are not analyzed again, unless their features were
+//! found with other [options](FindOptions) or by another version of the
+//! algorithms, see [`schema`](crate::analyze::features::schema).
+//!
+//! Features can be saved to XMP sidecars of files instead of a store, or as
+//! well, see [`sidecar`]. With the `xattrs` feature, they can be saved to
+//! extended attributes of files too, see [`xattrs`](super::xattrs).
+
+use super::sidecar;
+use super::store::Store;
+use crate::analyze::features::schema::Schema;
+use crate::analyze::features::{FindOptions, ImgFeatures};
+use crate::analyze::worker::Outcome;
+use async_std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often the store is synced to disk.
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Whether features of a file can be kept with it, in its sidecar or attributes.
+/// Those of images inside archives or in S3 can't, nothing is written there.
+fn travels(path: &Path) -> bool {
+    #[cfg(feature = "archives")]
+    if crate::analyze::archive::is_entry(path) {
+        return false;
+    }
+    #[cfg(feature = "s3")]
+    if crate::analyze::s3::is_object(path) {
+        return false;
+    }
+    #[cfg(not(any(feature = "archives", feature = "s3")))]
+    let _ = path;
+    true
+}
+
+/// A store that features are saved to during an analysis.
+///
+/// Can be shared between threads, e.g. those of the
+/// [worker pool](crate::analyze::worker::find_all).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::index::checkpoint::Checkpoint;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let checkpoint = Checkpoint::open("/home/user/.cache/succotash/index", Default::default())?;
+/// let path = "/home/user/Pictures/a.png".as_ref();
+/// if checkpoint.get(path).is_none() {
+///     checkpoint.save(path, &Ok(Vec::new()));
+/// }
+/// checkpoint.sync();
+/// # Ok(())
+/// # }
+/// ```
+pub struct Checkpoint {
+    /// Where features are saved to, unless they are only saved to attributes.
+    store: Option<Mutex<Store>>,
+    /// Whether features are saved to extended attributes of files too.
+    #[cfg(feature = "xattrs")]
+    xattrs: bool,
+    /// Whether features are read from and saved to sidecars of files too.
+    sidecars: sidecar::Mode,
+    /// How features are found in this analysis.
+    schema: Schema,
+    /// When the store was last synced.
+    synced: Mutex<Instant>,
+}
+
+impl Checkpoint {
+    /// Open an index to save features to, creating it if it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - where the index file is.
+    /// * `options` - how features are found in this analysis.
+    pub fn open<P: AsRef<Path>>(path: P, options: FindOptions) -> std::io::Result<Self> {
+        Ok(Self {
+            store: Some(Mutex::new(Store::open(path)?)),
+            ..Self::new(options)
+        })
+    }
+
+    /// Save features without an index, only to what they are
+    /// saved to [`with_sidecars`](Self::with_sidecars) and the like.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - how features are found in this analysis.
+    pub fn new(options: FindOptions) -> Self {
+        Self {
+            store: None,
+            #[cfg(feature = "xattrs")]
+            xattrs: false,
+            sidecars: sidecar::Mode::Ignore,
+            schema: Schema::of(options),
+            synced: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Read features from sidecars of files, and save them there
+    /// if `mode` is [`sidecar::Mode::Write`].
+    ///
+    /// Features are looked up in the index first, then in sidecars.
+    pub fn with_sidecars(mut self, mode: sidecar::Mode) -> Self {
+        self.sidecars = mode;
+        self
+    }
+
+    /// Save features to extended attributes of files too.
+    ///
+    /// Features are looked up in the index first, then in attributes.
+    #[cfg(feature = "xattrs")]
+    pub fn with_xattrs(mut self) -> Self {
+        self.xattrs = true;
+        self
+    }
+
+    /// Features of all images in a file at `path`, if they were saved
+    /// and their schema [satisfies](Schema::satisfies) the current one.
+    pub fn get(&self, path: &Path) -> Option<Vec<ImgFeatures>> {
+        let stored = self
+            .store
+            .as_ref()
+            .and_then(|store| self.get_stored(store, path));
+        #[cfg(feature = "xattrs")]
+        let stored = stored.or_else(|| self.get_xattrs(path));
+        stored.or_else(|| self.get_sidecar(path))
+    }
+
+    /// Features of all images in a file at `path`, if they are in `store`
+    /// and their schema satisfies the current one.
+    fn get_stored(&self, store: &Mutex<Store>, path: &Path) -> Option<Vec<ImgFeatures>> {
+        let store = store.lock().unwrap();
+        let features = store.get(path)?;
+        match store.schema(path) {
+            Some(schema) if schema.satisfies(&self.schema) => Some(features.to_vec()),
+            schema => {
+                debug!(
+                    "Features of '{}' were found as {}, not as {}",
+                    path.to_string_lossy(),
+                    schema.map_or("unknown".to_owned(), Schema::encode),
+                    self.schema.encode()
+                );
+                None
+            }
+        }
+    }
+
+    /// Features of all images in a file at `path`, if they are in its
+    /// sidecar and their schema satisfies the current one.
+    fn get_sidecar(&self, path: &Path) -> Option<Vec<ImgFeatures>> {
+        if self.sidecars == sidecar::Mode::Ignore || !travels(path) {
+            return None;
+        }
+        match sidecar::load(path, &self.schema) {
+            Ok(features) => features,
+            Err(e) => {
+                warn!(
+                    "Can't read features of '{}' from its sidecar: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Features of all images in a file at `path`, if they are in its
+    /// attributes and their schema satisfies the current one.
+    #[cfg(feature = "xattrs")]
+    fn get_xattrs(&self, path: &Path) -> Option<Vec<ImgFeatures>> {
+        if !self.xattrs || !travels(path) {
+            return None;
+        }
+        match super::xattrs::load(path, &self.schema) {
+            Ok(features) => features,
+            Err(e) => {
+                warn!(
+                    "Can't read features of '{}' from its attributes: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Save features of a file, syncing the store if it is time to.
+    ///
+    /// Files that couldn't be analyzed are not saved, so that a resumed run
+    /// tries them again. Errors are logged, the analysis goes on without them.
+    pub fn save(&self, path: &Path, outcome: &Outcome) {
+        let features = match outcome {
+            Ok(features) => features.clone(),
+            Err(_) => return,
+        };
+        #[cfg(feature = "xattrs")]
+        if self.xattrs && travels(path) {
+            if let Err(e) = super::xattrs::save(path, &self.schema, &features) {
+                warn!(
+                    "Can't save features of '{}' to its attributes: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+            }
+        }
+        if self.sidecars == sidecar::Mode::Write && travels(path) {
+            if let Err(e) = sidecar::save(path, &self.schema, &features) {
+                warn!(
+                    "Can't save features of '{}' to its sidecar: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+            }
+        }
+        let store = match &self.store {
+            Some(store) => store,
+            None => return,
+        };
+        if let Err(e) = store.lock().unwrap().put(path, &self.schema, features) {
+            warn!("Can't save features of '{}': {}", path.to_string_lossy(), e);
+        }
+
+        let mut synced = self.synced.lock().unwrap();
+        if synced.elapsed() >= SYNC_INTERVAL {
+            *synced = Instant::now();
+            drop(synced);
+            self.sync();
+        }
+    }
+
+    /// Sync the store to disk, if there is one.
+    pub fn sync(&self) {
+        let store = match &self.store {
+            Some(store) => store.lock().unwrap(),
+            None => return,
+        };
+        match store.sync() {
+            Ok(()) => debug!("Checkpointed features of {} files", store.len()),
+            Err(e) => warn!("Can't sync the index: {}", e),
+        }
+    }
+}
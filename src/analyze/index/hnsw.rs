@@ -0,0 +1,455 @@
+//! Hierarchical navigable small world graph, for approximate nearest neighbors.
+//!
+//! Every point is linked to a few close points on layer 0, and on a few more
+//! layers above it, each with exponentially fewer points. A search starts at the
+//! top layer and walks towards the query on every layer in turn, so it looks at
+//! a tiny part of the dataset, and finds most but not always all nearest points.
+//! Unlike a [`VpTree`](super::vptree::VpTree), it stays fast with millions of
+//! long vectors, e.g. [embeddings](crate::features::set::Value::Vector).
+//!
+//! Points can be inserted one by one. The graph can be [saved](Hnsw::save) and
+//! [loaded](Hnsw::load) back without building it again, points themselves are
+//! not saved, they are in the [store](super::store) already.
+
+use super::metric::{Hamming, Metric};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// First line of a saved graph.
+const HEADER: &str = "succotash-hnsw 1";
+
+/// How a graph is built and searched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    /// How many neighbors a point has on every layer above 0, twice as many on 0.
+    pub links: usize,
+    /// How many candidates are kept while inserting a point, more is slower to
+    /// build but finds better neighbors.
+    pub ef_construction: usize,
+    /// How many candidates are kept while searching, at least as many as are
+    /// asked for, more is slower but finds more of the nearest points.
+    pub ef_search: usize,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            links: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+/// A distance to a point, ordered by the distance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candidate {
+    distance: f64,
+    index: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// An HNSW graph of points, each with its index in the dataset.
+///
+/// Of hashes by Hamming distance unless told otherwise, see [`Hnsw::with_metric`].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::{hnsw::Hnsw, metric::Cosine};
+/// let embeddings = vec![vec![1., 0.], vec![0.9, 0.1], vec![0., 1.], vec![-1., 0.]];
+/// let graph = Hnsw::build(&embeddings, Cosine, Default::default());
+/// let found: Vec<usize> = graph
+///     .nearest(&vec![1., 0.05], 2)
+///     .into_iter()
+///     .map(|(index, _)| index)
+///     .collect();
+/// assert_eq!(found, vec![0, 1]);
+/// ```
+pub struct Hnsw<A = u64, M = Hamming> {
+    /// The points, their positions are their indices.
+    points: Vec<A>,
+    /// How far apart points are.
+    metric: M,
+    /// How the graph is built and searched.
+    params: Params,
+    /// Neighbors of every point on every layer it is on, layer 0 first.
+    links: Vec<Vec<Vec<usize>>>,
+    /// The point on the top layer searches start at.
+    entry: Option<usize>,
+    /// State of the generator of layers of points, so that graphs are reproducible.
+    state: u64,
+}
+
+impl Hnsw {
+    /// Build a graph of hashes, their positions in `hashes` are their indices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::index::hnsw::Hnsw;
+    /// // Compare with brute force on pseudo-random hashes.
+    /// let mut state = 0x2545f4914f6cdd1du64;
+    /// let hashes: Vec<u64> = (0..2000)
+    ///     .map(|_| {
+    ///         state ^= state << 13;
+    ///         state ^= state >> 7;
+    ///         state ^= state << 17;
+    ///         state
+    ///     })
+    ///     .collect();
+    /// let graph = Hnsw::new(&hashes);
+    /// let mut recalled = 0;
+    /// for &query in &hashes[..100] {
+    ///     let found = graph.nearest(&query, 5);
+    ///     assert_eq!(found[0], (hashes.iter().position(|&h| h == query).unwrap(), 0.));
+    ///     let mut brute: Vec<u32> = hashes.iter().map(|h| (h ^ query).count_ones()).collect();
+    ///     brute.sort_unstable();
+    ///     recalled += found.iter().filter(|(_, d)| *d <= f64::from(brute[4])).count();
+    /// }
+    /// assert!(recalled >= 450, "recalled {} of 500", recalled);
+    /// ```
+    pub fn new(hashes: &[u64]) -> Self {
+        Self::build(hashes, Hamming, Params::default())
+    }
+}
+
+impl<A: Clone, M: Metric<A>> Hnsw<A, M> {
+    /// An empty graph, far apart as `metric` says.
+    pub fn with_metric(metric: M, params: Params) -> Self {
+        Self {
+            points: Vec::new(),
+            metric,
+            params,
+            links: Vec::new(),
+            entry: None,
+            state: 0x2545f4914f6cdd1d,
+        }
+    }
+
+    /// Build a graph of points, their positions in `points` are their indices.
+    pub fn build(points: &[A], metric: M, params: Params) -> Self {
+        let mut graph = Self::with_metric(metric, params);
+        for point in points {
+            graph.insert(point.clone());
+        }
+        graph
+    }
+
+    /// How many points are in the graph.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Whether the graph has no points.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Add a point to the graph, returns its index.
+    pub fn insert(&mut self, point: A) -> usize {
+        let index = self.points.len();
+        let layer = self.random_layer();
+        self.points.push(point);
+        self.links.push(vec![Vec::new(); layer + 1]);
+
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => {
+                self.entry = Some(index);
+                return index;
+            }
+        };
+        let top = self.links[entry].len() - 1;
+        let query = self.points[index].clone();
+        let mut nearest = vec![self.candidate(&query, entry)];
+        for layer in (layer + 1..=top).rev() {
+            nearest = self.search_layer(&query, nearest, 1, layer);
+        }
+        for layer in (0..=layer.min(top)).rev() {
+            let found = self.search_layer(&query, nearest, self.params.ef_construction, layer);
+            let neighbors: Vec<usize> = found
+                .iter()
+                .take(self.params.links)
+                .map(|candidate| candidate.index)
+                .collect();
+            for &neighbor in &neighbors {
+                self.links[neighbor][layer].push(index);
+                self.prune(neighbor, layer);
+            }
+            self.links[index][layer] = neighbors;
+            nearest = found;
+        }
+        if layer > top {
+            self.entry = Some(index);
+        }
+        index
+    }
+
+    /// Indices of about `k` points nearest to `query` with their distances, the nearest first.
+    pub fn nearest(&self, query: &A, k: usize) -> Vec<(usize, f64)> {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+        let mut nearest = vec![self.candidate(query, entry)];
+        for layer in (1..self.links[entry].len()).rev() {
+            nearest = self.search_layer(query, nearest, 1, layer);
+        }
+        let ef = self.params.ef_search.max(k);
+        self.search_layer(query, nearest, ef, 0)
+            .into_iter()
+            .take(k)
+            .map(|candidate| (candidate.index, candidate.distance))
+            .collect()
+    }
+
+    /// Indices of points at most `radius` away from `query`, the nearest first.
+    ///
+    /// Asks for twice as many nearest points until the farthest of them is
+    /// beyond `radius`, so like [`nearest`](Self::nearest) it may miss some.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::index::{hnsw::{Hnsw, Params}, metric::Cosine};
+    /// let vectors = vec![vec![1., 0.], vec![1., 0.1], vec![0., 1.], vec![-1., 0.]];
+    /// let graph = Hnsw::build(&vectors, Cosine, Params::default());
+    /// assert_eq!(graph.within_distance(&vec![1., 0.], 0.1), vec![0, 1]);
+    /// assert_eq!(graph.within_distance(&vec![1., 0.], 1.).len(), 4);
+    /// ```
+    pub fn within_distance(&self, query: &A, radius: f64) -> Vec<usize> {
+        let mut k = self.params.ef_search.max(1);
+        loop {
+            let found = self.nearest(query, k);
+            let beyond = match found.last() {
+                Some(&(_, distance)) => distance > radius,
+                None => true,
+            };
+            if beyond || found.len() < k || k >= self.len() {
+                return found
+                    .into_iter()
+                    .take_while(|&(_, distance)| distance <= radius)
+                    .map(|(index, _)| index)
+                    .collect();
+            }
+            k *= 2;
+        }
+    }
+
+    /// A point by its index with its distance to `query`.
+    fn candidate(&self, query: &A, index: usize) -> Candidate {
+        Candidate {
+            distance: self.metric.distance(query, &self.points[index]),
+            index,
+        }
+    }
+
+    /// At most `ef` points nearest to `query` on a layer, reachable from `entries`,
+    /// the nearest first.
+    fn search_layer(
+        &self,
+        query: &A,
+        entries: Vec<Candidate>,
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entries.iter().map(|entry| entry.index).collect();
+        let mut to_visit: BinaryHeap<Reverse<Candidate>> =
+            entries.iter().copied().map(Reverse).collect();
+        let mut found: BinaryHeap<Candidate> = entries.into_iter().collect();
+        while found.len() > ef {
+            found.pop();
+        }
+
+        while let Some(Reverse(closest)) = to_visit.pop() {
+            match found.peek() {
+                Some(farthest) if found.len() >= ef && closest.distance > farthest.distance => {
+                    break
+                }
+                _ => {}
+            }
+            for &neighbor in &self.links[closest.index][layer] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let candidate = self.candidate(query, neighbor);
+                let farther = match found.peek() {
+                    Some(farthest) => candidate.distance >= farthest.distance,
+                    None => false,
+                };
+                if found.len() < ef || !farther {
+                    to_visit.push(Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Keep the nearest neighbors of a point on a layer, as many as it can have.
+    fn prune(&mut self, index: usize, layer: usize) {
+        let capacity = match layer {
+            0 => self.params.links * 2,
+            _ => self.params.links,
+        };
+        if self.links[index][layer].len() <= capacity {
+            return;
+        }
+        let point = &self.points[index];
+        let mut neighbors: Vec<Candidate> = self.links[index][layer]
+            .iter()
+            .map(|&neighbor| self.candidate(point, neighbor))
+            .collect();
+        neighbors.sort_unstable();
+        self.links[index][layer] = neighbors
+            .into_iter()
+            .take(capacity)
+            .map(|candidate| candidate.index)
+            .collect();
+    }
+
+    /// Layer of a new point, each one has `links` times fewer points than the one below.
+    fn random_layer(&mut self) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        // Uniform in (0, 1], so that its logarithm is finite.
+        let uniform = ((self.state >> 11) + 1) as f64 / (1u64 << 53) as f64;
+        let scale = 1. / (self.params.links.max(2) as f64).ln();
+        (-uniform.ln() * scale) as usize
+    }
+
+    /// Save the graph to a file, without its points.
+    ///
+    /// It is text: a header, the parameters and the entry point, then neighbors
+    /// of every point on a line, layers separated by `;`, neighbors by `,`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::index::{hnsw::Hnsw, metric::Hamming};
+    /// let hashes: Vec<u64> = (0..300u64).map(|i| i.wrapping_mul(0x9e3779b97f4a7c15)).collect();
+    /// let graph = Hnsw::new(&hashes);
+    /// let path = std::env::temp_dir().join("succotash-hnsw-doctest");
+    /// graph.save(&path).unwrap();
+    /// assert!(Hnsw::load(&path, hashes[1..].to_vec(), Hamming).is_err());
+    /// let loaded = Hnsw::load(&path, hashes.clone(), Hamming).unwrap();
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert_eq!(loaded.nearest(&hashes[7], 3), graph.nearest(&hashes[7], 3));
+    /// ```
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{}", HEADER)?;
+        let entry = match self.entry {
+            Some(entry) => entry.to_string(),
+            None => "-".to_owned(),
+        };
+        writeln!(
+            file,
+            "{} {} {} {} {}",
+            self.params.links,
+            self.params.ef_construction,
+            self.params.ef_search,
+            self.state,
+            entry
+        )?;
+        for layers in &self.links {
+            let layers: Vec<String> = layers
+                .iter()
+                .map(|neighbors| {
+                    let neighbors: Vec<String> = neighbors.iter().map(usize::to_string).collect();
+                    neighbors.join(",")
+                })
+                .collect();
+            writeln!(file, "{}", layers.join(";"))?;
+        }
+        file.flush()
+    }
+
+    /// Load a graph saved with [`save`](Self::save), with the points it was built of.
+    ///
+    /// Fails if the file isn't a graph or is of a different number of points.
+    pub fn load<P: AsRef<Path>>(path: P, points: Vec<A>, metric: M) -> io::Result<Self> {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_owned());
+        let file = io::BufReader::new(std::fs::File::open(path)?);
+        let mut lines = file.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid("not an HNSW graph of this version"));
+        }
+        let parameters = lines.next().transpose()?.unwrap_or_default();
+        let parameters: Vec<&str> = parameters.split(' ').collect();
+        let (params, state, entry) = match parameters[..] {
+            [links, ef_construction, ef_search, state, entry] => {
+                let number = |word: &str| word.parse().map_err(|_| invalid("malformed parameters"));
+                let params = Params {
+                    links: number(links)?,
+                    ef_construction: number(ef_construction)?,
+                    ef_search: number(ef_search)?,
+                };
+                let state = state.parse().map_err(|_| invalid("malformed parameters"))?;
+                let entry = match entry {
+                    "-" => None,
+                    entry => Some(number(entry)?),
+                };
+                (params, state, entry)
+            }
+            _ => return Err(invalid("malformed parameters")),
+        };
+
+        let mut links = Vec::with_capacity(points.len());
+        for line in lines {
+            let layers: Result<Vec<Vec<usize>>, _> = line?
+                .split(';')
+                .map(|neighbors| {
+                    neighbors
+                        .split(',')
+                        .filter(|neighbor| !neighbor.is_empty())
+                        .map(str::parse)
+                        .collect()
+                })
+                .collect();
+            links.push(layers.map_err(|_| invalid("malformed neighbors"))?);
+        }
+        let valid = links.len() == points.len()
+            && entry.map_or(points.is_empty(), |entry| entry < points.len())
+            && links.iter().flatten().flatten().all(|&n| n < points.len());
+        if !valid {
+            return Err(invalid("the graph is of other points"));
+        }
+        Ok(Self {
+            points,
+            metric,
+            params,
+            links,
+            entry,
+            state,
+        })
+    }
+}
+
+impl super::Near for Hnsw {
+    fn near(&self, query: u64, radius: u32) -> Vec<usize> {
+        self.within_distance(&query, f64::from(radius))
+    }
+}
@@ -0,0 +1,68 @@
+//! Distances between points that index structures search by.
+//!
+//! A [`Metric`] tells how far apart two points are. Structures like
+//! [`VpTree`](super::vptree::VpTree) rely on the triangle inequality to skip
+//! points, so they work with any metric: [`Hamming`] between hashes,
+//! or [`Cosine`] between embeddings of images.
+
+use crate::analyze::features::set;
+use std::sync::Arc;
+
+/// Distance between points of type `A`.
+///
+/// It has to be a metric: zero only between equal points, symmetric,
+/// and never more than the distances through a third point add up to.
+pub trait Metric<A: ?Sized>: Send + Sync {
+    /// How far apart `a` and `b` are.
+    fn distance(&self, a: &A, b: &A) -> f64;
+}
+
+/// Number of bits two hashes differ in.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::metric::{Hamming, Metric};
+/// assert_eq!(Hamming.distance(&0b1010u64, &0b0110), 2.);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Hamming;
+
+impl Metric<u64> for Hamming {
+    fn distance(&self, a: &u64, b: &u64) -> f64 {
+        f64::from((a ^ b).count_ones())
+    }
+}
+
+/// Angle between two vectors, from 0 for the same direction to 1 for opposite ones,
+/// see [`set::angular_distance`].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::metric::{Cosine, Metric};
+/// let (a, b, c) = ([1., 0.], [0., 2.], [-3., 0.]);
+/// assert!(Cosine.distance(&a[..], &[2., 0.][..]) < 1e-6);
+/// assert!((Cosine.distance(&a[..], &b[..]) - 0.5).abs() < 1e-6);
+/// assert!((Cosine.distance(&a[..], &c[..]) - 1.).abs() < 1e-6);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cosine;
+
+impl Metric<[f32]> for Cosine {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f64 {
+        set::angular_distance(a, b)
+    }
+}
+
+impl Metric<Vec<f32>> for Cosine {
+    fn distance(&self, a: &Vec<f32>, b: &Vec<f32>) -> f64 {
+        Metric::<[f32]>::distance(self, a, b)
+    }
+}
+
+impl Metric<Arc<[f32]>> for Cosine {
+    fn distance(&self, a: &Arc<[f32]>, b: &Arc<[f32]>) -> f64 {
+        Metric::<[f32]>::distance(self, a, b)
+    }
+}
@@ -0,0 +1,417 @@
+//! Indexes of image features.
+//!
+//! Structures that keep features of many images, to be searched
+//! for similar images or to be reused between runs.
+//!
+//! An index file is a [`store`] of features. It is filled by analyses as they go,
+//! or by [`build`], and answers [queries](query) for images similar to a given one
+//! without walking the library again.
+
+pub mod bands;
+pub mod checkpoint;
+pub mod hnsw;
+pub mod metric;
+pub mod quarantine;
+pub mod query;
+pub mod sidecar;
+pub mod store;
+pub mod vptree;
+#[cfg(feature = "xattrs")]
+pub mod xattrs;
+
+use super::features::{schema::Schema, set::FeatureSet, ImgFeatures};
+use super::group::{self, Group};
+use super::img::{Img, ImgRaw};
+use super::{find_features, score, walker, AnalyzeOptions};
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::io::Write;
+
+/// A structure to find close hashes in.
+pub trait Near: Send + Sync {
+    /// Indices of hashes at most `radius` bits away from `query`, in no particular order.
+    fn near(&self, query: u64, radius: u32) -> Vec<usize>;
+}
+
+/// How candidates for similar images are searched for, see
+/// [`group::candidates`](super::group::candidates).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Search {
+    /// Compare every image with every other one, best for small datasets.
+    #[default]
+    Brute,
+    /// Only compare images whose hashes are close, found in a [`vptree::VpTree`].
+    VpTree,
+    /// Only compare images whose hashes share a band, see [`bands`].
+    /// Has the number of bands.
+    Bands(usize),
+    /// Only compare images whose hashes are close, found in a [`hnsw::Hnsw`] graph.
+    /// Fastest on the largest libraries, but may miss a few similar images.
+    Hnsw,
+}
+
+impl Search {
+    /// Name of the search, as accepted by [`from_str`](std::str::FromStr::from_str).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Brute => "brute",
+            Self::VpTree => "vp-tree",
+            Self::Bands(_) => "bands",
+            Self::Hnsw => "hnsw",
+        }
+    }
+
+    /// Build what this search finds close hashes in, `None` if it compares all of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::index::Search;
+    /// let hashes = [0b0000u64, 0b0001, 0b1111_0000];
+    /// assert!(Search::Brute.build(&hashes).is_none());
+    /// for search in [Search::VpTree, Search::Bands(8), Search::Hnsw] {
+    ///     let mut found = search.build(&hashes).unwrap().near(0, 1);
+    ///     found.sort_unstable();
+    ///     assert_eq!(found, vec![0, 1]);
+    /// }
+    /// ```
+    pub fn build(&self, hashes: &[u64]) -> Option<Box<dyn Near>> {
+        match self {
+            Self::Brute => None,
+            Self::VpTree => Some(Box::new(vptree::VpTree::new(hashes))),
+            Self::Bands(count) => Some(Box::new(bands::Bands::new(hashes, *count))),
+            Self::Hnsw => Some(Box::new(hnsw::Hnsw::new(hashes))),
+        }
+    }
+}
+
+impl std::str::FromStr for Search {
+    type Err = String;
+
+    /// Parse a search by its name, bands are [`bands::DEFAULT_BANDS`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "brute" => Ok(Self::Brute),
+            "vp-tree" => Ok(Self::VpTree),
+            "bands" => Ok(Self::Bands(bands::DEFAULT_BANDS)),
+            "hnsw" => Ok(Self::Hnsw),
+            _ => Err(format!("unknown search '{}'", s)),
+        }
+    }
+}
+
+/// Compact an index file, see [`store::Store::compact`].
+///
+/// # Arguments
+///
+/// * `path` - where the index file is.
+fn try_compact(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut store = store::Store::open(path)?;
+    debug!(
+        "Index '{}' has {} live entries and {} dead records",
+        path,
+        store.len(),
+        store.dead()
+    );
+
+    let compaction = store.compact()?;
+    info!(
+        "Compacted index '{}': dropped {} dead records, reclaimed {} bytes",
+        path,
+        compaction.dropped,
+        compaction.reclaimed()
+    );
+    Ok(())
+}
+
+/// Compact an index file, do not propagate errors.
+///
+/// You can think of it as of `main` of the `index compact` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::index;
+/// index::compact("/home/user/.cache/succotash/index");
+/// ```
+pub fn compact(path: &str) -> Status {
+    match try_compact(path) {
+        Ok(_) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'index compact': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Build an index of files in directories, see [`build`].
+///
+/// Files already in the index are not analyzed again, see [`AnalyzeOptions::resume`].
+/// Returns how many images are in the index.
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `options` - what files to pick, how to find their features and
+///   [where the index is](AnalyzeOptions::index).
+pub async fn try_build(
+    dirs: Vec<String>,
+    options: &AnalyzeOptions,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let index = options.index.as_ref().ok_or("there is no index to build")?;
+    let files = walker::Source::Walk(dirs)
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+    let count = files.paths.len();
+    let imgs = find_features(files.paths, options).await?;
+    info!(
+        "Indexed {} images of {} files into '{}'",
+        imgs.len(),
+        count,
+        index.to_string_lossy()
+    );
+    Ok(imgs.len())
+}
+
+/// Build an index of files in directories, do not propagate errors.
+///
+/// You can think of it as of `main` of the `index build` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::{index, AnalyzeOptions};
+/// let options = AnalyzeOptions {
+///     index: Some("/home/user/photos.index".into()),
+///     resume: true,
+///     ..Default::default()
+/// };
+/// async_std::task::block_on(index::build(vec!["/home/user/Pictures".into()], options));
+/// ```
+pub async fn build(dirs: Vec<String>, options: AnalyzeOptions) -> Status {
+    match try_build(dirs, &options).await {
+        Ok(_) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'index build': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Warn about files in an index that were analyzed otherwise than `options` say,
+/// e.g. with other features, so that queries can't compare them properly.
+///
+/// # Arguments
+///
+/// * `snapshot` - the index.
+/// * `index` - where the index file is, for the warning.
+/// * `options` - how features of queried images are found.
+pub fn warn_stale(snapshot: &store::Snapshot, index: &str, options: &AnalyzeOptions) {
+    let schema = Schema::of(options.find_options());
+    let stale = snapshot
+        .entries()
+        .filter(|(path, _)| !snapshot.schema(path).is_some_and(|s| s.satisfies(&schema)))
+        .count();
+    if stale > 0 {
+        warn!(
+            "{} files in '{}' were analyzed otherwise, build the index again to compare them properly",
+            stale, index
+        );
+    }
+}
+
+/// Groups of similar images within an index, sorted by their score.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+///
+/// # Arguments
+///
+/// * `snapshot` - the index.
+/// * `options` - how to search for similar images, how close they are and
+///   how groups are scored.
+pub fn groups(snapshot: &store::Snapshot, options: &AnalyzeOptions) -> Vec<Group> {
+    let imgs: Vec<Img<PathBuf>> = snapshot
+        .entries()
+        .flat_map(|(path, features)| {
+            features.iter().map(move |features| Img {
+                path: path.to_path_buf(),
+                features: features.clone(),
+            })
+        })
+        .collect();
+    let features = options
+        .features
+        .clone()
+        .with_max_distance("lshash", f64::from(options.max_distance));
+    let pairs = group::candidates(&imgs, &features, options.search, &options.cancel);
+    let mut groups = group::group_pairs(imgs, &pairs);
+    score::sort(&mut groups, &options.weights, &features);
+    groups
+}
+
+/// Find files in an index with images similar to those in a file, see [`query`].
+///
+/// # Arguments
+///
+/// * `image` - the file to find similar images to, or its URL with the `urls`
+///   feature, see [`download`](crate::analyze::download).
+/// * `index` - where the index file is, or a directory to analyze instead
+///   the way [`build`] does, without saving what is found.
+/// * `options` - how to find features of `image`, how to search the index
+///   and [how close](AnalyzeOptions::max_distance) similar images are.
+pub async fn try_query(
+    image: PathBuf,
+    index: &str,
+    options: &AnalyzeOptions,
+) -> Result<Vec<query::Match>, Box<dyn std::error::Error>> {
+    let snapshot = if Path::new(index).is_dir().await {
+        let files = walker::Source::Walk(vec![index.to_owned()])
+            .files(&options.walk, &options.cancel, &options.progress)
+            .await?;
+        let imgs = find_features(files.paths, options).await?;
+        store::Snapshot::of(imgs, Schema::of(options.find_options()))
+    } else {
+        let snapshot = store::Snapshot::open(index)?;
+        warn_stale(&snapshot, index, options);
+        snapshot
+    };
+    debug!("Index '{}' has {} files", index, snapshot.len());
+    let query = query::Query::new(&snapshot, options.search);
+    similar_to(&query, image, options, options.max_distance).await
+}
+
+/// Find files with images similar to those in a file in an index prepared for queries.
+///
+/// The file itself is left out, if it is in the index.
+///
+/// # Arguments
+///
+/// * `query` - the index.
+/// * `image` - the file to find similar images to.
+/// * `options` - how to find features of `image` and what features of
+///   similar images are close enough.
+/// * `max_distance` - how many bits hashes of similar images can differ in.
+pub async fn similar_to(
+    query: &query::Query<'_>,
+    image: PathBuf,
+    options: &AnalyzeOptions,
+    max_distance: u32,
+) -> Result<Vec<query::Match>, Box<dyn std::error::Error>> {
+    let features = features_of(image.clone(), options).await?;
+    let set = options
+        .features
+        .clone()
+        .with_max_distance("lshash", f64::from(max_distance));
+    Ok(similar_to_any(query, &image, &features, &set))
+}
+
+/// Features of every image in a file, e.g. of every keyframe of a video.
+///
+/// # Arguments
+///
+/// * `image` - the file to find features of.
+/// * `options` - how to find them.
+pub async fn features_of(
+    image: PathBuf,
+    options: &AnalyzeOptions,
+) -> Result<Vec<ImgFeatures>, Box<dyn std::error::Error>> {
+    let imgs_raw = ImgRaw::load_all(image).await?;
+    Ok(imgs_raw
+        .iter()
+        .map(|img_raw| ImgFeatures::find_with(img_raw, options.find_options()))
+        .collect())
+}
+
+/// Files of an index with images similar to any of the images of a file, the closest first.
+///
+/// # Arguments
+///
+/// * `query` - the index.
+/// * `image` - the file, left out if it is in the index.
+/// * `features` - features of its images, see [`features_of`].
+/// * `set` - what features of similar images are close enough.
+pub fn similar_to_any(
+    query: &query::Query<'_>,
+    image: &Path,
+    features: &[ImgFeatures],
+    set: &FeatureSet,
+) -> Vec<query::Match> {
+    let mut found: Vec<query::Match> = Vec::new();
+    for features in features {
+        for similar in query.similar(features, set) {
+            if similar.path == image {
+                continue;
+            }
+            match found.iter_mut().find(|found| found.path == similar.path) {
+                Some(found) => found.distance = found.distance.min(similar.distance),
+                None => found.push(similar),
+            }
+        }
+    }
+    found.sort_by(|a, b| {
+        a.distance
+            .cmp(&b.distance)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    found
+}
+
+/// Print files in an index with images similar to those in a file, do not propagate errors.
+///
+/// Every line has the distance and the path, separated by a tab, the closest first,
+/// on stdout, or in `output` if given.
+/// You can think of it as of `main` of the `index query` subcommand.
+/// Returns [`Status::Found`] if similar images were found, and
+/// [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::index;
+/// async_std::task::block_on(index::query(
+///     "/home/user/Downloads/sunset.jpg".into(),
+///     "/home/user/photos.index",
+///     Default::default(),
+///     None,
+/// ));
+/// ```
+pub async fn query(
+    image: PathBuf,
+    index: &str,
+    options: AnalyzeOptions,
+    output: Option<PathBuf>,
+) -> Status {
+    let written = try_query(image, index, &options)
+        .await
+        .and_then(|found| write_matches(&found, output.as_deref()));
+    match written {
+        Ok(status) => status,
+        Err(e) => {
+            error!("Error during 'index query': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Print files with similar images the way [`query`] does.
+///
+/// Returns [`Status::Found`] if there are any.
+pub fn write_matches(
+    found: &[query::Match],
+    output: Option<&Path>,
+) -> Result<Status, Box<dyn std::error::Error>> {
+    info!("Found {} files with similar images", found.len());
+    let mut writer = super::output::open(output)?;
+    for found in found {
+        writeln!(
+            writer,
+            "{}\t{}",
+            found.distance,
+            found.path.to_string_lossy()
+        )?;
+    }
+    writer.flush()?;
+    Ok(Status::found(found.len()))
+}
@@ -0,0 +1,162 @@
+//! Files that couldn't be analyzed, kept next to an index.
+//!
+//! A file that fails to decode, e.g. a truncated JPEG, fails again on every
+//! run. An analysis with an [index](crate::analyze::AnalyzeOptions::index)
+//! puts such files into the quarantine list of the index with the reason they
+//! failed, and later analyses skip them instead of failing them again, unless
+//! they are asked to [retry](crate::analyze::AnalyzeOptions::retry_quarantined).
+//! A retried file that is analyzed fine leaves the quarantine.
+//!
+//! # Format
+//!
+//! The list is a text file next to the index, named after it with
+//! [`EXTENSION`] appended. The first line is [`HEADER`], every other line
+//! is a tab-separated [escaped](super::store::escape) path and the reason.
+
+use super::store::{escape, unescape};
+use crate::analyze::names;
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+/// The first line of a quarantine file, holds the version of the format.
+pub const HEADER: &str = "succotash-quarantine 1";
+
+/// Appended to the name of an index to name its quarantine file.
+pub const EXTENSION: &str = "quarantine";
+
+/// The quarantine file of an index.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::quarantine;
+/// let path = quarantine::path_of("/home/user/.cache/succotash/index".as_ref());
+/// assert_eq!(path, async_std::path::PathBuf::from("/home/user/.cache/succotash/index.quarantine"));
+/// ```
+pub fn path_of(index: &Path) -> PathBuf {
+    let mut path = index.as_os_str().to_owned();
+    path.push(".");
+    path.push(EXTENSION);
+    path.into()
+}
+
+/// Files that couldn't be analyzed and why, see the [module](self).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::index::quarantine::Quarantine;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut quarantine = Quarantine::open("/home/user/.cache/succotash/index".as_ref())?;
+/// quarantine.insert("/home/user/Pictures/cut.jpg".as_ref(), "unexpected end of file");
+/// quarantine.save()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Quarantine {
+    /// Where the quarantine file is.
+    path: PathBuf,
+    /// Paths as they were put and why they failed, by their [keys](names::key).
+    entries: BTreeMap<PathBuf, (PathBuf, String)>,
+}
+
+impl Quarantine {
+    /// Read the quarantine list of an index, empty if there is none yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - where the index file is.
+    pub fn open(index: &Path) -> io::Result<Self> {
+        let path = path_of(index);
+        let mut entries = BTreeMap::new();
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self { path, entries }),
+            Err(e) => return Err(e),
+        };
+        let mut lines = io::BufReader::new(file).lines();
+        match lines.next().transpose()? {
+            Some(header) if header == HEADER => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("'{}' is not a quarantine list", path.to_string_lossy()),
+                ))
+            }
+        }
+        for line in lines {
+            let line = line?;
+            if let Some((escaped, reason)) = line.split_once('\t') {
+                let file = PathBuf::from(unescape(escaped));
+                entries.insert(names::key(&file), (file, unescape(reason)));
+            }
+        }
+        Ok(Self { path, entries })
+    }
+
+    /// Where the quarantine file is.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Why a file at `path` couldn't be analyzed, if it is quarantined.
+    pub fn get(&self, path: &Path) -> Option<&str> {
+        self.entries
+            .get(&names::key(path))
+            .map(|(_, reason)| reason.as_str())
+    }
+
+    /// Quarantine a file at `path`, or update why it is.
+    pub fn insert(&mut self, path: &Path, reason: &str) {
+        let entry = (path.to_path_buf(), reason.to_owned());
+        self.entries.insert(names::key(path), entry);
+    }
+
+    /// Let a file at `path` out of the quarantine.
+    ///
+    /// Returns whether it was there.
+    pub fn remove(&mut self, path: &Path) -> bool {
+        self.entries.remove(&names::key(path)).is_some()
+    }
+
+    /// Quarantined files and why they couldn't be analyzed, ordered by path.
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &str)> {
+        self.entries
+            .values()
+            .map(|(path, reason)| (path.as_path(), reason.as_str()))
+    }
+
+    /// How many files are quarantined.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no files are quarantined.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the list, replacing the file atomically.
+    ///
+    /// An empty list removes the file.
+    pub fn save(&self) -> io::Result<()> {
+        if self.entries.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        let mut temporary = self.path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let mut file = io::BufWriter::new(std::fs::File::create(&temporary)?);
+        writeln!(file, "{}", HEADER)?;
+        for (path, reason) in self.entries() {
+            // Reasons are single lines, but errors of decoders are free text.
+            let (path, reason) = (escape(&path.to_string_lossy()), escape(reason));
+            writeln!(file, "{}\t{}", path, reason)?;
+        }
+        file.into_inner()?.sync_data()?;
+        std::fs::rename(temporary, &self.path)
+    }
+}
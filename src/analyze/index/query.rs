@@ -0,0 +1,199 @@
+//! Finding images similar to a given one in an index.
+//!
+//! Features of the library are read from an index built beforehand, e.g. with
+//! [`build`](super::build), so only the queried image is analyzed. Hashes of
+//! the index are put into a [`Search`] structure once, and every query
+//! only compares the images it finds.
+//!
+//! Features whose values are [vectors](Value::Vector), e.g. embeddings, are put
+//! into a [`VpTree`] by [`Cosine`] distance too, or into an [`Hnsw`] graph with
+//! [`Search::Hnsw`], which stays fast with millions of long vectors. Images are
+//! searched for by them when hashes aren't compared.
+
+use super::hnsw::{Hnsw, Params};
+use super::metric::Cosine;
+use super::store::Snapshot;
+use super::vptree::VpTree;
+use super::{Near, Search};
+use crate::analyze::features::set::{FeatureSet, Value};
+use crate::analyze::features::ImgFeatures;
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// An image of the index similar to the queried one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    /// The file the image is in.
+    pub path: PathBuf,
+    /// Hamming distance between hashes of the image and the queried one.
+    pub distance: u32,
+}
+
+/// Images of an index, ready to be queried.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::features::{set::FeatureSet, ImgFeatures};
+/// # use libsuccotash::analyze::index::{query::Query, store::Snapshot, Search};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let snapshot = Snapshot::open("/home/user/photos.index")?;
+/// let query = Query::new(&snapshot, Search::Bands(8));
+/// let features = ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2").unwrap();
+/// for found in query.similar(&features, FeatureSet::standard()) {
+///     println!("{}\t{}", found.distance, found.path.to_string_lossy());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Query<'a> {
+    /// Every image of the index with the file it is in.
+    imgs: Vec<(&'a Path, &'a ImgFeatures)>,
+    /// Where to find close hashes in, if not comparing all of them.
+    near: Option<Box<dyn Near>>,
+    /// Where to find close vectors in, by names of their features.
+    /// Empty if comparing all images.
+    vectors: BTreeMap<String, Vectors>,
+}
+
+/// Vectors of a feature of images that have it.
+struct Vectors {
+    /// Indices of the images into [`Query::imgs`].
+    indices: Vec<usize>,
+    /// The vectors, in the same order.
+    near: NearVectors,
+}
+
+/// A structure to find close vectors in.
+enum NearVectors {
+    /// Finds all of them, see [`Search::VpTree`].
+    VpTree(VpTree<Arc<[f32]>, Cosine>),
+    /// Finds most of them, see [`Search::Hnsw`].
+    Hnsw(Hnsw<Arc<[f32]>, Cosine>),
+}
+
+impl NearVectors {
+    /// Indices of vectors at most `radius` away from `query`, in no particular order.
+    fn within_distance(&self, query: &Arc<[f32]>, radius: f64) -> Vec<usize> {
+        match self {
+            Self::VpTree(tree) => tree.within_distance(query, radius),
+            Self::Hnsw(graph) => graph.within_distance(query, radius),
+        }
+    }
+}
+
+impl<'a> Query<'a> {
+    /// Prepare images of a snapshot for queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - the index.
+    /// * `search` - how to find images to compare.
+    pub fn new(snapshot: &'a Snapshot, search: Search) -> Self {
+        let imgs: Vec<(&Path, &ImgFeatures)> = snapshot
+            .entries()
+            .flat_map(|(path, features)| features.iter().map(move |features| (path, features)))
+            .collect();
+        let hashes: Vec<u64> = imgs
+            .iter()
+            .map(|(_, features)| features.lshash.bits())
+            .collect();
+        let vectors = match search {
+            Search::Brute => BTreeMap::new(),
+            _ => vectors(&imgs, search),
+        };
+        Self {
+            near: search.build(&hashes),
+            imgs,
+            vectors,
+        }
+    }
+
+    /// Indices of images whose vectors are close to those of `features`,
+    /// `None` if no feature of `set` with vectors is searchable.
+    fn near_vectors(&self, features: &ImgFeatures, set: &FeatureSet) -> Option<Vec<usize>> {
+        self.vectors.iter().find_map(|(name, vectors)| {
+            let radius = set.max_distance(name)?;
+            match features.custom.get(name) {
+                Some(Value::Vector(vector)) if !vector.is_empty() => Some(
+                    vectors
+                        .near
+                        .within_distance(vector, radius)
+                        .into_iter()
+                        .map(|i| vectors.indices[i])
+                        .collect(),
+                ),
+                _ => None,
+            }
+        })
+    }
+
+    /// Files with images similar to an image with `features`, the closest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - features of the queried image.
+    /// * `set` - what features of similar images are close enough,
+    ///   hashes are searched for if it has them, then vectors,
+    ///   every image is compared if it has neither.
+    pub fn similar(&self, features: &ImgFeatures, set: &FeatureSet) -> Vec<Match> {
+        let radius = set.max_distance("lshash");
+        let candidates: Vec<usize> = match (&self.near, radius) {
+            (Some(near), Some(radius)) => near.near(features.lshash.bits(), radius.max(0.) as u32),
+            _ => self
+                .near_vectors(features, set)
+                .unwrap_or_else(|| (0..self.imgs.len()).collect()),
+        };
+
+        let mut found: Vec<Match> = Vec::new();
+        for i in candidates {
+            let (path, other) = self.imgs[i];
+            if !features.comparable(other) || !set.similar(features, other) {
+                continue;
+            }
+            let distance = features.lshash.distance(&other.lshash);
+            // Keyframes of a video are one file, keep the closest one.
+            match found.iter_mut().find(|found| found.path == path) {
+                Some(found) => found.distance = found.distance.min(distance),
+                None => found.push(Match {
+                    path: path.to_path_buf(),
+                    distance,
+                }),
+            }
+        }
+        found.sort_by(|a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        found
+    }
+}
+
+/// Non-empty vectors of `imgs` by names of their features,
+/// in an [`Hnsw`] graph with [`Search::Hnsw`] and in a [`VpTree`] otherwise.
+fn vectors(imgs: &[(&Path, &ImgFeatures)], search: Search) -> BTreeMap<String, Vectors> {
+    let mut found = BTreeMap::new();
+    for (i, (_, features)) in imgs.iter().enumerate() {
+        for (name, value) in &features.custom {
+            if let Value::Vector(vector) = value {
+                if !vector.is_empty() {
+                    let found: &mut Vec<(usize, Arc<[f32]>)> = found.entry(name).or_default();
+                    found.push((i, Arc::clone(vector)));
+                }
+            }
+        }
+    }
+    found
+        .into_iter()
+        .map(|(name, found)| {
+            let (indices, vectors): (Vec<usize>, Vec<_>) = found.into_iter().unzip();
+            let near = match search {
+                Search::Hnsw => NearVectors::Hnsw(Hnsw::build(&vectors, Cosine, Params::default())),
+                _ => NearVectors::VpTree(VpTree::with_metric(&vectors, Cosine)),
+            };
+            (String::from(name), Vectors { indices, near })
+        })
+        .collect()
+}
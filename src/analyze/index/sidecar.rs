@@ -0,0 +1,268 @@
+//! Saving features to XMP sidecars of files.
+//!
+//! Photo managers like digiKam, darktable and Lightroom keep metadata of every
+//! image in an XMP file next to it, a sidecar. Features are saved to one more
+//! `rdf:Description` in the [`NAMESPACE`], other metadata in the sidecar is left
+//! as it is. Like records of a [`store`](super::store), sidecars hold the
+//! [schema](Schema) features were found with, and features found otherwise
+//! are found again.
+//!
+//! A sidecar of `a.jpg` is `a.jpg.xmp`, like digiKam and darktable name them,
+//! or `a.xmp`, like Lightroom does. The latter can be shared by `a.jpg` and
+//! `a.cr2`, so every description has the name of the file it is of.
+
+use crate::analyze::features::schema::Schema;
+use crate::analyze::features::ImgFeatures;
+use async_std::path::{Path, PathBuf};
+use std::io;
+
+/// XML namespace of the properties features are saved to.
+pub const NAMESPACE: &str = "https://github.com/ftvkyo/succotash/xmp/1.0/";
+
+/// Extension of sidecars, files with it are never analyzed.
+pub const EXTENSION: &str = "xmp";
+
+/// Whether features are read from and saved to sidecars.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Mode {
+    /// Sidecars are not touched.
+    #[default]
+    Ignore,
+    /// Features in sidecars are reused, nothing is written.
+    Read,
+    /// Features in sidecars are reused, and features that are found are saved.
+    Write,
+}
+
+/// Whether a file at `path` is a sidecar, judging by its extension.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::sidecar;
+/// assert!(sidecar::is_sidecar("/photos/a.jpg.xmp".as_ref()));
+/// assert!(sidecar::is_sidecar("/photos/A.XMP".as_ref()));
+/// assert!(!sidecar::is_sidecar("/photos/a.jpg".as_ref()));
+/// ```
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(EXTENSION))
+}
+
+/// Where a sidecar of a file at `path` can be, the preferred place first.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::sidecar;
+/// let [full, short] = sidecar::paths("/photos/a.jpg".as_ref());
+/// assert_eq!(full.to_str(), Some("/photos/a.jpg.xmp"));
+/// assert_eq!(short.to_str(), Some("/photos/a.xmp"));
+/// ```
+pub fn paths(path: &Path) -> [PathBuf; 2] {
+    let mut full = path.as_os_str().to_os_string();
+    full.push(".");
+    full.push(EXTENSION);
+    [full.into(), path.with_extension(EXTENSION)]
+}
+
+/// Features of all images in a file at `path`, if they were saved to its
+/// sidecar and their schema [satisfies](Schema::satisfies) `wanted`.
+pub fn load(path: &Path, wanted: &Schema) -> io::Result<Option<Vec<ImgFeatures>>> {
+    let file = file_name(path);
+    for sidecar in paths(path) {
+        let xmp = match std::fs::read_to_string(&sidecar) {
+            Ok(xmp) => xmp,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let description = match descriptions(&xmp)
+            .filter(|d| property(d, "file").as_deref() == Some(file.as_str()))
+            .last()
+        {
+            Some(description) => description,
+            None => continue,
+        };
+        let schema = property(description, "schema").and_then(|s| Schema::decode(&s));
+        if !schema.is_some_and(|schema| schema.satisfies(wanted)) {
+            return Ok(None);
+        }
+        return Ok(property(description, "features").and_then(|f| decode(&f)));
+    }
+    Ok(None)
+}
+
+/// Save features of all images in a file at `path` to its sidecar.
+///
+/// Features go to the sidecar that exists already, or to a new one in the
+/// preferred place. Features saved to it earlier are replaced, other
+/// metadata is kept. The sidecar is replaced atomically.
+///
+/// # Arguments
+///
+/// * `path` - the file.
+/// * `schema` - how the features were found.
+/// * `features` - features of all images in the file.
+pub fn save(path: &Path, schema: &Schema, features: &[ImgFeatures]) -> io::Result<()> {
+    let [full, short] = paths(path);
+    let sidecar = match std::fs::metadata(&short) {
+        Ok(_) if std::fs::metadata(&full).is_err() => short,
+        _ => full,
+    };
+    let xmp = match std::fs::read_to_string(&sidecar) {
+        Ok(xmp) => xmp,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let encoded: Vec<String> = features.iter().map(ImgFeatures::encode).collect();
+    let hashes: Vec<String> = features
+        .iter()
+        .map(|features| format!("{:016x}", features.lshash.bits()))
+        .collect();
+    let description = format!(
+        "  <rdf:Description rdf:about=\"\"\n    xmlns:succotash=\"{}\"\n    succotash:file=\"{}\"\n    succotash:schema=\"{}\"\n    succotash:lshash=\"{}\"\n    succotash:features=\"{}\"/>\n",
+        NAMESPACE,
+        escape(&file_name(path)),
+        escape(&schema.encode()),
+        hashes.join(" "),
+        escape(&encoded.join(";")),
+    );
+    let xmp = replace_description(&xmp, &file_name(path), &description)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no rdf:RDF in the sidecar"))?;
+
+    let mut temp = sidecar.as_os_str().to_os_string();
+    temp.push(".succotash");
+    std::fs::write(&temp, xmp)?;
+    std::fs::rename(&temp, &sidecar)
+}
+
+/// Name of a file, as it is saved to its sidecar.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Descriptions in an XMP document, each up to the next one.
+fn descriptions(xmp: &str) -> impl Iterator<Item = &str> {
+    xmp.split("<rdf:Description").skip(1)
+}
+
+/// Decode features of all images, separated by semicolons.
+fn decode(features: &str) -> Option<Vec<ImgFeatures>> {
+    if features.is_empty() {
+        return Some(Vec::new());
+    }
+    features.split(';').map(ImgFeatures::decode).collect()
+}
+
+/// Put `description` of a file named `file` into an XMP document in place of
+/// the one written earlier, or last if there is none.
+///
+/// `None` if it isn't an XMP document.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::sidecar::replace_description;
+/// let a = "  <rdf:Description succotash:file=\"a.jpg\" succotash:schema=\"v1\"/>\n";
+/// let xmp = replace_description("", "a.jpg", a).unwrap();
+/// assert!(xmp.contains("<rdf:Description succotash:file=\"a.jpg\" succotash:schema=\"v1\"/>\n </rdf:RDF>"));
+/// let b = "  <rdf:Description succotash:file=\"a.cr2\"/>\n";
+/// let xmp = replace_description(&xmp, "a.cr2", b).unwrap();
+/// let a = "  <rdf:Description succotash:file=\"a.jpg\" succotash:schema=\"v2\"/>\n";
+/// let xmp = replace_description(&xmp, "a.jpg", a).unwrap();
+/// assert_eq!(xmp.matches("<rdf:Description").count(), 2);
+/// assert!(!xmp.contains("v1"));
+/// ```
+pub fn replace_description(xmp: &str, file: &str, description: &str) -> Option<String> {
+    if xmp.trim().is_empty() {
+        return Some(format!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n{} </rdf:RDF>\n</x:xmpmeta>\n",
+            description
+        ));
+    }
+
+    // Descriptions written earlier are single empty tags with the file.
+    let ours = format!("succotash:file=\"{}\"", escape(file));
+    let mut xmp = xmp.to_owned();
+    let mut from = 0;
+    while let Some(start) = xmp[from..].find("<rdf:Description").map(|i| from + i) {
+        let end = match xmp[start..].find('>') {
+            Some(i) => start + i + 1,
+            None => break,
+        };
+        let tag = &xmp[start..end];
+        if !tag.ends_with("/>") || !tag.contains(&ours) {
+            from = end;
+            continue;
+        }
+        // Take the indentation and the line break too.
+        let start = match xmp[..start].rfind('\n') {
+            Some(i) if xmp[i + 1..start].trim().is_empty() => i + 1,
+            _ => start,
+        };
+        let end = end + usize::from(xmp[end..].starts_with('\n'));
+        xmp.replace_range(start..end, "");
+        from = start;
+    }
+
+    let end = xmp.rfind("</rdf:RDF>")?;
+    let end = match xmp[..end].rfind('\n') {
+        Some(i) if xmp[i + 1..end].trim().is_empty() => i + 1,
+        _ => end,
+    };
+    xmp.insert_str(end, description);
+    Some(xmp)
+}
+
+/// Value of a property in the [`NAMESPACE`], the last one if there are many.
+///
+/// Properties are found both as attributes, as they are written,
+/// and as elements, as other tools may rewrite them.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::sidecar::property;
+/// let xmp = "<rdf:Description succotash:file=\"a &amp; b.jpg\"/>";
+/// assert_eq!(property(xmp, "file").as_deref(), Some("a & b.jpg"));
+/// let xmp = "<rdf:Description><succotash:file>c.jpg</succotash:file></rdf:Description>";
+/// assert_eq!(property(xmp, "file").as_deref(), Some("c.jpg"));
+/// assert_eq!(property(xmp, "schema"), None);
+/// ```
+pub fn property(xmp: &str, name: &str) -> Option<String> {
+    let attribute = format!("succotash:{}=\"", name);
+    let element = format!("<succotash:{}>", name);
+    let value = match (xmp.rfind(&attribute), xmp.rfind(&element)) {
+        (Some(a), Some(e)) if e > a => element_value(&xmp[e + element.len()..])?,
+        (Some(a), _) => xmp[a + attribute.len()..].split('"').next()?,
+        (None, Some(e)) => element_value(&xmp[e + element.len()..])?,
+        (None, None) => return None,
+    };
+    Some(unescape(value))
+}
+
+/// Text of an element up to its end tag.
+fn element_value(rest: &str) -> Option<&str> {
+    rest.find("</").map(|end| &rest[..end])
+}
+
+/// Escape text to be put into an attribute value.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Undo [`escape`], and the same entities written by other tools.
+fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
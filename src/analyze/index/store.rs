@@ -0,0 +1,530 @@
+//! Persistent store of image features.
+//!
+//! The store is an append-only log, so that adding features of a freshly
+//! analyzed file is cheap and a crash can lose at most the last record.
+//! Putting features of a path supersedes the previous ones, removing a path
+//! appends a tombstone. Superseded records and tombstones stay in the file
+//! until it is rewritten with [`Store::compact`].
+//!
+//! Paths are stored as they are, but looked up after Unicode normalization
+//! (see [`names`]), so a path written in NFD finds the entry of the same
+//! path written in NFC, and the other way around.
+//!
+//! # Concurrency
+//!
+//! There can be one writer ([`Store`]) at a time, it holds a lock on the file.
+//! A writer that locks a file a compaction has just replaced opens the new one
+//! instead, so that nothing is written to a file that is gone.
+//! Any number of readers ([`Snapshot`]) can read the file meanwhile, e.g. to
+//! answer queries during a long scan. Readers only see complete records,
+//! and compaction replaces the file atomically, so every reader gets
+//! a consistent view of the store as of some point in time.
+//!
+//! # Format
+//!
+//! The first line is [`HEADER`]. Every other line is a record with tab-separated
+//! fields: `+`, the path, the [encoded](Schema::encode) schema the features were
+//! found with and [encoded](ImgFeatures::encode) features of every image in the
+//! file, or `-` and the path for a tombstone. Records of the previous version
+//! have no schema, their features are found again when an analysis resumes. Backslashes, tabs and
+//! newlines in paths are escaped as `\\`, `\t` and `\n`. Paths that aren't valid
+//! UTF-8 are stored lossily.
+
+use crate::analyze::features::schema::Schema;
+use crate::analyze::features::ImgFeatures;
+use crate::analyze::img::Img;
+use crate::analyze::names;
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+/// The first line of a store file, holds the version of the format.
+pub const HEADER: &str = "succotash-index 6";
+
+/// First lines of store files of older versions that can still be read.
+const OLD_HEADERS: [&str; 1] = ["succotash-index 5"];
+
+/// Features of all images in a file.
+struct Entry {
+    /// The path of the file as it was put.
+    path: PathBuf,
+    /// How the features were found, if it is known.
+    schema: Option<Schema>,
+    features: Vec<ImgFeatures>,
+}
+
+/// Result of [`Store::compact`].
+#[derive(Debug)]
+pub struct Compaction {
+    /// How many records were dropped.
+    pub dropped: usize,
+    /// Size of the file before compaction, in bytes.
+    pub size_before: u64,
+    /// Size of the file after compaction, in bytes.
+    pub size_after: u64,
+}
+
+impl Compaction {
+    /// How many bytes compaction has freed.
+    pub fn reclaimed(&self) -> u64 {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+/// A consistent read-only view of a store file.
+///
+/// Doesn't change once taken, take a new one to see newer entries.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::index::store::Snapshot;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let snapshot = Snapshot::open("/home/user/.cache/succotash/index")?;
+/// for (path, features) in snapshot.entries() {
+///     println!("'{}' has {} images", path.to_string_lossy(), features.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Snapshot {
+    /// Live entries, by [`names::key`] of their paths.
+    entries: BTreeMap<PathBuf, Entry>,
+    /// How many records in the file are superseded or are tombstones.
+    dead: usize,
+}
+
+impl Snapshot {
+    /// Take a snapshot of a store file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - where the store file is.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = std::fs::File::open(path.as_ref())?;
+        let (entries, dead, _) = read_records(io::BufReader::new(file))?;
+        Ok(Self { entries, dead })
+    }
+
+    /// A snapshot of features found without a store file, e.g. of files
+    /// in a directory, see [`find_features`](crate::analyze::find_features).
+    ///
+    /// # Arguments
+    ///
+    /// * `imgs` - images and their features, those of a file one after another.
+    /// * `schema` - how the features were found.
+    pub fn of(imgs: Vec<Img<PathBuf>>, schema: Schema) -> Self {
+        let mut entries: BTreeMap<PathBuf, Entry> = BTreeMap::new();
+        for Img { path, features } in imgs {
+            entries
+                .entry(names::key(&path))
+                .or_insert_with(|| Entry {
+                    path,
+                    schema: Some(schema.clone()),
+                    features: Vec::new(),
+                })
+                .features
+                .push(features);
+        }
+        Self { entries, dead: 0 }
+    }
+
+    /// Features of all images in a file at `path`, if they are stored.
+    pub fn get(&self, path: &Path) -> Option<&[ImgFeatures]> {
+        self.entries
+            .get(&names::key(path))
+            .map(|entry| entry.features.as_slice())
+    }
+
+    /// How features of a file at `path` were found, if they are stored with a schema.
+    pub fn schema(&self, path: &Path) -> Option<&Schema> {
+        self.entries
+            .get(&names::key(path))
+            .and_then(|entry| entry.schema.as_ref())
+    }
+
+    /// All live entries, ordered by [`names::key`] of their paths.
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &[ImgFeatures])> {
+        self.entries
+            .values()
+            .map(|entry| (entry.path.as_path(), entry.features.as_slice()))
+    }
+
+    /// Keep only entries of files whose paths `f` is true for.
+    pub fn retain(&mut self, mut f: impl FnMut(&Path) -> bool) {
+        self.entries.retain(|_, entry| f(&entry.path));
+    }
+
+    /// How many live entries there are.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no live entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// How many records in the file are superseded or are tombstones.
+    pub fn dead(&self) -> usize {
+        self.dead
+    }
+}
+
+/// Lock a store file for writing.
+fn lock(file: &std::fs::File, path: &Path) -> io::Result<()> {
+    file.try_lock().map_err(|e| match e {
+        std::fs::TryLockError::WouldBlock => io::Error::new(
+            io::ErrorKind::WouldBlock,
+            format!(
+                "index '{}' is being written by another process",
+                path.to_string_lossy()
+            ),
+        ),
+        std::fs::TryLockError::Error(e) => e,
+    })
+}
+
+/// Whether an open file is still the one at `path`, rather than one replaced since.
+#[cfg(unix)]
+fn is_at(file: &std::fs::File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let open = file.metadata()?;
+    match std::fs::metadata(path) {
+        Ok(at) => Ok(open.dev() == at.dev() && open.ino() == at.ino()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether an open file is still the one at `path`, assumed to be where
+/// files can't be told apart by their metadata.
+#[cfg(not(unix))]
+fn is_at(_: &std::fs::File, _: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// A store file open for writing.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::index::store::Store;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut store = Store::open("/home/user/.cache/succotash/index")?;
+/// store.remove("/home/user/Pictures/deleted.jpg".as_ref())?;
+/// let compaction = store.compact()?;
+/// println!("Reclaimed {} bytes", compaction.reclaimed());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Store {
+    /// Where the store file is.
+    path: PathBuf,
+    /// The file, open for appending and locked.
+    file: std::fs::File,
+    /// What is in the file.
+    view: Snapshot,
+}
+
+impl Store {
+    /// Open a store file for writing, creating it if it doesn't exist.
+    ///
+    /// Fails if another [`Store`] has the file open.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - where the store file is.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = loop {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(&path)?;
+            lock(&file, &path)?;
+            // A compaction may have replaced the file after it was opened, and
+            // released the lock on the old one, which nothing reads anymore.
+            if is_at(&file, &path)? {
+                break file;
+            }
+            debug!(
+                "Index '{}' was replaced while opening it, opening it again",
+                path.to_string_lossy()
+            );
+        };
+
+        let (entries, dead) = if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", HEADER)?;
+            (BTreeMap::new(), 0)
+        } else {
+            let (entries, dead, complete) = read_records(io::BufReader::new(&file))?;
+            // Drop a record cut short by a crash, so that it isn't glued to the next one.
+            if complete < file.metadata()?.len() {
+                warn!(
+                    "Dropping an incomplete record at the end of '{}'",
+                    path.to_string_lossy()
+                );
+                file.set_len(complete)?;
+            }
+            (entries, dead)
+        };
+
+        Ok(Self {
+            path,
+            file,
+            view: Snapshot { entries, dead },
+        })
+    }
+
+    /// Features of all images in a file at `path`, if they are stored.
+    pub fn get(&self, path: &Path) -> Option<&[ImgFeatures]> {
+        self.view.get(path)
+    }
+
+    /// How features of a file at `path` were found, if they are stored with a schema.
+    pub fn schema(&self, path: &Path) -> Option<&Schema> {
+        self.view.schema(path)
+    }
+
+    /// All live entries, ordered by path.
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &[ImgFeatures])> {
+        self.view.entries()
+    }
+
+    /// How many live entries there are.
+    pub fn len(&self) -> usize {
+        self.view.len()
+    }
+
+    /// Whether there are no live entries.
+    pub fn is_empty(&self) -> bool {
+        self.view.is_empty()
+    }
+
+    /// How many records in the file are superseded or are tombstones.
+    pub fn dead(&self) -> usize {
+        self.view.dead()
+    }
+
+    /// Store features of all images in a file at `path`, superseding the previous ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file.
+    /// * `schema` - how the features were found.
+    /// * `features` - features of all images in the file.
+    pub fn put(
+        &mut self,
+        path: &Path,
+        schema: &Schema,
+        features: Vec<ImgFeatures>,
+    ) -> io::Result<()> {
+        writeln!(self.file, "{}", put_record(path, Some(schema), &features))?;
+        let entry = Entry {
+            path: path.to_path_buf(),
+            schema: Some(schema.clone()),
+            features,
+        };
+        let key = names::key(path);
+        if self.view.entries.insert(key, entry).is_some() {
+            self.view.dead += 1;
+        }
+        Ok(())
+    }
+
+    /// Remove features of a file at `path`.
+    ///
+    /// Returns whether there were any.
+    pub fn remove(&mut self, path: &Path) -> io::Result<bool> {
+        let key = names::key(path);
+        if !self.view.entries.contains_key(&key) {
+            return Ok(false);
+        }
+        // The tombstone goes first, so that the view stays as the file is if writing fails.
+        let tombstone = format!("-\t{}\n", escape(&path.to_string_lossy()));
+        self.file.write_all(tombstone.as_bytes())?;
+        self.file.flush()?;
+        self.view.entries.remove(&key);
+        // Both the removed record and the tombstone itself are dead now.
+        self.view.dead += 2;
+        Ok(true)
+    }
+
+    /// Make sure everything put or removed so far is on disk, e.g. survives a power loss.
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// Rewrite the file with live entries only.
+    ///
+    /// The new file replaces the old one atomically, so readers that
+    /// have the old one open are not disturbed.
+    pub fn compact(&mut self) -> io::Result<Compaction> {
+        let size_before = self.file.metadata()?.len();
+
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".compacting");
+        let temp_path = self.path.with_file_name(temp_name);
+
+        // Left behind by an interrupted compaction, if it exists.
+        match std::fs::remove_file(&temp_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+
+        // The new file is locked before it replaces the old one, so that
+        // no other writer can open it in between.
+        let temp = std::fs::OpenOptions::new()
+            .create_new(true)
+            .read(true)
+            .append(true)
+            .open(&temp_path)?;
+        lock(&temp, &temp_path)?;
+
+        let mut writer = io::BufWriter::new(&temp);
+        writeln!(writer, "{}", HEADER)?;
+        for entry in self.view.entries.values() {
+            let record = put_record(&entry.path, entry.schema.as_ref(), &entry.features);
+            writeln!(writer, "{}", record)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        temp.sync_all()?;
+        std::fs::rename(&temp_path, &self.path)?;
+        self.file = temp;
+
+        let compaction = Compaction {
+            dropped: self.view.dead,
+            size_before,
+            size_after: self.file.metadata()?.len(),
+        };
+        self.view.dead = 0;
+        Ok(compaction)
+    }
+}
+
+fn put_record(path: &Path, schema: Option<&Schema>, features: &[ImgFeatures]) -> String {
+    let mut record = format!("+\t{}", escape(&path.to_string_lossy()));
+    if let Some(schema) = schema {
+        record.push('\t');
+        record.push_str(&schema.encode());
+    }
+    for features in features {
+        record.push('\t');
+        record.push_str(&features.encode());
+    }
+    record
+}
+
+/// Replay records, returning live entries, the number of dead records
+/// and the length of the complete records (including the header) in bytes.
+///
+/// Records that can't be parsed are skipped and counted as dead.
+/// A record without a newline at the end is incomplete: it was either
+/// cut short by a crash or is still being written, so it is skipped too.
+fn read_records<R: BufRead>(mut reader: R) -> io::Result<(BTreeMap<PathBuf, Entry>, usize, u64)> {
+    let mut line = Vec::new();
+    let mut complete = reader.read_until(b'\n', &mut line)? as u64;
+    let header = line.strip_suffix(b"\n").unwrap_or_default();
+    if header != HEADER.as_bytes() && !OLD_HEADERS.iter().any(|old| header == old.as_bytes()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a succotash index or an unsupported version",
+        ));
+    }
+
+    let mut entries = BTreeMap::new();
+    let mut dead = 0;
+    loop {
+        line.clear();
+        let length = reader.read_until(b'\n', &mut line)?;
+        if length == 0 || line.pop() != Some(b'\n') {
+            break;
+        }
+        complete += length as u64;
+
+        let record = String::from_utf8_lossy(&line);
+        let mut fields = record.split('\t').peekable();
+        let kind = fields.next();
+        let path = fields.next().map(|p| PathBuf::from(unescape(p)));
+        // Encoded features start with a hex digit, so they are never taken for a schema.
+        let schema = fields
+            .next_if(|field| field.starts_with('v'))
+            .map(Schema::decode);
+
+        match (kind, path, schema) {
+            (Some("+"), Some(_), Some(None)) => {
+                warn!("Skipping a record with an invalid schema: '{}'", record);
+                dead += 1;
+            }
+            (Some("+"), Some(path), schema) => match fields.map(ImgFeatures::decode).collect() {
+                Some(features) => {
+                    let key = names::key(&path);
+                    let schema = schema.flatten();
+                    let entry = Entry {
+                        path,
+                        schema,
+                        features,
+                    };
+                    if entries.insert(key, entry).is_some() {
+                        dead += 1;
+                    }
+                }
+                None => {
+                    warn!("Skipping a record with invalid features: '{}'", record);
+                    dead += 1;
+                }
+            },
+            (Some("-"), Some(path), _) => {
+                if entries.remove(&names::key(&path)).is_some() {
+                    dead += 1;
+                }
+                dead += 1;
+            }
+            _ => {
+                warn!("Skipping an invalid record: '{}'", record);
+                dead += 1;
+            }
+        }
+    }
+
+    Ok((entries, dead, complete))
+}
+
+/// Escape a path to be put into a record.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::store::{escape, unescape};
+/// let path = "/photos/odd\tname\\.jpg";
+/// assert_eq!(escape(path), "/photos/odd\\tname\\\\.jpg");
+/// assert_eq!(unescape(&escape(path)), path);
+/// ```
+pub fn escape(path: &str) -> String {
+    path.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+/// Undo [`escape`].
+pub fn unescape(escaped: &str) -> String {
+    let mut unescaped = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
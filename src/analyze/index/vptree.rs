@@ -0,0 +1,167 @@
+//! Vantage-point tree over a [metric](super::metric), Hamming distance between hashes by default.
+//!
+//! Every node picks a vantage point and splits the other points in halves:
+//! those within the median distance from it and those beyond. A search only
+//! goes into the halves the triangle inequality doesn't rule out, so it is
+//! much faster than comparing with all points when the radius is small
+//! compared to the distances in the dataset.
+
+use super::metric::{Hamming, Metric};
+
+/// Where the halves of the node at `start` of a subtree end.
+///
+/// The subtree is `start..end`, the inner half is `start + 1..middle`
+/// and the outer half is `middle..end`.
+fn middle(start: usize, end: usize) -> usize {
+    start + 1 + (end - start - 1) / 2
+}
+
+/// A vantage-point tree of points, each with its index in the dataset.
+///
+/// Of hashes by Hamming distance unless told otherwise, see [`VpTree::with_metric`].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::index::vptree::VpTree;
+/// let hashes = [0b0000u64, 0b0001, 0b0011, 0b1111_0000];
+/// let tree = VpTree::new(&hashes);
+/// let mut found = tree.within(0b0000, 2);
+/// found.sort_unstable();
+/// assert_eq!(found, vec![0, 1, 2]);
+/// ```
+pub struct VpTree<A = u64, M = Hamming> {
+    /// Points with their indices, ordered so that every subtree is a range:
+    /// its vantage point, then points of its inner half, then of its outer half.
+    items: Vec<(A, usize)>,
+    /// Median distance from the vantage point at the same position in `items`.
+    radii: Vec<f64>,
+    /// How far apart points are.
+    metric: M,
+}
+
+impl VpTree {
+    /// Build a tree of hashes, their positions in `hashes` are their indices.
+    pub fn new(hashes: &[u64]) -> Self {
+        Self::with_metric(hashes, Hamming)
+    }
+
+    /// Indices of hashes at most `radius` bits away from `query`, in no particular order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::index::vptree::VpTree;
+    /// // Compare with brute force on pseudo-random hashes.
+    /// let mut state = 0x2545f4914f6cdd1du64;
+    /// let hashes: Vec<u64> = (0..500)
+    ///     .map(|_| {
+    ///         state ^= state << 13;
+    ///         state ^= state >> 7;
+    ///         state ^= state << 17;
+    ///         state & 0xffff
+    ///     })
+    ///     .collect();
+    /// let tree = VpTree::new(&hashes);
+    /// for &query in &hashes[..50] {
+    ///     let mut found = tree.within(query, 3);
+    ///     found.sort_unstable();
+    ///     let brute: Vec<usize> = (0..hashes.len())
+    ///         .filter(|&i| (hashes[i] ^ query).count_ones() <= 3)
+    ///         .collect();
+    ///     assert_eq!(found, brute);
+    /// }
+    /// ```
+    pub fn within(&self, query: u64, radius: u32) -> Vec<usize> {
+        self.within_distance(&query, f64::from(radius))
+    }
+}
+
+impl<A: Clone, M: Metric<A>> VpTree<A, M> {
+    /// Build a tree of points, far apart as `metric` says,
+    /// their positions in `points` are their indices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::index::{metric::Cosine, vptree::VpTree};
+    /// let embeddings = vec![vec![1., 0.], vec![0.9, 0.1], vec![0., 1.]];
+    /// let tree = VpTree::with_metric(&embeddings, Cosine);
+    /// let mut found = tree.within_distance(&vec![1., 0.], 0.1);
+    /// found.sort_unstable();
+    /// assert_eq!(found, vec![0, 1]);
+    /// ```
+    pub fn with_metric(points: &[A], metric: M) -> Self {
+        let mut items: Vec<(A, usize)> = points.iter().cloned().zip(0..).collect();
+        let mut radii = vec![0.; items.len()];
+
+        let mut ranges = vec![(0, items.len())];
+        while let Some((start, end)) = ranges.pop() {
+            if end - start <= 1 {
+                continue;
+            }
+            let (head, rest) = items[start..end].split_at_mut(1);
+            let vantage = &head[0].0;
+            let middle = middle(start, end);
+            // Points up to the median are within its distance, the rest are beyond it.
+            rest.select_nth_unstable_by(middle - start - 1, |(a, _), (b, _)| {
+                let (a, b) = (metric.distance(vantage, a), metric.distance(vantage, b));
+                a.total_cmp(&b)
+            });
+            radii[start] = metric.distance(vantage, &rest[middle - start - 1].0);
+            ranges.push((start + 1, middle));
+            ranges.push((middle, end));
+        }
+        Self {
+            items,
+            radii,
+            metric,
+        }
+    }
+
+    /// How many points are in the tree.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the tree has no points.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Indices of points at most `radius` away from `query`, in no particular order.
+    pub fn within_distance(&self, query: &A, radius: f64) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut ranges = vec![(0, self.items.len())];
+        while let Some((start, end)) = ranges.pop() {
+            if start >= end {
+                continue;
+            }
+            let (vantage, index) = &self.items[start];
+            let to_vantage = self.metric.distance(query, vantage);
+            if to_vantage <= radius {
+                found.push(*index);
+            }
+            if end - start == 1 {
+                continue;
+            }
+            let middle = middle(start, end);
+            let median = self.radii[start];
+            // Points of a half are too far from the query if the vantage point is
+            // farther from the query than from them by more than the radius.
+            if to_vantage <= median + radius {
+                ranges.push((start + 1, middle));
+            }
+            if to_vantage + radius >= median {
+                ranges.push((middle, end));
+            }
+        }
+        found
+    }
+}
+
+impl super::Near for VpTree {
+    fn near(&self, query: u64, radius: u32) -> Vec<usize> {
+        self.within(query, radius)
+    }
+}
@@ -0,0 +1,115 @@
+//! Saving features to extended attributes of files.
+//!
+//! An alternative to an index file: features are kept in attributes of every
+//! file itself, so they travel with it when it is moved or renamed. Like
+//! records of a [`store`](super::store), attributes hold the
+//! [schema](Schema) features were found with, and features found otherwise
+//! are found again. Only the `xattrs` feature has this, and only on Unix,
+//! elsewhere attributes are never found and can't be saved.
+//!
+//! # Attributes
+//!
+//! * [`SCHEMA`] - the [encoded](Schema::encode) schema.
+//! * [`FEATURES`] - [encoded](ImgFeatures::encode) features of every image
+//!   in the file, separated by tabs.
+//! * [`LSHASH`] - hashes of every image in hex, separated by spaces, for other
+//!   tools to read. They are not read back, [`FEATURES`] has them too.
+
+use crate::analyze::features::schema::Schema;
+use crate::analyze::features::ImgFeatures;
+use async_std::path::Path;
+use std::io;
+
+/// Name of the attribute with the schema.
+pub const SCHEMA: &str = "user.succotash.schema";
+
+/// Name of the attribute with features of all images.
+pub const FEATURES: &str = "user.succotash.features";
+
+/// Name of the attribute with hashes of all images.
+pub const LSHASH: &str = "user.succotash.lshash";
+
+/// Features of all images in a file at `path`, if they were saved to its
+/// attributes and their schema [satisfies](Schema::satisfies) `wanted`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::{features::{schema::Schema, FindOptions}, index::xattrs};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let path = "/home/user/Pictures/a.png".as_ref();
+/// let schema = Schema::of(FindOptions::default());
+/// if let Some(features) = xattrs::load(path, &schema)? {
+///     println!("'a.png' has {} images", features.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn load(path: &Path, wanted: &Schema) -> io::Result<Option<Vec<ImgFeatures>>> {
+    let schema = match get(path, SCHEMA)? {
+        Some(schema) => Schema::decode(&String::from_utf8_lossy(&schema)),
+        None => return Ok(None),
+    };
+    match schema {
+        Some(schema) if schema.satisfies(wanted) => {}
+        _ => return Ok(None),
+    }
+
+    let features = match get(path, FEATURES)? {
+        Some(features) => features,
+        None => return Ok(None),
+    };
+    let features = String::from_utf8_lossy(&features);
+    if features.is_empty() {
+        return Ok(Some(Vec::new()));
+    }
+    Ok(features.split('\t').map(ImgFeatures::decode).collect())
+}
+
+/// Save features of all images in a file at `path` to its attributes.
+///
+/// # Arguments
+///
+/// * `path` - the file.
+/// * `schema` - how the features were found.
+/// * `features` - features of all images in the file.
+pub fn save(path: &Path, schema: &Schema, features: &[ImgFeatures]) -> io::Result<()> {
+    let encoded: Vec<String> = features.iter().map(ImgFeatures::encode).collect();
+    let hashes: Vec<String> = features
+        .iter()
+        .map(|features| format!("{:016x}", features.lshash.bits()))
+        .collect();
+    // The schema is cleared first and goes last, so that
+    // features of a partly saved file aren't taken for valid.
+    set(path, SCHEMA, b"")?;
+    set(path, FEATURES, encoded.join("\t").as_bytes())?;
+    set(path, LSHASH, hashes.join(" ").as_bytes())?;
+    set(path, SCHEMA, schema.encode().as_bytes())
+}
+
+#[cfg(unix)]
+fn get(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    match xattr::get(path, name) {
+        // The filesystem doesn't support attributes, so there can't be any.
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(None),
+        result => result,
+    }
+}
+
+#[cfg(unix)]
+fn set(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    xattr::set(path, name, value)
+}
+
+#[cfg(not(unix))]
+fn get(_path: &Path, _name: &str) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+fn set(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are only supported on Unix",
+    ))
+}
@@ -0,0 +1,61 @@
+//! Asynchronous IO of the analysis, the only place it meets the runtime.
+//!
+//! Images, their features and the rest of the core take [`std::path::Path`]s
+//! and bytes, and never wait on anything. What has to wait, reading files
+//! or running blocking code off the executor, goes through here, so that
+//! another runtime would only need these few functions. Programs that run
+//! one already can call the synchronous parts directly, e.g.
+//! [`ImgRaw::decode`](super::img::ImgRaw::decode) and
+//! [`ImgFeatures::find_with`](super::features::ImgFeatures::find_with).
+
+use super::features::exif::Exif;
+use std::path::Path;
+
+/// Read the whole file at `path`, the entry of an archive it names,
+/// see [`archive`](super::archive), or download the object or the URL,
+/// see [`s3`](super::s3) and [`download`](super::download).
+pub async fn read(path: &Path) -> std::io::Result<Vec<u8>> {
+    #[cfg(feature = "urls")]
+    if super::download::is_url(path.into()) {
+        let url = path.to_path_buf();
+        return unblock(move || super::download::get(url.as_ref())).await;
+    }
+    #[cfg(feature = "s3")]
+    if super::s3::is_object(path.into()) {
+        let path = path.to_path_buf();
+        return unblock(move || super::s3::get(path.as_ref())).await;
+    }
+    #[cfg(feature = "archives")]
+    if let Some((archive, entry)) = super::archive::split(path.into()) {
+        return unblock(move || super::archive::read(&archive, &entry)).await;
+    }
+    async_std::fs::read(path).await
+}
+
+/// Read EXIF metadata of the file at `path`, only as much of it as needed.
+pub async fn exif(path: &Path) -> Option<Exif> {
+    // Entries of archives are read whole, EXIF metadata is read from their contents.
+    #[cfg(feature = "archives")]
+    if super::archive::is_entry(path.into()) {
+        return read(path).await.ok().and_then(|data| Exif::of_bytes(&data));
+    }
+    #[cfg(feature = "s3")]
+    if super::s3::is_object(path.into()) {
+        return read(path).await.ok().and_then(|data| Exif::of_bytes(&data));
+    }
+    let path = path.to_path_buf();
+    unblock(move || {
+        let file = std::fs::File::open(path).ok()?;
+        Exif::parse(&mut std::io::BufReader::new(file))
+    })
+    .await
+}
+
+/// Run `f` on a thread where it can block, and wait for it.
+pub async fn unblock<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    async_std::task::spawn_blocking(f).await
+}
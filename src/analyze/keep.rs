@@ -0,0 +1,310 @@
+//! Which file of a group to keep, by weighing criteria.
+//!
+//! Every [`Criterion`] rates the files of a group within [0, 1], e.g. the
+//! resolution of an image against the highest one in the group. The file
+//! with the highest sum of rates, each multiplied by the weight of its
+//! criterion, is kept, and of equals the first one by path. [`Criteria`]
+//! without weights leave it to [`Plan::new`](super::plan::Plan::new),
+//! which keeps the sharpest image.
+//!
+//! Before weighing, [`Priorities`] narrow the files down to those in
+//! preferred places, e.g. a camera folder, or away from deprioritized ones,
+//! e.g. a messenger folder.
+
+use super::group::Group;
+use super::img::{Img, Probe};
+use super::walker::glob::Pattern;
+use async_std::path::{Path, PathBuf};
+
+/// Names of folders files are originals in, see [`Criterion::Path`].
+pub const ORIGINALS: &[&str] = &["original", "originals"];
+
+/// Extensions of formats that lose nothing of images, see [`Criterion::Format`].
+pub const LOSSLESS: &[&str] = &[
+    "png", "tif", "tiff", "bmp", "pbm", "pgm", "ppm", "pnm", "tga", "ff", "dng", "cr2", "nef",
+    "arw",
+];
+
+/// What makes a file worth keeping.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Criterion {
+    /// Pixels of the image, against the most in the group.
+    Resolution,
+    /// Size of the file, against the largest in the group.
+    Size,
+    /// 1 for a lossless format, e.g. PNG, see [`LOSSLESS`], and 0 for the rest, e.g. JPEG.
+    Format,
+    /// Sharpness of the image, against the sharpest in the group.
+    Sharpness,
+    /// How much of the EXIF metadata is there: time, camera, orientation and location.
+    Exif,
+    /// 1 for files in a folder of originals, see [`ORIGINALS`], and 0 for the rest.
+    Path,
+}
+
+impl Criterion {
+    /// Name of the criterion, as `--keep-weight` takes it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Resolution => "resolution",
+            Self::Size => "size",
+            Self::Format => "format",
+            Self::Sharpness => "sharpness",
+            Self::Exif => "exif",
+            Self::Path => "path",
+        }
+    }
+
+    /// Rates of the files of a group, in the order of the files.
+    ///
+    /// Blocks the thread to read sizes and dimensions of files.
+    pub fn rate(&self, files: &[&Img<PathBuf>]) -> Vec<f64> {
+        let relative = |values: Vec<f64>| {
+            let max = values.iter().copied().fold(0., f64::max);
+            let rates = values
+                .iter()
+                .map(|value| if max > 0. { value / max } else { 0. });
+            rates.collect()
+        };
+        match self {
+            Self::Resolution => relative(
+                files
+                    .iter()
+                    .map(|img| {
+                        let probe = Probe::of_file(img.path.as_ref()).ok();
+                        let dimensions = probe.and_then(|probe| probe.dimensions);
+                        dimensions.map_or(0., |(width, height)| width as f64 * height as f64)
+                    })
+                    .collect(),
+            ),
+            Self::Size => relative(
+                files
+                    .iter()
+                    .map(|img| std::fs::metadata(&img.path).map_or(0., |m| m.len() as f64))
+                    .collect(),
+            ),
+            Self::Format => files
+                .iter()
+                .map(|img| {
+                    let extension = img.path.extension().unwrap_or_default();
+                    let extension = extension.to_string_lossy().to_lowercase();
+                    if LOSSLESS.contains(&extension.as_str()) {
+                        1.
+                    } else {
+                        0.
+                    }
+                })
+                .collect(),
+            Self::Sharpness => relative(
+                files
+                    .iter()
+                    .map(|img| img.features.sharpness.variance())
+                    .collect(),
+            ),
+            Self::Exif => files
+                .iter()
+                .map(|img| match &img.features.exif {
+                    Some(exif) => {
+                        let fields = [
+                            exif.taken.is_some(),
+                            exif.camera.is_some(),
+                            exif.orientation.is_some(),
+                            exif.gps.is_some(),
+                        ];
+                        fields.iter().filter(|&&field| field).count() as f64 / 4.
+                    }
+                    None => 0.,
+                })
+                .collect(),
+            Self::Path => files
+                .iter()
+                .map(|img| if in_originals(&img.path) { 1. } else { 0. })
+                .collect(),
+        }
+    }
+}
+
+impl std::str::FromStr for Criterion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "resolution" => Ok(Self::Resolution),
+            "size" => Ok(Self::Size),
+            "format" => Ok(Self::Format),
+            "sharpness" => Ok(Self::Sharpness),
+            "exif" => Ok(Self::Exif),
+            "path" => Ok(Self::Path),
+            _ => Err(format!("unknown criterion '{}'", s)),
+        }
+    }
+}
+
+/// Whether a file is in a folder of originals, see [`ORIGINALS`].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::keep;
+/// assert!(keep::in_originals("/photos/Originals/2023/a.jpg".as_ref()));
+/// assert!(!keep::in_originals("/photos/edits/originals.jpg".as_ref()));
+/// ```
+pub fn in_originals(path: &Path) -> bool {
+    let folders = path.parent().into_iter().flat_map(|parent| parent.iter());
+    folders
+        .map(|folder| folder.to_string_lossy().to_lowercase())
+        .any(|folder| ORIGINALS.contains(&folder.as_str()))
+}
+
+/// How much every [`Criterion`] weighs in picking the file to keep.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Criteria(Vec<(Criterion, f64)>);
+
+impl Criteria {
+    /// Criteria from `criterion=weight` pairs, e.g. `resolution=2` and `path=1`.
+    ///
+    /// A criterion given twice weighs what it was given last.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::keep::Criteria;
+    /// assert!(Criteria::parse(["resolution=2", "format=0.5"]).is_ok());
+    /// assert!(Criteria::parse(["resolution"]).is_err());
+    /// assert!(Criteria::parse(["smell=1"]).is_err());
+    /// assert!(Criteria::parse(["size=-1"]).is_err());
+    /// ```
+    pub fn parse<'a>(pairs: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut criteria = Vec::new();
+        for pair in pairs {
+            let (name, weight) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("weight '{}' isn't like 'criterion=weight'", pair))?;
+            let criterion: Criterion = name
+                .parse()
+                .map_err(|e| format!("{} in weight '{}'", e, pair))?;
+            let weight: f64 = weight
+                .parse()
+                .map_err(|e| format!("weight '{}' isn't a number: {}", pair, e))?;
+            if !weight.is_finite() || weight < 0. {
+                return Err(format!("weight '{}' isn't a non-negative number", pair));
+            }
+            criteria.retain(|(given, _)| *given != criterion);
+            criteria.push((criterion, weight));
+        }
+        Ok(Self(criteria))
+    }
+
+    /// Whether no criterion is weighed.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The image of a group to keep, the one whose file scores highest.
+    ///
+    /// Blocks the thread, see [`io::unblock`](super::io::unblock).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{features::ImgFeatures, group::Group, img::Img, keep::Criteria};
+    /// let img = |path: &str, features| Img {
+    ///     path: async_std::path::PathBuf::from(path),
+    ///     features: ImgFeatures::decode(features).unwrap(),
+    /// };
+    /// let group = Group {
+    ///     imgs: vec![
+    ///         img("/exports/a.jpg", "00000000000000ff 0 90 0.5 0.2"),
+    ///         img("/originals/a.png", "00000000000000ff 0 60 0.5 0.2"),
+    ///     ],
+    ///     score: None,
+    ///     explanations: Vec::new(),
+    /// };
+    /// let keeper = |pairs: &[&str]| Criteria::parse(pairs.iter().copied()).unwrap().keeper(&group).path.clone();
+    /// assert_eq!(keeper(&["sharpness=1"]), async_std::path::PathBuf::from("/exports/a.jpg"));
+    /// assert_eq!(keeper(&["sharpness=1", "format=1"]), async_std::path::PathBuf::from("/originals/a.png"));
+    /// assert_eq!(keeper(&["sharpness=2", "path=0.5"]), async_std::path::PathBuf::from("/exports/a.jpg"));
+    /// ```
+    pub fn keeper<'a>(&self, group: &'a Group) -> &'a Img<PathBuf> {
+        self.keeper_of(&group.files())
+    }
+
+    /// The file to keep of some files of a group, see [`Criteria::keeper`].
+    pub fn keeper_of<'a>(&self, files: &[&'a Img<PathBuf>]) -> &'a Img<PathBuf> {
+        let mut scores = vec![0.; files.len()];
+        for (criterion, weight) in &self.0 {
+            if *weight == 0. {
+                continue;
+            }
+            for (score, rate) in scores.iter_mut().zip(criterion.rate(files)) {
+                *score += weight * rate;
+            }
+        }
+        // The first of the best, files are ordered by path.
+        let best = scores.iter().enumerate().fold(
+            0,
+            |best, (i, score)| if *score > scores[best] { i } else { best },
+        );
+        files[best]
+    }
+}
+
+/// Which files of a group to keep rather than the others, whatever the [`Criteria`].
+///
+/// Patterns with a separator match whole paths, e.g. `**/Camera/**`, and
+/// the rest match file names, e.g. `*.dng`. A file matching both a
+/// preferred and a deprioritized pattern is neither.
+#[derive(Clone, Debug, Default)]
+pub struct Priorities {
+    /// Files to keep rather than the others, see `--prefer`.
+    pub prefer: Vec<Pattern>,
+    /// Files to keep only if all others are too, see `--deprioritize`.
+    pub deprioritize: Vec<Pattern>,
+}
+
+impl Priorities {
+    /// Whether no pattern is given.
+    pub fn is_empty(&self) -> bool {
+        self.prefer.is_empty() && self.deprioritize.is_empty()
+    }
+
+    /// Priority of a file: 1 if preferred, -1 if deprioritized, and 0 otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{keep::Priorities, walker::glob::Pattern};
+    /// let priorities = Priorities {
+    ///     prefer: vec![Pattern::new("**/Camera/**").unwrap()],
+    ///     deprioritize: vec![Pattern::new("**/WhatsApp/**").unwrap(), Pattern::new("*.gif").unwrap()],
+    /// };
+    /// assert_eq!(priorities.priority("/phone/DCIM/Camera/a.jpg".as_ref()), 1);
+    /// assert_eq!(priorities.priority("/phone/WhatsApp/Media/a.jpg".as_ref()), -1);
+    /// assert_eq!(priorities.priority("/phone/Download/a.gif".as_ref()), -1);
+    /// assert_eq!(priorities.priority("/phone/Camera/a.gif".as_ref()), 0);
+    /// assert_eq!(priorities.priority("/phone/Download/a.jpg".as_ref()), 0);
+    /// ```
+    pub fn priority(&self, path: &Path) -> i8 {
+        let matches = |patterns: &[Pattern]| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches_file(path.as_ref()))
+        };
+        matches(&self.prefer) as i8 - matches(&self.deprioritize) as i8
+    }
+
+    /// The files of the highest priority, in their order.
+    pub fn top<'a>(&self, files: Vec<&'a Img<PathBuf>>) -> Vec<&'a Img<PathBuf>> {
+        if self.is_empty() {
+            return files;
+        }
+        let priorities: Vec<i8> = files.iter().map(|img| self.priority(&img.path)).collect();
+        let highest = priorities.iter().copied().max().unwrap_or_default();
+        files
+            .into_iter()
+            .zip(priorities)
+            .filter(|(_, priority)| *priority == highest)
+            .map(|(img, _)| img)
+            .collect()
+    }
+}
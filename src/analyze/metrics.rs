@@ -0,0 +1,321 @@
+//! Statistics of the analysis.
+//!
+//! Every stage of the analysis updates the counters and histograms in
+//! [`METRICS`] as it goes, from whatever thread or task it runs on.
+//! Everything that reports on the analysis, e.g. the summary at the end
+//! of it, reads them from there. A running [server](crate::serve) or
+//! [daemon](crate::daemon) counts queries it answers here too, and exposes
+//! all of them to Prometheus, see [`serve::metrics`](crate::serve::metrics).
+//!
+//! Metrics are global and only ever grow, so an analysis that wants
+//! numbers of its own should take a [`Snapshot`] before it starts and
+//! subtract it, see [`Snapshot::since`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// A number that only grows.
+pub struct Counter {
+    /// Name of the counter, `snake_case`.
+    pub name: &'static str,
+    /// What the counter counts.
+    pub help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    /// Add one.
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Add `n`.
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Add a duration, for counters of microseconds.
+    pub fn add_duration(&self, duration: Duration) {
+        self.add(duration.as_micros() as u64);
+    }
+
+    /// The current value.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Upper bounds of [`Histogram`] buckets, in microseconds. The last bucket has no bound.
+pub const BUCKETS: [u64; 8] = [
+    1_000, 4_000, 16_000, 64_000, 256_000, 1_000_000, 4_000_000, 16_000_000,
+];
+
+/// Distribution of durations.
+pub struct Histogram {
+    /// Name of the histogram, `snake_case`.
+    pub name: &'static str,
+    /// What the histogram measures.
+    pub help: &'static str,
+    /// How many durations fell into each bucket, see [`BUCKETS`].
+    buckets: [AtomicU64; BUCKETS.len() + 1],
+    /// Sum of all durations, in microseconds.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len() + 1],
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a duration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::metrics::METRICS;
+    /// # use std::time::Duration;
+    /// let before = METRICS.file_time.count();
+    /// METRICS.file_time.observe(Duration::from_millis(2));
+    /// assert_eq!(METRICS.file_time.count(), before + 1);
+    /// ```
+    pub fn observe(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        let bucket = BUCKETS
+            .iter()
+            .position(|&bound| micros <= bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// How many durations fell into each bucket, see [`BUCKETS`].
+    pub fn buckets(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// How many durations were recorded.
+    pub fn count(&self) -> u64 {
+        self.buckets().iter().sum()
+    }
+
+    /// Sum of all recorded durations.
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum.load(Ordering::Relaxed))
+    }
+}
+
+/// All metrics of the analysis.
+pub struct Metrics {
+    /// Directories the walker has read.
+    pub dirs_walked: Counter,
+    /// Files and directories the walker has skipped.
+    pub entries_skipped: Counter,
+    /// Files the walker has picked.
+    pub files_found: Counter,
+    /// Files whose features were found.
+    pub files_analyzed: Counter,
+    /// Files that couldn't be analyzed.
+    pub files_failed: Counter,
+    /// Files skipped because their decoder isn't in this build.
+    pub files_unsupported: Counter,
+    /// Files skipped because they couldn't be analyzed by an earlier run.
+    pub files_quarantined: Counter,
+    /// Images found in the analyzed files.
+    pub imgs_found: Counter,
+    /// Pairs of images with close hashes.
+    pub pairs_found: Counter,
+    /// Pairs of images dropped by verification.
+    pub pairs_dropped: Counter,
+    /// Images found inside other images.
+    pub imgs_contained: Counter,
+    /// Groups of similar images.
+    pub groups_found: Counter,
+    /// Time spent walking, in microseconds.
+    pub walk_micros: Counter,
+    /// Time spent finding features, in microseconds.
+    pub features_micros: Counter,
+    /// Time spent comparing images, in microseconds.
+    pub compare_micros: Counter,
+    /// Queries for similar images a server answered, failed ones included.
+    pub queries: Counter,
+    /// Files a server couldn't decode, queried or shown ones.
+    pub decode_failures: Counter,
+    /// Requests a server answered with groups of the index found already.
+    pub cache_hits: Counter,
+    /// Requests a server had to find groups of the index for first.
+    pub cache_misses: Counter,
+    /// Time it took to analyze a file.
+    pub file_time: Histogram,
+    /// Time it took a server to decode and hash a queried image.
+    pub hashing: Histogram,
+    /// Time it took a server to look for similar images in the index.
+    pub querying: Histogram,
+}
+
+/// The metrics of this process.
+pub static METRICS: Metrics = Metrics {
+    dirs_walked: Counter::new("dirs_walked", "Directories the walker has read"),
+    entries_skipped: Counter::new(
+        "entries_skipped",
+        "Files and directories the walker has skipped",
+    ),
+    files_found: Counter::new("files_found", "Files the walker has picked"),
+    files_analyzed: Counter::new("files_analyzed", "Files whose features were found"),
+    files_failed: Counter::new("files_failed", "Files that couldn't be analyzed"),
+    files_unsupported: Counter::new(
+        "files_unsupported",
+        "Files skipped because their decoder isn't in this build",
+    ),
+    files_quarantined: Counter::new(
+        "files_quarantined",
+        "Files skipped because they couldn't be analyzed by an earlier run",
+    ),
+    imgs_found: Counter::new("imgs_found", "Images found in the analyzed files"),
+    pairs_found: Counter::new("pairs_found", "Pairs of images with close hashes"),
+    pairs_dropped: Counter::new("pairs_dropped", "Pairs of images dropped by verification"),
+    imgs_contained: Counter::new("imgs_contained", "Images found inside other images"),
+    groups_found: Counter::new("groups_found", "Groups of similar images"),
+    walk_micros: Counter::new("walk_micros", "Time spent walking, in microseconds"),
+    features_micros: Counter::new(
+        "features_micros",
+        "Time spent finding features, in microseconds",
+    ),
+    compare_micros: Counter::new(
+        "compare_micros",
+        "Time spent comparing images, in microseconds",
+    ),
+    queries: Counter::new("queries", "Queries for similar images answered"),
+    decode_failures: Counter::new("decode_failures", "Files that couldn't be decoded"),
+    cache_hits: Counter::new(
+        "cache_hits",
+        "Requests answered with groups of the index found already",
+    ),
+    cache_misses: Counter::new(
+        "cache_misses",
+        "Requests that had to find groups of the index first",
+    ),
+    file_time: Histogram::new("file_time", "Time it took to analyze a file"),
+    hashing: Histogram::new("hashing", "Time it took to decode and hash a queried image"),
+    querying: Histogram::new(
+        "query",
+        "Time it took to look for similar images in the index",
+    ),
+};
+
+impl Metrics {
+    /// All the counters, for reporting them one by one.
+    pub fn counters(&self) -> [&Counter; 19] {
+        [
+            &self.dirs_walked,
+            &self.entries_skipped,
+            &self.files_found,
+            &self.files_analyzed,
+            &self.files_failed,
+            &self.files_unsupported,
+            &self.files_quarantined,
+            &self.imgs_found,
+            &self.pairs_found,
+            &self.pairs_dropped,
+            &self.imgs_contained,
+            &self.groups_found,
+            &self.walk_micros,
+            &self.features_micros,
+            &self.compare_micros,
+            &self.queries,
+            &self.decode_failures,
+            &self.cache_hits,
+            &self.cache_misses,
+        ]
+    }
+
+    /// All the histograms, for reporting them one by one.
+    pub fn histograms(&self) -> [&Histogram; 3] {
+        [&self.file_time, &self.hashing, &self.querying]
+    }
+
+    /// Values of all the counters at this point.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.counters().map(Counter::get))
+    }
+}
+
+/// Values of all the counters at some point, in the order of [`Metrics::counters`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Snapshot(pub [u64; 19]);
+
+impl Snapshot {
+    /// How much every counter has grown since `earlier`.
+    pub fn since(&self, earlier: &Self) -> Self {
+        let mut grown = *self;
+        for (value, earlier) in grown.0.iter_mut().zip(earlier.0) {
+            *value -= earlier;
+        }
+        grown
+    }
+
+    /// Value of a counter, by its name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::metrics::METRICS;
+    /// let before = METRICS.snapshot();
+    /// METRICS.groups_found.add(2);
+    /// assert_eq!(METRICS.snapshot().since(&before).get("groups_found"), Some(2));
+    /// assert_eq!(before.get("no_such_counter"), None);
+    /// ```
+    pub fn get(&self, name: &str) -> Option<u64> {
+        METRICS
+            .counters()
+            .iter()
+            .position(|counter| counter.name == name)
+            .map(|i| self.0[i])
+    }
+}
+
+/// Log the summary of an analysis.
+///
+/// # Arguments
+///
+/// * `analysis` - how the counters have grown during the analysis.
+pub fn report(analysis: &Snapshot) {
+    let get = |name| analysis.get(name).unwrap_or(0);
+    let seconds = |name| Duration::from_micros(get(name)).as_secs_f64();
+
+    info!(
+        "Analyzed {} of {} files ({} failed, {} quarantined, {} unsupported), found {} images in {:.2}s",
+        get("files_analyzed"),
+        get("files_found"),
+        get("files_failed"),
+        get("files_quarantined"),
+        get("files_unsupported"),
+        get("imgs_found"),
+        seconds("walk_micros") + seconds("features_micros"),
+    );
+    info!(
+        "Compared images in {:.2}s: {} similar pairs, {} dropped by verification, {} contained images",
+        seconds("compare_micros"),
+        get("pairs_found"),
+        get("pairs_dropped"),
+        get("imgs_contained"),
+    );
+    for (counter, value) in METRICS.counters().iter().zip(analysis.0) {
+        debug!("{} = {} ({})", counter.name, value, counter.help);
+    }
+}
@@ -3,65 +3,686 @@
 //! This module contains the 'analyze' subcommand.
 //! Analyze allows finding similar images in a directoy.
 
-use async_std::fs;
-use async_std::prelude::*;
-
+pub mod action;
+#[cfg(feature = "archives")]
+pub mod archive;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod burst;
+pub mod cancel;
+pub mod decoders;
+#[cfg(feature = "urls")]
+pub mod download;
+pub mod explain;
+pub use crate::features;
+pub mod filter;
+pub mod group;
 pub mod img;
-pub mod features;
+pub mod index;
+pub mod io;
+pub mod keep;
+pub mod metrics;
+pub mod names;
+pub mod output;
+pub mod parallel;
+pub mod plan;
+pub mod preset;
+pub mod progress;
+#[cfg(feature = "raw")]
+pub mod raw;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod savings;
+pub mod score;
+pub mod verify;
+#[cfg(feature = "video")]
+pub mod video;
+pub mod walker;
+pub mod worker;
+
+use crate::status::Status;
+use async_std::path::PathBuf;
+use index::checkpoint::Checkpoint;
+use index::quarantine::Quarantine;
+use metrics::METRICS;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Options of the analysis.
+pub struct AnalyzeOptions {
+    /// What files to pick, see [`walker::WalkOptions`].
+    pub walk: walker::WalkOptions,
+    /// How many worker processes to find features in, see [`worker`].
+    /// Zero means features are found in this process.
+    pub workers: usize,
+    /// How many threads to find features on in this process, see [`parallel`].
+    /// Zero means one per core.
+    pub threads: usize,
+    /// Soft limit of memory files take while their features are found on threads,
+    /// in bytes, see [`parallel::find_all`].
+    pub max_memory: Option<u64>,
+    /// Files larger than this many bytes aren't read, they are reported as failed.
+    pub max_file_size: Option<u64>,
+    /// How long decoding a file can take before it is given up on and
+    /// reported as failed, see [`parallel::Limits::timeout`]. Worker processes
+    /// that take longer are killed.
+    pub decode_timeout: Option<Duration>,
+    /// Where to save features to as they are found, see [`index::checkpoint`].
+    pub index: Option<PathBuf>,
+    /// Whether to reuse features saved to [`AnalyzeOptions::index`] by an earlier run.
+    pub resume: bool,
+    /// Whether to analyze files that couldn't be analyzed by an earlier run
+    /// again, rather than skip them, see [`index::quarantine`].
+    pub retry_quarantined: bool,
+    /// Whether to save features to extended attributes of files and reuse
+    /// those saved there already, see [`index::xattrs`]. Features saved to
+    /// [`AnalyzeOptions::index`] are reused then too, as if [resuming](AnalyzeOptions::resume).
+    #[cfg(feature = "xattrs")]
+    pub xattrs: bool,
+    /// Whether to reuse features in XMP sidecars of files and save them there,
+    /// see [`index::sidecar`]. Features saved to [`AnalyzeOptions::index`]
+    /// are reused then too, as if [resuming](AnalyzeOptions::resume).
+    pub sidecars: index::sidecar::Mode,
+    /// Stops finding features when cancelled, see [`cancel`].
+    pub cancel: cancel::CancelToken,
+    /// Where to report how far the analysis has got, see [`progress`].
+    pub progress: progress::Progress,
+    /// How many bits hashes of similar images can differ in, see [`group`].
+    pub max_distance: u32,
+    /// Features images are compared by, see [`features::set`].
+    pub features: &'static features::set::FeatureSet,
+    /// What images to leave out once their features are found, see [`filter`].
+    pub filters: filter::Filters,
+    /// How candidates for similar images are searched for, see [`index::Search`].
+    pub search: index::Search,
+    /// How much features weigh in scores groups are sorted by, see [`score`].
+    pub weights: score::Weights,
+    /// How much criteria weigh in picking the file of a group to keep, see [`keep`].
+    pub keep: keep::Criteria,
+    /// Which files of a group to keep rather than the others, see [`keep::Priorities`].
+    pub priorities: keep::Priorities,
+    /// Whether to report why images are similar, see [`explain`].
+    pub explain: bool,
+    /// Whether to find partial duplicates too, see [`features::tiles`].
+    pub partial: bool,
+    /// Whether to group bursts of photos instead of similar images, taken at most
+    /// this long after the previous one, and plan to keep the best of each, see [`burst`].
+    pub bursts: Option<Duration>,
+    /// Whether to trim uniform borders of images, see [`features::border`].
+    pub trim: bool,
+    /// How to convert images to grayscale for hashing, see [`features::grayscale`].
+    pub grayscale: features::grayscale::Grayscale,
+    /// What pixels are compared to when hashing, see [`features::lshash::HashThreshold`].
+    pub threshold: features::lshash::HashThreshold,
+    /// How to verify similar images by their pixels, if at all, see [`verify`].
+    pub verify: Option<verify::Method>,
+    /// Cutoff of the verification, [`verify::Method::default_cutoff`] if not set.
+    pub verify_cutoff: Option<f64>,
+    /// How to write the groups of similar images.
+    pub format: output::Format,
+    /// Where to write the groups of similar images, see [`output::write`].
+    pub output: Option<PathBuf>,
+    /// Where and how to write distances between all images, if at all, see [`output::matrix`].
+    pub matrix: Option<output::matrix::Export>,
+    /// Where to write pairs of similar images as a graph, if at all, see [`output::graph`].
+    pub graph: Option<PathBuf>,
+    /// Where to write files planned for deletion, `-` for stdout,
+    /// see [`plan::write_delete_list`].
+    pub delete_list: Option<PathBuf>,
+    /// What to do with files planned for deletion, if anything, see [`action`].
+    pub action: Option<action::Action>,
+    /// How to do [`AnalyzeOptions::action`].
+    pub action_options: action::ActionOptions,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        Self {
+            walk: walker::WalkOptions::default(),
+            workers: 0,
+            threads: 0,
+            max_memory: None,
+            max_file_size: None,
+            decode_timeout: None,
+            index: None,
+            resume: false,
+            retry_quarantined: false,
+            #[cfg(feature = "xattrs")]
+            xattrs: false,
+            sidecars: index::sidecar::Mode::default(),
+            cancel: cancel::CancelToken::new(),
+            progress: progress::Progress::default(),
+            max_distance: group::MAX_DISTANCE,
+            features: features::set::FeatureSet::standard(),
+            filters: filter::Filters::default(),
+            search: index::Search::default(),
+            weights: score::Weights::default(),
+            keep: keep::Criteria::default(),
+            priorities: keep::Priorities::default(),
+            explain: false,
+            partial: false,
+            bursts: None,
+            trim: true,
+            grayscale: features::grayscale::Grayscale::default(),
+            threshold: features::lshash::HashThreshold::default(),
+            verify: None,
+            verify_cutoff: None,
+            format: output::Format::default(),
+            output: None,
+            matrix: None,
+            graph: None,
+            delete_list: None,
+            action: None,
+            action_options: action::ActionOptions::default(),
+        }
+    }
+}
+
+impl AnalyzeOptions {
+    /// Let the analysis be cancelled with `token`, see [`cancel`].
+    ///
+    /// Walking, finding features and comparing images all stop soon after
+    /// the token is cancelled, and the results of what was done are written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use libsuccotash::analyze::{self, cancel::CancelToken, walker::Source, AnalyzeOptions};
+    /// let token = CancelToken::new();
+    /// let options = AnalyzeOptions::default().cancel_token(token.clone());
+    /// let analysis = async_std::task::spawn(async {
+    ///     analyze::run(Source::Walk(vec!["/mnt/nas".to_owned()]), options).await
+    /// });
+    /// // E.g. the user has pressed "Stop".
+    /// token.cancel();
+    /// async_std::task::block_on(analysis);
+    /// ```
+    pub fn cancel_token(mut self, token: cancel::CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// How features of images are found.
+    pub fn find_options(&self) -> features::FindOptions {
+        features::FindOptions {
+            tiles: self.partial,
+            trim: self.trim,
+            grayscale: self.grayscale,
+            threshold: self.threshold,
+            features: self.features,
+        }
+    }
+}
+
+/// Log features of an image.
+fn report(img: &img::Img<PathBuf>) {
+    info!(
+        "img '{}' has lshash of {}",
+        img.path.to_string_lossy(),
+        img.features.lshash
+    );
+
+    info!(
+        "img '{}' has hue of {}",
+        img.path.to_string_lossy(),
+        img.features.hue
+    );
 
-/// Run the analysis on the given path.
+    info!(
+        "img '{}' has sharpness of {}",
+        img.path.to_string_lossy(),
+        img.features.sharpness
+    );
+
+    info!(
+        "img '{}' has brightness of {}",
+        img.path.to_string_lossy(),
+        img.features.brightness
+    );
+
+    info!(
+        "img '{}' has contrast of {}",
+        img.path.to_string_lossy(),
+        img.features.contrast
+    );
+
+    for (name, value) in &img.features.custom {
+        info!(
+            "img '{}' has {} of {:?}",
+            img.path.to_string_lossy(),
+            name,
+            value
+        );
+    }
+}
+
+/// Find features of images in files, as [`AnalyzeOptions`] say.
+///
+/// Features are reused from and saved to the [index](AnalyzeOptions::index),
+/// and are found in [workers](AnalyzeOptions::workers) or on
+/// [threads](AnalyzeOptions::threads). Files that can't be analyzed
+/// are logged and left out, and with an index they are quarantined and
+/// skipped by later runs, see [`index::quarantine`].
 ///
 /// # Arguments
 ///
-/// * `dir` - Where to run the analysis.
-async fn try_run(dir: async_std::path::PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    debug!("Loading dir '{}' entries...", dir.to_string_lossy());
-    let mut entries = fs::read_dir(&dir).await?;
-    debug!("Loaded dir '{}' entries", dir.to_string_lossy());
-
-    while let Some(res) = entries.next().await {
-        let entry = res?;
-
-        debug!(
-            "Asynchronously opening image '{}'",
-            entry.file_name().to_string_lossy()
-        );
-        let img_raw = img::ImgRaw::load(entry.path()).await?;
-        debug!(
-            "Getting the lshash of image '{}'",
-            entry.file_name().to_string_lossy()
+/// * `paths` - files to analyze.
+/// * `options` - how to find the features.
+pub async fn find_features(
+    paths: Vec<PathBuf>,
+    options: &AnalyzeOptions,
+) -> Result<Vec<img::Img<PathBuf>>, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let find_options = options.find_options();
+    let checkpoint = match &options.index {
+        Some(index) => Some(Checkpoint::open(index, find_options)?),
+        None if options.resume => return Err("there is no index to resume from".into()),
+        None => None,
+    };
+    // Features in sidecars and attributes travel with files, they are always reused.
+    let mut resume = options.resume;
+    let checkpoint = match (checkpoint, options.sidecars) {
+        (checkpoint, index::sidecar::Mode::Ignore) => checkpoint,
+        (checkpoint, mode) => {
+            resume = true;
+            let checkpoint = checkpoint.unwrap_or_else(|| Checkpoint::new(find_options));
+            Some(checkpoint.with_sidecars(mode))
+        }
+    };
+    #[cfg(feature = "xattrs")]
+    let checkpoint = match (checkpoint, options.xattrs) {
+        (checkpoint, false) => checkpoint,
+        (checkpoint, true) => {
+            resume = true;
+            let checkpoint = checkpoint.unwrap_or_else(|| Checkpoint::new(find_options));
+            Some(checkpoint.with_xattrs())
+        }
+    };
+    let checkpoint = checkpoint.map(Arc::new);
+    let mut outcomes = Vec::new();
+    let paths = match &checkpoint {
+        Some(checkpoint) if resume => {
+            let mut rest = Vec::new();
+            for path in paths {
+                match checkpoint.get(&path) {
+                    Some(features) => outcomes.push((path, Ok(features))),
+                    None => rest.push(path),
+                }
+            }
+            info!(
+                "Resuming: {} files were analyzed already, {} are left",
+                outcomes.len(),
+                rest.len()
+            );
+            rest
+        }
+        _ => paths,
+    };
+    let mut quarantine = match &options.index {
+        Some(index) => Some(Quarantine::open(index)?),
+        None => None,
+    };
+    let paths = match &quarantine {
+        Some(quarantine) if !options.retry_quarantined && !quarantine.is_empty() => {
+            let (skipped, rest): (Vec<_>, Vec<_>) = paths
+                .into_iter()
+                .partition(|path| quarantine.get(path).is_some());
+            for path in &skipped {
+                debug!(
+                    "Skipping quarantined '{}': {}",
+                    path.to_string_lossy(),
+                    quarantine.get(path).unwrap_or_default()
+                );
+            }
+            if !skipped.is_empty() {
+                METRICS.files_quarantined.add(skipped.len() as u64);
+                warn!(
+                    "Skipping {} files that couldn't be analyzed before, listed in '{}'",
+                    skipped.len(),
+                    quarantine.path().to_string_lossy()
+                );
+            }
+            rest
+        }
+        _ => paths,
+    };
+    let paths = match options.max_file_size {
+        Some(max) => {
+            let mut fitting = Vec::with_capacity(paths.len());
+            for path in paths {
+                match async_std::fs::metadata(&path).await {
+                    Ok(metadata) if metadata.len() > max => {
+                        let size = crate::units::format_size(metadata.len());
+                        let max = crate::units::format_size(max);
+                        let e = format!("is {}, larger than the limit of {}", size, max);
+                        outcomes.push((path, Err(e)));
+                    }
+                    // Files that can't be read fail when they are.
+                    _ => fitting.push(path),
+                }
+            }
+            fitting
+        }
+        None => paths,
+    };
+
+    let (cancel, total) = (&options.cancel, outcomes.len() + paths.len());
+    let progress = options
+        .progress
+        .stage(progress::Stage::Features, Some(total as u64));
+    if !outcomes.is_empty() {
+        progress.advance(outcomes.len() as u64, None);
+    }
+    outcomes.extend(if options.workers > 0 {
+        debug!("Finding features in {} workers", options.workers);
+        let (workers, timeout) = (options.workers, options.decode_timeout);
+        let (checkpoint, cancel) = (checkpoint.clone(), cancel.clone());
+        worker::find_all(
+            paths,
+            workers,
+            timeout,
+            checkpoint,
+            cancel,
+            progress,
+            find_options,
+        )
+        .await?
+    } else {
+        // Every object in S3 takes a round trip, a few are downloaded at once.
+        #[cfg(feature = "s3")]
+        let reads = match paths.iter().any(|path| s3::is_object(path)) {
+            true => s3::CONCURRENCY,
+            false => 1,
+        };
+        #[cfg(not(feature = "s3"))]
+        let reads = 1;
+        let limits = parallel::Limits {
+            max_memory: options.max_memory,
+            timeout: options.decode_timeout,
+            reads,
+        };
+        let checkpoint = checkpoint.clone();
+        parallel::find_all(
+            paths,
+            options.threads,
+            limits,
+            checkpoint,
+            cancel,
+            progress,
+            find_options,
+        )
+        .await?
+    });
+    if let Some(checkpoint) = &checkpoint {
+        checkpoint.sync();
+    }
+    if cancel.is_cancelled() {
+        warn!(
+            "Cancelled, going on with the {} of {} files analyzed so far",
+            outcomes.len(),
+            total
         );
-        let img = img::Img::from(img_raw);
+    }
 
+    let mut imgs = Vec::new();
+    let mut quarantined = false;
+    for (path, outcome) in outcomes {
+        match outcome {
+            Ok(all_features) => {
+                METRICS.files_analyzed.inc();
+                if let Some(quarantine) = &mut quarantine {
+                    quarantined |= quarantine.remove(&path);
+                }
+                for features in all_features {
+                    imgs.push(img::Img {
+                        path: path.clone(),
+                        features,
+                    });
+                }
+            }
+            // A file that can't be analyzed doesn't stop the analysis,
+            // e.g. a configuration file at the root of the library.
+            Err(e) => {
+                METRICS.files_failed.inc();
+                error!("Couldn't analyze '{}': {}", path.to_string_lossy(), e);
+                if let Some(quarantine) = &mut quarantine {
+                    quarantine.insert(&path, &e);
+                    quarantined = true;
+                }
+            }
+        }
+    }
+    if let Some(quarantine) = quarantine.filter(|_| quarantined) {
+        if let Err(e) = quarantine.save() {
+            warn!("Can't save the quarantine list: {}", e);
+        }
+    }
+    // Outcomes come in the order files were analyzed in, which differs between runs.
+    // The sort is stable, so keyframes of a video stay in their order.
+    imgs.sort_by(|a, b| a.path.cmp(&b.path));
+    METRICS.imgs_found.add(imgs.len() as u64);
+    METRICS.features_micros.add_duration(started.elapsed());
+
+    Ok(imgs)
+}
+
+/// Run the analysis on the given files.
+///
+/// Returns whether similar images were found, or files failed to be analyzed.
+///
+/// # Arguments
+///
+/// * `source` - Where the files to analyze come from.
+/// * `options` - How to run the analysis.
+async fn try_run(
+    source: &walker::Source,
+    options: &AnalyzeOptions,
+) -> Result<Status, Box<dyn std::error::Error>> {
+    let analysis = METRICS.snapshot();
+    let started = Instant::now();
+    let files = source
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+    METRICS.walk_micros.add_duration(started.elapsed());
+    if files.roots.len() > 1 {
+        for (root, count) in files.roots.iter().zip(files.counts()) {
+            info!("Found {} files in '{}'", count, root);
+        }
+    }
+    let mut imgs = find_features(files.paths.clone(), options).await?;
+    if !options.filters.is_empty() {
+        let before = imgs.len();
+        imgs.retain(|img| options.filters.matches(&img.features));
+        info!("{} of {} images match the filters", imgs.len(), before);
+    }
+
+    imgs.iter().for_each(report);
+    let started = Instant::now();
+    let features = options
+        .features
+        .clone()
+        .with_max_distance("lshash", f64::from(options.max_distance));
+    // Comparing reports no progress of its own, only when it starts and ends.
+    let comparing = options.progress.stage(progress::Stage::Compare, Some(1));
+    let mut bursts = Vec::new();
+    let mut pairs = match options.bursts {
+        Some(window) => {
+            bursts = group::bursts(&imgs, window, options.max_distance);
+            bursts.iter().map(|&(i, j, _)| (i, j)).collect()
+        }
+        None => group::candidates(&imgs, &features, options.search, &options.cancel),
+    };
+    comparing.advance(1, None);
+    METRICS.pairs_found.add(pairs.len() as u64);
+    let verified = options.verify.map(|method| {
+        let cutoff = options
+            .verify_cutoff
+            .unwrap_or_else(|| method.default_cutoff());
+        (method, cutoff)
+    });
+    if let Some((method, cutoff)) = verified {
+        debug!("Verifying {} pairs with {:?}", pairs.len(), method);
+        let before = pairs.len();
+        let cancel = &options.cancel;
+        pairs = verify::verify(&imgs, pairs, method, cutoff, cancel, &options.progress).await;
+        METRICS.pairs_dropped.add((before - pairs.len()) as u64);
+    }
+    let mut explanations = Vec::new();
+    if options.explain {
+        for &(i, j) in &pairs {
+            let trigger = match (options.bursts, bursts.iter().find(|b| (b.0, b.1) == (i, j))) {
+                (Some(window), Some(&(_, _, apart))) => explain::Trigger::Burst { apart, window },
+                _ => explain::Trigger::Features,
+            };
+            explanations.push(explain::Explanation::new(
+                &imgs[i], &imgs[j], trigger, &features, verified,
+            ));
+        }
+    }
+    if options.partial {
+        let contained = group::contained(&imgs, group::MIN_CONTAINED, &options.cancel);
+        for (inner, outer, part) in contained {
+            if options.explain {
+                let trigger = explain::Trigger::Contained {
+                    part,
+                    min_part: group::MIN_CONTAINED,
+                };
+                explanations.push(explain::Explanation::new(
+                    &imgs[outer],
+                    &imgs[inner],
+                    trigger,
+                    &features,
+                    None,
+                ));
+            }
+            info!(
+                "img '{}' is contained in '{}' ({:.0}% of tiles found)",
+                imgs[inner].path.to_string_lossy(),
+                imgs[outer].path.to_string_lossy(),
+                part * 100.
+            );
+            METRICS.imgs_contained.inc();
+            pairs.push((inner, outer));
+        }
+    }
+    if let Some(export) = &options.matrix {
+        output::matrix::write(&imgs, export)?;
+    }
+    if let Some(graph) = &options.graph {
+        output::graph::write(&imgs, &pairs, graph)?;
+    }
+    let paths: Vec<PathBuf> = imgs.iter().map(|img| img.path.clone()).collect();
+    let mut groups = group::group_pairs(imgs, &pairs);
+    score::sort(&mut groups, &options.weights, &features);
+    explain::attach(&mut groups, explanations);
+    METRICS.groups_found.add(groups.len() as u64);
+    METRICS.compare_micros.add_duration(started.elapsed());
+    if options.bursts.is_some() {
+        for group in &groups {
+            info!(
+                "Burst of {} photos from '{}', the best is '{}'",
+                group.files().len(),
+                group.imgs[0].path.to_string_lossy(),
+                burst::keeper(group).path.to_string_lossy()
+            );
+        }
+    }
+
+    for conflict in names::conflicts(&paths, &groups) {
+        let paths: Vec<_> = conflict
+            .paths
+            .iter()
+            .map(|path| format!("'{}'", path.to_string_lossy()))
+            .collect();
         info!(
-            "img '{}' has lshash of {}",
-            entry.file_name().to_string_lossy(),
-            img.features.lshash
+            "Files named '{}' are not similar: {}",
+            conflict.name,
+            paths.join(", ")
         );
+    }
+    let summary = METRICS.snapshot().since(&analysis);
+    metrics::report(&summary);
+    if !files.hardlinks.is_empty() {
+        info!(
+            "Skipped {} hard links to files found under other names, they share data already",
+            files.hardlinks.len()
+        );
+    }
+    for (decoder, count) in decoders::counts(&files.unsupported) {
+        warn!(
+            "Skipped {} {} files: {}",
+            count,
+            decoder.format,
+            decoder.reason()
+        );
+    }
+    output::write(&groups, &files, options.format, options.output.as_deref())?;
+
+    let plan = match options.bursts {
+        Some(_) => plan::Plan::keeping(&groups, burst::keeper),
+        None => plan::Plan::weighing(&groups, &options.keep, &options.priorities),
+    };
+    if let Some(delete_list) = &options.delete_list {
+        plan::write_delete_list(&plan, delete_list)?;
+        if delete_list == async_std::path::Path::new(plan::STDOUT) {
+            info!("Printed {} files planned for deletion", plan.delete.len());
+        } else {
+            info!(
+                "Wrote {} files planned for deletion to '{}'",
+                plan.delete.len(),
+                delete_list.to_string_lossy()
+            );
+        }
+    }
 
+    let found = groups.len();
+    let mut failed_actions = 0;
+    if let Some(action) = options.action {
+        let acting = options.action_options.clone();
+        let report = io::unblock(move || action::apply(action, &groups, &plan, &acting)).await;
         info!(
-            "img '{}' has hue of {}",
-            entry.file_name().to_string_lossy(),
-            img.features.hue
+            "Applied '{}' to {} of {} files planned for deletion, reclaiming {}",
+            action.name(),
+            report.done(),
+            report.outcomes.len(),
+            crate::units::format_size(report.reclaimed)
         );
+        if report.elsewhere() > 0 {
+            warn!(
+                "Left {} files alone: they are on other filesystems than the kept files of their groups",
+                report.elsewhere()
+            );
+        }
+        failed_actions = report.failed() as u64;
     }
 
-    Ok(())
+    // Files that couldn't be analyzed are errors, even though the analysis went on.
+    let failed = summary.get("files_failed").unwrap_or(0) + failed_actions;
+    Ok(Status::found(found).max(Status::failed(failed)))
 }
 
-/// Run the analysis on the given path, do not propagate errors.
+/// Run the analysis on the given files, do not propagate errors.
 ///
 /// You can think of it as of `main` of the `analyze` subcommand.
+/// Returns [`Status::Found`] if similar images were found, and
+/// [`Status::Failed`] if there were errors, see [`status`](crate::status).
 ///
 /// # Examples
 ///
 /// ```no_run
-/// # use libsuccotash::analyze;
-/// analyze::run("/home/user/Pictures".into());
+/// # use libsuccotash::analyze::{self, walker::Source};
+/// let source = Source::Walk(vec!["/home/user/Pictures/**/*.jpg".to_owned(), "/mnt/nas".to_owned()]);
+/// analyze::run(source, analyze::AnalyzeOptions::default());
 /// ```
-pub async fn run(dir: async_std::path::PathBuf) {
-    match try_run(dir).await {
-        Ok(_) => debug!("Done 'analyze'"),
-        Err(e) => error!("Error during 'analyze': {}", e),
+pub async fn run(source: walker::Source, options: AnalyzeOptions) -> Status {
+    match try_run(&source, &options).await {
+        Ok(status) => {
+            debug!("Done 'analyze'");
+            status
+        }
+        Err(e) => {
+            error!("Error during 'analyze': {}", e);
+            Status::Failed
+        }
     }
 }
@@ -0,0 +1,216 @@
+//! Unicode normalization of paths.
+//!
+//! The same name can be encoded in more than one way: macOS has
+//! traditionally stored names decomposed (NFD), while Linux keeps them
+//! the way they were typed, usually composed (NFC). Paths are normalized
+//! to NFC before they are compared, so that copies of a tree made
+//! on different systems match.
+//!
+//! On case-insensitive filesystems `Photo.JPG` and `photo.jpg` are the
+//! same file too. Whether a filesystem is one is found out by probing it,
+//! see [`is_case_insensitive`], and paths on such filesystems are lowercased
+//! before they are compared, see [`key`].
+
+use super::group::Group;
+use async_std::path::{Path, PathBuf};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{LazyLock, Mutex};
+use unicode_normalization::UnicodeNormalization;
+
+/// Normalize a path to NFC.
+///
+/// Paths that aren't valid UTF-8 are left as they are.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::names;
+/// let composed = async_std::path::Path::new("/photos/caf\u{e9}.jpg");
+/// let decomposed = async_std::path::Path::new("/photos/cafe\u{301}.jpg");
+/// assert_ne!(composed, decomposed);
+/// assert_eq!(names::normalize(composed), names::normalize(decomposed));
+/// ```
+pub fn normalize(path: &Path) -> Cow<'_, Path> {
+    match path.to_str() {
+        Some(s) if !unicode_normalization::is_nfc(s) => {
+            Cow::Owned(PathBuf::from(s.nfc().collect::<String>()))
+        }
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// Whether directories are case-insensitive, by directory.
+static CASE_INSENSITIVE: LazyLock<Mutex<HashMap<PathBuf, bool>>> = LazyLock::new(Default::default);
+
+/// Swap the case of ASCII letters in a name.
+fn flip_case(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_lowercase() {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect()
+}
+
+/// Whether two entries are the same file.
+#[cfg(unix)]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Whether two entries are the same file.
+#[cfg(not(unix))]
+fn same_file(_: &std::fs::Metadata, _: &std::fs::Metadata) -> bool {
+    // There are no cheap file identities here, and two files whose names only
+    // differ in case are rare enough on the filesystems of these systems.
+    true
+}
+
+/// Look up an entry of `dir` by its name with the case swapped.
+///
+/// Returns `None` if there is no entry whose name has letters in it.
+fn probe(dir: &std::path::Path) -> Option<bool> {
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let flipped = flip_case(name);
+        if flipped == name {
+            continue;
+        }
+        let Ok(original) = std::fs::symlink_metadata(entry.path()) else {
+            continue;
+        };
+        // Both names can exist on a case-sensitive filesystem, but not as the same file.
+        return Some(
+            std::fs::symlink_metadata(dir.join(flipped))
+                .is_ok_and(|flipped| same_file(&original, &flipped)),
+        );
+    }
+    None
+}
+
+/// Whether names in a directory are case-insensitive.
+///
+/// The directory is probed by looking up one of its entries with the case of
+/// the name swapped. Directories without such entries are judged by their
+/// parents, and if no parent can be probed either, by what is usual for the
+/// system. The answer is cached, so every directory is probed at most once.
+///
+/// # Arguments
+///
+/// * `dir` - the directory, doesn't have to exist.
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    if let Some(&insensitive) = CASE_INSENSITIVE.lock().unwrap().get(dir) {
+        return insensitive;
+    }
+
+    let dir: &std::path::Path = dir.as_ref();
+    let insensitive = dir
+        .ancestors()
+        // A relative path's last ancestor is empty, and means the current directory.
+        .map(|ancestor| {
+            if ancestor.as_os_str().is_empty() {
+                std::path::Path::new(".")
+            } else {
+                ancestor
+            }
+        })
+        .find_map(probe)
+        .unwrap_or(cfg!(any(windows, target_os = "macos")));
+    if insensitive {
+        debug!("'{}' is case-insensitive", dir.to_string_lossy());
+    }
+    CASE_INSENSITIVE
+        .lock()
+        .unwrap()
+        .insert(dir.into(), insensitive);
+    insensitive
+}
+
+/// A key under which a path is compared to other paths.
+///
+/// Paths are normalized to NFC (see [`normalize`]), and on case-insensitive
+/// filesystems (see [`is_case_insensitive`]) lowercased too, so that every
+/// way to name a file gets the same key.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::names;
+/// # use async_std::path::PathBuf;
+/// let dir = PathBuf::from(std::env::temp_dir()).join("succotash-names-key");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("Photo.JPG"), b"").unwrap();
+/// let same = names::key(&dir.join("Photo.JPG")) == names::key(&dir.join("photo.jpg"));
+/// assert_eq!(same, names::is_case_insensitive(&dir));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn key(path: &Path) -> PathBuf {
+    let normalized = normalize(path);
+    let dir = path.parent().unwrap_or(path);
+    match normalized.to_str() {
+        Some(s) if is_case_insensitive(dir) => PathBuf::from(s.to_lowercase()),
+        _ => normalized.into_owned(),
+    }
+}
+
+/// Files that share a name, but not their contents.
+#[derive(Debug)]
+pub struct Conflict {
+    /// The name, normalized.
+    pub name: String,
+    /// The files, ordered by path.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Find files that have the same name, but are not similar.
+///
+/// Names are compared after normalization, so `café.jpg` written
+/// in NFC and in NFD is the same name.
+///
+/// # Arguments
+///
+/// * `paths` - the files to look through.
+/// * `groups` - groups of similar images among the files.
+pub fn conflicts(paths: &[PathBuf], groups: &[Group]) -> Vec<Conflict> {
+    let mut group_of = HashMap::new();
+    for (i, group) in groups.iter().enumerate() {
+        for img in &group.imgs {
+            group_of.insert(img.path.as_path(), i);
+        }
+    }
+
+    let mut by_name = BTreeMap::<String, Vec<&Path>>::new();
+    for path in paths {
+        if let Some(name) = path.file_name() {
+            let name = normalize(Path::new(name)).to_string_lossy().into_owned();
+            by_name.entry(name).or_default().push(path);
+        }
+    }
+
+    by_name
+        .into_iter()
+        .filter_map(|(name, mut paths)| {
+            paths.sort();
+            paths.dedup();
+            // Files not in any group are similar to nothing, not even to each other.
+            let first_group = group_of.get(paths[0]);
+            let all_similar =
+                first_group.is_some() && paths.iter().all(|path| group_of.get(path) == first_group);
+            if paths.len() < 2 || all_similar {
+                return None;
+            }
+            Some(Conflict {
+                name,
+                paths: paths.into_iter().map(Path::to_path_buf).collect(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,116 @@
+//! Writing results like Czkawka saves similar images, to review them there.
+//!
+//! The document is what Czkawka writes when it saves results of its
+//! "Similar Images" tool as JSON: an array of groups, every group an array
+//! of files with their size, dimensions, modification time, hash and
+//! similarity, the Hamming distance to the first file of the group.
+//! Both the newer `width` and `height` and the older `dimensions` are written,
+//! so that more versions read it.
+//!
+//! ```json
+//! [
+//!   [
+//!     {"path": "/home/user/Pictures/a.jpg", "size": 81234, "width": 640, "height": 480,
+//!      "dimensions": "640x480", "modified_date": 1618000000,
+//!      "hash": [0, 0, 0, 0, 0, 0, 0, 255], "similarity": 0, "image_type": "Normal"}
+//!   ]
+//! ]
+//! ```
+//!
+//! Like with [`fdupes`](super::fdupes), every file is written once and
+//! groups of images of one file are left out.
+
+use super::super::img::Probe;
+use super::Group;
+use async_std::path::Path;
+use serde_json::{json, Value};
+
+/// The document for groups of similar images.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{features::ImgFeatures, group::Group, img::Img, output::czkawka};
+/// let img = |path: &str, features| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode(features).unwrap(),
+/// };
+/// let group = Group {
+///     imgs: vec![
+///         img("/a.jpg", "00000000000000ff 0 0 0.5 0.2"),
+///         img("/b.jpg", "00000000000000fe 0 0 0.5 0.2"),
+///     ],
+///     score: None,
+///     explanations: Vec::new(),
+/// };
+/// let document = czkawka::document(&[group]);
+/// assert_eq!(document[0][1]["path"], "/b.jpg");
+/// assert_eq!(document[0][1]["similarity"], 1);
+/// assert_eq!(document[0][1]["hash"][7], 254);
+/// ```
+pub fn document(groups: &[Group]) -> Value {
+    let groups: Vec<Value> = groups
+        .iter()
+        .map(Group::files)
+        .filter(|files| files.len() > 1)
+        .map(|files| {
+            let first = &files[0].features.lshash;
+            let entries: Vec<Value> = files
+                .iter()
+                .map(|img| {
+                    let metadata = std::fs::metadata(&img.path).ok();
+                    let modified = metadata
+                        .as_ref()
+                        .and_then(|metadata| metadata.modified().ok())
+                        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok());
+                    // Only the header is read, not the whole image again.
+                    let dimensions = Probe::of_file(img.path.as_ref())
+                        .ok()
+                        .and_then(|probe| probe.dimensions)
+                        .unwrap_or_default();
+                    json!({
+                        "path": img.path.to_string_lossy(),
+                        "size": metadata.map_or(0, |metadata| metadata.len()),
+                        "width": dimensions.0,
+                        "height": dimensions.1,
+                        "dimensions": format!("{}x{}", dimensions.0, dimensions.1),
+                        "modified_date": modified.map_or(0, |modified| modified.as_secs()),
+                        "hash": img.features.lshash.bits().to_be_bytes(),
+                        "similarity": first.distance(&img.features.lshash),
+                        "image_type": "Normal",
+                    })
+                })
+                .collect();
+            Value::Array(entries)
+        })
+        .collect();
+    Value::Array(groups)
+}
+
+/// Write groups of similar images like Czkawka saves them,
+/// to a file, or to stdout without one.
+///
+/// # Arguments
+///
+/// * `groups` - what to write.
+/// * `output` - the file to write to.
+pub fn write(groups: &[Group], output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let document = document(groups);
+    match output {
+        Some(output) => {
+            let file = std::io::BufWriter::new(std::fs::File::create(output)?);
+            serde_json::to_writer_pretty(file, &document)?;
+            info!(
+                "Wrote {} groups to '{}'",
+                document.as_array().map_or(0, Vec::len),
+                output.to_string_lossy()
+            );
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &document)?;
+            std::io::Write::write_all(&mut stdout, b"\n")?;
+        }
+    }
+    Ok(())
+}
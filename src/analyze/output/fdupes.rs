@@ -0,0 +1,78 @@
+//! Writing results like fdupes does, for scripts built around it.
+//!
+//! Every image of a group is on its own line, and every group is followed
+//! by a blank line, exactly as `fdupes` and `jdupes` print sets of duplicates:
+//!
+//! ```text
+//! /home/user/Pictures/a.jpg
+//! /home/user/Pictures/copies/a.jpg
+//!
+//! /home/user/Pictures/b.png
+//! /home/user/Pictures/b (1).png
+//!
+//! ```
+//!
+//! Every file is written once, even if more than one of its images are in
+//! the group, e.g. keyframes of a video. Groups of images of one file are
+//! left out. Paths are written as they are, so those with newlines in them
+//! can't be told apart, like with fdupes itself.
+
+use super::Group;
+use async_std::path::Path;
+use std::io::Write;
+
+/// Write groups of similar images in the fdupes format.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{features::ImgFeatures, group::Group, img::Img, output::fdupes};
+/// let img = |path: &str| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2").unwrap(),
+/// };
+/// let group = Group {
+///     imgs: vec![img("/a.jpg"), img("/b.jpg")],
+///     score: None,
+///     explanations: Vec::new(),
+/// };
+/// let mut written = Vec::new();
+/// fdupes::write_to(&[group], &mut written).unwrap();
+/// assert_eq!(written, b"/a.jpg\n/b.jpg\n\n");
+/// ```
+pub fn write_to<W: Write>(groups: &[Group], writer: &mut W) -> std::io::Result<()> {
+    for group in groups {
+        let files = group.files();
+        if files.len() < 2 {
+            continue;
+        }
+        for img in files {
+            writeln!(writer, "{}", img.path.to_string_lossy())?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+/// Write groups of similar images in the fdupes format,
+/// to a file, or to stdout without one.
+///
+/// # Arguments
+///
+/// * `groups` - what to write.
+/// * `output` - the file to write to.
+pub fn write(groups: &[Group], output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    match output {
+        Some(output) => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(output)?);
+            write_to(groups, &mut file)?;
+            info!(
+                "Wrote {} groups to '{}'",
+                groups.len(),
+                output.to_string_lossy()
+            );
+        }
+        None => write_to(groups, &mut std::io::stdout().lock())?,
+    }
+    Ok(())
+}
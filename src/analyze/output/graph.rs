@@ -0,0 +1,74 @@
+//! Writing pairs of similar images as a graph for Graphviz.
+//!
+//! Images are nodes, labeled with their file names, and every pair of similar
+//! images the groups are made of is an edge, labeled with how many bits their
+//! hashes differ in. A group is a connected part of the graph, so a long
+//! chain of images in it tells that the ends are similar only through the
+//! images between them. Images without a similar one are left out.
+//!
+//! ```console
+//! $ dot -Tsvg groups.dot > groups.svg
+//! ```
+
+use crate::analyze::img::Img;
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+/// A string as a quoted DOT identifier.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::output::graph;
+/// assert_eq!(graph::quote(r#"say "cheese"\"#), r#""say \"cheese\"\\""#);
+/// ```
+pub fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Write pairs of similar images as a graph in the DOT language.
+///
+/// # Arguments
+///
+/// * `imgs` - all images.
+/// * `pairs` - pairs of similar images, as indices into `imgs`.
+/// * `writer` - where to write the graph to.
+pub fn write_dot(
+    imgs: &[Img<PathBuf>],
+    pairs: &[(usize, usize)],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    writeln!(writer, "graph similar {{")?;
+    writeln!(writer, "  node [shape=box];")?;
+    let nodes: BTreeSet<usize> = pairs.iter().flat_map(|&(i, j)| [i, j]).collect();
+    for i in nodes {
+        let path = imgs[i].path.to_string_lossy();
+        let name = imgs[i].path.file_name().map(|name| name.to_string_lossy());
+        writeln!(
+            writer,
+            "  n{} [label={}, tooltip={}];",
+            i,
+            quote(name.as_deref().unwrap_or(&path)),
+            quote(&path)
+        )?;
+    }
+    for &(i, j) in pairs {
+        let distance = imgs[i].features.lshash.distance(&imgs[j].features.lshash);
+        writeln!(writer, "  n{} -- n{} [label={}];", i, j, distance)?;
+    }
+    writeln!(writer, "}}")
+}
+
+/// Write pairs of similar images as a graph to a file, see [`write_dot`].
+pub fn write(imgs: &[Img<PathBuf>], pairs: &[(usize, usize)], path: &Path) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(std::fs::File::create(path)?);
+    write_dot(imgs, pairs, &mut writer)?;
+    writer.flush()?;
+    info!(
+        "Wrote {} pairs of similar images to '{}'",
+        pairs.len(),
+        path.to_string_lossy()
+    );
+    Ok(())
+}
@@ -0,0 +1,215 @@
+//! Writing results as JSON, for other programs to read.
+//!
+//! The document has the roots the files came from, the groups with
+//! features of every image and the bytes deleting all but one of their
+//! files would reclaim keeping the file every
+//! [strategy](super::super::savings::Strategy) keeps, the same in total,
+//! the files that were skipped because
+//! their decoder isn't in this build, with counts by format, and hard links
+//! to analyzed files, which share their data already. Images have the
+//! `hue_name` their hue is in, see [`HueName`](crate::features::hue::HueName),
+//! `taken`, `camera`, `orientation` and `gps` from their EXIF metadata, or
+//! null, and the `tags` of their files in a [database](crate::db). With
+//! [`explain`](super::super::explain), groups have their `pairs` of similar
+//! images too, with distances in every feature and what made them a match:
+//!
+//! ```json
+//! {
+//!   "roots": ["/home/user/Pictures"],
+//!   "groups": [
+//!     {"score": 0.9, "images": [{"path": "...", "root": "...", "width": 640, "height": 480, "tags": [], ...}],
+//!      "savings": {"sharpest": 2202009, "largest": 2202009, "smallest": 3565158},
+//!      "pairs": [{"a": "...", "b": "...", "trigger": "features", "closest": "lshash", "distances": [...], ...}]}
+//!   ],
+//!   "savings": {"sharpest": 2202009, "largest": 2202009, "smallest": 3565158},
+//!   "unsupported": {
+//!     "counts": [{"format": "RAW", "feature": "raw", "files": 1, "reason": "..."}],
+//!     "files": [{"path": "...", "format": "RAW", "reason": "..."}]
+//!   },
+//!   "hardlinks": [{"path": "...", "links_to": "..."}]
+//! }
+//! ```
+
+use super::super::decoders;
+use super::super::explain::{Explanation, Trigger};
+use super::super::features::exif::Exif;
+use super::super::img::Probe;
+use super::super::savings::{self, Savings};
+use super::super::walker::Files;
+use super::Group;
+use async_std::path::Path;
+use serde_json::{json, Value};
+
+/// A pair of similar images with why they are similar, see [`Explanation`].
+fn explanation(explanation: &Explanation) -> Value {
+    let distances: Vec<Value> = explanation
+        .distances
+        .iter()
+        .map(|distance| {
+            json!({
+                "feature": distance.name,
+                "distance": distance.distance,
+                "max_distance": distance.max_distance,
+            })
+        })
+        .collect();
+    let (trigger, part, min_part) = match explanation.trigger {
+        Trigger::Features => ("features", None, None),
+        Trigger::Contained { part, min_part } => ("contained", Some(part), Some(min_part)),
+        Trigger::Burst { .. } => ("burst", None, None),
+    };
+    // Seconds between photos of a burst, and how many there can be.
+    let (apart, window) = match explanation.trigger {
+        Trigger::Burst { apart, window } => (Some(apart.as_secs_f64()), Some(window.as_secs_f64())),
+        _ => (None, None),
+    };
+    json!({
+        "a": explanation.a.to_string_lossy(),
+        "b": explanation.b.to_string_lossy(),
+        "trigger": trigger,
+        "part": part,
+        "min_part": min_part,
+        "apart": apart,
+        "window": window,
+        "closest": explanation.closest().map(|distance| distance.name),
+        "distances": distances,
+        "verify": explanation.verify.map(|(method, cutoff)| json!({
+            "method": method.name(),
+            "cutoff": cutoff,
+        })),
+    })
+}
+
+/// Bytes every strategy would reclaim, by its name.
+fn savings(savings: &[Savings], bytes: impl Fn(&Savings) -> u64) -> Value {
+    savings
+        .iter()
+        .map(|savings| (savings.strategy.name().to_owned(), bytes(savings).into()))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// The document for groups of similar images.
+///
+/// # Arguments
+///
+/// * `groups` - groups of similar images.
+/// * `files` - the files that were analyzed.
+pub fn document(groups: &[Group], files: &Files) -> Value {
+    let estimate = savings::estimate(groups, &savings::sizes(groups));
+    let groups: Vec<Value> = groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let images: Vec<Value> = group
+                .imgs
+                .iter()
+                .map(|img| {
+                    // Only the header is read, not the whole image again.
+                    let probe = Probe::of_file(img.path.as_ref()).ok();
+                    let format = probe.and_then(|probe| probe.format);
+                    let dimensions = probe.and_then(|probe| probe.dimensions);
+                    let exif = img.features.exif.as_ref();
+                    json!({
+                        "path": img.path.to_string_lossy(),
+                        "root": files.root_of(&img.path),
+                        "format": format.map(|format| format!("{:?}", format).to_lowercase()),
+                        "width": dimensions.map(|(width, _)| width),
+                        "height": dimensions.map(|(_, height)| height),
+                        "distance": group.distance(img),
+                        "sharpness": img.features.sharpness.variance(),
+                        "brightness": img.features.brightness.mean(),
+                        "contrast": img.features.contrast.rms(),
+                        "hue": img.features.hue.degrees(),
+                        "hue_name": img.features.hue.name().name(),
+                        "taken": exif.and_then(Exif::date_time),
+                        "camera": exif.and_then(|exif| exif.camera.as_ref()),
+                        "orientation": exif.and_then(|exif| exif.orientation),
+                        "gps": exif.and_then(|exif| exif.gps).map(|(latitude, longitude)| json!({
+                            "latitude": latitude,
+                            "longitude": longitude,
+                        })),
+                        "tags": files.tags_of(&img.path),
+                    })
+                })
+                .collect();
+            let mut group_value = json!({
+                "score": group.score,
+                "images": images,
+                "savings": savings(&estimate, |savings| savings.groups[i]),
+            });
+            if !group.explanations.is_empty() {
+                group_value["pairs"] = group.explanations.iter().map(explanation).collect();
+            }
+            group_value
+        })
+        .collect();
+
+    let counts: Vec<Value> = decoders::counts(&files.unsupported)
+        .into_iter()
+        .map(|(decoder, count)| {
+            json!({
+                "format": decoder.format,
+                "feature": decoder.feature,
+                "files": count,
+                "reason": decoder.reason(),
+            })
+        })
+        .collect();
+    let unsupported: Vec<Value> = files
+        .unsupported
+        .iter()
+        .map(|(path, decoder)| {
+            json!({
+                "path": path.to_string_lossy(),
+                "format": decoder.format,
+                "reason": decoder.reason(),
+            })
+        })
+        .collect();
+
+    let hardlinks: Vec<Value> = files
+        .hardlinks
+        .iter()
+        .map(|(path, picked)| {
+            json!({
+                "path": path.to_string_lossy(),
+                "links_to": picked.to_string_lossy(),
+            })
+        })
+        .collect();
+
+    json!({
+        "roots": files.roots,
+        "groups": groups,
+        "savings": savings(&estimate, |savings| savings.total),
+        "unsupported": { "counts": counts, "files": unsupported },
+        "hardlinks": hardlinks,
+    })
+}
+
+/// Write groups of similar images to `output`, or to stdout.
+pub fn write(
+    groups: &[Group],
+    files: &Files,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let document = document(groups, files);
+    match output {
+        Some(output) => {
+            let file = std::io::BufWriter::new(std::fs::File::create(output)?);
+            serde_json::to_writer_pretty(file, &document)?;
+            info!(
+                "Wrote {} groups to '{}'",
+                groups.len(),
+                output.to_string_lossy()
+            );
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &document)?;
+            std::io::Write::write_all(&mut stdout, b"\n")?;
+        }
+    }
+    Ok(())
+}
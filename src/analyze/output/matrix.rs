@@ -0,0 +1,212 @@
+//! Writing distances between all images, for other programs to cluster them.
+//!
+//! Distances are how many bits hashes of two images differ in, whatever other
+//! features images are compared by. Every image is a row, keyframes of a video
+//! are rows of their own with the same path. The matrix is either full, with
+//! a distance for every pair, or sparse, with only pairs at most a given
+//! distance apart, see [`Export::max_distance`].
+//!
+//! # CSV
+//!
+//! A full matrix has a header of paths and a row of distances to every image
+//! for every image, starting with its path. A sparse one has a header
+//! `a,b,distance` and a row with the paths and the distance for every pair.
+//!
+//! # Binary
+//!
+//! [`MAGIC`], a byte that is 0 for a full matrix and 1 for a sparse one, the
+//! number of images as a little-endian `u32`, and every path as its length in
+//! bytes as a little-endian `u32` and UTF-8 bytes. A full matrix follows as
+//! a distance in a byte for every pair, row by row, e.g. with numpy:
+//!
+//! ```python
+//! distances = numpy.frombuffer(data, numpy.uint8, count=n * n, offset=end_of_paths).reshape(n, n)
+//! ```
+//!
+//! A sparse one follows as the number of pairs as a little-endian `u64`, and
+//! every pair as two little-endian `u32` indices and a byte of the distance.
+
+use crate::analyze::img::Img;
+use async_std::path::{Path, PathBuf};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+/// The first bytes of a binary matrix, with the version of the format.
+pub const MAGIC: &[u8; 8] = b"SCMTRX01";
+
+/// How to write the matrix.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// Comma-separated values, see the [module](self).
+    #[default]
+    Csv,
+    /// Little-endian integers and a byte for every distance, see the [module](self).
+    Binary,
+}
+
+impl Format {
+    /// Name of the format, as `--matrix-format` takes it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!("unknown matrix format '{}'", s)),
+        }
+    }
+}
+
+/// Where and how to write the matrix, see `--export-matrix`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Export {
+    /// The file to write it to.
+    pub path: PathBuf,
+    /// How to write it.
+    pub format: Format,
+    /// Only pairs at most this many bits apart are written if given, as a
+    /// sparse matrix, all pairs are otherwise.
+    pub max_distance: Option<u32>,
+}
+
+/// Pairs of images at most `max_distance` bits apart, as indices with the
+/// first one lower, and their distance.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{features::ImgFeatures, img::Img, output::matrix};
+/// let img = |hash: &str| Img {
+///     path: async_std::path::PathBuf::from(hash),
+///     features: ImgFeatures::decode(&format!("{} 0 90 0.5 0.2", hash)).unwrap(),
+/// };
+/// let imgs = [img("000000000000000f"), img("0000000000000007"), img("ff00000000000000")];
+/// assert_eq!(matrix::pairs(&imgs, 4), vec![(0, 1, 1)]);
+/// assert_eq!(matrix::pairs(&imgs, 64).len(), 3);
+/// ```
+pub fn pairs(imgs: &[Img<PathBuf>], max_distance: u32) -> Vec<(usize, usize, u32)> {
+    let mut pairs = Vec::new();
+    for (i, a) in imgs.iter().enumerate() {
+        for (j, b) in imgs.iter().enumerate().skip(i + 1) {
+            let distance = a.features.lshash.distance(&b.features.lshash);
+            if distance <= max_distance {
+                pairs.push((i, j, distance));
+            }
+        }
+    }
+    pairs
+}
+
+/// A path as a CSV field, quoted if it has to be.
+fn field(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if path.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", path.replace('"', "\"\""))
+    } else {
+        path.into_owned()
+    }
+}
+
+/// Write distances between images as CSV.
+fn write_csv(
+    imgs: &[Img<PathBuf>],
+    max_distance: Option<u32>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let paths: Vec<String> = imgs.iter().map(|img| field(&img.path)).collect();
+    match max_distance {
+        None => {
+            writeln!(writer, ",{}", paths.join(","))?;
+            for (a, path) in imgs.iter().zip(&paths) {
+                write!(writer, "{}", path)?;
+                for b in imgs {
+                    write!(
+                        writer,
+                        ",{}",
+                        a.features.lshash.distance(&b.features.lshash)
+                    )?;
+                }
+                writeln!(writer)?;
+            }
+        }
+        Some(max_distance) => {
+            writeln!(writer, "a,b,distance")?;
+            for (a, b, distance) in pairs(imgs, max_distance) {
+                writeln!(writer, "{},{},{}", paths[a], paths[b], distance)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write distances between images in the binary format.
+fn write_binary(
+    imgs: &[Img<PathBuf>],
+    max_distance: Option<u32>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let len = |len: usize| {
+        u32::try_from(len).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many images or too long a path",
+            )
+        })
+    };
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[max_distance.is_some() as u8])?;
+    writer.write_all(&len(imgs.len())?.to_le_bytes())?;
+    for img in imgs {
+        let path = img.path.to_string_lossy();
+        writer.write_all(&len(path.len())?.to_le_bytes())?;
+        writer.write_all(path.as_bytes())?;
+    }
+    match max_distance {
+        None => {
+            for a in imgs {
+                let row: Vec<u8> = imgs
+                    .iter()
+                    .map(|b| a.features.lshash.distance(&b.features.lshash) as u8)
+                    .collect();
+                writer.write_all(&row)?;
+            }
+        }
+        Some(max_distance) => {
+            let pairs = pairs(imgs, max_distance);
+            writer.write_all(&(pairs.len() as u64).to_le_bytes())?;
+            for (a, b, distance) in pairs {
+                writer.write_all(&(a as u32).to_le_bytes())?;
+                writer.write_all(&(b as u32).to_le_bytes())?;
+                writer.write_all(&[distance as u8])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write distances between images as `export` says.
+///
+/// Blocks the thread, and takes time growing with the square of the number of images.
+pub fn write(imgs: &[Img<PathBuf>], export: &Export) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(std::fs::File::create(&export.path)?);
+    match export.format {
+        Format::Csv => write_csv(imgs, export.max_distance, &mut writer)?,
+        Format::Binary => write_binary(imgs, export.max_distance, &mut writer)?,
+    }
+    writer.flush()?;
+    info!(
+        "Wrote distances between {} images to '{}'",
+        imgs.len(),
+        export.path.to_string_lossy()
+    );
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! Writing results of the analysis.
+//!
+//! Groups of similar images (see [`group`](super::group)) are written
+//! in one of the [`Format`]s, either to stdout or to a file. Only messages
+//! go to the log, so that results can be piped.
+//! When files come from more than one root (see [`Files`]),
+//! the root of every file is written too. Distances between all images
+//! can be written besides, see [`matrix`], and so can pairs of similar
+//! images as a graph, see [`graph`].
+
+pub mod czkawka;
+pub mod fdupes;
+pub mod graph;
+pub mod json;
+pub mod matrix;
+pub mod text;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+use super::group::Group;
+use super::walker::Files;
+use async_std::path::Path;
+
+/// How to write the results.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// Text for people to read, see [`text`]; written to stdout without a file.
+    #[default]
+    Text,
+    /// JSON document, see [`json`]; written to stdout without a file.
+    Json,
+    /// Paths like fdupes prints them, see [`fdupes`]; written to stdout without a file.
+    Fdupes,
+    /// Similar images like Czkawka saves them, see [`czkawka`]; written to stdout without a file.
+    Czkawka,
+    /// Excel workbook, only with the `xlsx` feature.
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            // Groups used to be written to the log.
+            "text" | "log" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "fdupes" => Ok(Self::Fdupes),
+            "czkawka" => Ok(Self::Czkawka),
+            #[cfg(feature = "xlsx")]
+            "xlsx" => Ok(Self::Xlsx),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+/// Open `output` to write results to, or stdout without it.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::output;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::io::Write;
+/// writeln!(output::open(None)?, "to stdout")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn open(output: Option<&Path>) -> std::io::Result<Box<dyn std::io::Write>> {
+    Ok(match output {
+        Some(output) => Box::new(std::io::BufWriter::new(std::fs::File::create(output)?)),
+        None => Box::new(std::io::stdout().lock()),
+    })
+}
+
+/// Write groups of similar images.
+///
+/// # Arguments
+///
+/// * `groups` - what to write.
+/// * `files` - the files that were analyzed, to tell roots of images.
+/// * `format` - how to write it.
+/// * `output` - the file to write to, required by some formats.
+pub fn write(
+    groups: &[Group],
+    files: &Files,
+    format: Format,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        Format::Text => text::write(groups, files, output),
+        Format::Json => json::write(groups, files, output),
+        Format::Fdupes => fdupes::write(groups, output),
+        Format::Czkawka => czkawka::write(groups, output),
+        #[cfg(feature = "xlsx")]
+        Format::Xlsx => {
+            let output = output.ok_or("the xlsx format needs a file to write to")?;
+            xlsx::write(groups, files, output)
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Writing results as text for people to read.
+//!
+//! Every group is a line with its number, size and score, followed by
+//! a line for every image with its distance to the first one, and the root
+//! it is from if files come from more than one, and by what deleting all
+//! but one of its files would reclaim (see [`savings`](super::super::savings)).
+//! What all groups would reclaim comes next, and hard links to analyzed
+//! files, which share their data already, last:
+//!
+//! ```text
+//! Group 1 of 2 images (score 0.93):
+//!     '/home/user/Pictures/a.jpg' (distance 0)
+//!     '/home/user/Pictures/copies/a.jpg' (distance 1)
+//!     Reclaims 2.1 MiB keeping the sharpest file, 2.1 MiB the largest, 3.4 MiB the smallest
+//! In total, reclaims 2.1 MiB keeping the sharpest file, 2.1 MiB the largest, 3.4 MiB the smallest
+//! '/home/user/Pictures/b.jpg' is a hard link to '/home/user/Pictures/2019/b.jpg', deduplicated already
+//! ```
+//!
+//! With [`explain`](crate::analyze::AnalyzeOptions::explain), explanations
+//! of every pair follow the images.
+
+use super::super::savings::{self, Savings};
+use super::super::walker::Files;
+use super::Group;
+use async_std::path::Path;
+use std::io::Write;
+
+/// What strategies would reclaim, as the end of a sentence.
+fn reclaims(savings: &[Savings], bytes: impl Fn(&Savings) -> u64) -> String {
+    savings
+        .iter()
+        .enumerate()
+        .map(|(i, savings)| {
+            let size = crate::units::format_size(bytes(savings));
+            match i {
+                0 => format!("{} keeping the {} file", size, savings.strategy.name()),
+                _ => format!("{} the {}", size, savings.strategy.name()),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Write groups of similar images as text.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{features::ImgFeatures, group::Group, img::Img, output::text, walker::Files};
+/// let img = |path: &str| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2").unwrap(),
+/// };
+/// let group = Group {
+///     imgs: vec![img("/a.jpg"), img("/b.jpg")],
+///     score: None,
+///     explanations: Vec::new(),
+/// };
+/// let mut written = Vec::new();
+/// text::write_to(&[group], &Files::default(), &mut written).unwrap();
+/// let written = String::from_utf8(written).unwrap();
+/// assert!(written.starts_with("Group 1 of 2 images:\n    '/a.jpg' (distance 0)\n"));
+/// assert!(written.ends_with("In total, reclaims 0 B keeping the sharpest file, 0 B the largest, 0 B the smallest\n"));
+/// ```
+pub fn write_to<W: Write>(groups: &[Group], files: &Files, writer: &mut W) -> std::io::Result<()> {
+    let many_roots = files.roots.len() > 1;
+    let savings = savings::estimate(groups, &savings::sizes(groups));
+    for (i, group) in groups.iter().enumerate() {
+        match group.score {
+            Some(score) => writeln!(
+                writer,
+                "Group {} of {} images (score {:.2}):",
+                i + 1,
+                group.imgs.len(),
+                score
+            )?,
+            None => writeln!(writer, "Group {} of {} images:", i + 1, group.imgs.len())?,
+        }
+        for img in &group.imgs {
+            match files.root_of(&img.path).filter(|_| many_roots) {
+                Some(root) => writeln!(
+                    writer,
+                    "    '{}' (distance {}, from '{}')",
+                    img.path.to_string_lossy(),
+                    group.distance(img),
+                    root
+                )?,
+                None => writeln!(
+                    writer,
+                    "    '{}' (distance {})",
+                    img.path.to_string_lossy(),
+                    group.distance(img)
+                )?,
+            }
+        }
+        let reclaims = reclaims(&savings, |savings| savings.groups[i]);
+        writeln!(writer, "    Reclaims {}", reclaims)?;
+        for explanation in &group.explanations {
+            writeln!(
+                writer,
+                "    '{}' ~ '{}': {}",
+                explanation.a.to_string_lossy(),
+                explanation.b.to_string_lossy(),
+                explanation.describe()
+            )?;
+        }
+    }
+    if !groups.is_empty() {
+        let reclaims = reclaims(&savings, |savings| savings.total);
+        writeln!(writer, "In total, reclaims {}", reclaims)?;
+    }
+    for (path, picked) in &files.hardlinks {
+        writeln!(
+            writer,
+            "'{}' is a hard link to '{}', deduplicated already",
+            path.to_string_lossy(),
+            picked.to_string_lossy()
+        )?;
+    }
+    writer.flush()
+}
+
+/// Write groups of similar images as text, to a file, or to stdout without one.
+///
+/// # Arguments
+///
+/// * `groups` - what to write.
+/// * `files` - the files that were analyzed, to tell roots of images.
+/// * `output` - the file to write to.
+pub fn write(
+    groups: &[Group],
+    files: &Files,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_to(groups, files, &mut super::open(output)?)?;
+    match output {
+        Some(output) => info!(
+            "Wrote {} groups of similar images to '{}'",
+            groups.len(),
+            output.to_string_lossy()
+        ),
+        None => info!("Found {} groups of similar images", groups.len()),
+    }
+    Ok(())
+}
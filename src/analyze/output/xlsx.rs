@@ -0,0 +1,151 @@
+//! Writing results as an Excel workbook.
+//!
+//! The workbook has a "Groups" sheet with a row per group and a "Files" sheet
+//! with a row per image. Files whose decoder isn't in this build are listed
+//! in an "Unsupported" sheet, if there are any. Paths are hyperlinks, so that
+//! images can be opened right from Excel while deciding what to keep.
+
+use super::super::walker::Files;
+use super::Group;
+use async_std::path::Path;
+use rust_xlsxwriter::{Format, Url, Workbook, Worksheet, XlsxError};
+use std::collections::HashSet;
+
+/// Write the header row of a sheet.
+fn write_header(sheet: &mut Worksheet, titles: &[&str]) -> Result<(), XlsxError> {
+    let bold = Format::new().set_bold();
+    for (col, title) in titles.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, &bold)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+/// Write a path as a hyperlink to the file.
+fn write_path(sheet: &mut Worksheet, row: u32, col: u16, path: &Path) -> Result<(), XlsxError> {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.into());
+    let link = format!("file:///{}", absolute.to_string_lossy());
+    sheet.write_url_with_text(row, col, Url::new(link), path.to_string_lossy())?;
+    Ok(())
+}
+
+/// Write groups of similar images to a workbook at `output`.
+pub fn write(
+    groups: &[Group],
+    files: &Files,
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).ok();
+    let mut workbook = Workbook::new();
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Groups")?;
+    write_header(
+        sheet,
+        &[
+            "Group",
+            "Images",
+            "Total size",
+            "Max distance",
+            "Score",
+            "First file",
+        ],
+    )?;
+    for (i, group) in groups.iter().enumerate() {
+        let row = i as u32 + 1;
+        // Keyframes of a video share the file, count it once.
+        let paths: HashSet<_> = group.imgs.iter().map(|img| &img.path).collect();
+        let total_size: u64 = paths.iter().filter_map(|path| size(path)).sum();
+        let max_distance = group
+            .imgs
+            .iter()
+            .map(|img| group.distance(img))
+            .max()
+            .unwrap_or(0);
+
+        sheet.write_number(row, 0, (i + 1) as f64)?;
+        sheet.write_number(row, 1, group.imgs.len() as f64)?;
+        sheet.write_number(row, 2, total_size as f64)?;
+        sheet.write_number(row, 3, max_distance)?;
+        if let Some(score) = group.score {
+            sheet.write_number(row, 4, score)?;
+        }
+        write_path(sheet, row, 5, &group.imgs[0].path)?;
+    }
+    sheet.autofit();
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Files")?;
+    write_header(
+        sheet,
+        &[
+            "Group",
+            "File",
+            "Size",
+            "Modified",
+            "Distance",
+            "Sharpness",
+            "Brightness",
+            "Contrast",
+            "Hue",
+            "Root",
+            "Taken",
+            "Camera",
+        ],
+    )?;
+    let mut row = 1;
+    for (i, group) in groups.iter().enumerate() {
+        for img in &group.imgs {
+            let modified = std::fs::metadata(&img.path).and_then(|m| m.modified());
+
+            sheet.write_number(row, 0, (i + 1) as f64)?;
+            write_path(sheet, row, 1, &img.path)?;
+            if let Some(size) = size(&img.path) {
+                sheet.write_number(row, 2, size as f64)?;
+            }
+            if let Ok(modified) = modified {
+                let modified = chrono::DateTime::<chrono::Local>::from(modified);
+                sheet.write_string(row, 3, modified.format("%Y-%m-%d %H:%M:%S").to_string())?;
+            }
+            sheet.write_number(row, 4, group.distance(img))?;
+            sheet.write_number(row, 5, img.features.sharpness.variance())?;
+            sheet.write_number(row, 6, img.features.brightness.mean())?;
+            sheet.write_number(row, 7, img.features.contrast.rms())?;
+            sheet.write_number(row, 8, img.features.hue.degrees())?;
+            if let Some(root) = files.root_of(&img.path) {
+                sheet.write_string(row, 9, root)?;
+            }
+            if let Some(exif) = &img.features.exif {
+                if let Some(taken) = exif.date_time() {
+                    sheet.write_string(row, 10, taken)?;
+                }
+                if let Some(camera) = &exif.camera {
+                    sheet.write_string(row, 11, camera)?;
+                }
+            }
+            row += 1;
+        }
+    }
+    sheet.autofit();
+
+    if !files.unsupported.is_empty() {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Unsupported")?;
+        write_header(sheet, &["File", "Format", "Reason"])?;
+        for (i, (path, decoder)) in files.unsupported.iter().enumerate() {
+            let row = i as u32 + 1;
+            write_path(sheet, row, 0, path)?;
+            sheet.write_string(row, 1, decoder.format)?;
+            sheet.write_string(row, 2, decoder.reason())?;
+        }
+        sheet.autofit();
+    }
+
+    workbook.save(output)?;
+    info!(
+        "Wrote {} groups to '{}'",
+        groups.len(),
+        output.to_string_lossy()
+    );
+    Ok(())
+}
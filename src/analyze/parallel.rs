@@ -0,0 +1,282 @@
+//! Finding features on all cores of this process.
+//!
+//! Reading files is IO, so it stays on the async executor, while decoding
+//! images and finding their features is CPU-bound and runs on a pool of
+//! threads, one per core unless told otherwise. Results come back through
+//! a channel, in the order files are done in.
+//!
+//! Only a few files per thread are read ahead of the pool, so that contents
+//! of a whole library don't pile up in memory while waiting to be decoded.
+//! Contents and decoded pixels of a file are dropped as soon as its features
+//! are found, only the features are kept. With a memory limit, fewer files
+//! are in flight when they are large, see [`footprint`]. With a timeout,
+//! files that take too long to decode are given up on, see [`Limits`].
+//! Files are read one at a time, or a few at once from slower storage, e.g.
+//! from [S3](super::s3), see [`Limits::reads`].
+
+use super::cancel::CancelToken;
+use super::features::{exif::Exif, FindOptions, ImgFeatures};
+use super::img::{ImgRaw, Probe};
+use super::index::checkpoint::Checkpoint;
+use super::metrics::METRICS;
+use super::progress::Tracker;
+use super::worker::Outcome;
+use async_std::path::PathBuf;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How many files per thread are read ahead of the pool.
+pub const READ_AHEAD: usize = 2;
+
+/// Limits of resources files take while their features are found.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// Soft limit of bytes files in flight take. A file that doesn't fit
+    /// is read anyway, but waits until all others are done.
+    pub max_memory: Option<u64>,
+    /// How long decoding a file and finding its features can take.
+    ///
+    /// A decoder can't be stopped, so it goes on in the background until it
+    /// is done, but its file is reported as failed right away and the pool
+    /// moves on. Memory it takes isn't counted against `max_memory` then.
+    pub timeout: Option<Duration>,
+    /// How many files are read at once, one if zero. Files read ahead
+    /// wait for memory like others do after they are read.
+    pub reads: usize,
+}
+
+/// What the async side hands to the pool.
+enum Job {
+    /// Contents of a file, to be decoded.
+    Contents(Vec<u8>),
+    /// Images that were decoded already, e.g. keyframes of a video.
+    #[cfg(feature = "video")]
+    Decoded(Vec<ImgRaw<PathBuf>>),
+}
+
+impl Job {
+    /// Decode the images if needed and find their features.
+    fn run(self, path: &PathBuf, options: FindOptions) -> Outcome {
+        // Contents are dropped once decoded, and images once their features are found.
+        let (imgs_raw, exif) = match self {
+            Job::Contents(data_raw) => {
+                let img_raw = ImgRaw::decode(path.clone(), &data_raw).map_err(|e| e.to_string())?;
+                (vec![img_raw], Exif::of_bytes(&data_raw))
+            }
+            #[cfg(feature = "video")]
+            Job::Decoded(imgs_raw) => (imgs_raw, None),
+        };
+        Ok(imgs_raw
+            .into_iter()
+            .map(|img_raw| ImgFeatures {
+                exif: exif.clone(),
+                ..ImgFeatures::find_with(&img_raw, options)
+            })
+            .collect())
+    }
+}
+
+/// Run a job, giving up on it after `timeout` if there is one, see [`Limits::timeout`].
+fn run(
+    job: Result<Job, String>,
+    path: &PathBuf,
+    options: FindOptions,
+    timeout: Option<Duration>,
+) -> Outcome {
+    let timeout = match timeout {
+        // A panicking decoder would abort the whole process on the pool.
+        None => {
+            return std::panic::catch_unwind(AssertUnwindSafe(|| {
+                job.and_then(|job| job.run(path, options))
+            }))
+            .unwrap_or_else(|_| Err("decoder panicked".to_owned()))
+        }
+        Some(timeout) => timeout,
+    };
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let (job, path_owned) = (job?, path.clone());
+    std::thread::Builder::new()
+        .name("succotash-decode-timed".to_owned())
+        .spawn(move || {
+            // Nobody listens once the job has timed out.
+            let _ = sender.send(job.run(&path_owned, options));
+        })
+        .map_err(|e| e.to_string())?;
+    match receiver.recv_timeout(timeout) {
+        Ok(outcome) => outcome,
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+            warn!(
+                "Gave up on '{}' after {:?}, it is still decoded in the background",
+                path.to_string_lossy(),
+                timeout
+            );
+            Err(format!("took longer than {:?} to decode", timeout))
+        }
+        // The thread is gone without sending, so the decoder has panicked.
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Err("decoder panicked".to_owned()),
+    }
+}
+
+/// Roughly how many bytes analyzing a file takes: its contents and its pixels
+/// as 4 bytes each, with dimensions from the header. Overestimates JPEGs,
+/// which are decoded at a reduced scale, see [`ImgRaw::decode`].
+fn footprint(job: &Result<Job, String>) -> u64 {
+    match job {
+        Ok(Job::Contents(data_raw)) => {
+            let pixels = Probe::of_bytes(data_raw)
+                .dimensions
+                .map_or(0, |(width, height)| u64::from(width) * u64::from(height));
+            data_raw.len() as u64 + 4 * pixels
+        }
+        #[cfg(feature = "video")]
+        Ok(Job::Decoded(imgs_raw)) => imgs_raw
+            .iter()
+            .map(|img_raw| img_raw.data.as_bytes().len() as u64)
+            .sum(),
+        Err(_) => 0,
+    }
+}
+
+/// Prepare a job for a file, doing all of its IO.
+async fn prepare(path: &PathBuf) -> Result<Job, String> {
+    #[cfg(feature = "video")]
+    if super::video::is_video(path) {
+        // FFmpeg reads the file itself, on a blocking thread of its own.
+        let imgs_raw = ImgRaw::load_all(path.clone()).await;
+        return imgs_raw.map(Job::Decoded).map_err(|e| e.to_string());
+    }
+
+    debug!("Asynchronously reading image '{}'", path.to_string_lossy());
+    super::io::read(path.as_ref())
+        .await
+        .map(Job::Contents)
+        .map_err(|e| e.to_string())
+}
+
+/// Analyze files on a pool of threads.
+///
+/// Returns the outcome for every path, in no particular order,
+/// like [`worker::find_all`](super::worker::find_all) does.
+///
+/// # Arguments
+///
+/// * `paths` - files to analyze.
+/// * `threads` - how many threads to decode on, zero for one per core.
+/// * `limits` - how much memory files in flight and how much time every
+///   file can take.
+/// * `checkpoint` - where to save features to as soon as they are found.
+/// * `cancel` - stops reading more files, those read already are still analyzed.
+/// * `progress` - advanced after every file.
+/// * `options` - what to do when finding features.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::{parallel, progress::{Progress, Stage}};
+/// # async_std::task::block_on(async {
+/// let paths = vec!["/home/user/a.png".into(), "/home/user/b.jpg".into()];
+/// let limits = parallel::Limits {
+///     max_memory: Some(2 << 30),
+///     timeout: Some(std::time::Duration::from_secs(60)),
+///     reads: 1,
+/// };
+/// let cancel = Default::default();
+/// let progress = Progress::new(|event| eprintln!("{} of 2", event.current));
+/// let progress = progress.stage(Stage::Features, Some(2));
+/// let outcomes =
+///     parallel::find_all(paths, 0, limits, None, &cancel, progress, Default::default()).await?;
+/// for (path, outcome) in outcomes {
+///     println!("'{}': {:?}", path.to_string_lossy(), outcome.map(|imgs| imgs.len()));
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// # });
+/// ```
+pub async fn find_all(
+    paths: Vec<PathBuf>,
+    threads: usize,
+    limits: Limits,
+    checkpoint: Option<Arc<Checkpoint>>,
+    cancel: &CancelToken,
+    progress: Tracker,
+    options: FindOptions,
+) -> Result<Vec<(PathBuf, Outcome)>, Box<dyn std::error::Error>> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|index| format!("succotash-decode-{}", index))
+        .build()?;
+    debug!("Finding features on {} threads", pool.current_num_threads());
+    let read_ahead = READ_AHEAD * pool.current_num_threads();
+
+    let (sender, receiver) = async_std::channel::unbounded();
+    let mut outcomes = Vec::with_capacity(paths.len());
+    let mut pending = 0;
+    // Bytes taken by files in flight.
+    let mut in_flight = 0;
+    let mut paths = paths.into_iter();
+    // Files being read, in the order they come in.
+    let mut reading = VecDeque::new();
+    loop {
+        while reading.len() < limits.reads.max(1) && !cancel.is_cancelled() {
+            let Some(path) = paths.next() else {
+                break;
+            };
+            reading.push_back(async_std::task::spawn(async move {
+                let started = Instant::now();
+                let job = prepare(&path).await;
+                (path, job, started.elapsed())
+            }));
+        }
+        let Some(read) = reading.pop_front() else {
+            break;
+        };
+        let (path, job, read) = read.await;
+
+        if pending >= read_ahead {
+            let (path, outcome, footprint) = receiver.recv().await?;
+            outcomes.push((path, outcome));
+            in_flight -= footprint;
+            pending -= 1;
+        }
+
+        let footprint = footprint(&job);
+        while pending > 0
+            && limits
+                .max_memory
+                .is_some_and(|max| in_flight + footprint > max)
+        {
+            trace!(
+                "Waiting for memory to analyze '{}' ({} bytes, {} in flight)",
+                path.to_string_lossy(),
+                footprint,
+                in_flight
+            );
+            let (path, outcome, footprint) = receiver.recv().await?;
+            outcomes.push((path, outcome));
+            in_flight -= footprint;
+            pending -= 1;
+        }
+        in_flight += footprint;
+
+        let sender = sender.clone();
+        let (checkpoint, progress) = (checkpoint.clone(), progress.clone());
+        pool.spawn(move || {
+            let started = Instant::now();
+            let outcome = run(job, &path, options, limits.timeout);
+            METRICS.file_time.observe(read + started.elapsed());
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.save(&path, &outcome);
+            }
+            progress.advance(1, Some(&path));
+            // The channel is unbounded and open until all outcomes are received.
+            let _ = sender.try_send((path, outcome, footprint));
+        });
+        pending += 1;
+    }
+    for _ in 0..pending {
+        let (path, outcome, _) = receiver.recv().await?;
+        outcomes.push((path, outcome));
+    }
+    Ok(outcomes)
+}
@@ -0,0 +1,205 @@
+//! Deciding what to do with groups of similar images.
+//!
+//! Of every group, the file with the sharpest image is kept and the other
+//! files are planned for deletion, unless criteria or priorities say
+//! otherwise, see [`Plan::weighing`], or someone decided otherwise, see
+//! [`Plan::decided`]. Nothing is deleted here: a [`Plan`] can be written down
+//! for other tools, see [`write_delete_list`].
+
+use super::group::Group;
+use super::img::Img;
+use super::keep::{Criteria, Priorities};
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// The path that stands for stdout in [`write_delete_list`].
+pub const STDOUT: &str = "-";
+
+/// What to keep and what to delete.
+pub struct Plan {
+    /// Files to keep, ordered by path.
+    pub keep: Vec<PathBuf>,
+    /// Files to delete, ordered by path.
+    pub delete: Vec<PathBuf>,
+}
+
+impl Plan {
+    /// Plan what to do with groups of similar images.
+    ///
+    /// The sharpest image of every group is kept, and of equally sharp ones
+    /// the one taken first by its [EXIF metadata](super::features::exif),
+    /// more likely the original than a copy. A file kept in one group is
+    /// never deleted, even if it is in another group too, e.g. a video with
+    /// a few keyframes.
+    ///
+    /// # Arguments
+    ///
+    /// * `groups` - groups of similar images, see [`group`](super::group).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{group, img::Img, features::ImgFeatures, plan::Plan};
+    /// let img = |path: &str, features| Img {
+    ///     path: async_std::path::PathBuf::from(path),
+    ///     features: ImgFeatures::decode(features).unwrap(),
+    /// };
+    /// let imgs = vec![
+    ///     img("/blurry.jpg", "00000000000000ff 0 10 0.5 0.2"),
+    ///     img("/sharp.jpg", "00000000000000ff 0 90 0.5 0.2"),
+    /// ];
+    /// let plan = Plan::new(&group::group(imgs, group::MAX_DISTANCE));
+    /// assert_eq!(plan.keep, vec![async_std::path::PathBuf::from("/sharp.jpg")]);
+    /// assert_eq!(plan.delete, vec![async_std::path::PathBuf::from("/blurry.jpg")]);
+    ///
+    /// let imgs = vec![
+    ///     img("/copy.jpg", "00000000000000ff 0 90 0.5 0.2 exif.taken=1622548860"),
+    ///     img("/original.jpg", "00000000000000ff 0 90 0.5 0.2 exif.taken=1622548800"),
+    /// ];
+    /// let plan = Plan::new(&group::group(imgs, group::MAX_DISTANCE));
+    /// assert_eq!(plan.keep, vec![async_std::path::PathBuf::from("/original.jpg")]);
+    /// ```
+    pub fn new(groups: &[Group]) -> Self {
+        Self::keeping(groups, sharpest)
+    }
+
+    /// Plan what to do with groups of similar images, keeping the file
+    /// of the image `keeper` picks in every group, e.g. [`burst::keeper`](super::burst::keeper).
+    ///
+    /// Like [`Plan::new`], a file kept in one group is never deleted.
+    pub fn keeping<F>(groups: &[Group], keeper: F) -> Self
+    where
+        F: Fn(&Group) -> &Img<PathBuf>,
+    {
+        let mut keep = BTreeSet::new();
+        let mut delete = BTreeSet::new();
+
+        for group in groups {
+            keep.insert(keeper(group).path.clone());
+            delete.extend(group.imgs.iter().map(|img| img.path.clone()));
+        }
+
+        Self {
+            delete: delete.difference(&keep).cloned().collect(),
+            keep: keep.into_iter().collect(),
+        }
+    }
+
+    /// Plan what to do with groups of similar images, keeping the file of
+    /// every group that scores highest by `criteria`, see [`keep`](super::keep),
+    /// or the sharpest image like [`Plan::new`] if no criterion is weighed.
+    /// Only files with the highest `priorities` in their group are considered.
+    ///
+    /// Blocks the thread, see [`io::unblock`](super::io::unblock).
+    pub fn weighing(groups: &[Group], criteria: &Criteria, priorities: &Priorities) -> Self {
+        if criteria.is_empty() && priorities.is_empty() {
+            return Self::new(groups);
+        }
+        Self::keeping(groups, |group| {
+            let files = priorities.top(group.files());
+            if criteria.is_empty() {
+                sharpest_of(&files)
+            } else {
+                criteria.keeper_of(&files)
+            }
+        })
+    }
+
+    /// Plan to delete files someone has picked, e.g. in the [web page](crate::serve::ui),
+    /// and to keep the other files of the groups.
+    ///
+    /// Fails if a file isn't in any group, or if no file of a group would be kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `groups` - groups of similar images, see [`group`](super::group).
+    /// * `delete` - files to delete.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{group, img::Img, features::ImgFeatures, plan::Plan};
+    /// let img = |path: &str| Img {
+    ///     path: async_std::path::PathBuf::from(path),
+    ///     features: ImgFeatures::decode("00000000000000ff 0 10 0.5 0.2").unwrap(),
+    /// };
+    /// let groups = group::group(vec![img("/a.jpg"), img("/b.jpg")], group::MAX_DISTANCE);
+    /// let plan = Plan::decided(&groups, &["/a.jpg".into()]).unwrap();
+    /// assert_eq!(plan.keep, vec![async_std::path::PathBuf::from("/b.jpg")]);
+    /// assert!(Plan::decided(&groups, &["/a.jpg".into(), "/b.jpg".into()]).is_err());
+    /// assert!(Plan::decided(&groups, &["/c.jpg".into()]).is_err());
+    /// ```
+    pub fn decided(groups: &[Group], delete: &[PathBuf]) -> Result<Self, String> {
+        let delete: BTreeSet<&PathBuf> = delete.iter().collect();
+        let mut keep = BTreeSet::new();
+        let mut grouped = BTreeSet::new();
+
+        for group in groups {
+            let paths: BTreeSet<&PathBuf> = group.imgs.iter().map(|img| &img.path).collect();
+            let kept: Vec<&PathBuf> = paths.difference(&delete).copied().collect();
+            if kept.is_empty() {
+                let first = group.imgs[0].path.to_string_lossy();
+                return Err(format!(
+                    "no file of the group with '{}' would be kept",
+                    first
+                ));
+            }
+            keep.extend(kept);
+            grouped.extend(paths);
+        }
+        if let Some(path) = delete.difference(&grouped).next() {
+            return Err(format!("'{}' is in no group", path.to_string_lossy()));
+        }
+
+        Ok(Self {
+            keep: keep.into_iter().cloned().collect(),
+            delete: delete.into_iter().cloned().collect(),
+        })
+    }
+}
+
+/// The image of a group [`Plan::new`] keeps, the sharpest one.
+pub fn sharpest(group: &Group) -> &Img<PathBuf> {
+    sharpest_of(&group.imgs.iter().collect::<Vec<_>>())
+}
+
+/// The sharpest of some images of a group, see [`sharpest`].
+pub fn sharpest_of<'a>(imgs: &[&'a Img<PathBuf>]) -> &'a Img<PathBuf> {
+    // Images are ordered by path, so the first of the sharpest ones wins
+    // ties unless another one says it was taken earlier.
+    let taken = |img: &Img<PathBuf>| img.features.exif.as_ref().and_then(|exif| exif.taken);
+    imgs.iter().copied().fold(imgs[0], |best, img| {
+        let (sharpness, best_sharpness) = (
+            img.features.sharpness.variance(),
+            best.features.sharpness.variance(),
+        );
+        let earlier = match (taken(img), taken(best)) {
+            (Some(taken), Some(best_taken)) => taken < best_taken,
+            (taken, best_taken) => taken.is_some() && best_taken.is_none(),
+        };
+        if sharpness > best_sharpness || (sharpness == best_sharpness && earlier) {
+            img
+        } else {
+            best
+        }
+    })
+}
+
+/// Write files planned for deletion to `output`, each followed by a NUL byte.
+///
+/// The list can be fed to `xargs -0` or any other tool that deletes files.
+/// It goes to stdout if `output` is `-`, see [`STDOUT`].
+pub fn write_delete_list(plan: &Plan, output: &Path) -> std::io::Result<()> {
+    let mut list = Vec::new();
+    for path in &plan.delete {
+        list.extend(super::worker::path_to_bytes(path.as_ref()));
+        list.push(b'\0');
+    }
+    if output == Path::new(STDOUT) {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&list)?;
+        return stdout.flush();
+    }
+    std::fs::write(output, list)
+}
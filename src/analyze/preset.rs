@@ -0,0 +1,74 @@
+//! Presets of the analysis.
+//!
+//! A preset is a named set of [`AnalyzeOptions`] for users who don't want
+//! to pick thresholds themselves. Options given explicitly override it.
+
+use super::{group, verify, AnalyzeOptions};
+
+/// How strict the analysis is about what is similar.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Preset {
+    /// Only near-identical images, verified by their pixels.
+    Strict,
+    /// The default options.
+    #[default]
+    Balanced,
+    /// Also images that were edited more heavily, with more false positives.
+    Loose,
+}
+
+impl Preset {
+    /// Name of the preset, as accepted by [`from_str`](std::str::FromStr::from_str).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Strict => "strict",
+            Self::Balanced => "balanced",
+            Self::Loose => "loose",
+        }
+    }
+
+    /// Options of the analysis this preset stands for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::preset::Preset;
+    /// let strict = Preset::Strict.options();
+    /// let loose = Preset::Loose.options();
+    /// assert!(strict.max_distance < loose.max_distance);
+    /// assert!(strict.verify.is_some());
+    /// ```
+    pub fn options(&self) -> AnalyzeOptions {
+        match self {
+            Self::Strict => AnalyzeOptions {
+                max_distance: group::MAX_DISTANCE / 2,
+                verify: Some(verify::Method::Ssim),
+                ..AnalyzeOptions::default()
+            },
+            Self::Balanced => AnalyzeOptions::default(),
+            Self::Loose => AnalyzeOptions {
+                max_distance: group::MAX_DISTANCE + 4,
+                ..AnalyzeOptions::default()
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "balanced" => Ok(Self::Balanced),
+            "loose" => Ok(Self::Loose),
+            _ => Err(format!("unknown preset '{}'", s)),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Reporting how far an analysis has got.
+//!
+//! Stages of the analysis report [`Event`]s as they go: walking after every
+//! directory, finding features after every file, verifying after every pair.
+//! Events go to the [`Progress`] the analysis is given, nowhere by default.
+//! With [`Format::Json`], they are written to stderr as JSON lines,
+//! for programs wrapping succotash, e.g. a GUI:
+//!
+//! ```json
+//! {"current":3,"path":"/home/user/Pictures/a.jpg","stage":"features","total":120}
+//! ```
+
+use async_std::path::Path;
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A stage of the analysis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Walking directories, counting files found; the total isn't known.
+    Walk,
+    /// Finding features, counting files done, reused ones included.
+    Features,
+    /// Comparing images, done all at once.
+    Compare,
+    /// Verifying similar images by their pixels, counting pairs.
+    Verify,
+    /// Decoding files in full to find corrupted ones, counting files,
+    /// see [`integrity`](crate::integrity).
+    Decode,
+}
+
+impl Stage {
+    /// Name of the stage in events.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Walk => "walk",
+            Self::Features => "features",
+            Self::Compare => "compare",
+            Self::Verify => "verify",
+            Self::Decode => "decode",
+        }
+    }
+}
+
+/// How far a stage has got.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event<'a> {
+    /// The stage reporting.
+    pub stage: Stage,
+    /// How many things the stage is done with.
+    pub current: u64,
+    /// How many things the stage has to do, if known.
+    pub total: Option<u64>,
+    /// The path the stage is at, if any.
+    pub path: Option<&'a Path>,
+}
+
+impl Event<'_> {
+    /// The event as a JSON line, without the line break.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::progress::{Event, Stage};
+    /// let event = Event {
+    ///     stage: Stage::Features,
+    ///     current: 3,
+    ///     total: Some(120),
+    ///     path: Some("/a.jpg".as_ref()),
+    /// };
+    /// assert_eq!(event.json(), r#"{"current":3,"path":"/a.jpg","stage":"features","total":120}"#);
+    /// ```
+    pub fn json(&self) -> String {
+        json!({
+            "stage": self.stage.name(),
+            "current": self.current,
+            "total": self.total,
+            "path": self.path.map(|path| path.to_string_lossy()),
+        })
+        .to_string()
+    }
+}
+
+/// How to report progress.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// Progress isn't reported.
+    #[default]
+    None,
+    /// JSON lines on stderr, see [`Event::json`].
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown progress format '{}'", s)),
+        }
+    }
+}
+
+impl Format {
+    /// Where events go in this format.
+    pub fn progress(self) -> Progress {
+        match self {
+            Self::None => Progress::default(),
+            Self::Json => Progress::new(|event| {
+                use std::io::Write;
+                // Progress is best effort, it never stops the analysis.
+                let _ = writeln!(std::io::stderr().lock(), "{}", event.json());
+            }),
+        }
+    }
+}
+
+/// What events are reported to, see [`Progress::new`].
+pub type Report = dyn Fn(&Event) + Send + Sync;
+
+/// Where events of an analysis go.
+///
+/// Clones report to the same place. The default one reports nowhere.
+#[derive(Clone, Default)]
+pub struct Progress(Option<Arc<Report>>);
+
+impl Progress {
+    /// Report events by calling `report`, from whatever thread the stage runs on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::progress::{Progress, Stage};
+    /// let progress = Progress::new(|event| println!("{} {}", event.stage.name(), event.current));
+    /// let tracker = progress.stage(Stage::Verify, Some(2));
+    /// tracker.advance(1, None);
+    /// assert_eq!(tracker.current(), 1);
+    /// ```
+    pub fn new(report: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(report)))
+    }
+
+    /// Start reporting on a stage with `total` things to do, if known.
+    pub fn stage(&self, stage: Stage, total: Option<u64>) -> Tracker {
+        let tracker = Tracker {
+            progress: self.clone(),
+            stage,
+            total,
+            current: Arc::new(AtomicU64::new(0)),
+        };
+        tracker.report(0, None);
+        tracker
+    }
+}
+
+/// Progress of a stage, see [`Progress::stage`].
+///
+/// Clones advance the same count, e.g. from more than one thread.
+#[derive(Clone)]
+pub struct Tracker {
+    progress: Progress,
+    stage: Stage,
+    total: Option<u64>,
+    current: Arc<AtomicU64>,
+}
+
+impl Tracker {
+    /// Note that `n` more things are done, the last one at `path`.
+    pub fn advance(&self, n: u64, path: Option<&Path>) {
+        let current = self.current.fetch_add(n, Ordering::Relaxed) + n;
+        self.report(current, path);
+    }
+
+    /// How many things are done.
+    pub fn current(&self) -> u64 {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    fn report(&self, current: u64, path: Option<&Path>) {
+        if let Some(report) = &self.progress.0 {
+            report(&Event {
+                stage: self.stage,
+                current,
+                total: self.total,
+                path,
+            });
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Loading camera RAW files.
+//!
+//! Only available with the `raw` feature. Instead of developing the sensor
+//! data, the JPEG preview embedded by the camera is used: it is what the
+//! camera would have saved as a JPEG, so RAW+JPEG pairs hash alike.
+//!
+//! CR2, NEF, ARW and DNG are all based on TIFF, so the previews are found
+//! by walking the TIFF directories (including SubIFDs) of the file.
+
+use async_std::path::Path;
+use std::collections::HashSet;
+
+/// File extensions of supported RAW formats, lowercase.
+pub const EXTENSIONS: &[&str] = super::decoders::RAW.extensions;
+
+/// Whether a file looks like a RAW file, judging by its extension.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::raw;
+/// assert!(raw::is_raw("/home/user/Pictures/DSC_0001.NEF".as_ref()));
+/// assert!(!raw::is_raw("/home/user/Pictures/DSC_0001.JPG".as_ref()));
+/// ```
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Decode the largest embedded preview of a RAW file.
+///
+/// Returns `None` if there is no preview that can be decoded.
+///
+/// # Arguments
+///
+/// * `data` - contents of the RAW file.
+pub fn preview(data: &[u8]) -> Option<image::DynamicImage> {
+    let tiff = Tiff::new(data)?;
+    let mut previews = tiff.jpegs();
+    previews.sort_by_key(|&(_, length)| std::cmp::Reverse(length));
+    previews.into_iter().find_map(|(offset, length)| {
+        let jpeg = data.get(offset..offset.checked_add(length)?)?;
+        image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg).ok()
+    })
+}
+
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014a;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_IFD: u16 = 13;
+
+/// Compression values meaning the strip is a JPEG stream.
+const COMPRESSION_JPEG: &[u32] = &[6, 7];
+
+/// An entry of a TIFF directory.
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    /// Where the value (or the offset of the value) of the entry is.
+    value_at: usize,
+}
+
+/// Just enough of a TIFF reader to find JPEG streams.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(0..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = [*self.data.get(at)?, *self.data.get(at + 1)?];
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.data.get(at..at + 4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Read the entries of a directory and the offset of the next one.
+    fn directory(&self, at: usize) -> Option<(Vec<Entry>, usize)> {
+        let count = usize::from(self.u16(at)?);
+        let entries = (0..count)
+            .map(|i| {
+                let entry_at = at + 2 + i * 12;
+                Some(Entry {
+                    tag: self.u16(entry_at)?,
+                    kind: self.u16(entry_at + 2)?,
+                    count: self.u32(entry_at + 4)?,
+                    value_at: entry_at + 8,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let next = self.u32(at + 2 + count * 12)? as usize;
+        Some((entries, next))
+    }
+
+    /// Get the `i`-th integer value of an entry.
+    fn value(&self, entry: &Entry, i: usize) -> Option<u32> {
+        let size = match entry.kind {
+            TYPE_SHORT => 2,
+            TYPE_LONG | TYPE_IFD => 4,
+            _ => return None,
+        };
+        if i >= entry.count as usize {
+            return None;
+        }
+
+        // Values that fit into 4 bytes are stored in the entry itself.
+        let values_at = if entry.count as usize * size <= 4 {
+            entry.value_at
+        } else {
+            self.u32(entry.value_at)? as usize
+        };
+        match size {
+            2 => self.u16(values_at + i * 2).map(u32::from),
+            _ => self.u32(values_at + i * 4),
+        }
+    }
+
+    /// Find offsets and lengths of JPEG streams in all directories.
+    fn jpegs(&self) -> Vec<(usize, usize)> {
+        let mut jpegs = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending: Vec<usize> = self.u32(4).map(|at| at as usize).into_iter().collect();
+
+        while let Some(at) = pending.pop() {
+            // Offset 0 ends a chain of directories, and broken files may have loops.
+            if at == 0 || !visited.insert(at) {
+                continue;
+            }
+            let (entries, next) = match self.directory(at) {
+                Some(directory) => directory,
+                None => continue,
+            };
+            pending.push(next);
+
+            let find = |tag| entries.iter().find(|e| e.tag == tag);
+            let first_value = |tag| find(tag).and_then(|e| self.value(e, 0));
+
+            if let Some(sub_ifds) = find(TAG_SUB_IFDS) {
+                pending.extend(
+                    (0..sub_ifds.count as usize)
+                        .filter_map(|i| self.value(sub_ifds, i).map(|at| at as usize)),
+                );
+            }
+
+            if let (Some(offset), Some(length)) =
+                (first_value(TAG_JPEG_OFFSET), first_value(TAG_JPEG_LENGTH))
+            {
+                jpegs.push((offset as usize, length as usize));
+            }
+
+            // A JPEG stream can also be stored as a single strip.
+            let is_jpeg =
+                first_value(TAG_COMPRESSION).is_some_and(|c| COMPRESSION_JPEG.contains(&c));
+            let single_strip = find(TAG_STRIP_OFFSETS).is_some_and(|e| e.count == 1);
+            if is_jpeg && single_strip {
+                if let (Some(offset), Some(length)) = (
+                    first_value(TAG_STRIP_OFFSETS),
+                    first_value(TAG_STRIP_BYTE_COUNTS),
+                ) {
+                    jpegs.push((offset as usize, length as usize));
+                }
+            }
+        }
+
+        jpegs
+    }
+}
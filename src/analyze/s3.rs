@@ -0,0 +1,353 @@
+//! Images in S3 and S3-compatible object storage, e.g. MinIO.
+//!
+//! Only available with the `s3` feature. An input like `s3://bucket/prefix`
+//! is walked by listing the objects under the prefix, and every object gets
+//! a virtual path, `s3://bucket/key`. Such paths are analyzed, saved to the
+//! index and reported like any other, but their contents are downloaded
+//! into memory, see [`get`], a few at a time, see [`CONCURRENCY`].
+//!
+//! Credentials and where the storage is come from the environment, the way
+//! AWS tools take them: `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+//! `AWS_SESSION_TOKEN`, `AWS_REGION` (or `AWS_DEFAULT_REGION`) and
+//! `AWS_ENDPOINT_URL` for storage other than AWS. Without credentials,
+//! requests are anonymous, for public buckets. Buckets are addressed by path,
+//! `ENDPOINT/bucket/key`, which every S3-compatible storage understands.
+
+use async_std::path::{Path, PathBuf};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::sync::OnceLock;
+
+/// What paths of objects start with.
+pub const SCHEME: &str = "s3://";
+
+/// How many objects are downloaded at once by default.
+pub const CONCURRENCY: usize = 16;
+
+/// Region when the environment doesn't tell one.
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// Characters that don't need escaping in URLs, besides alphanumeric ones.
+const UNRESERVED: &[u8] = b"-_.~";
+
+/// A bucket and a prefix of keys in it.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::s3::Location;
+/// let location = Location::parse("s3://photos/2021/summer").unwrap();
+/// assert_eq!(location.bucket, "photos");
+/// assert_eq!(location.prefix, "2021/summer");
+/// assert_eq!(Location::parse("s3://photos").unwrap().prefix, "");
+/// assert_eq!(Location::parse("/home/user/Pictures"), None);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    /// Name of the bucket.
+    pub bucket: String,
+    /// What keys of objects start with, empty for the whole bucket.
+    pub prefix: String,
+}
+
+impl Location {
+    /// Parse a location written as `s3://bucket/prefix`, `None` for other inputs.
+    pub fn parse(input: &str) -> Option<Self> {
+        let rest = input.strip_prefix(SCHEME)?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        (!bucket.is_empty()).then(|| Self {
+            bucket: bucket.to_owned(),
+            prefix: prefix.to_owned(),
+        })
+    }
+
+    /// Virtual path of an object with a `key` in the bucket.
+    pub fn path(&self, key: &str) -> PathBuf {
+        format!("{}{}/{}", SCHEME, self.bucket, key).into()
+    }
+}
+
+/// Whether a path is of an object in a bucket.
+pub fn is_object(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with(SCHEME))
+}
+
+/// An object found by [`list`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Object {
+    /// Key of the object in its bucket.
+    pub key: String,
+    /// Size of the object, in bytes.
+    pub size: u64,
+}
+
+/// Credentials to sign requests with.
+struct Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+/// Where the storage is and how to talk to it.
+struct Client {
+    agent: ureq::Agent,
+    /// Scheme, host and port, without a trailing slash.
+    endpoint: String,
+    /// Host and port, as requests name them.
+    host: String,
+    region: String,
+    credentials: Option<Credentials>,
+}
+
+/// The client, configured from the environment the first time it is needed.
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let var = |name| {
+            std::env::var(name)
+                .ok()
+                .filter(|value: &String| !value.is_empty())
+        };
+        let region = var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| DEFAULT_REGION.to_owned());
+        let endpoint = var("AWS_ENDPOINT_URL_S3")
+            .or_else(|| var("AWS_ENDPOINT_URL"))
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+        let endpoint = endpoint.trim_end_matches('/').to_owned();
+        let host = endpoint
+            .split_once("://")
+            .map_or(endpoint.as_str(), |(_, rest)| rest)
+            .to_owned();
+        let credentials = match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key), Some(secret_key)) => Some(Credentials {
+                access_key,
+                secret_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+            }),
+            _ => None,
+        };
+        debug!("Talking to S3 at '{}' in region '{}'", endpoint, region);
+        Client {
+            agent: ureq::AgentBuilder::new()
+                .max_idle_connections_per_host(CONCURRENCY)
+                .build(),
+            endpoint,
+            host,
+            region,
+            credentials,
+        }
+    })
+}
+
+/// Escape a string for a URL, keeping slashes if `path` is set.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::s3;
+/// assert_eq!(s3::escape("2021/summer camp/a+b.jpg", true), "2021/summer%20camp/a%2Bb.jpg");
+/// assert_eq!(s3::escape("2021/", false), "2021%2F");
+/// ```
+pub fn escape(s: &str, path: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || UNRESERVED.contains(&byte) || (path && byte == b'/') {
+            escaped.push(byte as char);
+        } else {
+            escaped += &format!("%{:02X}", byte);
+        }
+    }
+    escaped
+}
+
+/// Lowercase hexadecimal digits of bytes.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// HMAC-SHA256 of `data` with `key`.
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl Client {
+    /// Send a GET request, signed with Signature Version 4 if there are credentials.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - escaped path of the URL, starting with a slash.
+    /// * `query` - parameters of the query, not escaped.
+    fn get(&self, path: &str, query: &[(&str, &str)]) -> io::Result<ureq::Response> {
+        let mut query: Vec<_> = query
+            .iter()
+            .map(|(name, value)| (escape(name, false), escape(value, false)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+        let url = if query.is_empty() {
+            format!("{}{}", self.endpoint, path)
+        } else {
+            format!("{}{}?{}", self.endpoint, path, query)
+        };
+
+        let mut request = self.agent.get(&url);
+        if let Some(credentials) = &self.credentials {
+            let now = chrono::Utc::now();
+            let (date, time) = (
+                now.format("%Y%m%d").to_string(),
+                now.format("%Y%m%dT%H%M%SZ").to_string(),
+            );
+            let payload = hex(&Sha256::digest(b""));
+            let mut headers = vec![
+                ("host", self.host.as_str()),
+                ("x-amz-content-sha256", payload.as_str()),
+                ("x-amz-date", time.as_str()),
+            ];
+            if let Some(token) = &credentials.session_token {
+                headers.push(("x-amz-security-token", token));
+            }
+            let signed = headers
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(";");
+            let canonical = format!(
+                "GET\n{}\n{}\n{}\n{}\n{}",
+                path,
+                query,
+                headers
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+                    .collect::<String>(),
+                signed,
+                payload
+            );
+            let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+            let to_sign = format!(
+                "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+                time,
+                scope,
+                hex(&Sha256::digest(canonical.as_bytes()))
+            );
+            let key = ["AWS4", &credentials.secret_key].concat();
+            let key = [date.as_str(), &self.region, "s3", "aws4_request"]
+                .iter()
+                .fold(key.into_bytes(), |key, part| hmac(&key, part));
+            let authorization = format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                credentials.access_key,
+                scope,
+                signed,
+                hex(&hmac(&key, &to_sign))
+            );
+            // Host is set by the agent from the URL.
+            for (name, value) in &headers[1..] {
+                request = request.set(name, value);
+            }
+            request = request.set("authorization", &authorization);
+        }
+
+        request.call().map_err(|e| match e {
+            ureq::Error::Status(status, response) => {
+                let kind = match status {
+                    404 => io::ErrorKind::NotFound,
+                    401 | 403 => io::ErrorKind::PermissionDenied,
+                    _ => io::ErrorKind::Other,
+                };
+                // Storage explains what is wrong in the body, e.g. `SignatureDoesNotMatch`.
+                let body = response.into_string().unwrap_or_default();
+                let code = tags(&body, "Code").into_iter().next();
+                let message = code.unwrap_or_else(|| format!("status {}", status));
+                io::Error::new(kind, format!("S3 responded with {}", message))
+            }
+            ureq::Error::Transport(e) => io::Error::other(e.to_string()),
+        })
+    }
+}
+
+/// Text in every element named `name` of an XML document, unescaped.
+///
+/// Responses of S3 are simple enough to be read without a parser.
+fn tags(xml: &str, name: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", name), format!("</{}>", name));
+    let mut texts = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        let text = rest[..end]
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&");
+        texts.push(text);
+        rest = &rest[end + close.len()..];
+    }
+    texts
+}
+
+/// Objects under a location, in the order of their keys. Keys ending with
+/// a slash, which some tools create to stand for folders, are left out.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+pub fn list(location: &Location) -> io::Result<Vec<Object>> {
+    let path = format!("/{}", escape(&location.bucket, false));
+    let mut objects = Vec::new();
+    let mut token: Option<String> = None;
+    loop {
+        let mut query = vec![("list-type", "2"), ("prefix", location.prefix.as_str())];
+        if let Some(token) = &token {
+            query.push(("continuation-token", token));
+        }
+        let body = client().get(&path, &query)?.into_string()?;
+        for contents in tags(&body, "Contents") {
+            let key = tags(&contents, "Key").into_iter().next();
+            let size = tags(&contents, "Size").into_iter().next();
+            if let (Some(key), Some(size)) = (key, size) {
+                if !key.ends_with('/') {
+                    let size = size.parse().unwrap_or_default();
+                    objects.push(Object { key, size });
+                }
+            }
+        }
+        token = tags(&body, "NextContinuationToken").into_iter().next();
+        if tags(&body, "IsTruncated").first().map(String::as_str) != Some("true") || token.is_none()
+        {
+            return Ok(objects);
+        }
+    }
+}
+
+/// Download an object into memory.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+///
+/// # Arguments
+///
+/// * `path` - the virtual path of the object, see [`Location::path`].
+pub fn get(path: &Path) -> io::Result<Vec<u8>> {
+    let location = path
+        .to_str()
+        .and_then(Location::parse)
+        .filter(|location| !location.prefix.is_empty())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a path of an object"))?;
+    let path = format!(
+        "/{}/{}",
+        escape(&location.bucket, false),
+        escape(&location.prefix, true)
+    );
+    let mut data = Vec::new();
+    io::Read::read_to_end(&mut client().get(&path, &[])?.into_reader(), &mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,130 @@
+//! How much space deleting files of groups would reclaim.
+//!
+//! Every file of a group but the kept one takes space for nothing, and how
+//! much depends on which one is kept. Savings are estimated for every
+//! [`Strategy`] of picking it, for every group and in total, by sizes of
+//! the files. A file in more than one group, e.g. a video, counts once in
+//! the total, and files that aren't on disk, e.g. in archives, count as empty.
+
+use super::group::Group;
+use super::img::Img;
+use super::plan::{self, Plan};
+use async_std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap};
+
+/// Which file of a group is kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// The sharpest image, as [`Plan::new`] keeps.
+    Sharpest,
+    /// The largest file.
+    Largest,
+    /// The smallest file.
+    Smallest,
+}
+
+impl Strategy {
+    /// All strategies, in the order savings are written in.
+    pub const ALL: [Self; 3] = [Self::Sharpest, Self::Largest, Self::Smallest];
+
+    /// Name of the strategy, in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sharpest => "sharpest",
+            Self::Largest => "largest",
+            Self::Smallest => "smallest",
+        }
+    }
+
+    /// The image of a group the strategy keeps, the first one of equals.
+    fn keeper<'a>(&self, group: &'a Group, sizes: &HashMap<PathBuf, u64>) -> &'a Img<PathBuf> {
+        let size = |img: &&Img<PathBuf>| sizes.get(&img.path).copied().unwrap_or(0);
+        match self {
+            Self::Sharpest => plan::sharpest(group),
+            Self::Largest => group.imgs.iter().rev().max_by_key(size).unwrap(),
+            Self::Smallest => group.imgs.iter().min_by_key(size).unwrap(),
+        }
+    }
+}
+
+/// What a strategy would reclaim.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Savings {
+    /// Which file of a group is kept.
+    pub strategy: Strategy,
+    /// Bytes of every group, in the order of the groups.
+    pub groups: Vec<u64>,
+    /// Bytes of all groups.
+    pub total: u64,
+}
+
+/// Sizes of the files of groups, of those that are on disk.
+///
+/// Blocks the thread, see [`io::unblock`](super::io::unblock).
+pub fn sizes(groups: &[Group]) -> HashMap<PathBuf, u64> {
+    groups
+        .iter()
+        .flat_map(|group| &group.imgs)
+        .filter_map(|img| {
+            let metadata = std::fs::metadata(&img.path).ok()?;
+            Some((img.path.clone(), metadata.len()))
+        })
+        .collect()
+}
+
+/// Estimate what every strategy would reclaim, in the order of [`Strategy::ALL`].
+///
+/// # Arguments
+///
+/// * `groups` - groups of similar images, see [`group`](super::group).
+/// * `sizes` - sizes of their files, see [`sizes`].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{group, img::Img, features::ImgFeatures, savings::{self, Strategy}};
+/// # use std::collections::HashMap;
+/// let img = |path: &str, features| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode(features).unwrap(),
+/// };
+/// let imgs = vec![
+///     img("/small.jpg", "00000000000000ff 0 90 0.5 0.2"),
+///     img("/large.png", "00000000000000ff 0 10 0.5 0.2"),
+/// ];
+/// let groups = group::group(imgs, group::MAX_DISTANCE);
+/// let sizes = HashMap::from([("/small.jpg".into(), 100), ("/large.png".into(), 400)]);
+/// let savings = savings::estimate(&groups, &sizes);
+/// assert_eq!(savings[0].strategy, Strategy::Sharpest);
+/// assert_eq!(savings[0].total, 400);
+/// assert_eq!(savings[1].groups, vec![100]);
+/// assert_eq!(savings[2].total, 400);
+/// ```
+pub fn estimate(groups: &[Group], sizes: &HashMap<PathBuf, u64>) -> Vec<Savings> {
+    let size = |path: &PathBuf| sizes.get(path).copied().unwrap_or(0);
+    Strategy::ALL
+        .iter()
+        .map(|&strategy| {
+            let plan = Plan::keeping(groups, |group| strategy.keeper(group, sizes));
+            let deleted = |path: &PathBuf| plan.delete.binary_search(path).is_ok();
+            let groups = groups
+                .iter()
+                .map(|group| {
+                    // Keyframes of a video are one file.
+                    let paths: BTreeSet<&PathBuf> =
+                        group.imgs.iter().map(|img| &img.path).collect();
+                    paths
+                        .into_iter()
+                        .filter(|path| deleted(path))
+                        .map(size)
+                        .sum()
+                })
+                .collect();
+            Savings {
+                strategy,
+                groups,
+                total: plan.delete.iter().map(size).sum(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,166 @@
+//! Scoring how similar images are.
+//!
+//! The distance in every [feature](super::features::set) of two images becomes
+//! a similarity within [0, 1]: 1 if the feature is the same in both, 0 if it is
+//! as far apart as in similar images at most, or farther. The score is the mean
+//! of these similarities, weighted by [`Weights`]. Groups of similar images are
+//! [sorted](sort) by their scores, the most similar first.
+
+use super::features::set::{self, FeatureSet};
+use super::features::ImgFeatures;
+use super::group::Group;
+use std::collections::BTreeMap;
+
+/// How much every feature weighs in a score, by its name.
+///
+/// Without weights, every feature images are compared by weighs the same.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Weights(BTreeMap<String, f64>);
+
+impl Weights {
+    /// Weights from `name=weight` pairs, e.g. `lshash=0.7` and `hue=0.3`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::score::Weights;
+    /// assert!(Weights::parse(["lshash=0.7", "hue=0.3"]).is_ok());
+    /// assert!(Weights::parse(["lshash"]).is_err());
+    /// assert!(Weights::parse(["smell=1"]).is_err());
+    /// assert!(Weights::parse(["hue=-1"]).is_err());
+    /// ```
+    pub fn parse<'a>(pairs: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut weights = BTreeMap::new();
+        for pair in pairs {
+            let (name, weight) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("weight '{}' isn't like 'feature=weight'", pair))?;
+            if set::lookup(name).is_none() {
+                return Err(format!("unknown feature '{}' in weight '{}'", name, pair));
+            }
+            let weight: f64 = weight
+                .parse()
+                .map_err(|e| format!("weight '{}' isn't a number: {}", pair, e))?;
+            if !weight.is_finite() || weight < 0. {
+                return Err(format!("weight '{}' isn't a non-negative number", pair));
+            }
+            weights.insert(name.to_owned(), weight);
+        }
+        Ok(Self(weights))
+    }
+
+    /// Score of two images within [0, 1], 1 if they are the same in all features.
+    ///
+    /// Features missing from either image are left out. A feature that isn't
+    /// in `features` is as far apart as it can be by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - what is compared, and how far apart it can be.
+    /// * `a`, `b` - features of the images.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::{features::{set::FeatureSet, ImgFeatures}, score::Weights};
+    /// let a = ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2").unwrap();
+    /// let b = ImgFeatures::decode("00000000000000fc 0 0 0.5 0.2").unwrap();
+    /// let by_hash = Weights::parse(["lshash=1"]).unwrap();
+    /// assert_eq!(by_hash.score(FeatureSet::standard(), &a, &b), 1. - 2. / 6.);
+    /// let by_hue = Weights::parse(["hue=1"]).unwrap();
+    /// assert_eq!(by_hue.score(FeatureSet::standard(), &a, &b), 1.);
+    /// ```
+    pub fn score(&self, features: &FeatureSet, a: &ImgFeatures, b: &ImgFeatures) -> f64 {
+        let weights: Vec<(&str, f64)> = if self.0.is_empty() {
+            features
+                .names()
+                .into_iter()
+                .map(|name| (name, 1.))
+                .collect()
+        } else {
+            self.0
+                .iter()
+                .map(|(name, weight)| (name.as_str(), *weight))
+                .collect()
+        };
+
+        let (mut sum, mut total) = (0., 0.);
+        for (name, weight) in weights {
+            let feature = match set::lookup(name) {
+                Some(feature) => feature,
+                None => continue,
+            };
+            let (a, b) = match (a.value(name), b.value(name)) {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+            let max_distance = features
+                .max_distance(name)
+                .unwrap_or_else(|| feature.max_distance());
+            let distance = feature.distance(&a, &b);
+            let similarity = if max_distance > 0. {
+                (1. - distance / max_distance).clamp(0., 1.)
+            } else if distance == 0. {
+                1.
+            } else {
+                0.
+            };
+            sum += weight * similarity;
+            total += weight;
+        }
+        if total > 0. {
+            sum / total
+        } else {
+            1.
+        }
+    }
+}
+
+/// Score groups and sort them, the most similar first.
+///
+/// The score of a group is the lowest score of its images against the first one,
+/// groups with the same score stay in the order they were in.
+///
+/// # Arguments
+///
+/// * `groups` - groups to score.
+/// * `weights` - how much every feature weighs.
+/// * `features` - what is compared, and how far apart it can be.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::{features::{set::FeatureSet, ImgFeatures}, group, img::Img, score};
+/// let img = |path: &str, features| Img {
+///     path: async_std::path::PathBuf::from(path),
+///     features: ImgFeatures::decode(features).unwrap(),
+/// };
+/// let imgs = vec![
+///     img("/a.jpg", "00000000000000ff 0 0 0.5 0.2"),
+///     img("/b.jpg", "00000000000000fc 0 0 0.5 0.2"),
+///     img("/c.jpg", "ffffffffffffff00 0 0 0.5 0.2"),
+///     img("/d.jpg", "ffffffffffffff00 0 0 0.5 0.2"),
+/// ];
+/// let mut groups = group::group(imgs, group::MAX_DISTANCE);
+/// score::sort(&mut groups, &Default::default(), FeatureSet::standard());
+/// assert_eq!(groups[0].imgs[0].path, async_std::path::PathBuf::from("/c.jpg"));
+/// assert_eq!(groups[0].score, Some(1.));
+/// assert!(groups[1].score < Some(1.));
+/// ```
+pub fn sort(groups: &mut [Group], weights: &Weights, features: &FeatureSet) {
+    for group in groups.iter_mut() {
+        let first = &group.imgs[0].features;
+        let score = group
+            .imgs
+            .iter()
+            .skip(1)
+            .map(|img| weights.score(features, first, &img.features))
+            .fold(1., f64::min);
+        group.score = Some(score);
+    }
+    groups.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
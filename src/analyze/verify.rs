@@ -0,0 +1,227 @@
+//! Verifying similar images by their pixels.
+//!
+//! Hashes can collide, so pairs of images found by [`group::candidates`]
+//! can be compared once more, this time pixel by pixel. Both images are
+//! converted to grayscale and scaled to [`SIZE`]x[`SIZE`], then compared
+//! with SSIM or PSNR, see [`Method`].
+//!
+//! [`group::candidates`]: super::group::candidates
+
+use super::cancel::CancelToken;
+use super::img::{Img, ImgRaw};
+use super::progress::{Progress, Stage};
+use async_std::path::PathBuf;
+use std::collections::HashMap;
+
+/// Images are scaled to this size on both sides before comparing.
+pub const SIZE: u32 = 128;
+
+/// Side of the windows SSIM is found in.
+const WINDOW: u32 = 8;
+
+/// How to compare images.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    /// Structural similarity, from -1 to 1 (identical).
+    Ssim,
+    /// Peak signal-to-noise ratio, in decibels, infinite for identical images.
+    Psnr,
+}
+
+impl Method {
+    /// Name of the method, as accepted by [`from_str`](std::str::FromStr::from_str).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ssim => "ssim",
+            Self::Psnr => "psnr",
+        }
+    }
+
+    /// Cutoff below which images are not similar, by default.
+    pub fn default_cutoff(&self) -> f64 {
+        match self {
+            Self::Ssim => 0.8,
+            Self::Psnr => 25.,
+        }
+    }
+
+    /// Compare two normalized images.
+    pub fn compare(&self, a: &image::GrayImage, b: &image::GrayImage) -> f64 {
+        match self {
+            Self::Ssim => ssim(a, b),
+            Self::Psnr => psnr(a, b),
+        }
+    }
+}
+
+impl std::str::FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ssim" => Ok(Self::Ssim),
+            "psnr" => Ok(Self::Psnr),
+            _ => Err(format!("unknown verification method '{}'", s)),
+        }
+    }
+}
+
+/// Convert an image to grayscale and scale it to [`SIZE`]x[`SIZE`].
+pub fn normalize(original: &image::DynamicImage) -> image::GrayImage {
+    image::imageops::resize(
+        &original.to_luma8(),
+        SIZE,
+        SIZE,
+        image::imageops::FilterType::Triangle,
+    )
+}
+
+/// Mean structural similarity of two images of the same size.
+///
+/// Found in [`WINDOW`]x[`WINDOW`] windows, overlapping by half.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::verify;
+/// let checkers = |odd| {
+///     image::GrayImage::from_fn(64, 64, move |x, y| {
+///         image::Luma([if (x + y) % 2 == odd { 255 } else { 0 }])
+///     })
+/// };
+/// assert!((verify::ssim(&checkers(0), &checkers(0)) - 1.).abs() < 1e-9);
+/// assert!(verify::ssim(&checkers(0), &checkers(1)) < 0.);
+/// ```
+pub fn ssim(a: &image::GrayImage, b: &image::GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.) * (0.01 * 255.);
+    const C2: f64 = (0.03 * 255.) * (0.03 * 255.);
+
+    let (width, height) = a.dimensions();
+    let (mut total, mut windows) = (0., 0.);
+    for top in (0..=height.saturating_sub(WINDOW)).step_by(WINDOW as usize / 2) {
+        for left in (0..=width.saturating_sub(WINDOW)).step_by(WINDOW as usize / 2) {
+            let pixels = || {
+                (top..(top + WINDOW).min(height)).flat_map(move |y| {
+                    (left..(left + WINDOW).min(width)).map(move |x| {
+                        (
+                            f64::from(a.get_pixel(x, y)[0]),
+                            f64::from(b.get_pixel(x, y)[0]),
+                        )
+                    })
+                })
+            };
+            let count = pixels().count() as f64;
+            let mean_a = pixels().map(|(pa, _)| pa).sum::<f64>() / count;
+            let mean_b = pixels().map(|(_, pb)| pb).sum::<f64>() / count;
+            let (var_a, var_b, covar) = pixels().fold((0., 0., 0.), |(va, vb, c), (pa, pb)| {
+                let (da, db) = (pa - mean_a, pb - mean_b);
+                (va + da * da, vb + db * db, c + da * db)
+            });
+            let (var_a, var_b, covar) = (var_a / count, var_b / count, covar / count);
+
+            total += ((2. * mean_a * mean_b + C1) * (2. * covar + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1.;
+        }
+    }
+
+    if windows == 0. {
+        return 1.;
+    }
+    total / windows
+}
+
+/// Peak signal-to-noise ratio of two images of the same size, in decibels.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::verify;
+/// let black = image::GrayImage::new(8, 8);
+/// let gray = image::GrayImage::from_pixel(8, 8, image::Luma([16]));
+/// assert_eq!(verify::psnr(&black, &black), f64::INFINITY);
+/// assert!((verify::psnr(&black, &gray) - 24.0).abs() < 0.1);
+/// ```
+pub fn psnr(a: &image::GrayImage, b: &image::GrayImage) -> f64 {
+    let squared_error: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&pa, &pb)| (f64::from(pa) - f64::from(pb)).powi(2))
+        .sum();
+    let mean_squared_error = squared_error / a.as_raw().len().max(1) as f64;
+    if mean_squared_error == 0. {
+        return f64::INFINITY;
+    }
+    10. * (255. * 255. / mean_squared_error).log10()
+}
+
+/// Keep only the pairs of images that are similar by their pixels.
+///
+/// Files are loaded again, once per file. Files with more than one image,
+/// e.g. videos, are similar to another image if any of their images is.
+/// Files that can't be loaded anymore drop their pairs.
+///
+/// # Arguments
+///
+/// * `imgs` - images the pairs refer to.
+/// * `pairs` - pairs of similar images, as indices into `imgs`.
+/// * `method` - how to compare images.
+/// * `cutoff` - images are similar if they compare at or above this.
+/// * `cancel` - stops loading files, pairs that weren't verified are dropped.
+/// * `progress` - where to report pairs whose files are loaded, see [`Stage::Verify`].
+pub async fn verify(
+    imgs: &[Img<PathBuf>],
+    mut pairs: Vec<(usize, usize)>,
+    method: Method,
+    cutoff: f64,
+    cancel: &CancelToken,
+    progress: &Progress,
+) -> Vec<(usize, usize)> {
+    let mut normalized: HashMap<&PathBuf, Vec<image::GrayImage>> = HashMap::new();
+    let progress = progress.stage(Stage::Verify, Some(pairs.len() as u64));
+    for &(i, j) in &pairs {
+        if cancel.is_cancelled() {
+            break;
+        }
+        for path in [&imgs[i].path, &imgs[j].path] {
+            if normalized.contains_key(path) {
+                continue;
+            }
+            let loaded = match ImgRaw::load_all(path.clone()).await {
+                Ok(imgs_raw) => imgs_raw.iter().map(|raw| normalize(&raw.data)).collect(),
+                Err(e) => {
+                    warn!("Can't verify '{}': {}", path.to_string_lossy(), e);
+                    Vec::new()
+                }
+            };
+            normalized.insert(path, loaded);
+        }
+        progress.advance(1, Some(&imgs[j].path));
+    }
+
+    let before = pairs.len();
+    pairs.retain(|&(i, j)| {
+        let (a, b) = match (normalized.get(&imgs[i].path), normalized.get(&imgs[j].path)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return false,
+        };
+        let best = a
+            .iter()
+            .flat_map(|a| b.iter().map(move |b| method.compare(a, b)))
+            .fold(f64::NEG_INFINITY, f64::max);
+        trace!(
+            "'{}' and '{}' compare at {}",
+            imgs[i].path.to_string_lossy(),
+            imgs[j].path.to_string_lossy(),
+            best
+        );
+        best >= cutoff
+    });
+    debug!(
+        "Verification dropped {} of {} pairs",
+        before - pairs.len(),
+        before
+    );
+    pairs
+}
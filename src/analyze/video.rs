@@ -0,0 +1,108 @@
+//! Extracting keyframes from videos.
+//!
+//! Only available with the `video` feature, decodes videos with FFmpeg.
+//! Keyframes are hashed just like images, so a "live photo" video
+//! can be matched with the photo it was taken with.
+
+use async_std::path::Path;
+
+/// How many keyframes to take from the start of a video.
+pub const KEYFRAMES: usize = 4;
+
+/// File extensions of videos, lowercase.
+pub const EXTENSIONS: &[&str] = super::decoders::VIDEO.extensions;
+
+/// Whether a file looks like a video, judging by its extension.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::video;
+/// assert!(video::is_video("/home/user/Pictures/IMG_0001.MOV".as_ref()));
+/// assert!(!video::is_video("/home/user/Pictures/IMG_0001.JPG".as_ref()));
+/// ```
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Decode the first [`KEYFRAMES`] keyframes of a video.
+///
+/// # Arguments
+///
+/// * `path` - the video to decode.
+pub async fn keyframes(path: &Path) -> Result<Vec<image::RgbImage>, Box<dyn std::error::Error>> {
+    let path = path.to_path_buf();
+    let keyframes =
+        async_std::task::spawn_blocking(move || decode_keyframes(path.as_ref())).await?;
+    Ok(keyframes)
+}
+
+fn decode_keyframes(path: &std::path::Path) -> Result<Vec<image::RgbImage>, ffmpeg::Error> {
+    use ffmpeg::software::scaling;
+
+    ffmpeg::init()?;
+
+    let mut input = ffmpeg::format::input(path)?;
+    let stream = input
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .ok_or(ffmpeg::Error::StreamNotFound)?;
+    let stream_index = stream.index();
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+        .decoder()
+        .video()?;
+
+    let mut scaler = scaling::Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::format::Pixel::RGB24,
+        decoder.width(),
+        decoder.height(),
+        scaling::Flags::BILINEAR,
+    )?;
+
+    let mut keyframes = Vec::new();
+    let mut receive_frames = |decoder: &mut ffmpeg::decoder::Video,
+                              keyframes: &mut Vec<image::RgbImage>|
+     -> Result<(), ffmpeg::Error> {
+        let mut decoded = ffmpeg::frame::Video::empty();
+        while keyframes.len() < KEYFRAMES && decoder.receive_frame(&mut decoded).is_ok() {
+            let mut rgb = ffmpeg::frame::Video::empty();
+            scaler.run(&decoded, &mut rgb)?;
+            keyframes.extend(to_rgb_image(&rgb));
+        }
+        Ok(())
+    };
+
+    // Keyframes can be decoded on their own, so the rest of the packets are skipped.
+    for (stream, packet) in input.packets() {
+        if stream.index() == stream_index && packet.is_key() {
+            decoder.send_packet(&packet)?;
+            receive_frames(&mut decoder, &mut keyframes)?;
+            if keyframes.len() >= KEYFRAMES {
+                return Ok(keyframes);
+            }
+        }
+    }
+    decoder.send_eof()?;
+    receive_frames(&mut decoder, &mut keyframes)?;
+
+    Ok(keyframes)
+}
+
+/// Copy an RGB24 frame into an image, dropping the row padding.
+fn to_rgb_image(frame: &ffmpeg::frame::Video) -> Option<image::RgbImage> {
+    let (width, height) = (frame.width(), frame.height());
+    let row = width as usize * 3;
+    let stride = frame.stride(0);
+    let data = frame.data(0);
+
+    let mut pixels = Vec::with_capacity(row * height as usize);
+    for y in 0..height as usize {
+        pixels.extend_from_slice(data.get(y * stride..y * stride + row)?);
+    }
+    image::RgbImage::from_raw(width, height, pixels)
+}
@@ -0,0 +1,99 @@
+//! Filtering files by their extended attributes.
+//!
+//! Lets curated files be marked, e.g. with a macOS Finder tag `keep-always`,
+//! so that they never get into the analysis. Extended attributes are only
+//! read on Unix, elsewhere the filter accepts everything.
+
+use async_std::path::{Path, PathBuf};
+
+/// Extended attribute that holds Finder tags.
+pub const FINDER_TAGS: &str = "com.apple.metadata:_kMDItemUserTags";
+
+/// Rejects files and directories carrying any of the attributes or tags.
+#[derive(Default)]
+pub struct AttrFilter {
+    /// Names of extended attributes.
+    pub xattrs: Vec<String>,
+    /// Names of Finder tags.
+    pub tags: Vec<String>,
+}
+
+impl AttrFilter {
+    /// Whether a file or directory at `path` carries none of the attributes or tags.
+    ///
+    /// Doesn't touch the file if there is nothing to look for.
+    pub async fn accepts(&self, path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.xattrs.is_empty() && self.tags.is_empty() {
+            return Ok(true);
+        }
+
+        for name in &self.xattrs {
+            if has_xattr(path, name).await? {
+                trace!("'{}' has attribute '{}'", path.to_string_lossy(), name);
+                return Ok(false);
+            }
+        }
+        if !self.tags.is_empty() {
+            let tags = finder_tags(path).await?;
+            if let Some(tag) = self.tags.iter().find(|t| tags.contains(t)) {
+                trace!("'{}' has tag '{}'", path.to_string_lossy(), tag);
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Whether a file has an extended attribute.
+pub async fn has_xattr(path: &Path, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(get_xattr(path.to_path_buf(), name.to_owned())
+        .await?
+        .is_some())
+}
+
+/// Get the Finder tags of a file.
+///
+/// Files without tags have no tags, on any platform.
+pub async fn finder_tags(path: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    match get_xattr(path.to_path_buf(), FINDER_TAGS.to_owned()).await? {
+        Some(value) => parse_tags(&value),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(unix)]
+async fn get_xattr(path: PathBuf, name: String) -> std::io::Result<Option<Vec<u8>>> {
+    async_std::task::spawn_blocking(move || match xattr::get(&path, &name) {
+        // The filesystem or the namespace of the name isn't supported,
+        // e.g. Finder tags on Linux, so there can't be such an attribute.
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => Ok(None),
+        result => result,
+    })
+    .await
+}
+
+#[cfg(not(unix))]
+async fn get_xattr(_path: PathBuf, _name: String) -> std::io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+/// Parse the value of [`FINDER_TAGS`].
+///
+/// The value is a property list with an array of tag names,
+/// each optionally followed by a newline and a color number.
+#[cfg(unix)]
+fn parse_tags(value: &[u8]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let tags: Vec<String> = plist::from_bytes(value)?;
+    Ok(tags
+        .into_iter()
+        .map(|tag| match tag.split_once('\n') {
+            Some((name, _color)) => name.to_owned(),
+            None => tag,
+        })
+        .collect())
+}
+
+#[cfg(not(unix))]
+fn parse_tags(_value: &[u8]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Ok(Vec::new())
+}
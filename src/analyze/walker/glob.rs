@@ -0,0 +1,398 @@
+//! Glob patterns for selecting files.
+//!
+//! Supports the usual shell syntax: `*` and `?` within one path segment,
+//! `**` for any number of directories, `[a-z]`/`[!a-z]` character classes
+//! and `{jpg,png}` brace expansion (nesting allowed).
+//!
+//! Patterns are expanded by succotash itself, so they behave the same
+//! regardless of the shell and the platform. Both `/` and, on Windows,
+//! `\` separate path segments. Elsewhere `\` escapes the next character.
+
+use std::path::Path;
+
+/// Error in a glob pattern.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A `{` without a matching `}`.
+    UnclosedBrace(String),
+    /// A `[` without a matching `]`.
+    UnclosedClass(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnclosedBrace(p) => write!(f, "unclosed '{{' in glob pattern '{}'", p),
+            Error::UnclosedClass(p) => write!(f, "unclosed '[' in glob pattern '{}'", p),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Whether `c` separates path segments on this platform.
+fn is_separator(c: char) -> bool {
+    c == '/' || (cfg!(windows) && c == '\\')
+}
+
+/// Whether `c` escapes the next character on this platform.
+fn is_escape(c: char) -> bool {
+    c == '\\' && !cfg!(windows)
+}
+
+/// Expand braces in a pattern.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::glob::expand_braces;
+/// let expanded = expand_braces("img.{jp{,e}g,png}").unwrap();
+/// assert_eq!(expanded, ["img.jpg", "img.jpeg", "img.png"]);
+/// ```
+pub fn expand_braces(pattern: &str) -> Result<Vec<String>, Error> {
+    let chars: Vec<char> = pattern.chars().collect();
+
+    // Find the first top-level opening brace.
+    let mut i = 0;
+    let mut in_class = false;
+    let open = loop {
+        match chars.get(i) {
+            None => return Ok(vec![pattern.to_owned()]),
+            Some(&c) if is_escape(c) => i += 1,
+            Some('[') if !in_class => in_class = true,
+            Some(']') if in_class => in_class = false,
+            Some('{') if !in_class => break i,
+            _ => {}
+        }
+        i += 1;
+    };
+
+    // Find the matching closing brace, remembering where the alternatives are split.
+    let mut depth = 0;
+    let mut splits = vec![open];
+    let mut i = open + 1;
+    let close = loop {
+        match chars.get(i) {
+            None => return Err(Error::UnclosedBrace(pattern.to_owned())),
+            Some(&c) if is_escape(c) => i += 1,
+            Some('[') if !in_class => in_class = true,
+            Some(']') if in_class => in_class = false,
+            Some('{') if !in_class => depth += 1,
+            Some('}') if !in_class && depth == 0 => break i,
+            Some('}') if !in_class => depth -= 1,
+            Some(',') if !in_class && depth == 0 => splits.push(i),
+            _ => {}
+        }
+        i += 1;
+    };
+    splits.push(close);
+
+    let prefix: String = chars[..open].iter().collect();
+    let suffix: String = chars[close + 1..].iter().collect();
+
+    let mut expanded = Vec::new();
+    for bounds in splits.windows(2) {
+        let alternative: String = chars[bounds[0] + 1..bounds[1]].iter().collect();
+        // The alternative and the suffix may have more braces in them.
+        for rest in expand_braces(&(alternative + &suffix))? {
+            expanded.push(format!("{}{}", prefix, rest));
+        }
+    }
+    Ok(expanded)
+}
+
+/// Whether a pattern segment (after brace expansion) has any wildcards in it.
+fn has_wildcards(segment: &str) -> bool {
+    let mut escaped = false;
+    for c in segment.chars() {
+        if escaped {
+            escaped = false;
+        } else if is_escape(c) {
+            escaped = true;
+        } else if matches!(c, '*' | '?' | '[') {
+            return true;
+        }
+    }
+    false
+}
+
+/// Remove escapes from a pattern segment without wildcards.
+fn unescape(segment: &str) -> String {
+    let mut unescaped = String::with_capacity(segment.len());
+    let mut escaped = false;
+    for c in segment.chars() {
+        if !escaped && is_escape(c) {
+            escaped = true;
+        } else {
+            unescaped.push(c);
+            escaped = false;
+        }
+    }
+    unescaped
+}
+
+/// Split a pattern (after brace expansion) into a literal base directory
+/// and the rest of the pattern, if the rest has any wildcards.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::glob::split_base;
+/// assert_eq!(split_base("/photos/2019/**/*.jpg"), ("/photos/2019".into(), Some("**/*.jpg".into())));
+/// assert_eq!(split_base("*.png"), (".".into(), Some("*.png".into())));
+/// assert_eq!(split_base("/photos"), ("/photos".into(), None));
+/// ```
+pub fn split_base(pattern: &str) -> (String, Option<String>) {
+    let segments: Vec<&str> = pattern.split(is_separator).collect();
+    match segments.iter().position(|s| has_wildcards(s)) {
+        None => (unescape(pattern), None),
+        Some(first) => {
+            let base = match first {
+                0 => ".".to_owned(),
+                // Only the root directory precedes the wildcards.
+                1 if segments[0].is_empty() => "/".to_owned(),
+                _ => unescape(&segments[..first].join("/")),
+            };
+            (base, Some(segments[first..].join("/")))
+        }
+    }
+}
+
+/// A part of a pattern segment.
+#[derive(Clone, Debug)]
+enum Token {
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyChars,
+    /// `[...]`, holds inclusive ranges and whether the class is negated.
+    Class(Vec<(char, char)>, bool),
+}
+
+/// A segment of a pattern, between separators.
+#[derive(Clone, Debug)]
+enum Segment {
+    /// `**`
+    AnyDirs,
+    Tokens(Vec<Token>),
+}
+
+/// A compiled glob pattern.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::glob::Pattern;
+/// let pattern = Pattern::new("2019/**/*.{jpg,png}").unwrap();
+/// assert!(pattern.matches("2019/summer/sea.jpg"));
+/// assert!(pattern.matches("2019/sea.png"));
+/// assert!(!pattern.matches("2020/sea.jpg"));
+/// assert!(!pattern.matches("2019/sea.gif"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    source: String,
+    /// One entry per alternative produced by brace expansion.
+    alternatives: Vec<Vec<Segment>>,
+}
+
+impl Pattern {
+    /// Compile a glob pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - the pattern to compile, see [module documentation](self) for the syntax.
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let alternatives = expand_braces(pattern)?
+            .iter()
+            .map(|alternative| {
+                alternative
+                    .split(is_separator)
+                    .map(|segment| compile_segment(segment, pattern))
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            source: pattern.to_owned(),
+            alternatives,
+        })
+    }
+
+    /// Whether the pattern has a separator in it, so that it is supposed
+    /// to match a path rather than just a file name.
+    pub fn is_anchored(&self) -> bool {
+        self.alternatives.iter().any(|segments| segments.len() > 1)
+    }
+
+    /// Check if a `/`-separated path matches the pattern.
+    pub fn matches(&self, path: &str) -> bool {
+        let parts: Vec<&str> = path.split(is_separator).collect();
+        self.alternatives
+            .iter()
+            .any(|segments| match_segments(segments, &parts))
+    }
+
+    /// Check if a path matches the pattern.
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.matches(&to_slash(path))
+    }
+
+    /// Check if a file matches the pattern: its whole path if the pattern
+    /// is [anchored](Pattern::is_anchored), its name otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::walker::glob::Pattern;
+    /// # use std::path::Path;
+    /// assert!(Pattern::new("*.gif").unwrap().matches_file(Path::new("2019/sea.gif")));
+    /// assert!(Pattern::new("2019/*").unwrap().matches_file(Path::new("2019/sea.gif")));
+    /// assert!(!Pattern::new("sea/*").unwrap().matches_file(Path::new("2019/sea/a.gif")));
+    /// ```
+    pub fn matches_file(&self, path: &Path) -> bool {
+        if self.is_anchored() {
+            self.matches_path(path)
+        } else {
+            path.file_name()
+                .is_some_and(|name| self.matches(&name.to_string_lossy()))
+        }
+    }
+
+    /// Check if anything inside of the directory at `path` could match the pattern.
+    ///
+    /// Allows skipping directories that can't have any matches in them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::walker::glob::Pattern;
+    /// let pattern = Pattern::new("2019/*/*.jpg").unwrap();
+    /// assert!(pattern.may_match_inside(std::path::Path::new("2019/summer")));
+    /// assert!(!pattern.may_match_inside(std::path::Path::new("2020")));
+    /// assert!(!pattern.may_match_inside(std::path::Path::new("2019/summer/sea")));
+    /// ```
+    pub fn may_match_inside(&self, path: &Path) -> bool {
+        let path = to_slash(path);
+        let parts: Vec<&str> = path.split('/').collect();
+        self.alternatives
+            .iter()
+            .any(|segments| match_prefix(segments, &parts))
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Join path components with `/`.
+fn to_slash(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn compile_segment(segment: &str, pattern: &str) -> Result<Segment, Error> {
+    if segment == "**" {
+        return Ok(Segment::AnyDirs);
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = segment.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '?' => Token::AnyChar,
+            '*' => Token::AnyChars,
+            c if is_escape(c) => Token::Char(chars.next().unwrap_or(c)),
+            '[' => {
+                let negated = matches!(chars.peek(), Some('!') | Some('^'));
+                if negated {
+                    chars.next();
+                }
+                let mut ranges = Vec::new();
+                let mut first = true;
+                loop {
+                    let start = match chars.next() {
+                        None => return Err(Error::UnclosedClass(pattern.to_owned())),
+                        // A leading ']' is a part of the class.
+                        Some(']') if !first => break,
+                        Some(c) if is_escape(c) => chars.next().unwrap_or(c),
+                        Some(c) => c,
+                    };
+                    first = false;
+
+                    let mut lookahead = chars.clone();
+                    match (lookahead.next(), lookahead.next()) {
+                        (Some('-'), Some(end)) if end != ']' => {
+                            chars.next();
+                            chars.next();
+                            ranges.push((start, end));
+                        }
+                        _ => ranges.push((start, start)),
+                    }
+                }
+                Token::Class(ranges, negated)
+            }
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    Ok(Segment::Tokens(tokens))
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    match tokens.split_first() {
+        None => text.is_empty(),
+        Some((Token::AnyChars, rest)) => (0..=text.len()).any(|i| match_tokens(rest, &text[i..])),
+        Some((token, rest)) => match text.split_first() {
+            None => false,
+            Some((&c, text_rest)) => {
+                let matched = match token {
+                    Token::Char(expected) => c == *expected,
+                    Token::AnyChar => true,
+                    Token::Class(ranges, negated) => {
+                        ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+                    }
+                    Token::AnyChars => unreachable!("handled above"),
+                };
+                matched && match_tokens(rest, text_rest)
+            }
+        },
+    }
+}
+
+fn match_segment(tokens: &[Token], part: &str) -> bool {
+    let text: Vec<char> = part.chars().collect();
+    match_tokens(tokens, &text)
+}
+
+fn match_segments(segments: &[Segment], parts: &[&str]) -> bool {
+    match segments.split_first() {
+        None => parts.is_empty(),
+        Some((Segment::AnyDirs, rest)) => {
+            (0..=parts.len()).any(|i| match_segments(rest, &parts[i..]))
+        }
+        Some((Segment::Tokens(tokens), rest)) => match parts.split_first() {
+            None => false,
+            Some((part, parts_rest)) => {
+                match_segment(tokens, part) && match_segments(rest, parts_rest)
+            }
+        },
+    }
+}
+
+fn match_prefix(segments: &[Segment], parts: &[&str]) -> bool {
+    match (segments.split_first(), parts.split_first()) {
+        // Something inside of the directory still needs to match.
+        (None, _) => false,
+        (Some((Segment::AnyDirs, _)), _) => true,
+        (Some(_), None) => true,
+        (Some((Segment::Tokens(tokens), rest)), Some((part, parts_rest))) => {
+            match_segment(tokens, part) && match_prefix(rest, parts_rest)
+        }
+    }
+}
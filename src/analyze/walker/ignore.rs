@@ -0,0 +1,196 @@
+//! Ignore files, in the syntax of `.gitignore`.
+//!
+//! A `.succotashignore` file in a walked directory lists what not to walk
+//! below it, e.g. folders of generated thumbnails. `.gitignore` files are
+//! respected too if asked, with `.succotashignore` taking precedence over
+//! `.gitignore` in the same directory, and files deeper in the tree taking
+//! precedence over those above. Only ignore files in the walked directories
+//! count, not in their parents.
+//!
+//! Patterns are translated to [`glob`](super::glob) patterns: a pattern
+//! with a `/` before its end is matched against the path relative to the
+//! ignore file, any other against names at any depth; a trailing `/` only
+//! matches directories, and a leading `!` re-includes what an earlier
+//! pattern has ignored.
+
+use super::glob;
+use async_std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Name of the ignore file of succotash.
+pub const IGNORE_FILE: &str = ".succotashignore";
+
+/// Name of the ignore file of git.
+pub const GITIGNORE_FILE: &str = ".gitignore";
+
+/// A line of an ignore file.
+#[derive(Debug)]
+struct Rule {
+    pattern: glob::Pattern,
+    /// Whether the rule re-includes what it matches.
+    negated: bool,
+    /// Whether the rule only matches directories.
+    dir_only: bool,
+}
+
+impl Rule {
+    /// Parse a line, `None` for blank lines and comments.
+    fn parse(line: &str) -> Option<Result<Self, glob::Error>> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // Trailing spaces don't count, unless escaped.
+        let trimmed = line.trim_end_matches(' ');
+        let line = if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+            &line[..trimmed.len() + 1]
+        } else {
+            trimmed
+        };
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+
+        // Escapes and braces are not the same in globs, so such characters
+        // become classes of one character, which work the same everywhere.
+        let mut pattern = String::from(if anchored { "" } else { "**/" });
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(escaped) => pattern.extend(['[', escaped, ']']),
+                    None => pattern.push_str("[\\\\]"),
+                },
+                '{' | '}' => pattern.extend(['[', c, ']']),
+                c => pattern.push(c),
+            }
+        }
+
+        Some(glob::Pattern::new(&pattern).map(|pattern| Self {
+            pattern,
+            negated,
+            dir_only,
+        }))
+    }
+}
+
+/// Rules of the ignore files of a directory and of the directories above it.
+#[derive(Debug, Default)]
+pub struct Rules {
+    /// The directory the rules are in, relative to the root of the walk.
+    base: PathBuf,
+    /// The rules of the directory, in the order they apply in.
+    rules: Vec<Rule>,
+    /// Rules of the directories above.
+    parent: Option<Arc<Rules>>,
+}
+
+impl Rules {
+    /// Parse ignore files of a directory.
+    ///
+    /// Invalid lines are skipped with a warning, like git does.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - the directory, relative to the root of the walk.
+    /// * `texts` - contents of the ignore files, ones that take precedence last.
+    /// * `parent` - rules of the directories above.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::walker::ignore::Rules;
+    /// let text = "# Generated\nsprites/\n*.tmp.png\n!keep.tmp.png\n/cover.jpg\n";
+    /// let rules = Rules::parse("art".into(), &[text], None);
+    /// assert!(rules.is_ignored("art/game/sprites".as_ref(), true));
+    /// assert!(!rules.is_ignored("art/game/sprites".as_ref(), false));
+    /// assert!(rules.is_ignored("art/a/b.tmp.png".as_ref(), false));
+    /// assert!(!rules.is_ignored("art/keep.tmp.png".as_ref(), false));
+    /// assert!(rules.is_ignored("art/cover.jpg".as_ref(), false));
+    /// assert!(!rules.is_ignored("art/old/cover.jpg".as_ref(), false));
+    /// ```
+    pub fn parse(base: PathBuf, texts: &[&str], parent: Option<Arc<Rules>>) -> Self {
+        let mut rules = Vec::new();
+        for line in texts.iter().flat_map(|text| text.lines()) {
+            match Rule::parse(line) {
+                Some(Ok(rule)) => rules.push(rule),
+                Some(Err(e)) => warn!("Skipping an ignore rule: {}", e),
+                None => {}
+            }
+        }
+        Self {
+            base,
+            rules,
+            parent,
+        }
+    }
+
+    /// Read ignore files of a directory.
+    ///
+    /// Returns `parent` if the directory has no ignore files.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - the directory.
+    /// * `relative` - the directory, relative to the root of the walk.
+    /// * `gitignore` - whether to read `.gitignore` too.
+    /// * `parent` - rules of the directories above.
+    pub async fn read(
+        dir: &Path,
+        relative: &Path,
+        gitignore: bool,
+        parent: Option<Arc<Rules>>,
+    ) -> Option<Arc<Rules>> {
+        let names: &[&str] = if gitignore {
+            &[GITIGNORE_FILE, IGNORE_FILE]
+        } else {
+            &[IGNORE_FILE]
+        };
+        let mut texts = Vec::new();
+        for name in names {
+            let path = dir.join(name);
+            match async_std::fs::read_to_string(&path).await {
+                Ok(text) => texts.push(text),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("Can't read '{}': {}", path.to_string_lossy(), e),
+            }
+        }
+        if texts.is_empty() {
+            return parent;
+        }
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        Some(Arc::new(Self::parse(relative.into(), &texts, parent)))
+    }
+
+    /// Whether a file or a directory is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative` - the path, relative to the root of the walk.
+    /// * `is_dir` - whether it is a directory.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let inside = relative.strip_prefix(&self.base).unwrap_or(relative);
+        let last_match =
+            self.rules.iter().rev().find(|rule| {
+                (is_dir || !rule.dir_only) && rule.pattern.matches_path(inside.as_ref())
+            });
+        match (last_match, &self.parent) {
+            (Some(rule), _) => !rule.negated,
+            (None, Some(parent)) => parent.is_ignored(relative, is_dir),
+            (None, None) => false,
+        }
+    }
+}
+
+/// Whether a file is an ignore file, which is never picked itself.
+pub fn is_ignore_file(name: &std::ffi::OsStr) -> bool {
+    name == IGNORE_FILE || name == GITIGNORE_FILE
+}
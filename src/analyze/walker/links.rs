@@ -0,0 +1,184 @@
+//! Symbolic links, Windows junctions and macOS Finder aliases.
+//!
+//! All of them point to other places in the filesystem, and the walker
+//! follows them or not according to a [`LinkPolicy`]. However many links
+//! lead to a file, it is the same file, see [`FileId`].
+//!
+//! On Windows, junctions and symbolic links to directories are both
+//! directory reparse points and are treated alike. Finder aliases are
+//! only recognized on macOS, elsewhere they are just small files.
+
+use async_std::fs;
+use async_std::path::{Path, PathBuf};
+
+/// What to do with a junction or an alias.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LinkPolicy {
+    /// Don't pick or enter what it points to.
+    #[default]
+    Skip,
+    /// Walk what it points to, as if it was in place of the link.
+    Follow,
+}
+
+impl std::str::FromStr for LinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "follow" => Ok(Self::Follow),
+            _ => Err(format!("unknown link policy '{}'", s)),
+        }
+    }
+}
+
+/// What identifies a file, however many links and names lead to it.
+///
+/// On unix, that's the device and the inode of the file, so hard links are one
+/// file too. Elsewhere, and if the file can't be looked at, it is the key
+/// of its canonical path, see [`names::key`](crate::analyze::names::key).
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum FileId {
+    /// Device and inode.
+    Inode(u64, u64),
+    /// Key of the canonical path.
+    Path(PathBuf),
+}
+
+impl FileId {
+    /// Identify a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `canonical` - the canonical path of the file.
+    pub async fn of(canonical: &Path) -> Self {
+        #[cfg(unix)]
+        if let Ok(metadata) = fs::metadata(canonical).await {
+            use std::os::unix::fs::MetadataExt;
+            return Self::Inode(metadata.dev(), metadata.ino());
+        }
+        Self::Path(crate::analyze::names::key(canonical))
+    }
+}
+
+/// Aliases are small, larger files aren't even looked into.
+pub const MAX_ALIAS_SIZE: u64 = 64 * 1024;
+
+/// What alias files start with.
+const ALIAS_MAGIC: &[u8] = b"book\0\0\0\0mark\0\0\0\0";
+
+/// Bookmark item key of the path components.
+const KEY_PATH: u32 = 0x1004;
+/// Bookmark TOCs start with this.
+const TOC_MAGIC: u32 = 0xffff_fffe;
+/// Bookmark item type masks.
+const TYPE_MASK: u32 = 0xffff_ff00;
+const TYPE_STRING: u32 = 0x0100;
+const TYPE_ARRAY: u32 = 0x0600;
+
+/// Whether a directory entry is a junction.
+///
+/// # Arguments
+///
+/// * `path` - where the entry is.
+/// * `file_type` - type of the entry itself, not of what it points to.
+pub async fn is_junction(path: &Path, file_type: &std::fs::FileType) -> bool {
+    cfg!(windows) && file_type.is_symlink() && fs::metadata(path).await.is_ok_and(|m| m.is_dir())
+}
+
+/// Whether a file is a Finder alias.
+///
+/// Always false if not on macOS.
+pub async fn is_alias(path: &Path) -> bool {
+    use async_std::io::ReadExt;
+
+    if !cfg!(target_os = "macos") {
+        return false;
+    }
+    let len = match fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return false,
+    };
+    if len < ALIAS_MAGIC.len() as u64 || len > MAX_ALIAS_SIZE {
+        return false;
+    }
+    let mut magic = [0; ALIAS_MAGIC.len()];
+    match fs::File::open(path).await {
+        Ok(mut file) => file.read_exact(&mut magic).await.is_ok() && magic == ALIAS_MAGIC,
+        Err(_) => false,
+    }
+}
+
+/// Get the path an alias points to from its bookmark data.
+///
+/// Returns `None` if the data is not a bookmark or it has no path.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::links;
+/// assert_eq!(links::resolve_alias(b"\x89PNG\r\n\x1a\n"), None);
+/// ```
+pub fn resolve_alias(data: &[u8]) -> Option<PathBuf> {
+    if !data.starts_with(&ALIAS_MAGIC[..4]) {
+        return None;
+    }
+
+    let u32_at = |at: usize| {
+        let bytes = data.get(at..at.checked_add(4)?)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    // Offsets in the bookmark are relative to the end of its header.
+    let header = u32_at(12)? as usize;
+    let item = |offset: u32| {
+        let at = header.checked_add(offset as usize)?;
+        let length = u32_at(at)? as usize;
+        let kind = u32_at(at + 4)?;
+        Some((kind, data.get(at + 8..(at + 8).checked_add(length)?)?))
+    };
+
+    let mut toc_at = header.checked_add(u32_at(header)? as usize)?;
+    // TOCs are chained, and broken data may have loops.
+    for _ in 0..16 {
+        if u32_at(toc_at + 4)? != TOC_MAGIC {
+            return None;
+        }
+        let next = u32_at(toc_at + 12)?;
+        let count = u32_at(toc_at + 16)? as usize;
+
+        for i in 0..count {
+            let entry_at = toc_at + 20 + i * 12;
+            if u32_at(entry_at)? != KEY_PATH {
+                continue;
+            }
+            let (kind, components) = item(u32_at(entry_at + 4)?)?;
+            if kind & TYPE_MASK != TYPE_ARRAY {
+                return None;
+            }
+
+            let mut path = PathBuf::from("/");
+            for offset in components.chunks_exact(4) {
+                let offset = u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]);
+                let (kind, component) = item(offset)?;
+                if kind & TYPE_MASK != TYPE_STRING {
+                    return None;
+                }
+                path.push(std::str::from_utf8(component).ok()?);
+            }
+            return Some(path);
+        }
+
+        if next == 0 {
+            return None;
+        }
+        toc_at = header.checked_add(next as usize)?;
+    }
+    None
+}
+
+/// Read an alias file and get the path it points to.
+pub async fn read_alias(path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let data = fs::read(path).await?;
+    resolve_alias(&data).ok_or_else(|| "alias without a path".into())
+}
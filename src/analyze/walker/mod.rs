@@ -0,0 +1,689 @@
+//! Finding the files to analyze.
+//!
+//! The walker takes an input that is either a path or a glob pattern
+//! (see [`glob`]) and recursively collects the files it refers to,
+//! applying the include/exclude rules from [`WalkOptions`].
+//! Files can also come from a list, see [`Source`].
+//! Every file is picked once, whatever links lead to it, and hard links
+//! to picked files are told apart, see [`Files::hardlinks`].
+
+use super::cancel::CancelToken;
+use super::decoders::{self, Decoder};
+use super::metrics::METRICS;
+use super::names;
+use super::progress::{Progress, Stage, Tracker};
+use async_std::fs;
+use async_std::path::{Path, PathBuf};
+use async_std::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+pub mod attrs;
+pub mod glob;
+pub mod ignore;
+pub mod links;
+pub mod size;
+pub mod time;
+
+/// Options of the directory traversal.
+///
+/// Include and exclude patterns are matched against paths relative to
+/// the directory the walk started from. Patterns without a separator
+/// are matched against file names only, so `*.png` matches at any depth.
+///
+/// A file is picked if it matches any include pattern (or there are none)
+/// and doesn't match any exclude pattern. Excluded directories are not
+/// descended into at all.
+///
+/// Files also have to pass the [`time::TimeFilter`] and the [`size::SizeFilter`]. Files and directories
+/// rejected by the [`attrs::AttrFilter`] are skipped, just like excluded ones.
+///
+/// Symbolic links to files are picked, the file they point to at most once.
+/// Symbolic links to directories, junctions and aliases (see [`links`]) are
+/// skipped unless asked otherwise.
+///
+/// What `.succotashignore` files (and `.gitignore` files, if asked) in the
+/// walked directories ignore is skipped too, see [`ignore`].
+#[derive(Default)]
+pub struct WalkOptions {
+    /// Only pick files matching one of these.
+    pub include: Vec<glob::Pattern>,
+    /// Never pick files or enter directories matching one of these.
+    pub exclude: Vec<glob::Pattern>,
+    /// Only pick files from this period.
+    pub time: time::TimeFilter,
+    /// Only pick files of these sizes.
+    pub size: size::SizeFilter,
+    /// Never pick files or enter directories with these attributes.
+    pub attrs: attrs::AttrFilter,
+    /// What to do with symbolic links to directories.
+    pub symlinks: links::LinkPolicy,
+    /// What to do with Windows junctions.
+    pub junctions: links::LinkPolicy,
+    /// What to do with macOS Finder aliases.
+    pub aliases: links::LinkPolicy,
+    /// Whether to skip what `.gitignore` files ignore.
+    pub gitignore: bool,
+    /// Whether to pick images inside archives rather than archives
+    /// themselves, see [`archive`](super::archive).
+    #[cfg(feature = "archives")]
+    pub archives: bool,
+}
+
+impl WalkOptions {
+    /// Whether a file or directory at `relative` path is excluded.
+    fn is_excluded(&self, relative: &Path) -> bool {
+        self.exclude
+            .iter()
+            .any(|p| p.matches_file(relative.as_ref()))
+    }
+
+    /// Whether a file at `relative` path is included.
+    fn is_included(&self, relative: &Path) -> bool {
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|p| p.matches_file(relative.as_ref()))
+    }
+
+    /// Whether a file at `path` passes all the filters.
+    async fn accepts_file(
+        &self,
+        path: &Path,
+        relative: &Path,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        // Sidecars are metadata of other files, see `index::sidecar`.
+        if crate::analyze::index::sidecar::is_sidecar(path) {
+            trace!("'{}' is a sidecar", path.to_string_lossy());
+            return Ok(false);
+        }
+        // Dimensions are read from the file, so the size filter goes last.
+        Ok(self.is_included(relative)
+            && self.time.accepts(path).await?
+            && self.attrs.accepts(path).await?
+            && self.size.accepts(path).await?)
+    }
+}
+
+/// Add a picked file to `found` as coming from root `root`, or images
+/// inside it if it is an archive and archives are walked into.
+async fn pick(found: &mut Files, path: PathBuf, root: usize, options: &WalkOptions) {
+    #[cfg(feature = "archives")]
+    if options.archives && super::archive::is_archive(&path) {
+        let archive = path.clone();
+        match super::io::unblock(move || {
+            super::archive::entries(&archive).map_err(|e| e.to_string())
+        })
+        .await
+        {
+            Ok(entries) => {
+                trace!("'{}' has {} images", path.to_string_lossy(), entries.len());
+                for entry in entries {
+                    found.push(super::archive::join(&path, &entry), root);
+                }
+            }
+            Err(e) => {
+                warn!("Can't read archive '{}': {}", path.to_string_lossy(), e);
+                METRICS.entries_skipped.inc();
+            }
+        }
+        return;
+    }
+    #[cfg(not(feature = "archives"))]
+    let _ = options;
+    found.push(path, root);
+}
+
+/// Collect objects under a location in S3 into `found`, as coming from root `index`.
+///
+/// Keys relative to the prefix are matched against include and exclude patterns,
+/// and sizes from the listing against limits on size. Other filters need files
+/// on a disk, and pass every object.
+#[cfg(feature = "s3")]
+async fn walk_bucket(
+    location: super::s3::Location,
+    index: usize,
+    options: &WalkOptions,
+    progress: &Tracker,
+    found: &mut Files,
+) -> Result<(), Box<dyn std::error::Error>> {
+    debug!(
+        "Listing '{}'",
+        location.path(&location.prefix).to_string_lossy()
+    );
+    let listed = location.clone();
+    let objects = super::io::unblock(move || super::s3::list(&listed)).await?;
+    for object in objects {
+        let path = location.path(&object.key);
+        let relative = object.key[location.prefix.len()..].trim_start_matches('/');
+        let relative = Path::new(relative);
+        if options.is_excluded(relative)
+            || !options.is_included(relative)
+            || !options.size.accepts_size(object.size)
+            || found.roots_of.contains_key(&path)
+        {
+            METRICS.entries_skipped.inc();
+            continue;
+        }
+        progress.advance(1, Some(&path));
+        found.push(path, index);
+    }
+    Ok(())
+}
+
+/// Where the files to analyze come from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    /// Paths or glob patterns to walk, see [`walk`].
+    Walk(Vec<String>),
+    /// A file listing paths, see [`read_list`]. `-` stands for stdin.
+    List(PathBuf),
+}
+
+impl Source {
+    /// Collect the files to analyze.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - what files to pick.
+    /// * `cancel` - stops collecting, the files found so far are returned.
+    /// * `progress` - where to report files found, see [`Stage::Walk`].
+    pub async fn files(
+        &self,
+        options: &WalkOptions,
+        cancel: &CancelToken,
+        progress: &Progress,
+    ) -> Result<Files, Box<dyn std::error::Error>> {
+        match self {
+            Self::Walk(inputs) => walk(inputs, options, cancel, progress).await,
+            Self::List(list) => read_list(list, options, cancel, progress).await,
+        }
+    }
+}
+
+/// Files to analyze, and the roots they came from.
+///
+/// Files whose decoder isn't in this build are set aside, see [`decoders`].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::Files;
+/// let mut files = Files::default();
+/// files.add_root("/home/user/Pictures");
+/// files.add_root("/mnt/nas/Pictures");
+/// files.push("/mnt/nas/Pictures/cat.jpg".into(), 1);
+/// assert_eq!(files.root_of("/mnt/nas/Pictures/cat.jpg".as_ref()), Some("/mnt/nas/Pictures"));
+/// files.push("/mnt/nas/Pictures/cat.heic".into(), 1);
+/// assert_eq!(files.paths.len(), 1);
+/// assert_eq!(files.unsupported.len(), 1);
+/// assert_eq!(files.counts(), vec![0, 2]);
+/// ```
+#[derive(Debug, Default)]
+pub struct Files {
+    /// Where the files came from: the walked inputs, or the list.
+    pub roots: Vec<String>,
+    /// The files, in the order they were found.
+    pub paths: Vec<PathBuf>,
+    /// The files that can't be analyzed by this build, and the decoders they need.
+    pub unsupported: Vec<(PathBuf, &'static Decoder)>,
+    /// Hard links to files that were found under another name, and that name.
+    ///
+    /// They share their data already, so only one of them is analyzed.
+    pub hardlinks: Vec<(PathBuf, PathBuf)>,
+    /// Tags of files, by path, for files kept in a [database](crate::db).
+    pub tags: HashMap<PathBuf, Vec<String>>,
+    /// Indices into `roots`, by path.
+    roots_of: HashMap<PathBuf, usize>,
+}
+
+impl Files {
+    /// Add a root, returns its index.
+    pub fn add_root(&mut self, root: &str) -> usize {
+        self.roots.push(root.to_owned());
+        self.roots.len() - 1
+    }
+
+    /// Add a file that came from the root with index `root`.
+    pub fn push(&mut self, path: PathBuf, root: usize) {
+        self.roots_of.insert(path.clone(), root);
+        match decoders::missing(&path) {
+            Some(decoder) => {
+                debug!(
+                    "Skipping '{}': {}",
+                    path.to_string_lossy(),
+                    decoder.reason()
+                );
+                METRICS.files_unsupported.inc();
+                self.unsupported.push((path, decoder));
+            }
+            None => self.paths.push(path),
+        }
+    }
+
+    /// Sort files by path, as they are reported.
+    pub fn sort(&mut self) {
+        self.paths.sort();
+        self.unsupported.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.hardlinks.sort();
+    }
+
+    /// Tags of a file, none unless it is kept in a database.
+    pub fn tags_of(&self, path: &Path) -> &[String] {
+        self.tags.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// How many files there are, including unsupported ones.
+    pub fn len(&self) -> usize {
+        self.paths.len() + self.unsupported.len()
+    }
+
+    /// Whether there are no files, not even unsupported ones.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The root a file came from.
+    pub fn root_of(&self, path: &Path) -> Option<&str> {
+        self.roots_of
+            .get(path)
+            .map(|&root| self.roots[root].as_str())
+    }
+
+    /// How many files came from each root, in the order of `roots`.
+    pub fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.roots.len()];
+        for &root in self.roots_of.values() {
+            counts[root] += 1;
+        }
+        counts
+    }
+}
+
+/// Split a list of paths, separated by NUL bytes if there are any, by newlines otherwise.
+///
+/// Empty entries are skipped.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::split_list;
+/// # use async_std::path::PathBuf;
+/// let lines = split_list(b"a.jpg\r\nb c.jpg\n\n");
+/// assert_eq!(lines, vec![PathBuf::from("a.jpg"), PathBuf::from("b c.jpg")]);
+/// let nuls = split_list(b"a\nb.jpg\0c.jpg\0");
+/// assert_eq!(nuls, vec![PathBuf::from("a\nb.jpg"), PathBuf::from("c.jpg")]);
+/// ```
+pub fn split_list(list: &[u8]) -> Vec<PathBuf> {
+    let entries: Vec<&[u8]> = if list.contains(&b'\0') {
+        list.split(|&b| b == b'\0').collect()
+    } else {
+        list.split(|&b| b == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .collect()
+    };
+    entries
+        .into_iter()
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| super::worker::path_from_bytes(entry.to_vec()))
+        .collect()
+}
+
+/// Collect files listed in a file, see [`split_list`].
+///
+/// Listed files pass the same filters as walked ones, with include and exclude
+/// patterns matched against the paths as they are listed. Files that don't exist
+/// are skipped with a warning, and every file is picked at most once, see [`names::key`].
+///
+/// # Arguments
+///
+/// * `list` - the file with the list, `-` for stdin.
+/// * `options` - what files to pick.
+/// * `cancel` - stops reading, the files found so far are returned.
+/// * `progress` - where to report files found, after every one.
+pub async fn read_list(
+    list: &Path,
+    options: &WalkOptions,
+    cancel: &CancelToken,
+    progress: &Progress,
+) -> Result<Files, Box<dyn std::error::Error>> {
+    use async_std::io::ReadExt;
+
+    let contents = if list == Path::new("-") {
+        let mut contents = Vec::new();
+        async_std::io::stdin().read_to_end(&mut contents).await?;
+        contents
+    } else {
+        fs::read(list).await?
+    };
+
+    let mut found = Files::default();
+    let root = found.add_root(&list.to_string_lossy());
+    let mut seen = Walked::default();
+    let progress = progress.stage(Stage::Walk, None);
+    for path in split_list(&contents) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let canonical = match fs::canonicalize(&path).await {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                warn!("Skipping listed '{}': {}", path.to_string_lossy(), e);
+                METRICS.entries_skipped.inc();
+                continue;
+            }
+        };
+        if !fs::metadata(&canonical).await?.is_file()
+            || options.is_excluded(&path)
+            || !options.accepts_file(&path, &path).await?
+        {
+            trace!("Excluded '{}'", path.to_string_lossy());
+            METRICS.entries_skipped.inc();
+            continue;
+        }
+        if seen.is_new(&path, &canonical, &mut found).await {
+            progress.advance(1, Some(&path));
+            pick(&mut found, path, root, options).await;
+        }
+    }
+
+    debug!("Found {} listed files", found.len());
+    METRICS.files_found.add(found.len() as u64);
+    Ok(found)
+}
+
+/// Where to start walking from.
+struct Root {
+    /// Directory (or file) without wildcards in its path.
+    base: PathBuf,
+    /// What the paths relative to the `base` have to match.
+    pattern: Option<glob::Pattern>,
+}
+
+impl Root {
+    /// Get roots for an input, which can be a path or a glob pattern.
+    ///
+    /// Brace expansion can produce more than one root, e.g. for `{a,b}/*.jpg`.
+    fn parse(input: &str) -> Result<Vec<Self>, glob::Error> {
+        glob::expand_braces(input)?
+            .iter()
+            .map(|alternative| {
+                let (base, pattern) = glob::split_base(alternative);
+                Ok(Self {
+                    base: base.into(),
+                    pattern: pattern.map(|p| glob::Pattern::new(&p)).transpose()?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Collect files to analyze.
+///
+/// Symbolic links to directories are followed as [`WalkOptions::symlinks`] says.
+/// Every file and directory is walked at most once, however many links lead to it,
+/// and on case-insensitive filesystems however its name is spelled. This holds
+/// across inputs too: a file reachable from two of them is only picked from
+/// the first one.
+///
+/// # Arguments
+///
+/// * `inputs` - paths to directories or files, or glob patterns; they are the roots of [`Files`].
+/// * `options` - what files to pick.
+/// * `cancel` - stops walking, the files found so far are returned.
+/// * `progress` - where to report files found, after every directory.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::analyze::walker;
+/// # async_std::task::block_on(async {
+/// let options = walker::WalkOptions::default();
+/// let inputs = ["/home/user/Pictures/2019/**/*.{jpg,png}".to_owned(), "/mnt/nas".to_owned()];
+/// let files = walker::walk(&inputs, &options, &Default::default(), &Default::default()).await?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// # });
+/// ```
+pub async fn walk(
+    inputs: &[String],
+    options: &WalkOptions,
+    cancel: &CancelToken,
+    progress: &Progress,
+) -> Result<Files, Box<dyn std::error::Error>> {
+    let mut found = Files::default();
+    let mut walked = Walked::default();
+    let progress = progress.stage(Stage::Walk, None);
+
+    for input in inputs {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let index = found.add_root(input);
+        let before = found.len();
+        let walked = &mut walked;
+        walk_input(input, index, options, cancel, &progress, &mut found, walked).await?;
+        debug!("Found {} files in '{}'", found.len() - before, input);
+    }
+
+    found.sort();
+    debug!("Found {} files", found.len());
+    METRICS.files_found.add(found.len() as u64);
+    Ok(found)
+}
+
+/// What [`walk`] has picked and walked already, across its inputs.
+#[derive(Default)]
+struct Walked {
+    /// Picked files, with their paths and canonical paths.
+    files: HashMap<links::FileId, (PathBuf, PathBuf)>,
+    /// Keys of canonical paths of walked directories, see [`names::key`].
+    dirs: HashSet<PathBuf>,
+}
+
+impl Walked {
+    /// Whether a file wasn't picked yet under any name, so it is to be picked now.
+    ///
+    /// A hard link to a picked file is added to `found` as one, see [`Files::hardlinks`].
+    /// Symbolic links have the canonical path of their target, so they aren't.
+    async fn is_new(&mut self, path: &Path, canonical: &Path, found: &mut Files) -> bool {
+        let id = links::FileId::of(canonical).await;
+        match self.files.get(&id) {
+            None => {
+                self.files
+                    .insert(id, (path.to_owned(), canonical.to_owned()));
+                true
+            }
+            Some((picked, picked_canonical)) => {
+                if matches!(id, links::FileId::Inode(..)) && picked_canonical != canonical {
+                    trace!(
+                        "'{}' is a hard link to '{}'",
+                        path.to_string_lossy(),
+                        picked.to_string_lossy()
+                    );
+                    found.hardlinks.push((path.to_owned(), picked.clone()));
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Collect files of one input of [`walk`] into `found`, as coming from root `index`.
+async fn walk_input(
+    input: &str,
+    index: usize,
+    options: &WalkOptions,
+    cancel: &CancelToken,
+    progress: &Tracker,
+    found: &mut Files,
+    walked: &mut Walked,
+) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "s3")]
+    if let Some(location) = super::s3::Location::parse(input) {
+        return walk_bucket(location, index, options, progress, found).await;
+    }
+    for root in Root::parse(input)? {
+        if !fs::metadata(&root.base).await?.is_dir() {
+            // The input names a file directly, only the filters apply.
+            let path = root.base;
+            if !options.is_excluded(&path)
+                && options.accepts_file(&path, &path).await?
+                && walked
+                    .is_new(&path, &fs::canonicalize(&path).await?, found)
+                    .await
+            {
+                progress.advance(1, Some(&path));
+                pick(found, path, index, options).await;
+            }
+            continue;
+        }
+
+        debug!("Walking '{}'", root.base.to_string_lossy());
+        // Directories come with their paths relative to the root,
+        // as followed links can lead outside of it.
+        let mut dirs = vec![(root.base.clone(), PathBuf::new(), None)];
+        while let Some((dir, relative_dir, ignored)) = dirs.pop() {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+            let canonical_dir = fs::canonicalize(&dir).await?;
+            if !walked.dirs.insert(names::key(&canonical_dir)) {
+                debug!("Already walked '{}'", dir.to_string_lossy());
+                continue;
+            }
+            METRICS.dirs_walked.inc();
+            let ignored =
+                ignore::Rules::read(&dir, &relative_dir, options.gitignore, ignored).await;
+
+            // Entries are sorted, so that the same files are found in the same order
+            // every time, and the same one of hard links to a file is picked.
+            let mut entries = Vec::new();
+            let mut read = fs::read_dir(&dir).await?;
+            while let Some(res) = read.next().await {
+                entries.push(res?);
+            }
+            entries.sort_by_key(|entry| entry.file_name());
+            let before = found.len();
+            for entry in entries {
+                let mut path = entry.path();
+                let mut canonical = canonical_dir.join(entry.file_name());
+                let relative = relative_dir.join(entry.file_name());
+
+                if options.is_excluded(&relative) {
+                    trace!("Excluded '{}'", path.to_string_lossy());
+                    METRICS.entries_skipped.inc();
+                    continue;
+                }
+
+                let file_type = entry.file_type().await?;
+                let mut is_dir = file_type.is_dir();
+                let mut is_file = if file_type.is_symlink() {
+                    fs::metadata(&path).await.is_ok_and(|m| m.is_file())
+                } else {
+                    file_type.is_file()
+                };
+                if is_file && file_type.is_symlink() {
+                    // A link is the same file as its target, whatever it is named.
+                    canonical = fs::canonicalize(&path).await?;
+                }
+
+                if ignore::is_ignore_file(&entry.file_name())
+                    || ignored
+                        .as_ref()
+                        .is_some_and(|rules| rules.is_ignored(&relative, is_dir))
+                {
+                    trace!("Ignored '{}'", path.to_string_lossy());
+                    METRICS.entries_skipped.inc();
+                    continue;
+                }
+
+                if links::is_junction(&path, &file_type).await {
+                    if options.junctions == links::LinkPolicy::Skip {
+                        trace!("Skipping junction '{}'", path.to_string_lossy());
+                        METRICS.entries_skipped.inc();
+                        continue;
+                    }
+                    is_dir = true;
+                } else if is_file && links::is_alias(&path).await {
+                    if options.aliases == links::LinkPolicy::Skip {
+                        trace!("Skipping alias '{}'", path.to_string_lossy());
+                        METRICS.entries_skipped.inc();
+                        continue;
+                    }
+                    let target = match links::read_alias(&path).await {
+                        Ok(target) => target,
+                        Err(e) => {
+                            warn!("Can't resolve alias '{}': {}", path.to_string_lossy(), e);
+                            continue;
+                        }
+                    };
+                    let metadata = match fs::metadata(&target).await {
+                        Ok(metadata) => metadata,
+                        Err(e) => {
+                            warn!(
+                                "Alias '{}' points to '{}', which is unavailable: {}",
+                                path.to_string_lossy(),
+                                target.to_string_lossy(),
+                                e
+                            );
+                            continue;
+                        }
+                    };
+                    is_dir = metadata.is_dir();
+                    is_file = metadata.is_file();
+                    canonical = fs::canonicalize(&target).await?;
+                    path = target;
+                } else if file_type.is_symlink() && !is_file {
+                    if options.symlinks == links::LinkPolicy::Skip {
+                        trace!("Not following link '{}'", path.to_string_lossy());
+                        METRICS.entries_skipped.inc();
+                        continue;
+                    }
+                    let target = match fs::canonicalize(&path).await {
+                        Ok(target) => target,
+                        Err(e) => {
+                            warn!("Can't follow link '{}': {}", path.to_string_lossy(), e);
+                            continue;
+                        }
+                    };
+                    if canonical_dir.starts_with(&target) {
+                        warn!(
+                            "Not following link '{}': it points to '{}', which contains it",
+                            path.to_string_lossy(),
+                            target.to_string_lossy()
+                        );
+                        METRICS.entries_skipped.inc();
+                        continue;
+                    }
+                    is_dir = fs::metadata(&target).await?.is_dir();
+                }
+
+                if is_dir {
+                    if !options.attrs.accepts(&path).await? {
+                        trace!("Excluded '{}' by attributes", path.to_string_lossy());
+                        METRICS.entries_skipped.inc();
+                        continue;
+                    }
+                    let may_match = root
+                        .pattern
+                        .as_ref()
+                        .is_none_or(|p| p.may_match_inside(relative.as_ref()));
+                    if may_match {
+                        dirs.push((path, relative, ignored.clone()));
+                    }
+                } else if is_file
+                    && root
+                        .pattern
+                        .as_ref()
+                        .is_none_or(|p| p.matches_path(relative.as_ref()))
+                    && options.accepts_file(&path, &relative).await?
+                    && walked.is_new(&path, &canonical, found).await
+                {
+                    pick(found, path, index, options).await;
+                }
+            }
+            progress.advance((found.len() - before) as u64, Some(&dir));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! Filtering files by their size and by dimensions of their images.
+//!
+//! Lets icons and other tiny images be left out cheaply: sizes come from
+//! the metadata of files, and dimensions from the headers of images,
+//! without decoding them, see [`probe`](crate::analyze::img::probe).
+//! Files whose dimensions can't be read from the header, e.g. videos,
+//! pass the dimension filter.
+
+use async_std::fs;
+use async_std::path::Path;
+
+/// Rejects files that are too small or too large.
+#[derive(Debug, Default)]
+pub struct SizeFilter {
+    /// Smallest size of a file, in bytes.
+    pub min_size: Option<u64>,
+    /// Largest size of a file, in bytes.
+    pub max_size: Option<u64>,
+    /// Smallest dimensions of an image, either way round.
+    pub min_dimensions: Option<(u32, u32)>,
+}
+
+impl SizeFilter {
+    /// Whether a file fits the limits.
+    ///
+    /// Doesn't touch the file if there are no limits,
+    /// and doesn't open it if there are no limits on dimensions.
+    pub async fn accepts(&self, path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let size = fs::metadata(path).await?.len();
+            if !self.accepts_size(size) {
+                trace!("'{}' has size of {} bytes", path.to_string_lossy(), size);
+                return Ok(false);
+            }
+        }
+
+        if let Some(min) = self.min_dimensions {
+            if let Some(dimensions) = crate::analyze::img::probe(path).await?.dimensions {
+                if !fits(dimensions, min) {
+                    trace!(
+                        "'{}' has dimensions of {}x{}",
+                        path.to_string_lossy(),
+                        dimensions.0,
+                        dimensions.1
+                    );
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether a file of `size` bytes fits the limits on size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::walker::size::SizeFilter;
+    /// let filter = SizeFilter { min_size: Some(1024), ..Default::default() };
+    /// assert!(filter.accepts_size(4096));
+    /// assert!(!filter.accepts_size(512));
+    /// ```
+    pub fn accepts_size(&self, size: u64) -> bool {
+        !(self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max))
+    }
+}
+
+/// Whether an image is at least as large as `min`, either way round,
+/// so that a portrait photo fits landscape limits too.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::size::fits;
+/// assert!(fits((3000, 4000), (640, 480)));
+/// assert!(fits((480, 640), (640, 480)));
+/// assert!(!fits((32, 32), (64, 64)));
+/// assert!(!fits((1000, 40), (64, 64)));
+/// ```
+pub fn fits(dimensions: (u32, u32), min: (u32, u32)) -> bool {
+    let sorted = |(a, b): (u32, u32)| (a.min(b), a.max(b));
+    let (short, long) = sorted(dimensions);
+    let (min_short, min_long) = sorted(min);
+    short >= min_short && long >= min_long
+}
@@ -0,0 +1,149 @@
+//! Filtering files by their time.
+//!
+//! Limits the analysis to a period, e.g. to files modified in 2020
+//! or photos taken during the last 30 days.
+
+use async_std::fs;
+use async_std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Where to take the time of a file from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TimeSource {
+    /// Modification time.
+    #[default]
+    Modified,
+    /// Status change time on Unix, creation time elsewhere.
+    Changed,
+    /// Original date/time from the EXIF metadata.
+    /// Files without it fall back to the modification time.
+    Exif,
+}
+
+impl std::str::FromStr for TimeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mtime" => Ok(Self::Modified),
+            "ctime" => Ok(Self::Changed),
+            "exif" => Ok(Self::Exif),
+            _ => Err(format!("unknown time source '{}'", s)),
+        }
+    }
+}
+
+/// Bounds on the time of a file, both exclusive.
+#[derive(Default)]
+pub struct TimeFilter {
+    /// Only pick files with time after this.
+    pub newer_than: Option<SystemTime>,
+    /// Only pick files with time before this.
+    pub older_than: Option<SystemTime>,
+    /// Which time of a file to compare.
+    pub source: TimeSource,
+}
+
+impl TimeFilter {
+    /// Whether a file at `path` is within the bounds.
+    ///
+    /// Doesn't touch the file if there are no bounds.
+    pub async fn accepts(&self, path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.newer_than.is_none() && self.older_than.is_none() {
+            return Ok(true);
+        }
+
+        let time = file_time(path, self.source).await?;
+        Ok(self.newer_than.is_none_or(|bound| time > bound)
+            && self.older_than.is_none_or(|bound| time < bound))
+    }
+}
+
+/// Get the time of a file.
+///
+/// # Arguments
+///
+/// * `path` - the file.
+/// * `source` - which time to get.
+pub async fn file_time(
+    path: &Path,
+    source: TimeSource,
+) -> Result<SystemTime, Box<dyn std::error::Error>> {
+    if source == TimeSource::Exif {
+        match exif_time(path).await {
+            Some(time) => return Ok(time),
+            None => trace!("No EXIF time in '{}'", path.to_string_lossy()),
+        }
+    }
+
+    let metadata = fs::metadata(path).await?;
+    match source {
+        TimeSource::Changed => changed_time(&metadata),
+        _ => Ok(metadata.modified()?),
+    }
+}
+
+#[cfg(unix)]
+fn changed_time(metadata: &fs::Metadata) -> Result<SystemTime, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::MetadataExt;
+    let since_epoch = Duration::new(metadata.ctime() as u64, metadata.ctime_nsec() as u32);
+    Ok(SystemTime::UNIX_EPOCH + since_epoch)
+}
+
+#[cfg(not(unix))]
+fn changed_time(metadata: &fs::Metadata) -> Result<SystemTime, Box<dyn std::error::Error>> {
+    Ok(metadata.created()?)
+}
+
+/// Read the original date/time from the EXIF metadata of a file,
+/// see [`Exif`](crate::analyze::features::exif::Exif), in local time.
+async fn exif_time(path: &Path) -> Option<SystemTime> {
+    use chrono::TimeZone;
+
+    let taken = crate::analyze::io::exif(path.as_ref()).await?.taken?;
+    let naive = chrono::NaiveDate::from_ymd_opt(1970, 1, 1)?
+        .and_hms_opt(0, 0, 0)?
+        .checked_add_signed(chrono::Duration::milliseconds(
+            (taken * 1000.).round() as i64
+        ))?;
+    Some(chrono::Local.from_local_datetime(&naive).earliest()?.into())
+}
+
+/// Parse a point in time.
+///
+/// Accepts a date (`2020-01-31`, local midnight), an RFC 3339 date and time
+/// (`2020-01-31T12:00:00+01:00`), or a duration ago, e.g. `30d`
+/// (see [`parse_duration`](crate::units::parse_duration)).
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::walker::time::parse_time;
+/// # use std::time::{Duration, SystemTime};
+/// let date = parse_time("2020-01-01").unwrap();
+/// let ago = parse_time("2w").unwrap();
+/// assert!(date < ago);
+/// assert!(ago < SystemTime::now() - Duration::from_secs(13 * 24 * 60 * 60));
+/// assert!(parse_time("yesterday").is_err());
+/// ```
+pub fn parse_time(s: &str) -> Result<SystemTime, Box<dyn std::error::Error>> {
+    use chrono::TimeZone;
+
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let midnight = chrono::Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .ok_or_else(|| format!("date '{}' doesn't exist in the local time zone", s))?;
+        return Ok(midnight.into());
+    }
+
+    if let Ok(date_time) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Ok(date_time.into());
+    }
+
+    let ago = crate::units::parse_duration(s)
+        .map_err(|_| format!("'{}' is neither a date nor a duration", s))?;
+    SystemTime::now()
+        .checked_sub(ago)
+        .ok_or_else(|| format!("'{}' is too long ago", s).into())
+}
@@ -0,0 +1,349 @@
+//! Running feature extraction in worker processes.
+//!
+//! Decoders run on whatever files they are given, and a crash in one of them
+//! would take the whole analysis down. With worker processes, only the worker
+//! dies: the file is reported as failed and a fresh worker takes its place.
+//! Workers are also replaced after [`FILES_PER_WORKER`] files, so that long
+//! runs don't suffer from memory fragmentation. With a timeout, a worker that
+//! takes too long on a file is killed and replaced as if it had crashed.
+//!
+//! # Protocol
+//!
+//! A worker is the succotash executable started with the `worker` subcommand,
+//! with `--tiles` if [`Tiles`](super::features::tiles::Tiles) are to be found
+//! `--no-trim` if [borders](super::features::border) are to be kept, and
+//! `--grayscale <CONVERSION>`, `--hash-threshold <THRESHOLD>` and
+//! `--features <LIST>` unless the default ones are used, see [`FindOptions`].
+//! Workers only know built-in [features](super::features::set).
+//! The pool writes paths to the stdin of a worker, each terminated by a NUL byte.
+//! For each path, the worker writes to its stdout a line `image <features>` for
+//! every image in the file (see [`img::ImgRaw::load_all`] and
+//! [`ImgFeatures::encode`]) followed by a line `done`, or a single line
+//! `error <message>` if the file couldn't be analyzed.
+
+use super::cancel::CancelToken;
+use super::features::{set::FeatureSet, FindOptions, ImgFeatures};
+use super::img;
+use super::index::checkpoint::Checkpoint;
+use super::metrics::METRICS;
+use super::progress::Tracker;
+use async_std::path::PathBuf;
+use std::io::{self, BufRead, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// How many files a worker analyzes before it is replaced.
+pub const FILES_PER_WORKER: usize = 1000;
+
+/// Features of all images in a file, or why they couldn't be found.
+pub type Outcome = Result<Vec<ImgFeatures>, String>;
+
+/// Bytes of a path as they are written to pipes and files.
+#[cfg(unix)]
+pub(super) fn path_to_bytes(path: &std::path::Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub(super) fn path_to_bytes(path: &std::path::Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// A path from bytes written by [`path_to_bytes`].
+#[cfg(unix)]
+pub(super) fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    std::ffi::OsString::from_vec(bytes).into()
+}
+
+#[cfg(not(unix))]
+pub(super) fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
+
+/// Serve requests of a pool on stdin/stdout until stdin is closed.
+///
+/// You can think of it as of `main` of the hidden `worker` subcommand.
+///
+/// # Arguments
+///
+/// * `options` - what to do when finding features.
+pub async fn serve(options: FindOptions) -> Result<(), Box<dyn std::error::Error>> {
+    use async_std::io::prelude::*;
+
+    let mut stdin = async_std::io::BufReader::new(async_std::io::stdin());
+    let mut stdout = async_std::io::stdout();
+
+    let mut request = Vec::new();
+    loop {
+        request.clear();
+        if stdin.read_until(b'\0', &mut request).await? == 0 {
+            return Ok(());
+        }
+        if request.last() == Some(&b'\0') {
+            request.pop();
+        }
+
+        let path = path_from_bytes(request.clone());
+        let exif = super::io::exif(path.as_ref()).await;
+        let response = match img::ImgRaw::load_all(path).await {
+            Ok(imgs_raw) => {
+                let mut response = String::new();
+                for img_raw in imgs_raw {
+                    let features = ImgFeatures {
+                        exif: exif.clone(),
+                        ..ImgFeatures::find_with(&img_raw, options)
+                    };
+                    response += &format!("image {}\n", features.encode());
+                }
+                response + "done\n"
+            }
+            Err(e) => format!("error {}\n", e.to_string().replace('\n', " ")),
+        };
+
+        stdout.write_all(response.as_bytes()).await?;
+        stdout.flush().await?;
+    }
+}
+
+/// Kills a worker that takes too long to answer a request.
+struct Watchdog {
+    stop: mpsc::Sender<()>,
+    /// Returns whether it has killed the worker.
+    thread: std::thread::JoinHandle<bool>,
+}
+
+impl Watchdog {
+    fn start(child: Arc<Mutex<std::process::Child>>, timeout: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = std::thread::spawn(move || match stopped.recv_timeout(timeout) {
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let _ = child.lock().unwrap().kill();
+                true
+            }
+            _ => false,
+        });
+        Self { stop, thread }
+    }
+
+    /// Stop watching, returns whether the worker was killed.
+    fn stop(self) -> bool {
+        let _ = self.stop.send(());
+        self.thread.join().unwrap_or(false)
+    }
+}
+
+/// A running worker process.
+struct Worker {
+    child: Arc<Mutex<std::process::Child>>,
+    stdin: std::process::ChildStdin,
+    stdout: io::BufReader<std::process::ChildStdout>,
+    /// How long the worker can take to answer a request, see [`Watchdog`].
+    timeout: Option<Duration>,
+    /// How many files the worker was asked to analyze.
+    served: usize,
+}
+
+impl Worker {
+    fn spawn(
+        program: &std::path::Path,
+        options: FindOptions,
+        timeout: Option<Duration>,
+    ) -> io::Result<Self> {
+        use std::process::{Command, Stdio};
+
+        let mut command = Command::new(program);
+        command.arg("worker");
+        if options.tiles {
+            command.arg("--tiles");
+        }
+        if !options.trim {
+            command.arg("--no-trim");
+        }
+        if options.grayscale != Default::default() {
+            command.args(["--grayscale", options.grayscale.name()]);
+        }
+        if options.threshold != Default::default() {
+            command.args(["--hash-threshold", options.threshold.name()]);
+        }
+        if options.features != FeatureSet::standard() {
+            command.args(["--features", &options.features.names().join(",")]);
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = io::BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        Ok(Self {
+            child: Arc::new(Mutex::new(child)),
+            stdin,
+            stdout,
+            timeout,
+            served: 0,
+        })
+    }
+
+    /// Ask the worker to analyze a file.
+    ///
+    /// Fails if the worker itself has failed, e.g. crashed or timed out.
+    fn request(&mut self, path: &std::path::Path) -> io::Result<Outcome> {
+        self.served += 1;
+        self.stdin.write_all(&path_to_bytes(path))?;
+        self.stdin.write_all(b"\0")?;
+        self.stdin.flush()?;
+
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return self.response(),
+        };
+        let watchdog = Watchdog::start(self.child.clone(), timeout);
+        let response = self.response();
+        match watchdog.stop() {
+            true => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("took longer than {:?}", timeout),
+            )),
+            false => response,
+        }
+    }
+
+    /// Read the answer to a request.
+    fn response(&mut self) -> io::Result<Outcome> {
+        let mut features = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "worker has exited",
+                ));
+            }
+
+            let line = line.trim_end_matches('\n');
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected response '{}'", line),
+                )
+            };
+            match line.split_once(' ') {
+                None if line == "done" => return Ok(Ok(features)),
+                Some(("image", encoded)) => {
+                    features.push(ImgFeatures::decode(encoded).ok_or_else(invalid)?)
+                }
+                Some(("error", message)) => return Ok(Err(message.to_owned())),
+                _ => return Err(invalid()),
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // The worker may be stuck, don't wait for it to notice the closed stdin.
+        let mut child = self.child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Analyze files in a pool of worker processes.
+///
+/// Workers run the current executable, which has to be succotash.
+///
+/// Returns the outcome for every path, in no particular order.
+///
+/// # Arguments
+///
+/// * `paths` - files to analyze.
+/// * `workers` - how many worker processes to run.
+/// * `timeout` - how long a worker can take on a file before it is killed.
+/// * `checkpoint` - where to save features to as soon as they are found.
+/// * `cancel` - stops sending more files to workers. Files that workers
+///   fail on once cancelled, e.g. because Ctrl+C has killed them too,
+///   are left out of the outcomes.
+/// * `progress` - advanced after every file.
+/// * `options` - what to do when finding features.
+pub async fn find_all(
+    paths: Vec<PathBuf>,
+    workers: usize,
+    timeout: Option<Duration>,
+    checkpoint: Option<Arc<Checkpoint>>,
+    cancel: CancelToken,
+    progress: Tracker,
+    options: FindOptions,
+) -> Result<Vec<(PathBuf, Outcome)>, Box<dyn std::error::Error>> {
+    let program = std::env::current_exe()?;
+    let outcomes = async_std::task::spawn_blocking(move || {
+        let spawn = || Worker::spawn(&program, options, timeout);
+        let checkpoint = checkpoint.as_deref();
+        run_pool(spawn, paths, workers, checkpoint, &cancel, &progress)
+    })
+    .await?;
+    Ok(outcomes)
+}
+
+fn run_pool(
+    spawn: impl Fn() -> io::Result<Worker> + Sync,
+    paths: Vec<PathBuf>,
+    workers: usize,
+    checkpoint: Option<&Checkpoint>,
+    cancel: &CancelToken,
+    progress: &Tracker,
+) -> io::Result<Vec<(PathBuf, Outcome)>> {
+    let queue = Mutex::new(paths.into_iter());
+    let outcomes = Mutex::new(Vec::new());
+
+    // One thread per worker, each feeding its worker from the shared queue.
+    let feed = || -> io::Result<()> {
+        let mut worker = spawn()?;
+        loop {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+            let path = match queue.lock().unwrap().next() {
+                Some(path) => path,
+                None => return Ok(()),
+            };
+
+            if worker.served >= FILES_PER_WORKER {
+                debug!("Replacing a worker that has served {} files", worker.served);
+                worker = spawn()?;
+            }
+
+            let started = std::time::Instant::now();
+            let outcome = match worker.request(path.as_ref()) {
+                Ok(outcome) => outcome,
+                Err(_) if cancel.is_cancelled() => return Ok(()),
+                Err(e) => {
+                    warn!(
+                        "Worker failed on '{}', replacing it: {}",
+                        path.to_string_lossy(),
+                        e
+                    );
+                    worker = spawn()?;
+                    Err(format!("worker failed: {}", e))
+                }
+            };
+            METRICS.file_time.observe(started.elapsed());
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.save(&path, &outcome);
+            }
+            progress.advance(1, Some(&path));
+            outcomes.lock().unwrap().push((path, outcome));
+        }
+    };
+
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..workers.max(1)).map(|_| scope.spawn(feed)).collect();
+        threads
+            .into_iter()
+            .try_for_each(|thread| thread.join().expect("worker thread panicked"))
+    })?;
+
+    Ok(outcomes.into_inner().unwrap())
+}
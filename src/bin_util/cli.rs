@@ -0,0 +1,1297 @@
+//! Arguments of subcommands, parsed into types of the library.
+//!
+//! A [`command`](super::command) declares its arguments, and a struct here
+//! is what they parse into, with values already of the library's types.
+//! It turns into options of the library merged with the
+//! [configuration](crate::config). Adding an argument means adding it to
+//! the declaration and to the struct of its subcommand.
+
+use crate::analyze::features::{grayscale::Grayscale, lshash::HashThreshold, set::FeatureSet};
+use crate::analyze::{self, index, walker, AnalyzeOptions};
+use crate::config::Config;
+use async_std::path::PathBuf;
+use std::error::Error;
+
+/// Value of an argument, only if it was given and not its default value.
+fn given<'a>(matches: &'a clap::ArgMatches, name: &str) -> Option<&'a str> {
+    match matches.occurrences_of(name) {
+        0 => None,
+        _ => matches.value_of(name),
+    }
+}
+
+/// Parse the value of an argument, if there is one.
+fn parse<T>(matches: &clap::ArgMatches, name: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: std::str::FromStr,
+    T::Err: Into<Box<dyn Error>>,
+{
+    matches
+        .value_of(name)
+        .map(str::parse)
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Compile glob patterns given as values of an argument.
+fn get_globs(
+    matches: &clap::ArgMatches,
+    name: &str,
+) -> Result<Vec<walker::glob::Pattern>, walker::glob::Error> {
+    matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(walker::glob::Pattern::new)
+        .collect()
+}
+
+/// Collect values of an argument.
+fn get_strings(matches: &clap::ArgMatches, name: &str) -> Vec<String> {
+    matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parse a point in time given as the value of an argument.
+fn get_time(
+    matches: &clap::ArgMatches,
+    name: &str,
+) -> Result<Option<std::time::SystemTime>, Box<dyn Error>> {
+    matches
+        .value_of(name)
+        .map(walker::time::parse_time)
+        .transpose()
+}
+
+/// Arguments that set how features are found.
+pub struct FindArgs {
+    /// `--tiles`, only `worker` has it.
+    pub tiles: bool,
+    /// Not `--no-trim`.
+    pub trim: bool,
+    /// `--grayscale`.
+    pub grayscale: Grayscale,
+    /// `--hash-threshold`, if given.
+    pub threshold: Option<HashThreshold>,
+    /// `--features`, if given.
+    pub features: Option<&'static FeatureSet>,
+}
+
+impl FindArgs {
+    /// Turn arguments into types.
+    ///
+    /// Defaults of `--hash-threshold` and `--features` are left out,
+    /// so that the configuration can set them.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        // The feature can only be picked once its model is there.
+        #[cfg(feature = "embedding")]
+        if let Some(model) = matches.value_of("embedding-model") {
+            analyze::features::embedding::register(model)?;
+        }
+        Ok(Self {
+            tiles: matches.is_present("tiles"),
+            trim: !matches.is_present("no-trim"),
+            grayscale: parse(matches, "grayscale")?.unwrap_or_default(),
+            threshold: given(matches, "hash-threshold")
+                .map(str::parse)
+                .transpose()?,
+            features: given(matches, "features")
+                .map(FeatureSet::parse)
+                .transpose()?
+                .map(FeatureSet::leak),
+        })
+    }
+
+    /// Options of finding features, arguments override the configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::{bin_util::{self, cli::FindArgs}, config::Config};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "hash", "--hash-threshold", "median", "a.png"]);
+    /// let args = FindArgs::from_matches(matches.subcommand_matches("hash").unwrap()).unwrap();
+    /// let config = Config {
+    ///     features: Some(vec!["lshash".into(), "dhash".into()]),
+    ///     ..Default::default()
+    /// };
+    /// let options = args.options(&config).unwrap();
+    /// assert!(options.trim);
+    /// assert_eq!(options.threshold.name(), "median");
+    /// assert_eq!(options.features.names(), vec!["lshash", "dhash"]);
+    /// ```
+    pub fn options(
+        &self,
+        config: &Config,
+    ) -> Result<analyze::features::FindOptions, Box<dyn Error>> {
+        let configured = config.find_options()?;
+        Ok(analyze::features::FindOptions {
+            tiles: self.tiles,
+            trim: self.trim,
+            grayscale: self.grayscale,
+            threshold: self.threshold.unwrap_or(configured.threshold),
+            features: self.features.unwrap_or(configured.features),
+        })
+    }
+
+    /// Put options of finding features into options of the analysis.
+    fn apply(&self, config: &Config, options: &mut AnalyzeOptions) -> Result<(), Box<dyn Error>> {
+        let find = self.options(config)?;
+        options.trim = find.trim;
+        options.grayscale = find.grayscale;
+        options.threshold = find.threshold;
+        options.features = find.features;
+        Ok(())
+    }
+}
+
+/// Arguments that set where features are kept besides the index.
+pub struct StorageArgs {
+    /// `--write-sidecars` or `--read-sidecars`.
+    pub sidecars: index::sidecar::Mode,
+    /// `--xattrs`.
+    #[cfg(feature = "xattrs")]
+    pub xattrs: bool,
+}
+
+impl StorageArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            sidecars: if matches.is_present("write-sidecars") {
+                index::sidecar::Mode::Write
+            } else if matches.is_present("read-sidecars") {
+                index::sidecar::Mode::Read
+            } else {
+                index::sidecar::Mode::Ignore
+            },
+            #[cfg(feature = "xattrs")]
+            xattrs: matches.is_present("xattrs"),
+        }
+    }
+
+    /// Put where features are kept into options of the analysis.
+    fn apply(&self, options: &mut AnalyzeOptions) {
+        options.sidecars = self.sidecars;
+        #[cfg(feature = "xattrs")]
+        {
+            options.xattrs = self.xattrs;
+        }
+    }
+}
+
+/// Turn `--search` and `--bands` into a [`index::Search`].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::{analyze::index::Search, bin_util::{self, cli::QueryArgs}};
+/// let matches = bin_util::app().get_matches_from(
+///     ["succotash", "index", "query", "--search", "bands", "--bands", "12", "a.png", "index"],
+/// );
+/// let matches = matches.subcommand_matches("index").unwrap();
+/// let args = QueryArgs::from_matches(matches.subcommand_matches("query").unwrap()).unwrap();
+/// assert_eq!(args.search, Search::Bands(12));
+/// ```
+fn search(matches: &clap::ArgMatches) -> Result<index::Search, Box<dyn Error>> {
+    let search = parse(matches, "search")?;
+    let bands = parse(matches, "bands")?;
+    Ok(match (search, bands) {
+        (Some(index::Search::Bands(_)), Some(count)) => index::Search::Bands(count),
+        (search, _) => search.unwrap_or_default(),
+    })
+}
+
+/// `--keep-weight`, if given.
+fn keep_weights(matches: &clap::ArgMatches) -> Result<Option<analyze::keep::Criteria>, String> {
+    matches
+        .values_of("keep-weight")
+        .map(analyze::keep::Criteria::parse)
+        .transpose()
+}
+
+/// `--prefer` and `--deprioritize`.
+fn priorities(
+    matches: &clap::ArgMatches,
+) -> Result<analyze::keep::Priorities, walker::glob::Error> {
+    Ok(analyze::keep::Priorities {
+        prefer: get_globs(matches, "prefer")?,
+        deprioritize: get_globs(matches, "deprioritize")?,
+    })
+}
+
+/// Criteria of picking the file to keep, `--keep-weight` overrides the configuration.
+fn keep(
+    given: Option<analyze::keep::Criteria>,
+    config: &Config,
+) -> Result<analyze::keep::Criteria, Box<dyn Error>> {
+    match (given, &config.keep) {
+        (Some(keep), _) => Ok(keep),
+        (None, Some(pairs)) => analyze::keep::Criteria::parse(pairs.iter().map(String::as_str))
+            .map_err(|e| format!("invalid 'keep' in the configuration: {}", e).into()),
+        (None, None) => Ok(Default::default()),
+    }
+}
+
+/// `--journal`, or a new [journal](crate::journal) where it is by default.
+fn journal(matches: &clap::ArgMatches) -> Option<std::path::PathBuf> {
+    match matches.value_of("journal") {
+        Some(journal) => Some(journal.into()),
+        None => crate::config::default_journal_path(),
+    }
+}
+
+/// Arguments of `analyze`.
+pub struct AnalyzeArgs {
+    /// `DIR`s or `--files-from`.
+    pub source: walker::Source,
+    /// What files to pick, `--exclude` adds to patterns in the configuration.
+    pub walk: walker::WalkOptions,
+    /// `--preset`, if given.
+    pub preset: Option<analyze::preset::Preset>,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--search` and `--bands`.
+    pub search: index::Search,
+    /// `--weight`.
+    pub weights: analyze::score::Weights,
+    /// `--keep-weight`, if given.
+    pub keep: Option<analyze::keep::Criteria>,
+    /// `--prefer` and `--deprioritize`.
+    pub priorities: analyze::keep::Priorities,
+    /// `--explain`.
+    pub explain: bool,
+    /// `--partial`.
+    pub partial: bool,
+    /// `--filter`.
+    pub filters: analyze::filter::Filters,
+    /// `--burst-window`, if `--bursts` is given.
+    pub bursts: Option<std::time::Duration>,
+    /// How features are found.
+    pub find: FindArgs,
+    /// `--verify`, if given.
+    pub verify: Option<analyze::verify::Method>,
+    /// `--verify-cutoff`, if given.
+    pub verify_cutoff: Option<f64>,
+    /// `--format`.
+    pub format: analyze::output::Format,
+    /// `--output`, if given.
+    pub output: Option<PathBuf>,
+    /// `--export-matrix`, `--matrix-format` and `--matrix-max-distance`, if the first is given.
+    pub matrix: Option<analyze::output::matrix::Export>,
+    /// `--export-graph`, if given.
+    pub graph: Option<PathBuf>,
+    /// `--emit-delete-list`, or [`STDOUT`](analyze::plan::STDOUT) with `--print0`.
+    pub delete_list: Option<PathBuf>,
+    /// `--action`, if given.
+    pub action: Option<analyze::action::Action>,
+    /// `--use-trash`.
+    pub use_trash: bool,
+    /// `--journal`, a new journal by default.
+    pub journal: Option<std::path::PathBuf>,
+    /// `--workers`, 0 if not given.
+    pub workers: usize,
+    /// `--threads`, if given.
+    pub threads: Option<usize>,
+    /// `--max-memory`, if given.
+    pub max_memory: Option<u64>,
+    /// `--max-file-size`, if given.
+    pub max_file_size: Option<u64>,
+    /// `--decode-timeout`, if given.
+    pub decode_timeout: Option<std::time::Duration>,
+    /// `--index`, if given.
+    pub index: Option<PathBuf>,
+    /// `--resume`.
+    pub resume: bool,
+    /// `--retry-quarantined`.
+    pub retry_quarantined: bool,
+    /// `--progress`.
+    pub progress: analyze::progress::Format,
+    /// Where features are kept besides the index.
+    pub storage: StorageArgs,
+}
+
+impl AnalyzeArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            source: match matches.values_of("DIR") {
+                Some(dirs) => walker::Source::Walk(dirs.map(Into::into).collect()),
+                None => walker::Source::List(matches.value_of("files-from").unwrap().into()),
+            },
+            walk: walker::WalkOptions {
+                include: get_globs(matches, "include")?,
+                exclude: get_globs(matches, "exclude")?,
+                time: walker::time::TimeFilter {
+                    newer_than: get_time(matches, "newer-than")?,
+                    older_than: get_time(matches, "older-than")?,
+                    source: parse(matches, "time-source")?.unwrap_or_default(),
+                },
+                size: walker::size::SizeFilter {
+                    min_size: matches
+                        .value_of("min-size")
+                        .map(crate::units::parse_size)
+                        .transpose()?,
+                    max_size: matches
+                        .value_of("max-size")
+                        .map(crate::units::parse_size)
+                        .transpose()?,
+                    min_dimensions: matches
+                        .value_of("min-dimensions")
+                        .map(crate::units::parse_dimensions)
+                        .transpose()?,
+                },
+                attrs: walker::attrs::AttrFilter {
+                    xattrs: get_strings(matches, "exclude-xattr"),
+                    tags: get_strings(matches, "exclude-tag"),
+                },
+                symlinks: if matches.is_present("follow-symlinks") {
+                    walker::links::LinkPolicy::Follow
+                } else {
+                    walker::links::LinkPolicy::Skip
+                },
+                junctions: parse(matches, "junctions")?.unwrap_or_default(),
+                aliases: parse(matches, "aliases")?.unwrap_or_default(),
+                gitignore: matches.is_present("gitignore"),
+                #[cfg(feature = "archives")]
+                archives: matches.is_present("archives"),
+            },
+            // The preset has a default value, only take it if it was given.
+            preset: given(matches, "preset").map(str::parse).transpose()?,
+            max_distance: parse(matches, "max-distance")?,
+            search: search(matches)?,
+            weights: analyze::score::Weights::parse(
+                matches.values_of("weight").into_iter().flatten(),
+            )?,
+            keep: keep_weights(matches)?,
+            priorities: priorities(matches)?,
+            explain: matches.is_present("explain"),
+            partial: matches.is_present("partial"),
+            filters: analyze::filter::Filters::parse(
+                matches.values_of("filter").into_iter().flatten(),
+            )?,
+            bursts: match matches.is_present("bursts") {
+                true => matches
+                    .value_of("burst-window")
+                    .map(crate::units::parse_duration)
+                    .transpose()?,
+                false => None,
+            },
+            find: FindArgs::from_matches(matches)?,
+            verify: parse(matches, "verify")?,
+            verify_cutoff: parse(matches, "verify-cutoff")?,
+            format: parse(matches, "format")?.unwrap_or_default(),
+            output: matches.value_of("output").map(Into::into),
+            matrix: match matches.value_of("export-matrix") {
+                Some(path) => Some(analyze::output::matrix::Export {
+                    path: path.into(),
+                    format: parse(matches, "matrix-format")?.unwrap_or_default(),
+                    max_distance: parse(matches, "matrix-max-distance")?,
+                }),
+                None => None,
+            },
+            graph: matches.value_of("export-graph").map(Into::into),
+            delete_list: match matches.is_present("print0") {
+                true => Some(analyze::plan::STDOUT.into()),
+                false => matches.value_of("emit-delete-list").map(Into::into),
+            },
+            action: parse(matches, "action")?,
+            use_trash: matches.is_present("use-trash"),
+            journal: journal(matches),
+            workers: parse(matches, "workers")?.unwrap_or(0),
+            threads: parse(matches, "threads")?,
+            max_memory: matches
+                .value_of("max-memory")
+                .map(crate::units::parse_size)
+                .transpose()?,
+            max_file_size: matches
+                .value_of("max-file-size")
+                .map(crate::units::parse_size)
+                .transpose()?,
+            decode_timeout: matches
+                .value_of("decode-timeout")
+                .map(crate::units::parse_duration)
+                .transpose()?,
+            index: matches.value_of("index").map(Into::into),
+            resume: matches.is_present("resume"),
+            retry_quarantined: matches.is_present("retry-quarantined"),
+            progress: parse(matches, "progress")?.unwrap_or_default(),
+            storage: StorageArgs::from_matches(matches),
+        })
+    }
+
+    /// The library whose configuration is used, the first directory.
+    pub fn library(&self) -> Option<&std::path::Path> {
+        match &self.source {
+            walker::Source::Walk(dirs) => dirs.first().map(AsRef::as_ref),
+            walker::Source::List(_) => None,
+        }
+    }
+
+    /// Options of the analysis, arguments override the configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::{bin_util::{self, cli::AnalyzeArgs}, config::Config};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "analyze", "--exclude", "thumbnails", "pictures"]);
+    /// let args = AnalyzeArgs::from_matches(matches.subcommand_matches("analyze").unwrap()).unwrap();
+    /// let config = Config {
+    ///     exclude: vec!["*.tmp".into()],
+    ///     threads: Some(2),
+    ///     ..Default::default()
+    /// };
+    /// let options = args.options(&config).unwrap();
+    /// assert_eq!(options.walk.exclude.len(), 2);
+    /// assert_eq!(options.threads, 2);
+    /// ```
+    pub fn options(self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        let preset = self.preset.unwrap_or(config.preset).options();
+        // Patterns on the command line add to those in the configuration.
+        let mut exclude: Vec<walker::glob::Pattern> = config
+            .exclude
+            .iter()
+            .map(String::as_str)
+            .map(walker::glob::Pattern::new)
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("invalid 'exclude' in the configuration: {}", e))?;
+        exclude.extend(self.walk.exclude);
+
+        let mut options = AnalyzeOptions {
+            walk: walker::WalkOptions {
+                exclude,
+                ..self.walk
+            },
+            workers: self.workers,
+            threads: self.threads.or(config.threads).unwrap_or(0),
+            max_memory: self.max_memory,
+            max_file_size: self.max_file_size,
+            decode_timeout: self.decode_timeout,
+            index: self.index.or_else(|| config.index.clone().map(Into::into)),
+            resume: self.resume,
+            retry_quarantined: self.retry_quarantined,
+            max_distance: self.max_distance.unwrap_or(preset.max_distance),
+            partial: self.partial,
+            filters: self.filters,
+            bursts: self.bursts,
+            weights: self.weights,
+            keep: keep(self.keep, config)?,
+            priorities: self.priorities,
+            search: self.search,
+            explain: self.explain,
+            verify: self.verify.or(preset.verify),
+            verify_cutoff: self.verify_cutoff,
+            format: self.format,
+            output: self.output,
+            matrix: self.matrix,
+            graph: self.graph,
+            delete_list: self.delete_list,
+            action: self.action,
+            action_options: analyze::action::ActionOptions {
+                trash: self.use_trash,
+                journal: self.journal,
+            },
+            progress: self.progress.progress(),
+            ..Default::default()
+        };
+        self.find.apply(config, &mut options)?;
+        self.storage.apply(&mut options);
+        Ok(options)
+    }
+}
+
+/// Arguments of `hash`.
+pub struct HashArgs {
+    /// `FILE`s.
+    pub files: Vec<PathBuf>,
+    /// `--output`, if given.
+    pub output: Option<PathBuf>,
+    /// How features are found.
+    pub find: FindArgs,
+}
+
+impl HashArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            files: matches.values_of("FILE").unwrap().map(Into::into).collect(),
+            output: matches.value_of("output").map(Into::into),
+            find: FindArgs::from_matches(matches)?,
+        })
+    }
+}
+
+/// Arguments of `verify`.
+pub struct VerifyArgs {
+    /// `DIR`s.
+    pub dirs: Vec<String>,
+    /// `--format`.
+    pub format: crate::integrity::Format,
+    /// `--output`, if given.
+    pub output: Option<PathBuf>,
+    /// `--threads`, if given.
+    pub threads: Option<usize>,
+    /// `--progress`.
+    pub progress: analyze::progress::Format,
+}
+
+impl VerifyArgs {
+    /// Turn arguments into types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::{self, cli::VerifyArgs};
+    /// # use libsuccotash::integrity::Format;
+    /// let matches = bin_util::app().get_matches_from(["succotash", "verify", "--format", "json", "/mnt/nas"]);
+    /// let args = VerifyArgs::from_matches(matches.subcommand_matches("verify").unwrap()).unwrap();
+    /// assert_eq!(args.format, Format::Json);
+    /// assert_eq!(args.dirs, vec!["/mnt/nas"]);
+    /// ```
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            dirs: matches.values_of("DIR").unwrap().map(Into::into).collect(),
+            format: parse(matches, "format")?.unwrap_or_default(),
+            output: matches.value_of("output").map(Into::into),
+            threads: parse(matches, "threads")?,
+            progress: parse(matches, "progress")?.unwrap_or_default(),
+        })
+    }
+
+    /// The library whose configuration is used, the first directory.
+    pub fn library(&self) -> Option<&std::path::Path> {
+        self.dirs.first().map(AsRef::as_ref)
+    }
+
+    /// Options of picking and decoding files, arguments override the configuration.
+    pub fn options(&self, config: &Config) -> AnalyzeOptions {
+        AnalyzeOptions {
+            threads: self.threads.or(config.threads).unwrap_or(0),
+            progress: self.progress.progress(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Arguments of `compare`.
+pub struct CompareArgs {
+    /// `A`.
+    pub a: PathBuf,
+    /// `B`.
+    pub b: PathBuf,
+    /// `--diff`, if given.
+    pub diff: Option<PathBuf>,
+}
+
+impl CompareArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            a: matches.value_of("A").unwrap().into(),
+            b: matches.value_of("B").unwrap().into(),
+            diff: matches.value_of("diff").map(Into::into),
+        }
+    }
+}
+
+/// Arguments of `stats`, `organize`, `montage` and `index build`.
+///
+/// Features in the index are reused, see [`stats`](crate::stats).
+pub struct StatsArgs {
+    /// `DIR`s.
+    pub dirs: Vec<String>,
+    /// `--index` of `stats`, or `--output` of `index build`, if given.
+    pub index: Option<PathBuf>,
+    /// `--output` of `stats`, if given.
+    pub output: Option<PathBuf>,
+    /// `--threads`, if given.
+    pub threads: Option<usize>,
+    /// `--progress`.
+    pub progress: analyze::progress::Format,
+    /// How features are found.
+    pub find: FindArgs,
+    /// Where features are kept besides the index.
+    pub storage: StorageArgs,
+}
+
+impl StatsArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            dirs: matches.values_of("DIR").unwrap().map(Into::into).collect(),
+            index: matches.value_of("index").map(Into::into),
+            output: matches.value_of("output").map(Into::into),
+            threads: parse(matches, "threads")?,
+            progress: parse(matches, "progress")?.unwrap_or_default(),
+            find: FindArgs::from_matches(matches)?,
+            storage: StorageArgs::from_matches(matches),
+        })
+    }
+
+    /// The library whose configuration is used, the first directory.
+    pub fn library(&self) -> Option<&std::path::Path> {
+        self.dirs.first().map(AsRef::as_ref)
+    }
+
+    /// Options of finding features, arguments override the configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::{self, cli::StatsArgs};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "stats", "--index", "photos.index", "pictures"]);
+    /// let args = StatsArgs::from_matches(matches.subcommand_matches("stats").unwrap()).unwrap();
+    /// let options = args.options(&Default::default()).unwrap();
+    /// assert!(options.resume);
+    /// ```
+    pub fn options(&self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        let index: Option<PathBuf> = self
+            .index
+            .clone()
+            .or_else(|| config.index.clone().map(Into::into));
+        let mut options = AnalyzeOptions {
+            threads: self.threads.or(config.threads).unwrap_or(0),
+            resume: index.is_some(),
+            index,
+            progress: self.progress.progress(),
+            ..Default::default()
+        };
+        self.find.apply(config, &mut options)?;
+        self.storage.apply(&mut options);
+        Ok(options)
+    }
+}
+
+/// Arguments of `organize`.
+pub struct OrganizeArgs {
+    /// `DIR`s and how features are found, the same as of `stats`.
+    pub library: StatsArgs,
+    /// `--into`.
+    pub into: PathBuf,
+    /// `--by`, `--symlink`, `--dry-run` and `--journal`.
+    pub organize: crate::organize::OrganizeOptions,
+}
+
+impl OrganizeArgs {
+    /// Turn arguments into types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::{self, cli::OrganizeArgs};
+    /// # use libsuccotash::organize::{Action, By};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "organize", "--by", "hue", "--symlink", "--into", "sorted", "pictures"]);
+    /// let args = OrganizeArgs::from_matches(matches.subcommand_matches("organize").unwrap()).unwrap();
+    /// assert_eq!(args.organize.by, By::Hue);
+    /// assert_eq!(args.organize.action, Action::Symlink);
+    /// assert_eq!(args.library.dirs, vec!["pictures"]);
+    /// ```
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        let action = if matches.is_present("symlink") {
+            crate::organize::Action::Symlink
+        } else {
+            crate::organize::Action::Move
+        };
+        Ok(Self {
+            library: StatsArgs::from_matches(matches)?,
+            into: matches.value_of("into").unwrap().into(),
+            organize: crate::organize::OrganizeOptions {
+                by: parse(matches, "by")?.unwrap_or_default(),
+                action,
+                dry_run: matches.is_present("dry-run"),
+                journal: journal(matches),
+            },
+        })
+    }
+}
+
+/// Arguments of `import`.
+pub struct ImportArgs {
+    /// `SRC`.
+    pub src: String,
+    /// `LIBRARY`.
+    pub library: String,
+    /// `--index`, if given.
+    pub index: Option<PathBuf>,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--search` and `--bands`.
+    pub search: index::Search,
+    /// `--rename-by-date`, `--dry-run`, `--summary` and `--journal`.
+    pub import: crate::import::ImportOptions,
+    /// `--threads`, if given.
+    pub threads: Option<usize>,
+    /// `--progress`.
+    pub progress: analyze::progress::Format,
+    /// How features are found.
+    pub find: FindArgs,
+    /// Where features are kept besides the index.
+    pub storage: StorageArgs,
+}
+
+impl ImportArgs {
+    /// Turn arguments into types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::{self, cli::ImportArgs};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "import", "--rename-by-date", "/media/sdcard", "pictures"]);
+    /// let args = ImportArgs::from_matches(matches.subcommand_matches("import").unwrap()).unwrap();
+    /// assert_eq!((args.src.as_str(), args.library.as_str()), ("/media/sdcard", "pictures"));
+    /// assert!(args.import.rename);
+    /// assert!(!args.import.dry_run);
+    /// ```
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            src: matches.value_of("SRC").unwrap().to_owned(),
+            library: matches.value_of("LIBRARY").unwrap().to_owned(),
+            index: matches.value_of("index").map(Into::into),
+            max_distance: parse(matches, "max-distance")?,
+            search: search(matches)?,
+            import: crate::import::ImportOptions {
+                rename: matches.is_present("rename-by-date"),
+                dry_run: matches.is_present("dry-run"),
+                summary: matches.value_of("summary").map(Into::into),
+                journal: journal(matches),
+            },
+            threads: parse(matches, "threads")?,
+            progress: parse(matches, "progress")?.unwrap_or_default(),
+            find: FindArgs::from_matches(matches)?,
+            storage: StorageArgs::from_matches(matches),
+        })
+    }
+
+    /// Options of finding and comparing features, arguments override the configuration.
+    pub fn options(&self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        let index: Option<PathBuf> = self
+            .index
+            .clone()
+            .or_else(|| config.index.clone().map(Into::into));
+        let mut options = AnalyzeOptions {
+            max_distance: self.max_distance.unwrap_or(analyze::group::MAX_DISTANCE),
+            search: self.search,
+            threads: self.threads.or(config.threads).unwrap_or(0),
+            resume: index.is_some(),
+            index,
+            progress: self.progress.progress(),
+            ..Default::default()
+        };
+        self.find.apply(config, &mut options)?;
+        self.storage.apply(&mut options);
+        Ok(options)
+    }
+}
+
+/// Arguments of `undo`.
+pub struct UndoArgs {
+    /// `JOURNAL`.
+    pub journal: std::path::PathBuf,
+}
+
+impl UndoArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            journal: matches.value_of("JOURNAL").unwrap().into(),
+        }
+    }
+}
+
+/// Arguments of `montage`.
+pub struct MontageArgs {
+    /// `DIR`s and how features are found, the same as of `stats`.
+    pub library: StatsArgs,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--search` and `--bands`.
+    pub search: index::Search,
+    /// `--by`, `--output-dir` and `--tile-size`.
+    pub montage: crate::montage::MontageOptions,
+}
+
+impl MontageArgs {
+    /// Turn arguments into types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::{self, cli::MontageArgs};
+    /// # use libsuccotash::montage::By;
+    /// let matches = bin_util::app().get_matches_from(["succotash", "montage", "--by", "hue", "--tile-size", "96", "--output-dir", "sheets", "pictures"]);
+    /// let args = MontageArgs::from_matches(matches.subcommand_matches("montage").unwrap()).unwrap();
+    /// assert_eq!(args.montage.by, By::Hue);
+    /// assert_eq!(args.montage.tile_size, 96);
+    /// assert_eq!(args.montage.output_dir, async_std::path::PathBuf::from("sheets"));
+    /// ```
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        let tile_size = parse(matches, "tile-size")?.unwrap_or(crate::montage::TILE_SIZE);
+        if tile_size == 0 {
+            return Err("--tile-size must be at least 1".into());
+        }
+        Ok(Self {
+            library: StatsArgs::from_matches(matches)?,
+            max_distance: parse(matches, "max-distance")?,
+            search: search(matches)?,
+            montage: crate::montage::MontageOptions {
+                by: parse(matches, "by")?.unwrap_or_default(),
+                output_dir: matches.value_of("output-dir").unwrap().into(),
+                tile_size,
+            },
+        })
+    }
+
+    /// Options of finding features and grouping images, arguments override the configuration.
+    pub fn options(&self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        Ok(AnalyzeOptions {
+            max_distance: self.max_distance.unwrap_or(analyze::group::MAX_DISTANCE),
+            search: self.search,
+            ..self.library.options(config)?
+        })
+    }
+}
+
+/// Arguments of `index query`.
+pub struct QueryArgs {
+    /// `IMAGE`.
+    pub image: PathBuf,
+    /// `INDEX`.
+    pub index: String,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--output`, if given.
+    pub output: Option<PathBuf>,
+    /// `--search` and `--bands`.
+    pub search: index::Search,
+    /// How features are found.
+    pub find: FindArgs,
+}
+
+impl QueryArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Self::with_index(matches, matches.value_of("INDEX").unwrap())
+    }
+
+    /// Turn arguments into types, with the index given otherwise, e.g. of `db search`.
+    pub fn with_index(matches: &clap::ArgMatches, index: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            image: matches.value_of("IMAGE").unwrap().into(),
+            index: index.to_owned(),
+            max_distance: parse(matches, "max-distance")?,
+            output: matches.value_of("output").map(Into::into),
+            search: search(matches)?,
+            find: FindArgs::from_matches(matches)?,
+        })
+    }
+
+    /// Options of the query, arguments override the configuration.
+    pub fn options(&self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        let mut options = AnalyzeOptions {
+            max_distance: self.max_distance.unwrap_or(analyze::group::MAX_DISTANCE),
+            search: self.search,
+            ..Default::default()
+        };
+        self.find.apply(config, &mut options)?;
+        Ok(options)
+    }
+}
+
+/// Arguments of `serve`.
+pub struct ServeArgs {
+    /// `--listen`.
+    pub listen: String,
+    /// `--index`, if given.
+    pub index: Option<String>,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--search` and `--bands`.
+    pub search: index::Search,
+    /// `--emit-delete-list`, if given.
+    pub delete_list: Option<PathBuf>,
+    /// `--keep-weight`, if given.
+    pub keep: Option<analyze::keep::Criteria>,
+    /// `--prefer` and `--deprioritize`.
+    pub priorities: analyze::keep::Priorities,
+    /// How features are found.
+    pub find: FindArgs,
+}
+
+impl ServeArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            listen: matches.value_of("listen").unwrap().to_owned(),
+            index: matches.value_of("index").map(Into::into),
+            max_distance: parse(matches, "max-distance")?,
+            search: search(matches)?,
+            delete_list: matches.value_of("emit-delete-list").map(Into::into),
+            keep: keep_weights(matches)?,
+            priorities: priorities(matches)?,
+            find: FindArgs::from_matches(matches)?,
+        })
+    }
+
+    /// The index to serve, the configured one if not given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::{bin_util::{self, cli::ServeArgs}, config::Config};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "serve"]);
+    /// let args = ServeArgs::from_matches(matches.subcommand_matches("serve").unwrap()).unwrap();
+    /// assert_eq!(args.listen, "127.0.0.1:8080");
+    /// assert!(args.index(&Config::default()).is_err());
+    /// let config = Config {
+    ///     index: Some("photos.index".into()),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(args.index(&config).unwrap(), "photos.index");
+    /// ```
+    pub fn index(&self, config: &Config) -> Result<String, Box<dyn Error>> {
+        match (&self.index, &config.index) {
+            (Some(index), _) => Ok(index.clone()),
+            (None, Some(index)) => Ok(index.to_string_lossy().into_owned()),
+            (None, None) => Err("there is no index to serve, give one with --index".into()),
+        }
+    }
+
+    /// Options of queries, arguments override the configuration.
+    pub fn options(&self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        let mut options = AnalyzeOptions {
+            max_distance: self.max_distance.unwrap_or(analyze::group::MAX_DISTANCE),
+            search: self.search,
+            delete_list: self.delete_list.clone(),
+            keep: keep(self.keep.clone(), config)?,
+            priorities: self.priorities.clone(),
+            ..Default::default()
+        };
+        self.find.apply(config, &mut options)?;
+        Ok(options)
+    }
+}
+
+/// Arguments of `daemon`.
+#[cfg(unix)]
+pub struct DaemonArgs {
+    /// `--socket`, if given.
+    pub socket: Option<PathBuf>,
+    /// `--metrics`, if given.
+    pub metrics: Option<String>,
+    /// `--index`, if given.
+    pub index: Option<String>,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--search` and `--bands`.
+    pub search: index::Search,
+    /// How features are found.
+    pub find: FindArgs,
+}
+
+#[cfg(unix)]
+impl DaemonArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            socket: matches.value_of("socket").map(Into::into),
+            metrics: matches.value_of("metrics").map(Into::into),
+            index: matches.value_of("index").map(Into::into),
+            max_distance: parse(matches, "max-distance")?,
+            search: search(matches)?,
+            find: FindArgs::from_matches(matches)?,
+        })
+    }
+
+    /// The socket to listen on, the default one if not given.
+    pub fn socket(&self) -> PathBuf {
+        self.socket
+            .clone()
+            .unwrap_or_else(crate::daemon::default_socket)
+    }
+
+    /// The index to keep in memory, the configured one if not given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::{bin_util::{self, cli::DaemonArgs}, config::Config};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "daemon", "--index", "photos.index"]);
+    /// let args = DaemonArgs::from_matches(matches.subcommand_matches("daemon").unwrap()).unwrap();
+    /// assert_eq!(args.index(&Config::default()).unwrap(), "photos.index");
+    /// assert_eq!(args.socket(), libsuccotash::daemon::default_socket());
+    /// ```
+    pub fn index(&self, config: &Config) -> Result<String, Box<dyn Error>> {
+        match (&self.index, &config.index) {
+            (Some(index), _) => Ok(index.clone()),
+            (None, Some(index)) => Ok(index.to_string_lossy().into_owned()),
+            (None, None) => Err("there is no index to keep, give one with --index".into()),
+        }
+    }
+
+    /// Options of queries, arguments override the configuration.
+    pub fn options(&self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        let mut options = AnalyzeOptions {
+            max_distance: self.max_distance.unwrap_or(analyze::group::MAX_DISTANCE),
+            search: self.search,
+            ..Default::default()
+        };
+        self.find.apply(config, &mut options)?;
+        Ok(options)
+    }
+}
+
+/// Arguments of `client query`.
+#[cfg(unix)]
+pub struct ClientQueryArgs {
+    /// `IMAGE`.
+    pub image: PathBuf,
+    /// `--socket`, if given.
+    pub socket: Option<PathBuf>,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--output`, if given.
+    pub output: Option<PathBuf>,
+}
+
+#[cfg(unix)]
+impl ClientQueryArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            image: matches.value_of("IMAGE").unwrap().into(),
+            socket: matches.value_of("socket").map(Into::into),
+            max_distance: parse(matches, "max-distance")?,
+            output: matches.value_of("output").map(Into::into),
+        })
+    }
+
+    /// The socket of the daemon, the default one if not given.
+    pub fn socket(&self) -> PathBuf {
+        self.socket
+            .clone()
+            .unwrap_or_else(crate::daemon::default_socket)
+    }
+}
+
+/// Arguments of `config show`.
+pub struct ConfigShowArgs {
+    /// `DIR`, if given.
+    pub library: Option<std::path::PathBuf>,
+    /// `--effective`.
+    pub effective: bool,
+}
+
+impl ConfigShowArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            library: matches.value_of("DIR").map(Into::into),
+            effective: matches.is_present("effective"),
+        }
+    }
+}
+
+/// Arguments of `init`.
+pub struct InitArgs {
+    /// `CONFIG`, if given.
+    pub config: Option<std::path::PathBuf>,
+}
+
+impl InitArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            config: matches.value_of("CONFIG").map(Into::into),
+        }
+    }
+}
+
+/// Arguments of `index compact`.
+pub struct IndexCompactArgs {
+    /// `INDEX`.
+    pub index: String,
+}
+
+impl IndexCompactArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Self {
+        Self {
+            index: matches.value_of("INDEX").unwrap().to_owned(),
+        }
+    }
+}
+
+/// Arguments of `db add`.
+pub struct DbAddArgs {
+    /// `DB`.
+    pub db: PathBuf,
+    /// `DIR`s and how features are found, the same as of `stats`.
+    pub library: StatsArgs,
+}
+
+impl DbAddArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            db: matches.value_of("DB").unwrap().into(),
+            library: StatsArgs::from_matches(matches)?,
+        })
+    }
+}
+
+/// Arguments of `db remove`.
+pub struct DbRemoveArgs {
+    /// `DB`.
+    pub db: PathBuf,
+    /// `GLOB`s.
+    pub patterns: Vec<walker::glob::Pattern>,
+}
+
+impl DbRemoveArgs {
+    /// Turn arguments into types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::{self, cli::DbRemoveArgs};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "db", "remove", "photos.db", "**/Trash/**", "*.gif"]);
+    /// let matches = matches.subcommand_matches("db").unwrap();
+    /// let args = DbRemoveArgs::from_matches(matches.subcommand_matches("remove").unwrap()).unwrap();
+    /// assert_eq!(args.db, async_std::path::PathBuf::from("photos.db"));
+    /// assert_eq!(args.patterns.len(), 2);
+    /// ```
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            db: matches.value_of("DB").unwrap().into(),
+            patterns: get_globs(matches, "GLOB")?,
+        })
+    }
+}
+
+/// Tags given as values of `name`, e.g. `--tag`s files must all have.
+fn db_tags_of(matches: &clap::ArgMatches, name: &str) -> Vec<String> {
+    matches
+        .values_of(name)
+        .map_or_else(Vec::new, |tags| tags.map(str::to_owned).collect())
+}
+
+/// Arguments of `db tag`.
+pub struct DbTagArgs {
+    /// `DB`.
+    pub db: PathBuf,
+    /// `GLOB`.
+    pub pattern: walker::glob::Pattern,
+    /// `TAG`s.
+    pub tags: Vec<String>,
+    /// `--untag`.
+    pub untag: bool,
+}
+
+impl DbTagArgs {
+    /// Turn arguments into types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::{self, cli::DbTagArgs};
+    /// let matches = bin_util::app().get_matches_from(["succotash", "db", "tag", "photos.db", "**/Rome/**", "vacation2022", "italy"]);
+    /// let matches = matches.subcommand_matches("db").unwrap();
+    /// let args = DbTagArgs::from_matches(matches.subcommand_matches("tag").unwrap()).unwrap();
+    /// assert_eq!(args.tags, vec!["vacation2022", "italy"]);
+    /// assert!(!args.untag);
+    /// ```
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        let tags = db_tags_of(matches, "TAG");
+        for tag in &tags {
+            crate::db::check_tag(tag)?;
+        }
+        Ok(Self {
+            db: matches.value_of("DB").unwrap().into(),
+            pattern: walker::glob::Pattern::new(matches.value_of("GLOB").unwrap())?,
+            tags,
+            untag: matches.is_present("untag"),
+        })
+    }
+}
+
+/// Arguments of `db search`.
+pub struct DbSearchArgs {
+    /// `DB` as the index, `IMAGE` and how features are found, the same as of `index query`.
+    pub query: QueryArgs,
+    /// `--tag`s.
+    pub tags: Vec<String>,
+}
+
+impl DbSearchArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            query: QueryArgs::with_index(matches, matches.value_of("DB").unwrap())?,
+            tags: db_tags_of(matches, "tag"),
+        })
+    }
+}
+
+/// Arguments of `db dedupe`.
+pub struct DbDedupeArgs {
+    /// `DB`.
+    pub db: PathBuf,
+    /// `--tag`s.
+    pub tags: Vec<String>,
+    /// `--max-distance`, if given.
+    pub max_distance: Option<u32>,
+    /// `--search` and `--bands`.
+    pub search: index::Search,
+    /// `--format`.
+    pub format: analyze::output::Format,
+    /// `--output`, if given.
+    pub output: Option<PathBuf>,
+    /// How features are compared.
+    pub find: FindArgs,
+}
+
+impl DbDedupeArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            db: matches.value_of("DB").unwrap().into(),
+            tags: db_tags_of(matches, "tag"),
+            max_distance: parse(matches, "max-distance")?,
+            search: search(matches)?,
+            format: parse(matches, "format")?.unwrap_or_default(),
+            output: matches.value_of("output").map(Into::into),
+            find: FindArgs::from_matches(matches)?,
+        })
+    }
+
+    /// Options of grouping images, arguments override the configuration.
+    pub fn options(&self, config: &Config) -> Result<AnalyzeOptions, Box<dyn Error>> {
+        let mut options = AnalyzeOptions {
+            max_distance: self.max_distance.unwrap_or(analyze::group::MAX_DISTANCE),
+            search: self.search,
+            format: self.format,
+            output: self.output.clone(),
+            ..Default::default()
+        };
+        self.find.apply(config, &mut options)?;
+        Ok(options)
+    }
+}
+
+/// Arguments of `completions`.
+pub struct CompletionsArgs {
+    /// `SHELL`.
+    pub shell: clap::Shell,
+}
+
+impl CompletionsArgs {
+    /// Turn arguments into types.
+    pub fn from_matches(matches: &clap::ArgMatches) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            shell: matches.value_of("SHELL").unwrap().parse()?,
+        })
+    }
+}
@@ -0,0 +1,1096 @@
+//! Subcommands of the executable.
+//!
+//! Every subcommand is a [`Command`]: its name, its arguments and how it runs.
+//! [`app`](super::app) declares the arguments of all [`COMMANDS`] and
+//! [`main`](super::main) runs the one given on the command line, so a new
+//! subcommand is added to the registry and nowhere else.
+
+use super::cli::{
+    AnalyzeArgs, CompareArgs, CompletionsArgs, ConfigShowArgs, DbAddArgs, DbDedupeArgs,
+    DbRemoveArgs, DbSearchArgs, DbTagArgs, FindArgs, HashArgs, ImportArgs, IndexCompactArgs,
+    InitArgs, MontageArgs, OrganizeArgs, QueryArgs, ServeArgs, StatsArgs, UndoArgs, VerifyArgs,
+};
+#[cfg(unix)]
+use super::cli::{ClientQueryArgs, DaemonArgs};
+use super::{archive_args, find_args, progress_arg, search_args, storage_args};
+use crate::status::Status;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Running a subcommand, see [`Command::run`].
+pub type Run<'a> = Pin<Box<dyn Future<Output = Result<Status, Box<dyn Error>>> + 'a>>;
+
+/// A subcommand of the executable.
+pub trait Command: Sync {
+    /// Name of the subcommand on the command line.
+    fn name(&self) -> &'static str;
+
+    /// Arguments of the subcommand, see [`cli`](super::cli) for them in types.
+    fn app(&self) -> clap::App<'static, 'static>;
+
+    /// Whether the subcommand logs, always to stderr so that stdout is for results.
+    fn logs(&self) -> bool {
+        true
+    }
+
+    /// Run the subcommand with its arguments.
+    ///
+    /// Errors of the subcommand itself are logged and turn into
+    /// [`Status::Failed`], those returned are of its arguments or configuration.
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a>;
+}
+
+/// All subcommands of the executable.
+pub static COMMANDS: &[&dyn Command] = &[
+    &Analyze,
+    &Hash,
+    &Stats,
+    &Organize,
+    &Import,
+    &Undo,
+    &Montage,
+    &Compare,
+    &Verify,
+    &Config,
+    &Init,
+    &Index,
+    &Db,
+    &Serve,
+    #[cfg(unix)]
+    &Daemon,
+    #[cfg(unix)]
+    &Client,
+    &Worker,
+    &Completions,
+];
+
+/// The subcommand named `name`.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::bin_util::command;
+/// assert_eq!(command::find("analyze").map(|c| c.name()), Some("analyze"));
+/// assert!(command::find("frobnicate").is_none());
+/// ```
+pub fn find(name: &str) -> Option<&'static dyn Command> {
+    COMMANDS
+        .iter()
+        .copied()
+        .find(|command| command.name() == name)
+}
+
+/// `analyze`, see [`analyze::run`](crate::analyze::run).
+pub struct Analyze;
+
+impl Command for Analyze {
+    fn name(&self) -> &'static str {
+        "analyze"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .arg(
+                clap::Arg::from_usage("[DIR]... 'Sets the directories (or glob patterns) to analyze'")
+                    .required_unless("files-from")
+                    .conflicts_with("files-from"),
+            )
+            .arg_from_usage(
+                "--files-from [LIST] 'Analyzes files listed in a file (- for stdin), one per line or NUL-separated'",
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--include [GLOB]... 'Only analyze files matching the pattern'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--exclude [GLOB]... 'Skip files and directories matching the pattern'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--exclude-xattr [NAME]... 'Skip files and directories with the extended attribute'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--exclude-tag [TAG]... 'Skip files and directories with the macOS Finder tag'",
+                )
+                .number_of_values(1),
+            )
+            .arg_from_usage(
+                "--newer-than [TIME] 'Only analyze files newer than a date (2020-01-31) or a duration ago (30d)'",
+            )
+            .arg_from_usage(
+                "--older-than [TIME] 'Only analyze files older than a date (2020-01-31) or a duration ago (30d)'",
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--time-source [SOURCE] 'Sets which time of a file --newer-than and --older-than use'",
+                )
+                .possible_values(&["mtime", "ctime", "exif"])
+                .default_value("mtime"),
+            )
+            .arg(
+                clap::Arg::from_usage("--min-size [SIZE] 'Only analyzes files of at least SIZE, e.g. 10KiB'")
+                    .validator(|s| crate::units::parse_size(&s).map(drop)),
+            )
+            .arg(
+                clap::Arg::from_usage("--max-size [SIZE] 'Only analyzes files of at most SIZE, e.g. 50MB'")
+                    .validator(|s| crate::units::parse_size(&s).map(drop)),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--min-dimensions [WxH] 'Only analyzes images of at least WxH pixels, either way round'",
+                )
+                .validator(|s| crate::units::parse_dimensions(&s).map(drop)),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--follow-symlinks 'Walks directories symbolic links point to'",
+                )
+                .overrides_with("no-follow-symlinks"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--no-follow-symlinks 'Skips symbolic links to directories (default)'",
+                )
+                .overrides_with("follow-symlinks"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--junctions [POLICY] 'Sets whether to follow Windows junctions'",
+                )
+                .possible_values(&["skip", "follow"])
+                .default_value("skip"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--aliases [POLICY] 'Sets whether to follow macOS Finder aliases'",
+                )
+                .possible_values(&["skip", "follow"])
+                .default_value("skip"),
+            )
+            .arg_from_usage(
+                "--gitignore 'Skips what .gitignore files ignore, like .succotashignore files'",
+            )
+            .args(&archive_args())
+            .arg(
+                clap::Arg::from_usage(
+                    "--preset [PRESET] 'Sets how strict the analysis is, other options override it'",
+                )
+                .possible_values(&["strict", "balanced", "loose"])
+                .default_value("balanced"),
+            )
+            .arg_from_usage(
+                "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+            )
+            .args(&search_args())
+            .arg_from_usage(
+                "--explain 'Reports why images are similar: distances in every feature, thresholds and what made a match'",
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--filter [KEY=VALUE]... 'Only analyzes images that match, e.g. hue=blue or hue=red,orange'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--weight [FEATURE=WEIGHT]... 'Sets how much a feature weighs in scores groups are sorted by, e.g. hue=0.3'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--keep-weight [CRITERION=WEIGHT]... 'Sets how much a criterion weighs in picking the file of a group to keep, e.g. resolution=2'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--prefer [GLOB]... 'Keeps files matching the pattern rather than others of their group, e.g. **/Camera/**'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--deprioritize [GLOB]... 'Keeps files matching the pattern only if all of their group do, e.g. **/WhatsApp/**'",
+                )
+                .number_of_values(1),
+            )
+            .arg_from_usage(
+                "--partial 'Also finds crops, collages and screenshots that contain other images'",
+            )
+            .arg_from_usage(
+                "--bursts 'Groups bursts of photos by their EXIF time instead, and plans to keep the best of each'",
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--burst-window [DURATION] 'Sets how long after the previous one a photo of a burst can be taken'",
+                )
+                .default_value("2s"),
+            )
+            .args(&find_args())
+            .arg(
+                clap::Arg::from_usage(
+                    "--verify [METHOD] 'Compares pixels of similar images to rule out hash collisions'",
+                )
+                .possible_values(&["ssim", "psnr"]),
+            )
+            .arg_from_usage(
+                "--verify-cutoff [VALUE] 'Sets the lowest SSIM (0.8 by default) or PSNR (25 dB) of similar images'",
+            )
+            .arg(
+                clap::Arg::from_usage("--format [FORMAT] 'Sets how to write groups of similar images'")
+                    .possible_values(&[
+                        "text",
+                        "log",
+                        "json",
+                        "fdupes",
+                        "czkawka",
+                        #[cfg(feature = "xlsx")]
+                        "xlsx",
+                    ])
+                    .default_value("text"),
+            )
+            .arg_from_usage("--output [FILE] 'Writes groups of similar images to the file'")
+            .arg_from_usage(
+                "--export-matrix [FILE] 'Writes how many bits hashes of every pair of images differ in to the file'",
+            )
+            .arg(
+                clap::Arg::from_usage("--matrix-format [FORMAT] 'Sets how to write the distances, csv by default'")
+                    .possible_values(&["csv", "binary"])
+                    .requires("export-matrix"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--matrix-max-distance [BITS] 'Only writes pairs of images at most BITS apart, as a sparse list'",
+                )
+                .requires("export-matrix"),
+            )
+            .arg_from_usage(
+                "--export-graph [FILE] 'Writes pairs of similar images as a Graphviz graph to the file'",
+            )
+            .arg_from_usage(
+                "--emit-delete-list [FILE] 'Writes NUL-separated paths of files planned for deletion, deletes nothing, - for stdout'",
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--print0 'Prints NUL-separated paths of files planned for deletion, for xargs -0'",
+                )
+                .conflicts_with("emit-delete-list"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--action [ACTION] 'Acts on files planned for deletion: reflink or hardlink replaces exact duplicates with reflinks or hard links to the kept file, delete deletes them'",
+                )
+                .possible_values(&["reflink", "hardlink", "delete"]),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--use-trash 'Moves files deleted by --action delete to the trash, rather than deleting them for good'",
+                )
+                .requires("action"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--journal [FILE] 'Records what --action does to FILE, to be undone with undo, a new file in $XDG_STATE_HOME/succotash by default'",
+                )
+                .requires("action"),
+            )
+            .arg_from_usage(
+                "--workers [N] 'Finds features in N worker processes, isolating decoder crashes'",
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--threads [N] 'Finds features on N threads, one per core by default'",
+                )
+                .conflicts_with("workers"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--max-memory [SIZE] 'Analyzes fewer files at once if they would take more than SIZE, e.g. 2GiB'",
+                )
+                .validator(|s| crate::units::parse_size(&s).map(drop))
+                .conflicts_with("workers"),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--max-file-size [SIZE] 'Reports files larger than SIZE as failed without reading them, e.g. 500MiB'",
+                )
+                .validator(|s| crate::units::parse_size(&s).map(drop)),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--decode-timeout [DURATION] 'Gives up on files that take longer than DURATION to decode, e.g. 30s'",
+                )
+                .validator(|s| crate::units::parse_duration(&s).map(drop)),
+            )
+            .arg_from_usage(
+                "--index [INDEX] 'Saves features to INDEX as they are found, the configured index by default'",
+            )
+            .arg_from_usage(
+                "--resume 'Only analyzes files that have no features in the index, e.g. after an interrupted run'",
+            )
+            .arg_from_usage(
+                "--retry-quarantined 'Analyzes files that failed to be analyzed before again, instead of skipping them'",
+            )
+            .args(&storage_args())
+            .arg(progress_arg())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = AnalyzeArgs::from_matches(matches)?;
+            // The configuration of a library is the one of its first directory.
+            let (config, _) = crate::config::load(args.library())?;
+            let source = args.source.clone();
+            let options = args.options(&config)?;
+            // Ctrl+C stops the analysis early, with results of what was analyzed.
+            super::cancel_on_interrupt(&options.cancel)?;
+            Ok(crate::analyze::run(source, options).await)
+        })
+    }
+}
+
+/// `hash`, see [`hash::run`](crate::hash::run).
+pub struct Hash;
+
+impl Command for Hash {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Prints hashes and hues of files, one image per line")
+            .arg_from_usage("<FILE>... 'Sets the files to hash'")
+            .args(&find_args())
+            .arg_from_usage("--output [FILE] 'Writes hashes to the file'")
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = HashArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(None)?;
+            let options = args.find.options(&config)?;
+            Ok(crate::hash::run(args.files, options, args.output).await)
+        })
+    }
+}
+
+/// `stats`, see [`stats::run`](crate::stats::run).
+pub struct Stats;
+
+impl Command for Stats {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Prints counts of formats, sizes, hues and hashes of files")
+            .arg_from_usage("<DIR>... 'Sets the directories to look through'")
+            .args(&find_args())
+            .arg_from_usage(
+                "--index [INDEX] 'Reuses and saves features in INDEX, the configured index by default'",
+            )
+            .args(&storage_args())
+            .arg_from_usage("--threads [N] 'Finds features on N threads, one per core by default'")
+            .arg_from_usage("--output [FILE] 'Writes the stats to the file'")
+            .arg(progress_arg())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = StatsArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(args.library())?;
+            let options = args.options(&config)?;
+            Ok(crate::stats::run(args.dirs, options, args.output).await)
+        })
+    }
+}
+
+/// `organize`, see [`organize::run`](crate::organize::run).
+pub struct Organize;
+
+impl Command for Organize {
+    fn name(&self) -> &'static str {
+        "organize"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Moves or links files into folders by date, hue or camera")
+            .arg_from_usage("<DIR>... 'Sets the directories to look through'")
+            .arg_from_usage("--into <TARGET> 'Sets the directory to sort files into'")
+            .arg(
+                clap::Arg::from_usage("--by [BY] 'Sets what folders files are sorted into'")
+                    .possible_values(&["date", "hue", "hue-name", "camera"])
+                    .default_value("date"),
+            )
+            .arg_from_usage("--symlink 'Links files into the folders instead of moving them'")
+            .arg_from_usage("--dry-run 'Prints where files would go, moves nothing'")
+            .arg_from_usage(
+                "--journal [FILE] 'Records what is done to FILE, to be undone with undo, a new file in $XDG_STATE_HOME/succotash by default'",
+            )
+            .args(&find_args())
+            .arg_from_usage(
+                "--index [INDEX] 'Reuses and saves features in INDEX, the configured index by default'",
+            )
+            .args(&storage_args())
+            .arg_from_usage("--threads [N] 'Finds features on N threads, one per core by default'")
+            .arg(progress_arg())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = OrganizeArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(args.library.library())?;
+            let options = args.library.options(&config)?;
+            super::cancel_on_interrupt(&options.cancel)?;
+            let dirs = args.library.dirs;
+            Ok(crate::organize::run(dirs, args.into, args.organize, options).await)
+        })
+    }
+}
+
+/// `import`, see [`import::run`](crate::import::run).
+pub struct Import;
+
+impl Command for Import {
+    fn name(&self) -> &'static str {
+        "import"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Copies files with no similar image in a library into it, e.g. from an SD card")
+            .arg_from_usage("<SRC> 'Sets the directory to import files from'")
+            .arg_from_usage("<LIBRARY> 'Sets the directory to import files into'")
+            .arg_from_usage("--rename-by-date 'Names files by when they were taken, in folders by year and month'")
+            .arg_from_usage("--dry-run 'Prints where files would go, copies nothing'")
+            .arg_from_usage("--summary [FILE] 'Writes files skipped as similar to ones in the library to the file'")
+            .arg_from_usage(
+                "--journal [FILE] 'Records what is done to FILE, to be undone with undo, a new file in $XDG_STATE_HOME/succotash by default'",
+            )
+            .args(&find_args())
+            .arg_from_usage(
+                "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+            )
+            .args(&search_args())
+            .arg_from_usage(
+                "--index [INDEX] 'Reuses and saves features in INDEX, the configured index by default'",
+            )
+            .args(&storage_args())
+            .arg_from_usage("--threads [N] 'Finds features on N threads, one per core by default'")
+            .arg(progress_arg())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = ImportArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(Some(args.library.as_ref()))?;
+            let options = args.options(&config)?;
+            super::cancel_on_interrupt(&options.cancel)?;
+            Ok(crate::import::run(&args.src, &args.library, args.import, options).await)
+        })
+    }
+}
+
+/// `undo`, see [`journal::run`](crate::journal::run).
+pub struct Undo;
+
+impl Command for Undo {
+    fn name(&self) -> &'static str {
+        "undo"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Undoes what 'analyze --action', 'organize' or 'import' recorded in a journal")
+            .arg_from_usage("<JOURNAL> 'Sets the journal to undo'")
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = UndoArgs::from_matches(matches);
+            Ok(crate::journal::run(&args.journal))
+        })
+    }
+}
+
+/// `montage`, see [`montage::run`](crate::montage::run).
+pub struct Montage;
+
+impl Command for Montage {
+    fn name(&self) -> &'static str {
+        "montage"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Renders contact sheets of groups of similar images or of hues")
+            .arg_from_usage("<DIR>... 'Sets the directories to look through'")
+            .arg_from_usage("--output-dir <DIR> 'Sets the directory to write sheets to'")
+            .arg(
+                clap::Arg::from_usage("--by [BY] 'Sets what images get a sheet together'")
+                    .possible_values(&["groups", "hue"])
+                    .default_value("groups"),
+            )
+            .arg_from_usage(
+                "--tile-size [PIXELS] 'Sets the size of the square thumbnails fit into, 160 by default'",
+            )
+            .arg_from_usage(
+                "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+            )
+            .args(&search_args())
+            .args(&find_args())
+            .arg_from_usage(
+                "--index [INDEX] 'Reuses and saves features in INDEX, the configured index by default'",
+            )
+            .args(&storage_args())
+            .arg_from_usage("--threads [N] 'Finds features on N threads, one per core by default'")
+            .arg(progress_arg())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = MontageArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(args.library.library())?;
+            let options = args.options(&config)?;
+            super::cancel_on_interrupt(&options.cancel)?;
+            let dirs = args.library.dirs;
+            Ok(crate::montage::run(dirs, args.montage, options).await)
+        })
+    }
+}
+
+/// `compare`, see [`compare::run`](crate::compare::run).
+pub struct Compare;
+
+impl Command for Compare {
+    fn name(&self) -> &'static str {
+        "compare"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Prints how two images differ and draws where they do")
+            .arg_from_usage("<A> 'Sets the first image'")
+            .arg_from_usage("<B> 'Sets the second image'")
+            .arg_from_usage(
+                "--diff [FILE] 'Writes a heatmap of differences of pixels to the file, e.g. diff.png'",
+            )
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = CompareArgs::from_matches(matches);
+            Ok(crate::compare::run(args.a, args.b, args.diff.as_deref()).await)
+        })
+    }
+}
+
+/// `verify`, see [`integrity::run`](crate::integrity::run).
+pub struct Verify;
+
+impl Command for Verify {
+    fn name(&self) -> &'static str {
+        "verify"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Decodes every image in full and reports truncated and corrupted files")
+            .arg_from_usage("<DIR>... 'Sets the directories to look through'")
+            .arg(
+                clap::Arg::from_usage("--format [FORMAT] 'Sets how to write the report'")
+                    .possible_values(&["text", "json"])
+                    .default_value("text"),
+            )
+            .arg_from_usage("--output [FILE] 'Writes the report to the file'")
+            .arg_from_usage("--threads [N] 'Decodes on N threads, one per core by default'")
+            .arg(progress_arg())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = VerifyArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(args.library())?;
+            let options = args.options(&config);
+            super::cancel_on_interrupt(&options.cancel)?;
+            Ok(crate::integrity::run(args.dirs, options, args.format, args.output).await)
+        })
+    }
+}
+
+/// `config` and its subcommands, see [`config`](crate::config).
+pub struct Config;
+
+impl Command for Config {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Shows the configuration")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::SubCommand::with_name("show")
+                    .about("Prints the configuration files, system-wide first")
+                    .arg_from_usage("--effective 'Prints the configuration merged from the files'")
+                    .arg_from_usage(
+                        "[DIR] 'Sets the library whose configuration file is merged last'",
+                    ),
+            )
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            match matches.subcommand() {
+                ("show", Some(matches)) => {
+                    let args = ConfigShowArgs::from_matches(matches);
+                    Ok(crate::config::show(args.library.as_deref(), args.effective))
+                }
+                (sub, _) => Err(format!("unknown subcommand 'config {}'", sub).into()),
+            }
+        })
+    }
+}
+
+/// `init`, see [`init::run`](crate::init::run).
+pub struct Init;
+
+impl Command for Init {
+    fn name(&self) -> &'static str {
+        "init"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Asks a few questions and writes the configuration file")
+            .arg_from_usage("[CONFIG] 'Sets where to write the configuration'")
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = InitArgs::from_matches(matches);
+            Ok(crate::init::run(args.config))
+        })
+    }
+}
+
+/// `index` and its subcommands, see [`index`](crate::analyze::index).
+pub struct Index;
+
+impl Command for Index {
+    fn name(&self) -> &'static str {
+        "index"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Manages index files")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::SubCommand::with_name("compact")
+                    .about("Rewrites an index without superseded and removed entries")
+                    .arg_from_usage("<INDEX> 'Sets the index file to compact'"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("build")
+                    .about("Saves features of files in directories to an index, to be queried")
+                    .arg_from_usage("<DIR>... 'Sets the directories to look through'")
+                    .arg(
+                        clap::Arg::with_name("index")
+                            .short("o")
+                            .long("output")
+                            .value_name("INDEX")
+                            .required(true)
+                            .help("Sets the index file to write, files in it already are not analyzed again"),
+                    )
+                    .args(&find_args())
+                    .args(&storage_args())
+                    .arg_from_usage("--threads [N] 'Finds features on N threads, one per core by default'")
+                    .arg(progress_arg()),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("query")
+                    .about("Prints files in an index with images similar to those in a file")
+                    .arg_from_usage("<IMAGE> 'Sets the file (or URL) to find similar images to'")
+                    .arg_from_usage("<INDEX> 'Sets the index file (or directory) to look in'")
+                    .args(&find_args())
+                    .arg_from_usage(
+                        "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+                    )
+                    .args(&search_args())
+                    .arg_from_usage("--output [FILE] 'Writes the files found to the file'"),
+            )
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        use crate::analyze::index;
+
+        Box::pin(async move {
+            match matches.subcommand() {
+                ("compact", Some(matches)) => {
+                    let args = IndexCompactArgs::from_matches(matches);
+                    Ok(index::compact(&args.index))
+                }
+                ("build", Some(matches)) => {
+                    let args = StatsArgs::from_matches(matches)?;
+                    let (config, _) = crate::config::load(args.library())?;
+                    let options = args.options(&config)?;
+                    super::cancel_on_interrupt(&options.cancel)?;
+                    Ok(index::build(args.dirs, options).await)
+                }
+                ("query", Some(matches)) => {
+                    let args = QueryArgs::from_matches(matches)?;
+                    let (config, _) = crate::config::load(None)?;
+                    let options = args.options(&config)?;
+                    Ok(index::query(args.image, &args.index, options, args.output).await)
+                }
+                (sub, _) => Err(format!("unknown subcommand 'index {}'", sub).into()),
+            }
+        })
+    }
+}
+
+/// `db` and its subcommands, see [`db`](crate::db).
+pub struct Db;
+
+impl Command for Db {
+    fn name(&self) -> &'static str {
+        "db"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Keeps a photo library indexed in a database")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::SubCommand::with_name("init")
+                    .about("Creates an empty database")
+                    .arg_from_usage("<DB> 'Sets the directory to create the database in'"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("add")
+                    .about("Adds files in directories to a database, analyzing new and changed ones")
+                    .arg_from_usage("<DB> 'Sets the database'")
+                    .arg_from_usage("<DIR>... 'Sets the directories to look through'")
+                    .args(&find_args())
+                    .args(&storage_args())
+                    .arg_from_usage("--threads [N] 'Finds features on N threads, one per core by default'")
+                    .arg(progress_arg()),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("remove")
+                    .about("Removes files matching patterns from a database")
+                    .arg_from_usage("<DB> 'Sets the database'")
+                    .arg_from_usage("<GLOB>... 'Sets the patterns, of whole paths if they have a separator, of file names otherwise'"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("tag")
+                    .about("Tags files matching a pattern in a database")
+                    .arg_from_usage("<DB> 'Sets the database'")
+                    .arg_from_usage("<GLOB> 'Sets the pattern, of whole paths if it has a separator, of file names otherwise'")
+                    .arg_from_usage("<TAG>... 'Sets the tags, without commas'")
+                    .arg_from_usage("--untag 'Takes the tags away instead'"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("search")
+                    .about("Prints files in a database with images similar to those in a file")
+                    .arg_from_usage("<DB> 'Sets the database'")
+                    .arg_from_usage("<IMAGE> 'Sets the file (or URL) to find similar images to'")
+                    .arg(
+                        clap::Arg::from_usage("--tag [TAG]... 'Only searches files with the tag'")
+                            .number_of_values(1),
+                    )
+                    .args(&find_args())
+                    .arg_from_usage(
+                        "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+                    )
+                    .args(&search_args())
+                    .arg_from_usage("--output [FILE] 'Writes the files found to the file'"),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("dedupe")
+                    .about("Groups similar images in a database and records the group of every file")
+                    .arg_from_usage("<DB> 'Sets the database'")
+                    .arg(
+                        clap::Arg::from_usage("--tag [TAG]... 'Only groups files with the tag'")
+                            .number_of_values(1),
+                    )
+                    .args(&find_args())
+                    .arg_from_usage(
+                        "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+                    )
+                    .args(&search_args())
+                    .arg(
+                        clap::Arg::from_usage("--format [FORMAT] 'Sets how to write groups of similar images'")
+                            .possible_values(&[
+                                "text",
+                                "log",
+                                "json",
+                                "fdupes",
+                                "czkawka",
+                                #[cfg(feature = "xlsx")]
+                                "xlsx",
+                            ])
+                            .default_value("text"),
+                    )
+                    .arg_from_usage("--output [FILE] 'Writes groups of similar images to the file'"),
+            )
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        use crate::db;
+
+        Box::pin(async move {
+            match matches.subcommand() {
+                ("init", Some(matches)) => {
+                    let dir = matches.value_of("DB").unwrap();
+                    Ok(db::init(dir.as_ref()))
+                }
+                ("add", Some(matches)) => {
+                    let args = DbAddArgs::from_matches(matches)?;
+                    let (config, _) = crate::config::load(None)?;
+                    let options = args.library.options(&config)?;
+                    super::cancel_on_interrupt(&options.cancel)?;
+                    Ok(db::add(&args.db, args.library.dirs, options).await)
+                }
+                ("remove", Some(matches)) => {
+                    let args = DbRemoveArgs::from_matches(matches)?;
+                    Ok(db::remove(&args.db, &args.patterns))
+                }
+                ("tag", Some(matches)) => {
+                    let args = DbTagArgs::from_matches(matches)?;
+                    Ok(db::tag(&args.db, &args.pattern, &args.tags, args.untag))
+                }
+                ("search", Some(matches)) => {
+                    let DbSearchArgs { query, tags } = DbSearchArgs::from_matches(matches)?;
+                    let (config, _) = crate::config::load(None)?;
+                    let options = query.options(&config)?;
+                    let dir = query.index.as_ref();
+                    Ok(db::search(dir, query.image, &tags, options, query.output).await)
+                }
+                ("dedupe", Some(matches)) => {
+                    let args = DbDedupeArgs::from_matches(matches)?;
+                    let (config, _) = crate::config::load(None)?;
+                    let options = args.options(&config)?;
+                    Ok(db::dedupe(args.db, args.tags, options).await)
+                }
+                (sub, _) => Err(format!("unknown subcommand 'db {}'", sub).into()),
+            }
+        })
+    }
+}
+
+/// `serve`, see [`serve`](crate::serve).
+pub struct Serve;
+
+impl Command for Serve {
+    fn name(&self) -> &'static str {
+        "serve"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Answers queries to an index over HTTP and serves a web page to review groups in")
+            .arg(
+                clap::Arg::from_usage("--listen [ADDRESS] 'Sets the address to listen on'")
+                    .default_value("127.0.0.1:8080"),
+            )
+            .arg_from_usage(
+                "--index [INDEX] 'Sets the index file (or database) to serve, the configured index by default'",
+            )
+            .args(&find_args())
+            .arg_from_usage(
+                "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+            )
+            .args(&search_args())
+            .arg_from_usage(
+                "--emit-delete-list [FILE] 'Writes NUL-separated paths of files picked for deletion in the web page, deletes nothing'",
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--keep-weight [CRITERION=WEIGHT]... 'Sets how much a criterion weighs in picking the file of a group to keep, e.g. resolution=2'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--prefer [GLOB]... 'Keeps files matching the pattern rather than others of their group, e.g. **/Camera/**'",
+                )
+                .number_of_values(1),
+            )
+            .arg(
+                clap::Arg::from_usage(
+                    "--deprioritize [GLOB]... 'Keeps files matching the pattern only if all of their group do, e.g. **/WhatsApp/**'",
+                )
+                .number_of_values(1),
+            )
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = ServeArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(None)?;
+            let index = args.index(&config)?;
+            let options = args.options(&config)?;
+            Ok(crate::serve::run(&args.listen, &index, options).await)
+        })
+    }
+}
+
+/// `daemon`, see [`daemon`](crate::daemon).
+#[cfg(unix)]
+pub struct Daemon;
+
+#[cfg(unix)]
+impl Command for Daemon {
+    fn name(&self) -> &'static str {
+        "daemon"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Keeps an index in memory and answers queries of 'client' to it over a unix socket")
+            .arg_from_usage(
+                "--socket [SOCKET] 'Sets the socket to listen on, succotash.sock in $XDG_RUNTIME_DIR by default'",
+            )
+            .arg_from_usage(
+                "--metrics [ADDRESS] 'Exposes metrics for Prometheus at /metrics on the address over HTTP'",
+            )
+            .arg_from_usage(
+                "--index [INDEX] 'Sets the index file to keep, the configured index by default'",
+            )
+            .args(&find_args())
+            .arg_from_usage(
+                "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in'",
+            )
+            .args(&search_args())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = DaemonArgs::from_matches(matches)?;
+            let (config, _) = crate::config::load(None)?;
+            let index = args.index(&config)?;
+            let options = args.options(&config)?;
+            let metrics = args.metrics.as_deref();
+            Ok(crate::daemon::run(&args.socket(), metrics, &index, options).await)
+        })
+    }
+}
+
+/// `client`, see [`daemon::client`](crate::daemon::client).
+#[cfg(unix)]
+pub struct Client;
+
+#[cfg(unix)]
+impl Command for Client {
+    fn name(&self) -> &'static str {
+        "client"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Sends queries to a running 'daemon'")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::SubCommand::with_name("query")
+                    .about("Prints files in the index of the daemon with images similar to those in a file")
+                    .arg_from_usage("<IMAGE> 'Sets the file to find similar images to'")
+                    .arg_from_usage(
+                        "--socket [SOCKET] 'Sets the socket of the daemon, succotash.sock in $XDG_RUNTIME_DIR by default'",
+                    )
+                    .arg_from_usage(
+                        "--max-distance [BITS] 'Sets how many bits hashes of similar images can differ in, what the daemon has by default'",
+                    )
+                    .arg_from_usage("--output [FILE] 'Writes the files found to the file'"),
+            )
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        use crate::daemon::client;
+
+        Box::pin(async move {
+            match matches.subcommand() {
+                ("query", Some(matches)) => {
+                    let args = ClientQueryArgs::from_matches(matches)?;
+                    Ok(
+                        client::query(&args.socket(), &args.image, args.max_distance, args.output)
+                            .await,
+                    )
+                }
+                (sub, _) => Err(format!("unknown subcommand 'client {}'", sub).into()),
+            }
+        })
+    }
+}
+
+/// `worker`, see [`worker::serve`](crate::analyze::worker::serve).
+pub struct Worker;
+
+impl Command for Worker {
+    fn name(&self) -> &'static str {
+        "worker"
+    }
+
+    fn logs(&self) -> bool {
+        // Stderr of workers is that of 'analyze', which logs already.
+        false
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Serves feature extraction requests of 'analyze --workers'")
+            .setting(clap::AppSettings::Hidden)
+            .arg_from_usage("--tiles 'Finds hashes of tiles too'")
+            .args(&find_args())
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            // Everything comes from the parent on the command line, not from the configuration.
+            let options = FindArgs::from_matches(matches)?.options(&Default::default())?;
+            crate::analyze::worker::serve(options).await?;
+            Ok(Status::Clean)
+        })
+    }
+}
+
+/// `completions`, see [`completions`](super::completions).
+pub struct Completions;
+
+impl Command for Completions {
+    fn name(&self) -> &'static str {
+        "completions"
+    }
+
+    fn app(&self) -> clap::App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Prints a completion script for a shell")
+            .arg(
+                clap::Arg::from_usage("<SHELL> 'Sets the shell to complete in'")
+                    .possible_values(&clap::Shell::variants()),
+            )
+    }
+
+    fn run<'a>(&'a self, matches: &'a clap::ArgMatches<'a>) -> Run<'a> {
+        Box::pin(async move {
+            let args = CompletionsArgs::from_matches(matches)?;
+            super::completions(args.shell, &mut std::io::stdout());
+            Ok(Status::Clean)
+        })
+    }
+}
@@ -0,0 +1,359 @@
+//! Utilities for the executable shipped with the succotash library.
+//!
+//! This file is in the library part to allow rustdoc example testing.
+
+pub mod cli;
+pub mod command;
+
+use crate::analyze;
+
+/// How the executable logs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogOptions {
+    /// Level of verbosity to set. Higher value = more verbosity.
+    pub verbosity: u64,
+    /// Whether to only log warnings and errors, whatever the verbosity.
+    pub quiet: bool,
+    /// A file to append the log to as well, without colors.
+    pub file: Option<std::path::PathBuf>,
+    /// Whether to write every line as a JSON object with
+    /// `time`, `level`, `target` and `message`.
+    pub json: bool,
+}
+
+impl LogOptions {
+    /// The level our own crates log at.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::bin_util::LogOptions;
+    /// let options = LogOptions { verbosity: 2, ..Default::default() };
+    /// assert_eq!(options.level(), log::LevelFilter::Trace);
+    /// let options = LogOptions { quiet: true, ..options };
+    /// assert_eq!(options.level(), log::LevelFilter::Warn);
+    /// ```
+    pub fn level(&self) -> log::LevelFilter {
+        match (self.quiet, self.verbosity) {
+            (true, _) => log::LevelFilter::Warn,
+            (false, 0) => log::LevelFilter::Info,
+            (false, 1) => log::LevelFilter::Debug,
+            (false, _) => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// A log line as a JSON object.
+fn json_line(message: &std::fmt::Arguments, record: &log::Record) -> String {
+    serde_json::json!({
+        "time": chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": message.to_string(),
+    })
+    .to_string()
+}
+
+/// Initialize fern logger, writing to stderr, so that stdout is for results.
+///
+/// # Arguments
+///
+/// * `options` - How to log.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::bin_util;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// bin_util::init_logging(&Default::default())?; // Initialize logging with minimal verbosity.
+/// # Ok(())
+/// # }
+/// ```
+pub fn init_logging(options: &LogOptions) -> Result<(), Box<dyn std::error::Error>> {
+    init_logging_to(options, Some(std::io::stderr().into()))
+}
+
+/// Initialize fern logger, writing to `output` and the [file](LogOptions::file).
+///
+/// # Arguments
+///
+/// * `options` - How to log.
+/// * `output` - Where to write the log to, only to the file if `None`.
+pub fn init_logging_to(
+    options: &LogOptions,
+    output: Option<fern::Output>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let level = options.level();
+    let json = options.json;
+    let cute = options.verbosity == 0 && !json;
+
+    let colors = fern::colors::ColoredLevelConfig::new().info(fern::colors::Color::Green);
+
+    let mut dispatch = fern::Dispatch::new()
+        .level(level.min(log::LevelFilter::Info))
+        .level_for(clap::crate_name!(), level)
+        .level_for(format!("lib{}", clap::crate_name!()), level);
+    if let Some(output) = output {
+        dispatch = dispatch.chain(
+            fern::Dispatch::new()
+                // Based on fern's usage example
+                .format(move |out, message, record| {
+                    if json {
+                        out.finish(format_args!("{}", json_line(message, record)));
+                    } else if cute {
+                        out.finish(format_args!(
+                            "[{}] {}",
+                            colors.color(record.level()),
+                            message
+                        ));
+                    } else {
+                        out.finish(format_args!(
+                            "{} {} [{}] {}",
+                            chrono::Local::now()
+                                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                            record.target(),
+                            colors.color(record.level()),
+                            message
+                        ));
+                    }
+                })
+                .chain(output),
+        );
+    }
+    if let Some(file) = &options.file {
+        // Files are read later, so they always have times and no colors.
+        dispatch = dispatch.chain(
+            fern::Dispatch::new()
+                .format(move |out, message, record| {
+                    if json {
+                        out.finish(format_args!("{}", json_line(message, record)));
+                    } else {
+                        out.finish(format_args!(
+                            "{} {} [{}] {}",
+                            chrono::Local::now()
+                                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                            record.target(),
+                            record.level(),
+                            message
+                        ));
+                    }
+                })
+                .chain(fern::log_file(file)?),
+        );
+    }
+    dispatch.apply()?;
+
+    info!("Using log level {}", level);
+    if cute {
+        info!("Using cute output mode");
+    }
+
+    Ok(())
+}
+
+/// Cancel `token` on Ctrl+C or SIGTERM, so that the analysis stops early
+/// and writes what it has found, see [`analyze::cancel`].
+///
+/// The second Ctrl+C or SIGTERM exits at once, e.g. if writing takes too long.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::{analyze::cancel::CancelToken, bin_util};
+/// let token = CancelToken::new();
+/// bin_util::cancel_on_interrupt(&token).unwrap();
+/// ```
+pub fn cancel_on_interrupt(
+    token: &analyze::cancel::CancelToken,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+
+    for signal in [SIGINT, SIGTERM] {
+        // Registered first, so it sees the flag before this signal sets it.
+        signal_hook::flag::register_conditional_shutdown(signal, 130, token.flag())?;
+        signal_hook::flag::register(signal, token.flag())?;
+    }
+    Ok(())
+}
+
+/// Arguments that set how features are found, see [`cli::FindArgs`].
+fn find_args() -> Vec<clap::Arg<'static, 'static>> {
+    vec![
+        clap::Arg::from_usage(
+            "--no-trim 'Keeps uniform borders and letterbox bars of images when finding features'",
+        ),
+        clap::Arg::from_usage(
+            "--grayscale [CONVERSION] 'Sets how images are converted to grayscale for hashing'",
+        )
+        .possible_values(&["default", "rec709", "rec709-linear"])
+        .default_value("default"),
+        clap::Arg::from_usage(
+            "--hash-threshold [THRESHOLD] 'Sets what pixels are compared to when hashing'",
+        )
+        .possible_values(&["mean", "median"])
+        .default_value("mean"),
+        clap::Arg::from_usage(
+            "--features [LIST] 'Sets comma-separated features images are compared by, e.g. lshash,dhash,hue'",
+        )
+        .default_value("lshash,brightness,contrast"),
+        #[cfg(feature = "embedding")]
+        clap::Arg::from_usage(
+            "--embedding-model [FILE] 'Sets the ONNX model that finds the embedding feature, e.g. of CLIP'",
+        ),
+    ]
+}
+
+/// Arguments that set where features are kept besides the index, see [`cli::StorageArgs`].
+fn storage_args() -> Vec<clap::Arg<'static, 'static>> {
+    vec![
+        clap::Arg::from_usage(
+            "--write-sidecars 'Saves features to XMP sidecars of files and reuses them, e.g. a.jpg.xmp'",
+        ),
+        clap::Arg::from_usage(
+            "--read-sidecars 'Reuses features in XMP sidecars of files without writing any'",
+        )
+        .conflicts_with("write-sidecars"),
+        #[cfg(feature = "xattrs")]
+        clap::Arg::from_usage(
+            "--xattrs 'Saves features to extended attributes of files and reuses them, they survive moves and renames'",
+        ),
+    ]
+}
+
+/// Arguments that walk into files besides directories, e.g. archives, if this build can.
+fn archive_args() -> Vec<clap::Arg<'static, 'static>> {
+    vec![
+        #[cfg(feature = "archives")]
+        clap::Arg::from_usage(
+            "--archives 'Looks for images inside ZIP, CBZ and TAR archives, e.g. issue-1.cbz!/001.png'",
+        ),
+    ]
+}
+
+/// The argument that sets how progress is reported, see [`progress`](crate::analyze::progress).
+fn progress_arg() -> clap::Arg<'static, 'static> {
+    clap::Arg::from_usage(
+        "--progress [FORMAT] 'Reports progress to stderr, json writes an object per line for programs to read'",
+    )
+    .possible_values(&["none", "json"])
+    .default_value("none")
+}
+
+/// Arguments that set how images to compare are found, `--search` and `--bands`.
+fn search_args() -> Vec<clap::Arg<'static, 'static>> {
+    vec![
+        clap::Arg::from_usage(
+            "--search [SEARCH] 'Sets how images to compare are found, vp-tree, bands and hnsw are faster on large libraries'",
+        )
+        .possible_values(&["brute", "vp-tree", "bands", "hnsw"])
+        .default_value("brute"),
+        clap::Arg::from_usage(
+            "--bands [COUNT] 'Sets how many bands hashes are split into with --search bands, 8 by default'",
+        ),
+    ]
+}
+
+/// Generate argument parser and parse command line arguments with it.
+///
+/// Returns a struct with the args, structs in [`cli`] turn them into types.
+/// Exits with [`Status::Failed`](crate::status::Status::Failed) if they are wrong.
+///
+/// # Examples
+///
+/// Let's say cmdline is `binary_name -vv`
+/// ```
+/// # let matches = clap::App::new(clap::crate_name!())
+/// #     .arg_from_usage("-v... 'Verbosity'")
+/// #     .get_matches_from(["binary_name", "-vv"].iter());
+/// // let matches = bin_util::get_args();
+/// let verbosity = matches.args.get("v").map(|v| v.occurs).unwrap_or(0);
+/// assert_eq!(verbosity, 2);
+/// ```
+pub fn get_args() -> clap::ArgMatches<'static> {
+    app().get_matches_safe().unwrap_or_else(|e| {
+        // Help and version go to stdout and exit with 0.
+        if !e.use_stderr() {
+            e.exit();
+        }
+        // Usage errors are errors, not "found something".
+        eprintln!("{}", e.message);
+        std::process::exit(crate::status::Status::Failed.code().into());
+    })
+}
+
+/// Run the subcommand given on the command line, see [`command`].
+///
+/// You can think of it as of `main` of the executable,
+/// it returns the status to exit with.
+pub fn main() -> crate::status::Status {
+    try_main().unwrap_or_else(|e| {
+        // The log may not be set up yet.
+        eprintln!("Error: {}", e);
+        crate::status::Status::Failed
+    })
+}
+
+/// Run the subcommand given on the command line, see [`main`].
+fn try_main() -> Result<crate::status::Status, Box<dyn std::error::Error>> {
+    let args = get_args();
+    let log = LogOptions {
+        verbosity: args.occurrences_of("v"),
+        quiet: args.is_present("quiet"),
+        file: args.value_of("log-file").map(Into::into),
+        json: args.is_present("log-json"),
+    };
+    let (name, matches) = match args.subcommand() {
+        (name, Some(matches)) => (name, matches),
+        _ => {
+            init_logging(&log)?;
+            error!("You haven't specified a subcommand; see help");
+            return Ok(crate::status::Status::Failed);
+        }
+    };
+    let command = command::find(name).ok_or_else(|| format!("unknown subcommand '{}'", name))?;
+
+    if command.logs() {
+        init_logging(&log)?;
+    }
+    async_std::task::block_on(command.run(matches))
+}
+
+/// Generate the argument parser of the executable, see [`get_args`].
+pub fn app() -> clap::App<'static, 'static> {
+    let app = clap::App::new(clap::crate_name!())
+        .name(clap::crate_name!())
+        .version(clap::crate_version!())
+        .author(clap::crate_authors!())
+        .about(clap::crate_description!())
+        .arg_from_usage("-v... 'Sets the level of verbosity'")
+        .arg(
+            clap::Arg::from_usage("-q, --quiet 'Only logs warnings and errors'")
+                .conflicts_with("v"),
+        )
+        .arg_from_usage("--log-file [FILE] 'Appends the log to FILE as well'")
+        .arg_from_usage("--log-json 'Writes the log as JSON objects, one per line'");
+    command::COMMANDS
+        .iter()
+        .fold(app, |app, command| app.subcommand(command.app()))
+}
+
+/// Write a completion script of the executable for a shell.
+///
+/// # Arguments
+///
+/// * `shell` - the shell to complete in.
+/// * `writer` - where to write the script.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::bin_util;
+/// let mut script = Vec::new();
+/// bin_util::completions(clap::Shell::Bash, &mut script);
+/// let script = String::from_utf8(script).unwrap();
+/// assert!(script.contains("analyze"));
+/// assert!(script.contains("--write-sidecars"));
+/// ```
+pub fn completions<W: std::io::Write>(shell: clap::Shell, writer: &mut W) {
+    app().gen_completions_to(clap::crate_name!(), shell, writer);
+}
@@ -0,0 +1,182 @@
+//! Subcommand 'compare' lives here.
+//!
+//! Compare tells how two images differ, e.g. two versions of an edited photo:
+//! how far apart their hashes are, their [SSIM and PSNR](crate::analyze::verify),
+//! and how much of them changed. With a diff image, it draws where they changed
+//! as a heatmap, see [`diff`]. Images are decoded the way 'analyze' decodes
+//! them, so large JPEGs are compared at a reduced scale, see [`ImgRaw::decode`].
+
+use crate::analyze::{self, features::ImgFeatures, img::ImgRaw, verify};
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::io::Write;
+
+/// A pixel whose channels differ by more than this part of their range changed,
+/// less is noise of compression.
+pub const CHANGED: f64 = 0.05;
+
+/// How two images of the same size differ pixel by pixel, see [`diff`].
+pub struct Diff {
+    /// How much every pixel differs: black where it doesn't, through red
+    /// and yellow to white where it differs the most, over the first image
+    /// dimmed in grayscale.
+    pub heatmap: image::RgbImage,
+    /// Mean difference of pixels, from 0 to 1.
+    pub mean: f64,
+    /// Part of pixels that [changed](CHANGED), from 0 to 1.
+    pub changed: f64,
+}
+
+/// The size both images are scaled to before they are compared pixel by pixel,
+/// the smaller width and the smaller height, so that neither is scaled up.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::compare;
+/// assert_eq!(compare::common_size((4000, 3000), (1600, 1200)), (1600, 1200));
+/// assert_eq!(compare::common_size((640, 480), (600, 600)), (600, 480));
+/// ```
+pub fn common_size(a: (u32, u32), b: (u32, u32)) -> (u32, u32) {
+    (a.0.min(b.0).max(1), a.1.min(b.1).max(1))
+}
+
+/// Color of a difference from 0 to 1 on the heatmap.
+fn heat(difference: f64) -> [f64; 3] {
+    let d = difference.clamp(0., 1.) * 3.;
+    [d.min(1.), (d - 1.).clamp(0., 1.), (d - 2.).clamp(0., 1.)]
+}
+
+/// Scale two images to their [common size](common_size) and compare them
+/// pixel by pixel.
+///
+/// Pixels differ by their most different channel.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::compare;
+/// let a = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(64, 64, image::Rgb([100, 100, 100])));
+/// let mut b = a.to_rgb8();
+/// for x in 0..16 {
+///     for y in 0..16 {
+///         b.put_pixel(x, y, image::Rgb([255, 100, 100]));
+///     }
+/// }
+/// let b = image::DynamicImage::ImageRgb8(b);
+/// let diff = compare::diff(&a, &b);
+/// assert_eq!(diff.changed, 1. / 16.);
+/// assert_eq!(diff.heatmap.get_pixel(0, 0), &image::Rgb([255, 210, 25]));
+/// assert_eq!(diff.heatmap.get_pixel(32, 32), &image::Rgb([25, 25, 25]));
+/// assert_eq!(compare::diff(&a, &a).changed, 0.);
+/// ```
+pub fn diff(a: &image::DynamicImage, b: &image::DynamicImage) -> Diff {
+    use image::GenericImageView;
+
+    let (width, height) = common_size(a.dimensions(), b.dimensions());
+    let scale = |img: &image::DynamicImage| {
+        let rgb = img.to_rgb8();
+        if rgb.dimensions() == (width, height) {
+            return rgb;
+        }
+        image::imageops::resize(&rgb, width, height, image::imageops::FilterType::Triangle)
+    };
+    let (a, b) = (scale(a), scale(b));
+
+    let (mut total, mut changed) = (0., 0);
+    let heatmap = image::RgbImage::from_fn(width, height, |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let difference = (0..3)
+            .map(|c| (f64::from(pa[c]) - f64::from(pb[c])).abs() / 255.)
+            .fold(0., f64::max);
+        total += difference;
+        if difference > CHANGED {
+            changed += 1;
+        }
+        let luma =
+            (0.299 * f64::from(pa[0]) + 0.587 * f64::from(pa[1]) + 0.114 * f64::from(pa[2])) / 255.;
+        let background = luma / 4.;
+        let color = heat(difference).map(|channel| (channel.max(background) * 255.).round() as u8);
+        image::Rgb(color)
+    });
+    let pixels = f64::from(width) * f64::from(height);
+    Diff {
+        heatmap,
+        mean: total / pixels,
+        changed: f64::from(changed) / pixels,
+    }
+}
+
+/// Lines of a report of how two images differ.
+fn report(a: &ImgRaw<PathBuf>, b: &ImgRaw<PathBuf>, diff: &Diff) -> Vec<String> {
+    let (features_a, features_b) = (ImgFeatures::find(a), ImgFeatures::find(b));
+    let (normalized_a, normalized_b) = (verify::normalize(&a.data), verify::normalize(&b.data));
+    vec![
+        format!(
+            "Hashes differ in {} bits",
+            features_a.lshash.distance(&features_b.lshash)
+        ),
+        format!(
+            "SSIM {:.4}",
+            verify::Method::Ssim.compare(&normalized_a, &normalized_b)
+        ),
+        format!(
+            "PSNR {:.2} dB",
+            verify::Method::Psnr.compare(&normalized_a, &normalized_b)
+        ),
+        format!("Mean difference {:.2}%", diff.mean * 100.),
+        format!("Changed pixels {:.2}%", diff.changed * 100.),
+    ]
+}
+
+/// Compare two images, see [`run`].
+async fn try_run(
+    a: PathBuf,
+    b: PathBuf,
+    output: Option<&Path>,
+) -> Result<f64, Box<dyn std::error::Error>> {
+    let (a, b) = (ImgRaw::load(a).await?, ImgRaw::load(b).await?);
+    let diff = diff(&a.data, &b.data);
+    let mut writer = analyze::output::open(None)?;
+    for line in report(&a, &b, &diff) {
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+    if let Some(output) = output {
+        diff.heatmap.save(output)?;
+        info!("Wrote the difference to '{}'", output.to_string_lossy());
+    }
+    Ok(diff.changed)
+}
+
+/// Print how two images differ, do not propagate errors.
+///
+/// You can think of it as of `main` of the `compare` subcommand.
+/// Returns [`Status::Found`] if any pixels [changed](CHANGED), like `diff` does,
+/// and [`Status::Failed`] if there were errors.
+///
+/// # Arguments
+///
+/// * `a` - the first image.
+/// * `b` - the second image.
+/// * `output` - where to write the heatmap of [`Diff`], in the format its extension tells.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::compare;
+/// # async_std::task::block_on(async {
+/// let output = async_std::path::PathBuf::from("diff.png");
+/// compare::run("a.jpg".into(), "b.jpg".into(), Some(&output)).await;
+/// # });
+/// ```
+pub async fn run(a: PathBuf, b: PathBuf, output: Option<&Path>) -> Status {
+    match try_run(a, b, output).await {
+        Ok(changed) if changed > 0. => Status::Found,
+        Ok(_) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'compare': {}", e);
+            Status::Failed
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! Configuration files of succotash.
+//!
+//! The configuration is a small TOML file (see [`syntax`]), written by the
+//! `init` subcommand (see [`init`](crate::init)). It lives at [`default_path`]
+//! unless told otherwise.
+//!
+//! # Layers
+//!
+//! The configuration is merged from up to four files, each a [`Layer`]
+//! that overrides what the ones before it set, see [`Source`]:
+//!
+//! 1. the system-wide file, see [`system_path`];
+//! 2. the file of the user, see [`default_path`];
+//! 3. the [`WORKING_FILE`] in the working directory;
+//! 4. the [`DIRECTORY_FILE`] at the root of the library being analyzed.
+//!
+//! Options given on the command line override all of them, except for
+//! [`exclude`](Config::exclude) patterns, which they add to. Relative paths
+//! in a file are relative to the directory the file is in.
+
+pub mod syntax;
+
+use crate::analyze::features::{lshash::HashThreshold, set::FeatureSet, FindOptions};
+use crate::analyze::preset::Preset;
+use crate::status::Status;
+use std::path::{Path, PathBuf};
+use syntax::Value;
+
+/// Name of the configuration file at the root of a library.
+pub const DIRECTORY_FILE: &str = ".succotash.toml";
+
+/// Name of the configuration file in the working directory.
+pub const WORKING_FILE: &str = "succotash.toml";
+
+/// How often to run the analysis on its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Schedule {
+    /// Only run when asked.
+    #[default]
+    Never,
+    /// Once a day.
+    Daily,
+    /// Once a week.
+    Weekly,
+}
+
+impl Schedule {
+    /// Name of the schedule, as accepted by [`from_str`](std::str::FromStr::from_str).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Never => "never",
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Self::Never),
+            "daily" => Ok(Self::Daily),
+            "weekly" => Ok(Self::Weekly),
+            _ => Err(format!("unknown schedule '{}'", s)),
+        }
+    }
+}
+
+/// Configuration of succotash.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// Directories with pictures to analyze.
+    pub roots: Vec<PathBuf>,
+    /// Where to keep the index of image features.
+    pub index: Option<PathBuf>,
+    /// Options of the analysis, see [`Preset`].
+    pub preset: Preset,
+    /// How often to run the analysis on its own.
+    pub schedule: Schedule,
+    /// What pixels are compared to when finding hashes, the default if not set.
+    pub threshold: Option<HashThreshold>,
+    /// Names of features images are compared by, the standard ones if not set.
+    pub features: Option<Vec<String>>,
+    /// Glob patterns of files to skip, see [`WalkOptions::exclude`](crate::analyze::walker::WalkOptions::exclude).
+    pub exclude: Vec<String>,
+    /// How many threads to analyze files in, as many as there are cores if not set.
+    pub threads: Option<usize>,
+    /// `criterion=weight` pairs of picking the file to keep, see
+    /// [`Criteria`](crate::analyze::keep::Criteria), the sharpest image if not set.
+    pub keep: Option<Vec<String>>,
+}
+
+/// Where a [`Layer`] comes from, ordered from the lowest precedence to the highest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    /// The system-wide file.
+    System,
+    /// The file of the user.
+    User,
+    /// The file in the working directory.
+    Working,
+    /// The file at the root of a library.
+    Directory,
+}
+
+impl Source {
+    /// Name of the source, for humans.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Working => "working directory",
+            Self::Directory => "directory",
+        }
+    }
+}
+
+/// What one configuration file sets, the rest comes from other layers.
+#[derive(Debug, Default, PartialEq)]
+pub struct Layer {
+    /// See [`Config::roots`].
+    pub roots: Option<Vec<PathBuf>>,
+    /// See [`Config::index`].
+    pub index: Option<PathBuf>,
+    /// See [`Config::preset`].
+    pub preset: Option<Preset>,
+    /// See [`Config::schedule`].
+    pub schedule: Option<Schedule>,
+    /// See [`Config::threshold`].
+    pub threshold: Option<HashThreshold>,
+    /// See [`Config::features`].
+    pub features: Option<Vec<String>>,
+    /// See [`Config::exclude`].
+    pub exclude: Option<Vec<String>>,
+    /// See [`Config::threads`].
+    pub threads: Option<usize>,
+    /// See [`Config::keep`].
+    pub keep: Option<Vec<String>>,
+}
+
+/// Get a string out of a value.
+fn string(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(string) => Ok(string),
+        value => Err(format!(
+            "'{}' has to be a string, not {}",
+            key,
+            value.type_name()
+        )),
+    }
+}
+
+/// Get strings out of an array value.
+fn strings(key: &str, value: Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Array(values) => values.into_iter().map(|value| string(key, value)).collect(),
+        value => Err(format!(
+            "'{}' has to be an array, not {}",
+            key,
+            value.type_name()
+        )),
+    }
+}
+
+impl Layer {
+    /// Parse a configuration file.
+    ///
+    /// Unknown keys are ignored with a warning, so that older versions
+    /// can read files written for newer ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - contents of the file.
+    /// * `base` - what relative paths are relative to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::config::Layer;
+    /// # use libsuccotash::analyze::preset::Preset;
+    /// let layer = Layer::parse("index = 'index'\npreset = 'strict'\n", "/mnt/nas".as_ref()).unwrap();
+    /// assert_eq!(layer.index, Some("/mnt/nas/index".into()));
+    /// assert_eq!(layer.preset, Some(Preset::Strict));
+    /// assert_eq!(layer.roots, None);
+    /// assert!(Layer::parse("preset = 3", "/".as_ref()).is_err());
+    ///
+    /// let layer = Layer::parse("exclude = ['*.tmp']\nthreads = 4\n", "/".as_ref()).unwrap();
+    /// assert_eq!(layer.exclude, Some(vec!["*.tmp".to_owned()]));
+    /// assert_eq!(layer.threads, Some(4));
+    /// assert!(Layer::parse("threads = -1", "/".as_ref()).is_err());
+    ///
+    /// let layer = Layer::parse("keep = ['resolution=2', 'path=1']\n", "/".as_ref()).unwrap();
+    /// assert_eq!(layer.keep, Some(vec!["resolution=2".to_owned(), "path=1".to_owned()]));
+    /// ```
+    pub fn parse(text: &str, base: &Path) -> Result<Self, String> {
+        let mut layer = Self::default();
+        for (key, value) in syntax::parse(text)? {
+            match key.as_str() {
+                "roots" => {
+                    let roots = strings(&key, value)?;
+                    layer.roots = Some(roots.into_iter().map(|root| base.join(root)).collect());
+                }
+                "index" => layer.index = Some(base.join(string(&key, value)?)),
+                "preset" => layer.preset = Some(string(&key, value)?.parse()?),
+                "schedule" => layer.schedule = Some(string(&key, value)?.parse()?),
+                "hash-threshold" => layer.threshold = Some(string(&key, value)?.parse()?),
+                "features" => layer.features = Some(strings(&key, value)?),
+                "exclude" => layer.exclude = Some(strings(&key, value)?),
+                "threads" => {
+                    let threads = match value {
+                        Value::Integer(threads) => std::convert::TryFrom::try_from(threads)
+                            .map_err(|_| format!("'threads' can't be {}", threads))?,
+                        value => {
+                            return Err(format!(
+                                "'threads' has to be an integer, not {}",
+                                value.type_name()
+                            ))
+                        }
+                    };
+                    layer.threads = Some(threads);
+                }
+                "keep" => layer.keep = Some(strings(&key, value)?),
+                _ => warn!("Ignoring unknown configuration key '{}'", key),
+            }
+        }
+        Ok(layer)
+    }
+
+    /// Read a configuration file, `None` if there is no such file.
+    pub fn load(path: &Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        let layer = Self::parse(&text, base)
+            .map_err(|e| format!("invalid configuration '{}': {}", path.display(), e))?;
+        Ok(Some(layer))
+    }
+}
+
+/// Quote a string as a TOML basic string.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\t' => quoted += "\\t",
+            c if c.is_control() => quoted += &format!("\\u{:04X}", u32::from(c)),
+            c => quoted.push(c),
+        }
+    }
+    quoted + "\""
+}
+
+/// Quote strings as a TOML array.
+fn array(strings: &[String]) -> String {
+    let quoted: Vec<String> = strings.iter().map(|s| quote(s)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+impl Config {
+    /// Options of finding features as the configuration sets them, defaults for the rest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::config::Config;
+    /// let config = Config {
+    ///     features: Some(vec!["lshash".into(), "dhash".into()]),
+    ///     ..Default::default()
+    /// };
+    /// let options = config.find_options().unwrap();
+    /// assert_eq!(options.features.names(), vec!["lshash", "dhash"]);
+    /// assert!(options.trim);
+    /// ```
+    pub fn find_options(&self) -> Result<FindOptions, Box<dyn std::error::Error>> {
+        Ok(FindOptions {
+            threshold: self.threshold.unwrap_or_default(),
+            features: match &self.features {
+                Some(names) => FeatureSet::parse(&names.join(","))
+                    .map_err(|e| format!("invalid 'features' in the configuration: {}", e))?
+                    .leak(),
+                None => FeatureSet::standard(),
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Write the configuration as TOML.
+    ///
+    /// Paths that aren't valid UTF-8 are written lossily.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::config::Config;
+    /// let config = Config {
+    ///     roots: vec!["/home/user/Pictures".into()],
+    ///     ..Config::default()
+    /// };
+    /// assert!(config.to_toml().contains("roots = [\"/home/user/Pictures\"]\n"));
+    /// ```
+    pub fn to_toml(&self) -> String {
+        let roots: Vec<String> = self
+            .roots
+            .iter()
+            .map(|root| root.to_string_lossy().into_owned())
+            .collect();
+
+        let mut toml = format!("roots = {}\n", array(&roots));
+        if let Some(index) = &self.index {
+            toml += &format!("index = {}\n", quote(&index.to_string_lossy()));
+        }
+        toml += &format!("preset = {}\n", quote(self.preset.name()));
+        toml += &format!("schedule = {}\n", quote(self.schedule.name()));
+        if let Some(threshold) = self.threshold {
+            toml += &format!("hash-threshold = {}\n", quote(threshold.name()));
+        }
+        if let Some(features) = &self.features {
+            toml += &format!("features = {}\n", array(features));
+        }
+        if !self.exclude.is_empty() {
+            toml += &format!("exclude = {}\n", array(&self.exclude));
+        }
+        if let Some(threads) = self.threads {
+            toml += &format!("threads = {}\n", threads);
+        }
+        if let Some(keep) = &self.keep {
+            toml += &format!("keep = {}\n", array(keep));
+        }
+        toml
+    }
+
+    /// Override what a layer sets.
+    pub fn apply(&mut self, layer: Layer) {
+        if let Some(roots) = layer.roots {
+            self.roots = roots;
+        }
+        if let Some(index) = layer.index {
+            self.index = Some(index);
+        }
+        if let Some(preset) = layer.preset {
+            self.preset = preset;
+        }
+        if let Some(schedule) = layer.schedule {
+            self.schedule = schedule;
+        }
+        if let Some(threshold) = layer.threshold {
+            self.threshold = Some(threshold);
+        }
+        if let Some(features) = layer.features {
+            self.features = Some(features);
+        }
+        if let Some(exclude) = layer.exclude {
+            self.exclude = exclude;
+        }
+        if let Some(threads) = layer.threads {
+            self.threads = Some(threads);
+        }
+        if let Some(keep) = layer.keep {
+            self.keep = Some(keep);
+        }
+    }
+
+    /// Write the configuration to a file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_toml())
+    }
+}
+
+/// A directory from an XDG environment variable, or a directory in home.
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    match std::env::var_os(variable) {
+        Some(dir) if !dir.is_empty() => Some(dir.into()),
+        _ => std::env::var_os("HOME").map(|home| Path::new(&home).join(fallback)),
+    }
+}
+
+/// Where the configuration file is by default,
+/// `$XDG_CONFIG_HOME/succotash/succotash.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("succotash").join("succotash.toml"))
+}
+
+/// Where the system-wide configuration file is,
+/// `/etc/succotash/succotash.toml` on Unix, `%ProgramData%\succotash\succotash.toml` on Windows.
+pub fn system_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("ProgramData")
+            .map(|dir| Path::new(&dir).join("succotash").join("succotash.toml"))
+    } else {
+        Some(PathBuf::from("/etc/succotash/succotash.toml"))
+    }
+}
+
+/// Files the configuration can be merged from, ordered by [`Source`].
+///
+/// # Arguments
+///
+/// * `library` - root of the library being analyzed, if any.
+pub fn layer_paths(library: Option<&Path>) -> Vec<(Source, PathBuf)> {
+    let mut paths = Vec::new();
+    paths.extend(system_path().map(|path| (Source::System, path)));
+    paths.extend(default_path().map(|path| (Source::User, path)));
+    paths.extend(
+        std::env::current_dir()
+            .ok()
+            .map(|dir| (Source::Working, dir.join(WORKING_FILE))),
+    );
+    paths.extend(library.map(|dir| (Source::Directory, dir.join(DIRECTORY_FILE))));
+    paths
+}
+
+/// Merge the configuration from all the files there are.
+///
+/// Returns the configuration and the files it was merged from.
+///
+/// # Arguments
+///
+/// * `library` - root of the library being analyzed, if any.
+pub fn load(library: Option<&Path>) -> Result<(Config, Vec<PathBuf>), Box<dyn std::error::Error>> {
+    let mut config = Config::default();
+    let mut loaded = Vec::new();
+    for (source, path) in layer_paths(library) {
+        if let Some(layer) = Layer::load(&path)? {
+            debug!("Using {} configuration '{}'", source.name(), path.display());
+            config.apply(layer);
+            loaded.push(path);
+        }
+    }
+    Ok((config, loaded))
+}
+
+/// Print the configuration files, or the configuration merged from them.
+///
+/// # Arguments
+///
+/// * `library` - root of the library the configuration is for, if any.
+/// * `effective` - whether to print the merged configuration.
+fn try_show(library: Option<&Path>, effective: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if effective {
+        let (config, loaded) = load(library)?;
+        for path in loaded {
+            println!("# Merged from '{}'", path.display());
+        }
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
+
+    for (source, path) in layer_paths(library) {
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                println!("# {} configuration '{}'", source.name(), path.display());
+                print!("{}", text);
+                if !text.is_empty() && !text.ends_with('\n') {
+                    println!();
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                println!(
+                    "# {} configuration '{}' doesn't exist",
+                    source.name(),
+                    path.display()
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Print the configuration, do not propagate errors.
+///
+/// You can think of it as of `main` of the `config show` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::config;
+/// config::show(Some("/home/user/Pictures".as_ref()), true);
+/// ```
+pub fn show(library: Option<&Path>, effective: bool) -> Status {
+    match try_show(library, effective) {
+        Ok(_) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'config show': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Where the index is by default, `$XDG_CACHE_HOME/succotash/index`.
+pub fn default_index_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache").map(|dir| dir.join("succotash").join("index"))
+}
+
+/// Where a run records what it does by default, a new
+/// [journal](crate::journal) in `$XDG_STATE_HOME/succotash` named after
+/// the time, e.g. `journal-20240301-120000`.
+pub fn default_journal_path() -> Option<PathBuf> {
+    let name = chrono::Local::now()
+        .format("journal-%Y%m%d-%H%M%S")
+        .to_string();
+    xdg_dir("XDG_STATE_HOME", ".local/state").map(|dir| dir.join("succotash").join(name))
+}
@@ -0,0 +1,231 @@
+//! Reading the subset of TOML configuration files are written in.
+//!
+//! Supported are `key = value` pairs with strings (basic and literal),
+//! integers, floats, booleans and arrays of them, and comments. Tables
+//! aren't, the configuration is flat.
+
+/// A value of a key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// `"basic"` or `'literal'`.
+    String(String),
+    /// `42`.
+    Integer(i64),
+    /// `0.5`.
+    Float(f64),
+    /// `true` or `false`.
+    Boolean(bool),
+    /// `[1, 2, 3]`, can span lines.
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// Name of the type of the value, for errors.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "a string",
+            Self::Integer(_) => "an integer",
+            Self::Float(_) => "a float",
+            Self::Boolean(_) => "a boolean",
+            Self::Array(_) => "an array",
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        format!("line {}: {}", line, message)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    /// Skip spaces and tabs, and newlines and comments if `lines` is set.
+    fn skip(&mut self, lines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if lines => {}
+                '#' => {
+                    let end = self.text[self.pos..]
+                        .find('\n')
+                        .unwrap_or(self.text.len() - self.pos);
+                    self.pos += end;
+                    continue;
+                }
+                _ => return,
+            }
+            self.bump();
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.bump();
+                }
+                if start == self.pos {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(self.text[start..self.pos].to_owned())
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some(u @ ('u' | 'U')) => {
+                            let len = if u == 'u' { 4 } else { 8 };
+                            let hex = self.text.get(self.pos..self.pos + len).unwrap_or("");
+                            self.pos += hex.len();
+                            u32::from_str_radix(hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    string.push(escaped);
+                }
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let start = self.pos;
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(self.text[start..self.pos - 1].to_owned()),
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => {
+                self.bump();
+                let mut values = Vec::new();
+                loop {
+                    self.skip(true);
+                    if self.peek() == Some(']') {
+                        self.bump();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip(true);
+                    match self.bump() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || "+-._".contains(c))
+                {
+                    self.bump();
+                }
+                let word = &self.text[start..self.pos];
+                let number = word.replace('_', "");
+                match word {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => number
+                        .parse()
+                        .map(Value::Integer)
+                        .or_else(|_| number.parse().map(Value::Float))
+                        .map_err(|_| self.error(&format!("invalid value '{}'", word))),
+                }
+            }
+        }
+    }
+}
+
+/// Parse key/value pairs, in the order they are in the text.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::config::syntax::{parse, Value};
+/// let text = "# Pictures\nroots = [\n  '/home/user/Pictures', # mine\n  \"/mnt/nas\",\n]\njobs = 4\n";
+/// let pairs = parse(text).unwrap();
+/// assert_eq!(pairs[1], ("jobs".to_owned(), Value::Integer(4)));
+/// assert!(parse("[table]").is_err());
+/// assert!(parse("key = \"unterminated").is_err());
+/// ```
+pub fn parse(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut parser = Parser { text, pos: 0 };
+    let mut pairs: Vec<(String, Value)> = Vec::new();
+    loop {
+        parser.skip(true);
+        match parser.peek() {
+            None => return Ok(pairs),
+            Some('[') => return Err(parser.error("tables aren't supported")),
+            Some(_) => {}
+        }
+
+        let key = parser.key()?;
+        if pairs.iter().any(|(other, _)| *other == key) {
+            return Err(parser.error(&format!("'{}' is set twice", key)));
+        }
+        parser.skip(false);
+        parser.expect('=')?;
+        parser.skip(false);
+        let value = parser.value()?;
+        parser.skip(false);
+        if !matches!(parser.bump(), Some('\n') | None) {
+            return Err(parser.error("expected the end of the line"));
+        }
+        pairs.push((key, value));
+    }
+}
@@ -0,0 +1,129 @@
+//! Subcommand 'client' lives here.
+//!
+//! The client sends queries to a running [`daemon`](super) and prints
+//! the answers the way `index query` does.
+
+use crate::analyze::output;
+use crate::status::Status;
+use async_std::io::BufReader;
+use async_std::os::unix::net::UnixStream;
+use async_std::path::{Path, PathBuf};
+use async_std::prelude::*;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::io::Write;
+
+/// A file with similar images, as the daemon answers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    /// Where the file is.
+    pub path: PathBuf,
+    /// How many bits its closest hash differs in.
+    pub distance: u32,
+}
+
+/// Ask the daemon on `socket` for files with images similar to those in `image`.
+///
+/// # Arguments
+///
+/// * `socket` - where the socket of the daemon is.
+/// * `image` - the file to find similar images to.
+/// * `max_distance` - how many bits hashes of similar images can differ in,
+///   what the daemon was started with if not given.
+pub async fn try_query(
+    socket: &Path,
+    image: &Path,
+    max_distance: Option<u32>,
+) -> Result<Vec<Match>, Box<dyn std::error::Error>> {
+    // The daemon runs elsewhere, relative paths mean nothing to it.
+    let image = image.canonicalize().await?;
+    let stream = UnixStream::connect(socket).await.map_err(|e| {
+        format!(
+            "couldn't connect to the daemon on '{}', is it running? {}",
+            socket.to_string_lossy(),
+            e
+        )
+    })?;
+    let mut request = json!({ "query": image.to_string_lossy() });
+    if let Some(max_distance) = max_distance {
+        request["max_distance"] = max_distance.into();
+    }
+    (&stream)
+        .write_all(format!("{}\n", request).as_bytes())
+        .await?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).await?;
+    let response: Value = serde_json::from_str(&line)
+        .map_err(|e| format!("malformed response of the daemon: {}", e))?;
+    if let Some(e) = response["error"].as_str() {
+        return Err(format!("the daemon couldn't answer: {}", e).into());
+    }
+    response["matches"]
+        .as_array()
+        .ok_or("the daemon answered without 'matches'")?
+        .iter()
+        .map(|found| {
+            let path = found["path"].as_str();
+            let distance = found["distance"].as_u64().map(u32::try_from);
+            match (path, distance) {
+                (Some(path), Some(Ok(distance))) => Ok(Match {
+                    path: path.into(),
+                    distance,
+                }),
+                _ => Err(format!("malformed match '{}' in the response", found).into()),
+            }
+        })
+        .collect()
+}
+
+/// Print files with images similar to those in a file, asking a daemon,
+/// do not propagate errors.
+///
+/// Prints the same as [`index::query`](crate::analyze::index::query), on stdout,
+/// or in `output` if given.
+/// You can think of it as of `main` of the `client query` subcommand.
+/// Returns [`Status::Found`] if similar images were found, and
+/// [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::daemon::{self, client};
+/// async_std::task::block_on(client::query(
+///     &daemon::default_socket(),
+///     "/home/user/Downloads/sunset.jpg".as_ref(),
+///     None,
+///     None,
+/// ));
+/// ```
+pub async fn query(
+    socket: &Path,
+    image: &Path,
+    max_distance: Option<u32>,
+    output: Option<PathBuf>,
+) -> Status {
+    let written = try_query(socket, image, max_distance)
+        .await
+        .and_then(|found| {
+            info!("Found {} files with similar images", found.len());
+            let mut writer = output::open(output.as_deref())?;
+            for found in &found {
+                writeln!(
+                    writer,
+                    "{}\t{}",
+                    found.distance,
+                    found.path.to_string_lossy()
+                )?;
+            }
+            writer.flush()?;
+            Ok(Status::found(found.len()))
+        });
+    match written {
+        Ok(status) => status,
+        Err(e) => {
+            error!("Error during 'client query': {}", e);
+            Status::Failed
+        }
+    }
+}
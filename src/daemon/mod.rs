@@ -0,0 +1,267 @@
+//! Subcommand 'daemon' lives here.
+//!
+//! The daemon reads an index once and keeps it in memory, answering queries
+//! to it over a unix domain socket, so that a large index isn't read again
+//! for every query, see [`client`]. Every request and every response is
+//! a JSON document on a line of its own, and a connection can carry many:
+//!
+//! ```json
+//! {"query": "/home/user/Downloads/sunset.jpg", "max_distance": 4}
+//! {"matches": [{"distance": 2, "path": "/home/user/Pictures/sunset.jpg"}]}
+//! ```
+//!
+//! `max_distance` is optional and overrides how many bits hashes of similar
+//! images can differ in. Paths are read by the daemon, so they have to be
+//! absolute. A request that fails is answered with `{"error": "..."}`.
+//!
+//! Given an address to expose [metrics](crate::serve::metrics) on,
+//! the daemon answers `GET /metrics` there over HTTP, for Prometheus.
+
+pub mod client;
+
+use crate::analyze::index::{self, query, store};
+use crate::analyze::metrics::METRICS;
+use crate::analyze::AnalyzeOptions;
+use crate::serve::http::{self, Response};
+use crate::serve::metrics;
+use crate::status::Status;
+use async_std::io::BufReader;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::path::{Path, PathBuf};
+use async_std::prelude::*;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::time::Instant;
+
+/// Where the socket is unless told otherwise,
+/// `succotash.sock` in `$XDG_RUNTIME_DIR` or in the temporary directory.
+pub fn default_socket() -> PathBuf {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    dir.join("succotash.sock").into()
+}
+
+/// What the daemon answers queries with.
+struct Daemon {
+    /// Images of the index, ready to be queried.
+    query: query::Query<'static>,
+    /// Files of the index, for metrics.
+    files: usize,
+    /// How to find features of queried images, how to search the index
+    /// and how close similar images are.
+    options: AnalyzeOptions,
+}
+
+impl Daemon {
+    /// Answer a request, a JSON document.
+    async fn respond(&self, request: &str) -> Result<Value, Box<dyn std::error::Error>> {
+        let request: Value = serde_json::from_str(request)?;
+        let image = request["query"]
+            .as_str()
+            .ok_or("no 'query' path to find similar images to")?;
+        if !Path::new(image).is_absolute() {
+            return Err(format!("'{}' is not an absolute path", image).into());
+        }
+        let max_distance = match &request["max_distance"] {
+            Value::Null => self.options.max_distance,
+            max_distance => max_distance
+                .as_u64()
+                .and_then(|max_distance| u32::try_from(max_distance).ok())
+                .ok_or("'max_distance' has to be a number of bits")?,
+        };
+        METRICS.queries.inc();
+
+        let started = Instant::now();
+        let features = index::features_of(image.into(), &self.options).await;
+        METRICS.hashing.observe(started.elapsed());
+        let features = features.inspect_err(|_| METRICS.decode_failures.inc())?;
+
+        let set = self
+            .options
+            .features
+            .clone()
+            .with_max_distance("lshash", f64::from(max_distance));
+        let started = Instant::now();
+        let found = index::similar_to_any(&self.query, Path::new(image), &features, &set);
+        METRICS.querying.observe(started.elapsed());
+        debug!("Found {} files similar to '{}'", found.len(), image);
+        let matches: Vec<Value> = found
+            .iter()
+            .map(|found| {
+                json!({
+                    "path": found.path.to_string_lossy(),
+                    "distance": found.distance,
+                })
+            })
+            .collect();
+        Ok(json!({ "matches": matches }))
+    }
+
+    /// Answer requests coming on `stream` until it is closed.
+    async fn handle(&self, stream: UnixStream) -> std::io::Result<()> {
+        let mut lines = BufReader::new(&stream).lines();
+        while let Some(line) = lines.next().await {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match self.respond(&line).await.map_err(|e| e.to_string()) {
+                Ok(response) => response,
+                Err(e) => {
+                    debug!("Responding with an error: {}", e);
+                    json!({ "error": e })
+                }
+            };
+            (&stream)
+                .write_all(format!("{}\n", response).as_bytes())
+                .await?;
+            (&stream).flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Answer a request for metrics coming on `stream`.
+async fn expose(daemon: &Daemon, stream: TcpStream) -> std::io::Result<()> {
+    let request = http::read_request(&mut &stream, &mut &stream).await;
+    let response = match request.map_err(|e| e.to_string()) {
+        Ok(request) => match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/metrics") => {
+                let metrics = metrics::render(daemon.files);
+                Response::bytes(metrics::CONTENT_TYPE, metrics.into_bytes())
+            }
+            (_, "/metrics") => Response::error(405, "method not allowed"),
+            _ => Response::error(404, "no such endpoint"),
+        },
+        Err(e) => Response::error(400, &e),
+    };
+    (&stream).write_all(&response.to_bytes()).await?;
+    (&stream).flush().await
+}
+
+/// Expose metrics of `daemon` on `listener` until the process is stopped.
+async fn expose_all(daemon: &'static Daemon, listener: TcpListener) {
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                async_std::task::spawn(async move {
+                    if let Err(e) = expose(daemon, stream).await {
+                        debug!("Couldn't respond with metrics: {}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("Couldn't accept a connection for metrics: {}", e),
+        }
+    }
+}
+
+/// Listen on `socket`, taking it over from a daemon that is gone.
+async fn bind(socket: &Path) -> Result<UnixListener, Box<dyn std::error::Error>> {
+    if socket.exists().await {
+        if UnixStream::connect(socket).await.is_ok() {
+            return Err(format!(
+                "a daemon is running on '{}' already",
+                socket.to_string_lossy()
+            )
+            .into());
+        }
+        debug!(
+            "Removing the socket '{}' left behind",
+            socket.to_string_lossy()
+        );
+        async_std::fs::remove_file(socket).await?;
+    }
+    Ok(UnixListener::bind(socket).await?)
+}
+
+/// Answer queries to an index over a unix domain socket until the process is stopped.
+///
+/// # Arguments
+///
+/// * `socket` - where to create the socket.
+/// * `metrics` - the address to expose metrics on over HTTP, e.g. `127.0.0.1:9464`, if any.
+/// * `index` - where the index file is.
+/// * `options` - how to find features of queried images, how to search
+///   the index and [how close](AnalyzeOptions::max_distance) similar images are.
+pub async fn try_run(
+    socket: &Path,
+    metrics: Option<&str>,
+    index: &str,
+    options: AnalyzeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The daemon runs until the process exits, so the index is kept for as long.
+    let snapshot: &'static store::Snapshot = Box::leak(Box::new(store::Snapshot::open(index)?));
+    index::warn_stale(snapshot, index, &options);
+    let daemon: &'static Daemon = Box::leak(Box::new(Daemon {
+        query: query::Query::new(snapshot, options.search),
+        files: snapshot.len(),
+        options,
+    }));
+
+    if let Some(metrics) = metrics {
+        let listener = TcpListener::bind(metrics).await?;
+        info!(
+            "Exposing metrics on http://{}/metrics",
+            listener.local_addr()?
+        );
+        async_std::task::spawn(expose_all(daemon, listener));
+    }
+    let listener = bind(socket).await?;
+    info!(
+        "Answering queries to {} files of '{}' on '{}'",
+        snapshot.len(),
+        index,
+        socket.to_string_lossy()
+    );
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Couldn't accept a connection: {}", e);
+                continue;
+            }
+        };
+        async_std::task::spawn(async move {
+            if let Err(e) = daemon.handle(stream).await {
+                debug!("Couldn't respond: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Answer queries to an index over a unix domain socket, do not propagate errors.
+///
+/// You can think of it as of `main` of the `daemon` subcommand.
+/// Returns [`Status::Failed`] if the daemon couldn't start or stopped answering.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::daemon;
+/// async_std::task::block_on(daemon::run(
+///     &daemon::default_socket(),
+///     None,
+///     "/home/user/photos.index",
+///     Default::default(),
+/// ));
+/// ```
+pub async fn run(
+    socket: &Path,
+    metrics: Option<&str>,
+    index: &str,
+    options: AnalyzeOptions,
+) -> Status {
+    match try_run(socket, metrics, index, options).await {
+        Ok(()) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'daemon': {}", e);
+            Status::Failed
+        }
+    }
+}
@@ -0,0 +1,625 @@
+//! Subcommand 'db' lives here.
+//!
+//! A database keeps a photo library indexed between runs, rather than
+//! scanning it anew every time: it is a directory with features of images
+//! in an [index](crate::analyze::index) and what is known of their files,
+//! see [`Record`]. Directories are [added](add) to it, and of their files
+//! only new and changed ones are analyzed. Files are [removed](remove) by
+//! patterns, the database is [searched](search) for images similar to
+//! a given one, and [deduplicated](dedupe) into groups of similar images,
+//! which every file remembers until the next time. Files can be [tagged](tag)
+//! too, e.g. with the trip they are from, to search and deduplicate only
+//! those with some tags.
+//!
+//! # Layout
+//!
+//! `index` in the directory is the store of features, see
+//! [`store`](crate::analyze::index::store). `files` is a text file: the first
+//! line is [`HEADER`], every other line has tab-separated [escaped](escape)
+//! path of a file, its size in bytes, its modification time in seconds since
+//! the Unix epoch, the number of its group, or `-` if it is in none, and its
+//! comma-separated tags. Files of the previous version have no tags.
+
+use crate::analyze::index::store::{self, escape, unescape, Store};
+use crate::analyze::walker::{self, glob::Pattern};
+use crate::analyze::{find_features, index, output, AnalyzeOptions};
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead, Write};
+use std::time::UNIX_EPOCH;
+
+/// The first line of the `files` file, holds the version of the format.
+pub const HEADER: &str = "succotash-db 2";
+
+/// First lines of `files` files of older versions that can still be read.
+const OLD_HEADERS: [&str; 1] = ["succotash-db 1"];
+
+/// Name of the store of features in a database.
+pub const INDEX: &str = "index";
+
+/// Name of the file with [`Record`]s of files in a database.
+pub const FILES: &str = "files";
+
+/// What a database knows of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Size in bytes, when it was analyzed.
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch, when it was analyzed.
+    pub modified: u64,
+    /// Number of its group of similar images, from 1, as of the last [`dedupe`].
+    pub group: Option<usize>,
+    /// Tags it was given, see [`tag`].
+    pub tags: BTreeSet<String>,
+}
+
+/// Check that a tag can be kept: it is neither empty nor has commas or control characters.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::db;
+/// assert!(db::check_tag("vacation 2022").is_ok());
+/// assert!(db::check_tag("").is_err());
+/// assert!(db::check_tag("cats,dogs").is_err());
+/// ```
+pub fn check_tag(tag: &str) -> Result<(), String> {
+    if tag.is_empty() || tag.contains(|c: char| c == ',' || c.is_control()) {
+        return Err(format!(
+            "tag '{}' is empty or has commas or control characters",
+            tag.escape_debug()
+        ));
+    }
+    Ok(())
+}
+
+impl Record {
+    /// A record of a file as it is now, in no group.
+    pub fn of(metadata: &std::fs::Metadata) -> Self {
+        let modified = metadata.modified().ok();
+        let since_epoch = modified.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+        Self {
+            size: metadata.len(),
+            modified: since_epoch.map_or(0, |since| since.as_secs()),
+            group: None,
+            tags: BTreeSet::new(),
+        }
+    }
+
+    /// Whether the file has all of `tags`.
+    pub fn has_all(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    /// Whether the file is as it was when it was analyzed.
+    pub fn is_current(&self, now: &Self) -> bool {
+        (self.size, self.modified) == (now.size, now.modified)
+    }
+
+    /// The record of `path` as a line of the `files` file, without the line break.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::db::Record;
+    /// let record = Record {
+    ///     size: 2048,
+    ///     modified: 1700000000,
+    ///     group: Some(3),
+    ///     tags: ["cats".to_owned(), "vacation 2022".to_owned()].into(),
+    /// };
+    /// let line = record.encode("/photos/a\tb.jpg".as_ref());
+    /// assert_eq!(line, "/photos/a\\tb.jpg\t2048\t1700000000\t3\tcats,vacation 2022");
+    /// assert_eq!(Record::decode(&line), Some(("/photos/a\tb.jpg".into(), record)));
+    /// assert_eq!(Record::decode("/photos/a.jpg\t2048"), None);
+    /// // Of the previous version, without tags.
+    /// assert!(Record::decode("/photos/a.jpg\t2048\t1700000000\t-").unwrap().1.tags.is_empty());
+    /// ```
+    pub fn encode(&self, path: &Path) -> String {
+        let group = self.group.map_or("-".to_owned(), |group| group.to_string());
+        let tags: Vec<&str> = self.tags.iter().map(String::as_str).collect();
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            escape(&path.to_string_lossy()),
+            self.size,
+            self.modified,
+            group,
+            tags.join(",")
+        )
+    }
+
+    /// Read a path and its record from a line of the `files` file.
+    pub fn decode(line: &str) -> Option<(PathBuf, Self)> {
+        let mut fields = line.split('\t');
+        let path = unescape(fields.next()?).into();
+        let record = Self {
+            size: fields.next()?.parse().ok()?,
+            modified: fields.next()?.parse().ok()?,
+            group: match fields.next()? {
+                "-" => None,
+                group => Some(group.parse().ok()?),
+            },
+            tags: match fields.next() {
+                Some(tags) => tags
+                    .split(',')
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+                None => BTreeSet::new(),
+            },
+        };
+        fields.next().is_none().then_some((path, record))
+    }
+}
+
+/// A database, see the [module](self).
+#[derive(Debug)]
+pub struct Db {
+    /// Where the database is.
+    dir: PathBuf,
+    /// What is known of files in it, by path.
+    pub files: BTreeMap<PathBuf, Record>,
+}
+
+impl Db {
+    /// Create an empty database in a directory, creating the directory too.
+    ///
+    /// Fails if there is a database there already.
+    pub fn init(dir: &Path) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let files = dir.join(FILES);
+        if std::fs::metadata(&files).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("there is a database in '{}' already", dir.to_string_lossy()),
+            ));
+        }
+        Store::open(dir.join(INDEX))?;
+        let db = Self {
+            dir: dir.to_path_buf(),
+            files: BTreeMap::new(),
+        };
+        db.save()?;
+        Ok(db)
+    }
+
+    /// Open the database in a directory.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(FILES);
+        let file = std::fs::File::open(&path).map_err(|e| {
+            let dir = dir.to_string_lossy();
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "there is no database in '{}' ({}), create one with 'db init'",
+                    dir, e
+                ),
+            )
+        })?;
+        let mut lines = io::BufReader::new(file).lines();
+        let header = lines.next().transpose()?;
+        let header = header.as_deref().unwrap_or_default();
+        if header != HEADER && !OLD_HEADERS.contains(&header) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("'{}' is not a succotash database", path.to_string_lossy()),
+            ));
+        }
+        let mut files = BTreeMap::new();
+        for (number, line) in lines.enumerate() {
+            let line = line?;
+            let (path, record) = Record::decode(&line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "line {} of '{}' is broken",
+                        number + 2,
+                        path.to_string_lossy()
+                    ),
+                )
+            })?;
+            files.insert(path, record);
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            files,
+        })
+    }
+
+    /// Where the store of features of the database is.
+    pub fn index(&self) -> PathBuf {
+        self.dir.join(INDEX)
+    }
+
+    /// Tags of tagged files, by path, see [`Files::tags`](walker::Files::tags).
+    pub fn tags(&self) -> HashMap<PathBuf, Vec<String>> {
+        self.files
+            .iter()
+            .filter(|(_, record)| !record.tags.is_empty())
+            .map(|(path, record)| (path.clone(), record.tags.iter().cloned().collect()))
+            .collect()
+    }
+
+    /// A snapshot of the store of features, of files with all of `tags` only.
+    pub fn snapshot(&self, tags: &[String]) -> io::Result<store::Snapshot> {
+        let mut snapshot = store::Snapshot::open(self.index())?;
+        if !tags.is_empty() {
+            let files = &self.files;
+            snapshot.retain(|path| files.get(path).is_some_and(|record| record.has_all(tags)));
+        }
+        Ok(snapshot)
+    }
+
+    /// Write what is known of files, replacing the `files` file atomically.
+    pub fn save(&self) -> io::Result<()> {
+        let path = self.dir.join(FILES);
+        let temporary = self.dir.join(format!(".{}.succotash-db", FILES));
+        let mut writer = io::BufWriter::new(std::fs::File::create(&temporary)?);
+        writeln!(writer, "{}", HEADER)?;
+        for (path, record) in &self.files {
+            writeln!(writer, "{}", record.encode(path))?;
+        }
+        writer.into_inner()?.sync_data()?;
+        std::fs::rename(&temporary, &path)
+    }
+}
+
+/// Create an empty database, do not propagate errors.
+///
+/// You can think of it as of `main` of the `db init` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::{db, status::Status};
+/// let dir = std::env::temp_dir().join("succotash-db-init");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// assert_eq!(db::init(dir.as_ref()), Status::Clean);
+/// assert!(db::Db::open(dir.as_ref()).unwrap().files.is_empty());
+/// assert_eq!(db::init(dir.as_ref()), Status::Failed);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn init(dir: &Path) -> Status {
+    match Db::init(dir) {
+        Ok(_) => {
+            info!("Created a database in '{}'", dir.to_string_lossy());
+            Status::Clean
+        }
+        Err(e) => {
+            error!("Error during 'db init': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Add files in directories to a database, see [`add`].
+///
+/// New files and files changed since they were added are analyzed,
+/// the rest are left as they are. Returns how many files were analyzed.
+pub async fn try_add(
+    dir: &Path,
+    dirs: Vec<String>,
+    mut options: AnalyzeOptions,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut db = Db::open(dir)?;
+    // Paths are kept whole, so that they mean the same from anywhere.
+    let mut inputs = Vec::with_capacity(dirs.len());
+    for input in dirs {
+        let canonical = async_std::fs::canonicalize(&input).await;
+        inputs.push(canonical.map_or(input, |path| path.to_string_lossy().into_owned()));
+    }
+    let files = walker::Source::Walk(inputs)
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+
+    let (mut changed, mut unchanged) = (Vec::new(), 0);
+    let mut records = BTreeMap::new();
+    for path in &files.paths {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            continue;
+        };
+        let now = Record::of(&metadata);
+        match db.files.get(path) {
+            Some(record) if record.is_current(&now) => {
+                unchanged += 1;
+                continue;
+            }
+            Some(_) => changed.push(path.clone()),
+            None => {}
+        }
+        records.insert(path.clone(), now);
+    }
+    if !changed.is_empty() {
+        // Features of changed files are found again, not reused.
+        let mut store = Store::open(db.index())?;
+        for path in &changed {
+            store.remove(path)?;
+        }
+    }
+
+    options.index = Some(db.index());
+    options.resume = true;
+    let paths = records.keys().cloned().collect();
+    let imgs = find_features(paths, &options).await?;
+    let mut added = 0;
+    for img in &imgs {
+        if let Some(record) = records.remove(&img.path) {
+            db.files.insert(img.path.clone(), record);
+            added += 1;
+        }
+    }
+    db.save()?;
+    info!(
+        "Analyzed {} files into '{}' ({} of them changed since), {} were unchanged",
+        added,
+        dir.to_string_lossy(),
+        changed.len(),
+        unchanged
+    );
+    Ok(added)
+}
+
+/// Add files in directories to a database, do not propagate errors.
+///
+/// You can think of it as of `main` of the `db add` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Arguments
+///
+/// * `dir` - where the database is.
+/// * `dirs` - directories to walk.
+/// * `options` - what files to pick and how to find their features.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::db;
+/// async_std::task::block_on(db::add(
+///     "/home/user/photos.db".as_ref(),
+///     vec!["/home/user/Pictures".into()],
+///     Default::default(),
+/// ));
+/// ```
+pub async fn add(dir: &Path, dirs: Vec<String>, options: AnalyzeOptions) -> Status {
+    match try_add(dir, dirs, options).await {
+        Ok(_) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'db add': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Remove files matching any of `patterns` from a database, see [`remove`].
+///
+/// Returns how many files were removed.
+pub fn try_remove(dir: &Path, patterns: &[Pattern]) -> io::Result<usize> {
+    let mut db = Db::open(dir)?;
+    let removed: Vec<PathBuf> = db
+        .files
+        .keys()
+        .filter(|path| patterns.iter().any(|p| p.matches_file(path.as_ref())))
+        .cloned()
+        .collect();
+    let mut store = Store::open(db.index())?;
+    for path in &removed {
+        store.remove(path)?;
+        db.files.remove(path);
+    }
+    store.sync()?;
+    db.save()?;
+    Ok(removed.len())
+}
+
+/// Remove files matching patterns from a database, do not propagate errors.
+///
+/// Patterns with a separator match whole paths, e.g. `/photos/2019/**`, and
+/// the rest match file names, e.g. `*.gif`.
+/// You can think of it as of `main` of the `db remove` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::{analyze::walker::glob::Pattern, db};
+/// db::remove("/home/user/photos.db".as_ref(), &[Pattern::new("**/Trash/**").unwrap()]);
+/// ```
+pub fn remove(dir: &Path, patterns: &[Pattern]) -> Status {
+    match try_remove(dir, patterns) {
+        Ok(removed) => {
+            info!("Removed {} files from '{}'", removed, dir.to_string_lossy());
+            Status::Clean
+        }
+        Err(e) => {
+            error!("Error during 'db remove': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Give tags to files matching a pattern in a database, or take them away, see [`tag`].
+///
+/// Returns how many files were matched.
+pub fn try_tag(dir: &Path, pattern: &Pattern, tags: &[String], untag: bool) -> io::Result<usize> {
+    for tag in tags {
+        check_tag(tag).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+    let mut db = Db::open(dir)?;
+    let mut matched = 0;
+    for (path, record) in &mut db.files {
+        if !pattern.matches_file(path.as_ref()) {
+            continue;
+        }
+        for tag in tags {
+            if untag {
+                record.tags.remove(tag);
+            } else {
+                record.tags.insert(tag.clone());
+            }
+        }
+        matched += 1;
+    }
+    db.save()?;
+    Ok(matched)
+}
+
+/// Give tags to files matching a pattern in a database, or take them away, do not propagate errors.
+///
+/// A pattern with a separator matches whole paths, e.g. `/photos/2022/Rome/**`,
+/// and one without matches file names, e.g. `IMG_2022*`. Tags can't be empty
+/// or have commas, see [`check_tag`].
+/// You can think of it as of `main` of the `db tag` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Arguments
+///
+/// * `dir` - where the database is.
+/// * `pattern` - which files to tag.
+/// * `tags` - the tags.
+/// * `untag` - whether to take the tags away rather than give them.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::{analyze::walker::glob::Pattern, db};
+/// let pattern = Pattern::new("/home/user/Pictures/2022/Rome/**").unwrap();
+/// db::tag("/home/user/photos.db".as_ref(), &pattern, &["vacation2022".into()], false);
+/// ```
+pub fn tag(dir: &Path, pattern: &Pattern, tags: &[String], untag: bool) -> Status {
+    match try_tag(dir, pattern, tags, untag) {
+        Ok(matched) => {
+            let done = if untag { "Untagged" } else { "Tagged" };
+            info!("{} {} files in '{}'", done, matched, dir.to_string_lossy());
+            Status::Clean
+        }
+        Err(e) => {
+            error!("Error during 'db tag': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Find files in a database with images similar to those in a file, see [`search`].
+///
+/// Only files with all of `tags` are searched.
+pub async fn try_search(
+    dir: &Path,
+    image: PathBuf,
+    tags: &[String],
+    options: &AnalyzeOptions,
+) -> Result<Vec<index::query::Match>, Box<dyn std::error::Error>> {
+    let db = Db::open(dir)?;
+    let snapshot = db.snapshot(tags)?;
+    debug!(
+        "Searching {} files of '{}'",
+        snapshot.len(),
+        dir.to_string_lossy()
+    );
+    let query = index::query::Query::new(&snapshot, options.search);
+    index::similar_to(&query, image, options, options.max_distance).await
+}
+
+/// Print files in a database with images similar to those in a file, do not propagate errors.
+///
+/// Files are printed the way [`index::query`] prints them, of those
+/// with all of `tags` only, if any are given.
+/// You can think of it as of `main` of the `db search` subcommand.
+/// Returns [`Status::Found`] if similar images were found, and
+/// [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::db;
+/// async_std::task::block_on(db::search(
+///     "/home/user/photos.db".as_ref(),
+///     "/home/user/Downloads/sunset.jpg".into(),
+///     &["vacation2022".into()],
+///     Default::default(),
+///     None,
+/// ));
+/// ```
+pub async fn search(
+    dir: &Path,
+    image: PathBuf,
+    tags: &[String],
+    options: AnalyzeOptions,
+    output: Option<PathBuf>,
+) -> Status {
+    let written = try_search(dir, image, tags, &options)
+        .await
+        .and_then(|found| index::write_matches(&found, output.as_deref()));
+    match written {
+        Ok(status) => status,
+        Err(e) => {
+            error!("Error during 'db search': {}", e);
+            Status::Failed
+        }
+    }
+}
+
+/// Group images in a database and remember the group of every file, see [`dedupe`].
+///
+/// Only files with all of `tags` are grouped, and only their groups are
+/// forgotten. Returns how many groups were found.
+pub fn try_dedupe(
+    dir: &Path,
+    tags: &[String],
+    options: &AnalyzeOptions,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut db = Db::open(dir)?;
+    let snapshot = db.snapshot(tags)?;
+    let groups = index::groups(&snapshot, options);
+    for record in db.files.values_mut() {
+        if record.has_all(tags) {
+            record.group = None;
+        }
+    }
+    for (number, group) in groups.iter().enumerate() {
+        for img in group.files() {
+            if let Some(record) = db.files.get_mut(&img.path) {
+                record.group = Some(number + 1);
+            }
+        }
+    }
+    db.save()?;
+    let mut files = walker::Files::default();
+    files.tags = db.tags();
+    output::write(&groups, &files, options.format, options.output.as_deref())?;
+    Ok(groups.len())
+}
+
+/// Group images in a database into similar ones, do not propagate errors.
+///
+/// Groups are written the way `analyze` writes them, see [`output`], with
+/// tags of their files, and recorded for every file of the database. If
+/// `tags` are given, only files with all of them are grouped.
+/// You can think of it as of `main` of the `db dedupe` subcommand.
+/// Returns [`Status::Found`] if similar images were found, and
+/// [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::db;
+/// async_std::task::block_on(db::dedupe(
+///     "/home/user/photos.db".into(),
+///     vec!["vacation2022".into()],
+///     Default::default(),
+/// ));
+/// ```
+pub async fn dedupe(dir: PathBuf, tags: Vec<String>, options: AnalyzeOptions) -> Status {
+    let deduped = crate::analyze::io::unblock(move || {
+        try_dedupe(&dir, &tags, &options).map_err(|e| e.to_string())
+    })
+    .await;
+    match deduped {
+        Ok(groups) => Status::found(groups),
+        Err(e) => {
+            error!("Error during 'db dedupe': {}", e);
+            Status::Failed
+        }
+    }
+}
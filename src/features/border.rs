@@ -0,0 +1,159 @@
+//! Uniform borders around images.
+//!
+//! Copies of an image with a white frame or black letterbox bars added
+//! would hash differently from the original. Borders are trimmed before
+//! features are found, so such copies match their originals.
+//!
+//! Every side is trimmed on its own: a line of pixels along a side is part
+//! of the border if almost all of its pixels are close to the color in the
+//! middle of the outermost line of that side.
+
+use std::borrow::Cow;
+
+/// Pixels whose channels differ from the border color by at most this are part of the border.
+pub const TOLERANCE: u8 = 24;
+
+/// At least this part of the pixels of a line have to be part of the border,
+/// so that noise and JPEG artifacts don't stop trimming.
+const MIN_UNIFORM: f64 = 0.98;
+
+/// At most this part of the image is trimmed from each side.
+///
+/// Images that are mostly flat, e.g. a dot on a white background,
+/// aren't trimmed down to the dot.
+pub const MAX_TRIM: f64 = 0.4;
+
+/// Widths of the borders of an image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Borders {
+    /// Columns of pixels on the left.
+    pub left: u32,
+    /// Rows of pixels on the top.
+    pub top: u32,
+    /// Columns of pixels on the right.
+    pub right: u32,
+    /// Rows of pixels on the bottom.
+    pub bottom: u32,
+}
+
+impl Borders {
+    /// Find the borders of an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `original_rgb` - the image to find the borders of.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::border::Borders;
+    /// let letterboxed = image::RgbImage::from_fn(64, 48, |x, y| match y {
+    ///     0..=5 | 42.. => image::Rgb([0, 0, 0]),
+    ///     _ => image::Rgb([(x * 4) as u8, (y * 5) as u8, 128]),
+    /// });
+    /// let borders = Borders::find(&letterboxed);
+    /// assert_eq!((borders.top, borders.bottom), (6, 6));
+    /// assert_eq!((borders.left, borders.right), (0, 0));
+    /// ```
+    pub fn find(original_rgb: &image::RgbImage) -> Self {
+        let (width, height) = original_rgb.dimensions();
+        if width == 0 || height == 0 {
+            return Self::default();
+        }
+        let (max_x, max_y) = (
+            (f64::from(width) * MAX_TRIM) as u32,
+            (f64::from(height) * MAX_TRIM) as u32,
+        );
+
+        let row = |y: u32| (0..width).map(move |x| original_rgb.get_pixel(x, y));
+        let column = |x: u32| (0..height).map(move |y| original_rgb.get_pixel(x, y));
+
+        Self {
+            left: count_uniform(
+                (0..width).map(column),
+                *original_rgb.get_pixel(0, height / 2),
+                max_x,
+            ),
+            top: count_uniform(
+                (0..height).map(row),
+                *original_rgb.get_pixel(width / 2, 0),
+                max_y,
+            ),
+            right: count_uniform(
+                (0..width).rev().map(column),
+                *original_rgb.get_pixel(width - 1, height / 2),
+                max_x,
+            ),
+            bottom: count_uniform(
+                (0..height).rev().map(row),
+                *original_rgb.get_pixel(width / 2, height - 1),
+                max_y,
+            ),
+        }
+    }
+
+    /// Whether there are no borders at all.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Count lines from the outside in while they are of the border color, up to `max`.
+fn count_uniform<'a, L, P>(lines: L, color: image::Rgb<u8>, max: u32) -> u32
+where
+    L: Iterator<Item = P>,
+    P: Iterator<Item = &'a image::Rgb<u8>>,
+{
+    let matches = |pixel: &image::Rgb<u8>| {
+        pixel
+            .0
+            .iter()
+            .zip(color.0)
+            .all(|(&a, b)| a.abs_diff(b) <= TOLERANCE)
+    };
+
+    lines
+        .take(max as usize)
+        .map_while(|line| {
+            let (mut total, mut matching) = (0usize, 0usize);
+            for pixel in line {
+                total += 1;
+                matching += usize::from(matches(pixel));
+            }
+            (matching as f64 >= total as f64 * MIN_UNIFORM).then_some(())
+        })
+        .count() as u32
+}
+
+/// Trim the uniform borders of an image, see [`Borders`].
+///
+/// Returns the image itself if there are no borders.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::border;
+/// let framed = image::RgbImage::from_fn(40, 40, |x, y| match (x, y) {
+///     (10..=29, 10..=29) => image::Rgb([(x * 8) as u8, (y * 8) as u8, 0]),
+///     _ => image::Rgb([255, 255, 255]),
+/// });
+/// assert_eq!(border::trim(&framed).dimensions(), (20, 20));
+/// ```
+pub fn trim(original_rgb: &image::RgbImage) -> Cow<'_, image::RgbImage> {
+    let borders = Borders::find(original_rgb);
+    if borders.is_empty() {
+        return Cow::Borrowed(original_rgb);
+    }
+
+    let (width, height) = original_rgb.dimensions();
+    Cow::Owned(
+        image::imageops::crop_imm(
+            original_rgb,
+            borders.left,
+            borders.top,
+            width - borders.left - borders.right,
+            height - borders.top - borders.bottom,
+        )
+        .to_image(),
+    )
+}
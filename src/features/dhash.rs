@@ -0,0 +1,64 @@
+//! Difference hash of an image.
+//!
+//! Each bit tells whether a pixel of a tiny version of the image is darker
+//! than its right neighbor. Unlike [`LsHash`](super::lshash::LsHash), which
+//! compares pixels to a level, it follows gradients, so it tells apart images
+//! with the same layout of light and dark but different details.
+//! It is not a field of [`ImgFeatures`](super::ImgFeatures), pick it with
+//! a [`FeatureSet`](super::set::FeatureSet).
+
+use super::set::{self, Feature, Preprocessed, Value};
+
+/// Images whose difference hashes differ in more bits than this can't be similar.
+pub const MAX_DISTANCE: u32 = 10;
+
+/// Find the difference hash of a grayscale image.
+///
+/// # Arguments
+///
+/// * `grayscale` - the image to find the hash for.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::dhash;
+/// let darkening = image::GrayImage::from_fn(32, 32, |x, _| image::Luma([255 - x as u8 * 8]));
+/// let lightening = image::GrayImage::from_fn(32, 32, |x, _| image::Luma([x as u8 * 8]));
+/// assert_eq!(dhash::find_luma(&darkening), 0);
+/// assert_eq!(dhash::find_luma(&lightening), u64::MAX);
+/// ```
+pub fn find_luma(grayscale: &image::GrayImage) -> u64 {
+    // One more column than bits in a row, as each bit compares two pixels.
+    let grayscale_9x8 =
+        image::imageops::resize(grayscale, 9, 8, image::imageops::FilterType::Triangle);
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = grayscale_9x8.get_pixel(x, y)[0] < grayscale_9x8.get_pixel(x + 1, y)[0];
+            hash |= u64::from(bit) << (y * 8 + x);
+        }
+    }
+    hash
+}
+
+/// Difference hash as a [`Feature`] named `dhash`.
+pub struct DHashFeature;
+
+impl Feature for DHashFeature {
+    fn name(&self) -> &'static str {
+        "dhash"
+    }
+
+    fn compute(&self, img: &Preprocessed) -> Value {
+        Value::Bits(find_luma(img.luma))
+    }
+
+    fn distance(&self, a: &Value, b: &Value) -> f64 {
+        set::bits_distance(a, b)
+    }
+
+    fn max_distance(&self) -> f64 {
+        f64::from(MAX_DISTANCE)
+    }
+}
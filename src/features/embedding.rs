@@ -0,0 +1,103 @@
+//! Embedding of an image by a neural network, e.g. CLIP.
+//!
+//! An [ONNX](https://onnx.ai) model turns an image into a vector, and images
+//! of the same scene get vectors pointing the same way even if their pixels
+//! differ, e.g. shots taken a moment apart or from another angle. Vectors are
+//! compared by the angle between them, see [`set::vector_distance`].
+//!
+//! The model is run with [tract](https://github.com/sonos/tract) and has to take
+//! a batch of one RGB image of [`SIZE`] by [`SIZE`] pixels, normalized the way
+//! CLIP expects, and give a vector. It is not bundled, [`register`] the feature
+//! with a model file to be able to pick it with a [`FeatureSet`](super::set::FeatureSet).
+//! It is not a field of [`ImgFeatures`](super::ImgFeatures).
+
+use super::set::{self, Feature, Preprocessed, Value};
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use tract_onnx::prelude::*;
+
+/// Name of the feature.
+pub const NAME: &str = "embedding";
+
+/// Width and height in pixels of images the model takes.
+pub const SIZE: u32 = 224;
+
+/// Images whose embeddings are farther apart than this can't be similar,
+/// about 30 degrees between the vectors.
+pub const MAX_DISTANCE: f64 = 0.17;
+
+/// Mean of every channel of images CLIP was trained on.
+const MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+
+/// Standard deviation of every channel of images CLIP was trained on.
+const STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+
+/// A model that embeds images, as a [`Feature`] named [`NAME`].
+pub struct Embedding {
+    model: TypedRunnableModel<TypedModel>,
+}
+
+impl Embedding {
+    /// Load an ONNX model from a file.
+    pub fn load(model: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let model = model.as_ref();
+        let input = f32::fact([1, 3, SIZE as usize, SIZE as usize]);
+        let model = tract_onnx::onnx()
+            .model_for_path(model)
+            .map_err(|e| format!("couldn't load model '{}': {}", model.display(), e))?
+            .with_input_fact(0, input.into())?
+            .into_optimized()?
+            .into_runnable()?;
+        Ok(Self { model })
+    }
+
+    /// Embed an image, the vector has length 1.
+    pub fn embed(&self, rgb: &image::RgbImage) -> Result<Vec<f32>, Box<dyn Error>> {
+        let resized =
+            image::imageops::resize(rgb, SIZE, SIZE, image::imageops::FilterType::Triangle);
+        let size = SIZE as usize;
+        let input: Tensor =
+            tract_ndarray::Array4::from_shape_fn((1, 3, size, size), |(_, c, y, x)| {
+                let value = f32::from(resized.get_pixel(x as u32, y as u32)[c]) / 255.;
+                (value - MEAN[c]) / STD[c]
+            })
+            .into();
+        let outputs = self.model.run(tvec!(input.into()))?;
+        let mut vector: Vec<f32> = outputs[0].to_array_view::<f32>()?.iter().copied().collect();
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0. {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        Ok(vector)
+    }
+}
+
+impl Feature for Embedding {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    /// The embedding, or an empty vector if the model failed, which is similar to nothing.
+    fn compute(&self, img: &Preprocessed) -> Value {
+        Value::Vector(self.embed(img.rgb).unwrap_or_default().into())
+    }
+
+    fn distance(&self, a: &Value, b: &Value) -> f64 {
+        set::vector_distance(a, b)
+    }
+
+    fn max_distance(&self) -> f64 {
+        MAX_DISTANCE
+    }
+}
+
+/// Load a model from a file and [register](set::register) it as the feature [`NAME`],
+/// unless a model is registered already.
+pub fn register(model: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+    if set::lookup(NAME).is_some() {
+        return Ok(());
+    }
+    set::register(Arc::new(Embedding::load(model)?))?;
+    Ok(())
+}
@@ -0,0 +1,329 @@
+//! EXIF metadata of photos.
+//!
+//! Cameras record when a photo was taken, with what, which way up and where,
+//! which tells apart photos of a [burst](crate::analyze::burst) from similar
+//! ones taken at other times or places. Metadata is parsed from the contents
+//! of a file, not its pixels, see [`Exif::of_bytes`], and is kept as
+//! [`ImgFeatures::exif`](super::ImgFeatures::exif). Files without EXIF
+//! metadata, or with other metadata, have none of it.
+
+use std::convert::TryFrom;
+use std::io::{BufRead, Cursor, Seek};
+use std::time::Duration;
+
+/// Mean radius of the Earth in meters.
+const EARTH_RADIUS: f64 = 6_371_000.;
+
+/// What the EXIF metadata of a photo says, every field only if recorded.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Exif {
+    /// When the photo was taken, in seconds since 1970-01-01 00:00:00 by the clock
+    /// of the camera, to a fraction of a second if recorded. EXIF has no time zone.
+    pub taken: Option<f64>,
+    /// Make and model of the camera, separated by a space.
+    pub camera: Option<String>,
+    /// How the photo has to be turned to be upright, from 1 to 8 as in EXIF,
+    /// 1 if it is upright already.
+    pub orientation: Option<u16>,
+    /// Latitude and longitude where the photo was taken, in degrees,
+    /// negative to the south and to the west.
+    pub gps: Option<(f64, f64)>,
+}
+
+impl Exif {
+    /// Parse EXIF metadata out of a JPEG, TIFF, HEIF, PNG or WebP file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::exif::Exif;
+    /// use exif::{experimental::Writer, Field, In, Rational, Tag, Value};
+    /// let field = |tag, value| Field { tag, ifd_num: In::PRIMARY, value };
+    /// let ascii = |value: &str| Value::Ascii(vec![value.as_bytes().to_vec()]);
+    /// let degrees = |d, m| Value::Rational(vec![Rational::from((d, 1)), Rational::from((m, 1)), Rational::from((0, 1))]);
+    /// let fields = [
+    ///     field(Tag::Make, ascii("Canon")),
+    ///     field(Tag::Model, ascii("EOS R6")),
+    ///     field(Tag::Orientation, Value::Short(vec![6])),
+    ///     field(Tag::DateTimeOriginal, ascii("2021:06:01 12:00:00")),
+    ///     field(Tag::SubSecTimeOriginal, ascii("25")),
+    ///     field(Tag::GPSLatitudeRef, ascii("N")),
+    ///     field(Tag::GPSLatitude, degrees(51, 30)),
+    ///     field(Tag::GPSLongitudeRef, ascii("W")),
+    ///     field(Tag::GPSLongitude, degrees(0, 6)),
+    /// ];
+    /// let mut writer = Writer::new();
+    /// fields.iter().for_each(|field| writer.push_field(field));
+    /// let mut tiff = std::io::Cursor::new(Vec::new());
+    /// writer.write(&mut tiff, false).unwrap();
+    ///
+    /// let exif = Exif::of_bytes(tiff.get_ref()).unwrap();
+    /// assert_eq!(exif.camera.as_deref(), Some("Canon EOS R6"));
+    /// assert_eq!(exif.date_time().as_deref(), Some("2021-06-01 12:00:00.25"));
+    /// assert_eq!(exif.orientation, Some(6));
+    /// assert_eq!(exif.gps, Some((51.5, -0.1)));
+    /// ```
+    pub fn parse<R: BufRead + Seek>(container: &mut R) -> Option<Self> {
+        let exif = exif::Reader::new().read_from_container(container).ok()?;
+        let field = |tag| {
+            exif.get_field(tag, exif::In::PRIMARY)
+                .map(|field| &field.value)
+        };
+        let ascii = |tag| match field(tag)? {
+            exif::Value::Ascii(values) => {
+                let value = String::from_utf8_lossy(values.first()?);
+                Some(value.trim_end_matches('\0').trim().to_owned())
+            }
+            _ => None,
+        };
+        let degrees = |tag, negative_ref, negative| match field(tag)? {
+            exif::Value::Rational(parts) if parts.len() == 3 => {
+                let degrees =
+                    parts[0].to_f64() + parts[1].to_f64() / 60. + parts[2].to_f64() / 3600.;
+                let sign = if ascii(negative_ref)?.starts_with(negative) {
+                    -1.
+                } else {
+                    1.
+                };
+                Some(sign * degrees).filter(|degrees| degrees.is_finite())
+            }
+            _ => None,
+        };
+
+        let taken = ascii(exif::Tag::DateTimeOriginal)
+            .map(|taken| (taken, ascii(exif::Tag::SubSecTimeOriginal)))
+            .or_else(|| {
+                let taken = ascii(exif::Tag::DateTime)?;
+                Some((taken, ascii(exif::Tag::SubSecTime)))
+            })
+            .and_then(|(taken, subsec)| time(&taken, subsec.as_deref()));
+        let camera = match (ascii(exif::Tag::Make), ascii(exif::Tag::Model)) {
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.or(model),
+        };
+        let orientation = field(exif::Tag::Orientation)
+            .and_then(|value| value.get_uint(0))
+            .and_then(|orientation| u16::try_from(orientation).ok())
+            .filter(|orientation| (1..=8).contains(orientation));
+        let latitude = degrees(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, 'S');
+        let longitude = degrees(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, 'W');
+        Some(Self {
+            taken,
+            camera,
+            orientation,
+            gps: latitude.zip(longitude),
+        })
+    }
+
+    /// Parse EXIF metadata out of the contents of a file, see [`Exif::parse`].
+    pub fn of_bytes(data_raw: &[u8]) -> Option<Self> {
+        Self::parse(&mut Cursor::new(data_raw))
+    }
+
+    /// When the photo was taken, as `YYYY-MM-DD HH:MM:SS` with a fraction
+    /// of a second if there is one.
+    pub fn date_time(&self) -> Option<String> {
+        let taken = self.taken?;
+        let seconds = taken.floor();
+        let (days, second) = (
+            (seconds / 86_400.).floor() as i64,
+            seconds.rem_euclid(86_400.) as u32,
+        );
+        let (year, month, day) = civil_from_days(days);
+        let mut date_time = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            second / 3600,
+            second / 60 % 60,
+            second % 60
+        );
+        let fraction = format!("{:.3}", taken - seconds);
+        let fraction = fraction.trim_start_matches('0').trim_end_matches('0');
+        if fraction.len() > 1 && !fraction.starts_with("1.") {
+            date_time += fraction;
+        }
+        Some(date_time)
+    }
+
+    /// How long apart two photos were taken, if both say when.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::exif::Exif;
+    /// # use std::time::Duration;
+    /// let at = |taken| Exif { taken: Some(taken), ..Exif::default() };
+    /// assert_eq!(at(10.5).apart(&at(10.25)), Some(Duration::from_millis(250)));
+    /// assert_eq!(at(10.5).apart(&Exif::default()), None);
+    /// ```
+    pub fn apart(&self, other: &Self) -> Option<Duration> {
+        Some(Duration::from_secs_f64((self.taken? - other.taken?).abs()))
+    }
+
+    /// How far apart two photos were taken, in meters, if both say where.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::exif::Exif;
+    /// let at = |latitude, longitude| Exif { gps: Some((latitude, longitude)), ..Exif::default() };
+    /// // A degree of latitude is about 111 kilometers.
+    /// let meters = at(51., 0.).meters(&at(52., 0.)).unwrap();
+    /// assert!((meters - 111_195.).abs() < 1.);
+    /// assert_eq!(at(51., 0.).meters(&Exif::default()), None);
+    /// ```
+    pub fn meters(&self, other: &Self) -> Option<f64> {
+        let ((lat_a, lon_a), (lat_b, lon_b)) = (self.gps?, other.gps?);
+        let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+        let (d_lat, d_lon) = (lat_b - lat_a, (lon_b - lon_a).to_radians());
+        let h = (d_lat / 2.).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.).sin().powi(2);
+        Some(2. * EARTH_RADIUS * h.sqrt().min(1.).asin())
+    }
+
+    /// Encode the metadata as `exif.name=value` words separated by spaces,
+    /// for [`ImgFeatures::encode`](super::ImgFeatures::encode).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::exif::Exif;
+    /// let exif = Exif {
+    ///     taken: Some(1622548800.25),
+    ///     camera: Some("Canon EOS R6".to_owned()),
+    ///     orientation: Some(6),
+    ///     gps: None,
+    /// };
+    /// let encoded = exif.encode();
+    /// assert_eq!(encoded, "exif.taken=1622548800.25 exif.camera=Canon%20EOS%20R6 exif.orientation=6");
+    /// let mut decoded = Exif::default();
+    /// for word in encoded.split(' ') {
+    ///     let (name, value) = word.split_once('=').unwrap();
+    ///     decoded.decode_word(name.strip_prefix("exif.").unwrap(), value).unwrap();
+    /// }
+    /// assert_eq!(decoded, exif);
+    /// ```
+    pub fn encode(&self) -> String {
+        let mut words = Vec::new();
+        if let Some(taken) = self.taken {
+            words.push(format!("exif.taken={}", taken));
+        }
+        if let Some(camera) = &self.camera {
+            words.push(format!("exif.camera={}", escape(camera)));
+        }
+        if let Some(orientation) = self.orientation {
+            words.push(format!("exif.orientation={}", orientation));
+        }
+        if let Some((latitude, longitude)) = self.gps {
+            words.push(format!("exif.gps={},{}", latitude, longitude));
+        }
+        words.join(" ")
+    }
+
+    /// Set a field from a word [encoded](Exif::encode) as `exif.name=value`,
+    /// given the name without the `exif.` prefix. Fails for unknown names.
+    pub fn decode_word(&mut self, name: &str, value: &str) -> Option<()> {
+        match name {
+            "taken" => self.taken = Some(value.parse().ok()?),
+            "camera" => self.camera = Some(unescape(value)?),
+            "orientation" => self.orientation = Some(value.parse().ok()?),
+            "gps" => {
+                let (latitude, longitude) = value.split_once(',')?;
+                self.gps = Some((latitude.parse().ok()?, longitude.parse().ok()?));
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+/// Seconds since 1970-01-01 00:00:00 from EXIF date/time, e.g. `2021:06:01 12:00:00`,
+/// and the digits of its fraction of a second, e.g. `25` for 0.25 seconds.
+fn time(date_time: &str, subsec: Option<&str>) -> Option<f64> {
+    let date_time = exif::DateTime::from_ascii(date_time.as_bytes()).ok()?;
+    let valid = (1..=12).contains(&date_time.month)
+        && (1..=31).contains(&date_time.day)
+        && date_time.hour < 24
+        && date_time.minute < 60
+        && date_time.second < 61;
+    if !valid {
+        return None;
+    }
+    let days = days_from_civil(
+        i64::from(date_time.year),
+        i64::from(date_time.month),
+        i64::from(date_time.day),
+    );
+    let seconds = days * 86_400
+        + i64::from(date_time.hour) * 3600
+        + i64::from(date_time.minute) * 60
+        + i64::from(date_time.second);
+    let fraction = subsec
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| format!("0.{}", digits).parse().ok())
+        .unwrap_or(0.);
+    Some(seconds as f64 + fraction)
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date of a number of days since 1970-01-01, see [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Percent-encode characters that would split an encoded word.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '%' || c.is_whitespace() || c.is_control() {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                escaped += &format!("%{:02X}", byte);
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// Decode a value [escaped](escape) by percent-encoding.
+fn unescape(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
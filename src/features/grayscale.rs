@@ -0,0 +1,114 @@
+//! Converting images to grayscale before hashing.
+//!
+//! Hashes are found on grayscale images, and how colors are mapped to gray
+//! changes the hashes. Hashes found with different [`Grayscale`] conversions
+//! can't be compared, so the conversion is recorded along with the features.
+
+/// How to convert an image to grayscale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd)]
+pub enum Grayscale {
+    /// Whatever the `image` crate does.
+    #[default]
+    Default,
+    /// Rec.709 weights applied to gamma-encoded channels, rounded.
+    Rec709,
+    /// Rec.709 weights applied to linearized sRGB channels,
+    /// then encoded back with the sRGB curve.
+    Rec709Linear,
+}
+
+/// Rec.709 weights of the red, green and blue channels.
+const REC709: [f64; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Convert an sRGB-encoded channel to linear light, from 0 to 1.
+fn linearize(encoded: u8) -> f64 {
+    let encoded = f64::from(encoded) / 255.;
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert linear light, from 0 to 1, to an sRGB-encoded channel.
+fn encode(linear: f64) -> u8 {
+    let encoded = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1. / 2.4) - 0.055
+    };
+    (encoded * 255.).round().clamp(0., 255.) as u8
+}
+
+impl Grayscale {
+    /// Name of the conversion, as accepted by [`from_str`](std::str::FromStr::from_str).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Rec709 => "rec709",
+            Self::Rec709Linear => "rec709-linear",
+        }
+    }
+
+    /// Convert an image to grayscale.
+    ///
+    /// # Arguments
+    ///
+    /// * `original_rgb` - the image to convert.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::grayscale::Grayscale;
+    /// let red = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 0, 0]));
+    /// assert_eq!(Grayscale::Rec709.convert(&red).get_pixel(0, 0)[0], 54);
+    /// // Linear light of pure red is 0.2126, which is much lighter when encoded.
+    /// assert_eq!(Grayscale::Rec709Linear.convert(&red).get_pixel(0, 0)[0], 127);
+    /// ```
+    pub fn convert(&self, original_rgb: &image::RgbImage) -> image::GrayImage {
+        let weigh = |channels: [f64; 3]| {
+            channels
+                .iter()
+                .zip(REC709)
+                .map(|(channel, weight)| channel * weight)
+                .sum::<f64>()
+        };
+
+        match self {
+            Self::Default => image::imageops::grayscale(original_rgb),
+            Self::Rec709 => {
+                image::GrayImage::from_fn(original_rgb.width(), original_rgb.height(), |x, y| {
+                    let image::Rgb(rgb) = *original_rgb.get_pixel(x, y);
+                    let luma = weigh(rgb.map(f64::from));
+                    image::Luma([luma.round().clamp(0., 255.) as u8])
+                })
+            }
+            Self::Rec709Linear => {
+                let linear: Vec<f64> = (0..=255).map(linearize).collect();
+                image::GrayImage::from_fn(original_rgb.width(), original_rgb.height(), |x, y| {
+                    let image::Rgb(rgb) = *original_rgb.get_pixel(x, y);
+                    image::Luma([encode(weigh(rgb.map(|c| linear[usize::from(c)])))])
+                })
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Grayscale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Grayscale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "rec709" => Ok(Self::Rec709),
+            "rec709-linear" => Ok(Self::Rec709Linear),
+            _ => Err(format!("unknown grayscale conversion '{}'", s)),
+        }
+    }
+}
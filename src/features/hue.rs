@@ -0,0 +1,242 @@
+//! Hue of images, and names of hues.
+//!
+//! [`Hue`] is the hue most pixels of an image have. [`HueName`] is the
+//! bucket of the color wheel it is in, e.g. `blue`, to sort and filter images
+//! by. Images without much of a color, e.g. grayscale ones, have a hue of 0,
+//! which is red.
+
+use super::set::{Feature, Preprocessed, Value};
+
+/// How many degrees of hue a bucket counted by [`Hue::find`] covers.
+pub const BUCKET_WIDTH: usize = 10;
+
+/// Pixels whose brightest and darkest channels differ less than this
+/// are too close to gray to count in [`Hue::find`].
+pub const MIN_CHROMA: u8 = 24;
+
+/// Images whose [`Hue`]s differ more than this many degrees can't be similar.
+pub const MAX_DIFFERENCE: f64 = 30.;
+
+/// Names of hues, the color wheel cut into buckets of uneven width.
+///
+/// Every bucket is named by the color it is around, and all of them together
+/// cover the wheel, see [`HueName::of`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HueName {
+    /// From 345° to 15°.
+    Red,
+    /// From 15° to 45°.
+    Orange,
+    /// From 45° to 75°.
+    Yellow,
+    /// From 75° to 165°.
+    Green,
+    /// From 165° to 195°.
+    Cyan,
+    /// From 195° to 255°.
+    Blue,
+    /// From 255° to 285°.
+    Purple,
+    /// From 285° to 345°.
+    Pink,
+}
+
+impl HueName {
+    /// All names, around the wheel from red.
+    pub const ALL: [Self; 8] = [
+        Self::Red,
+        Self::Orange,
+        Self::Yellow,
+        Self::Green,
+        Self::Cyan,
+        Self::Blue,
+        Self::Purple,
+        Self::Pink,
+    ];
+
+    /// Name of the bucket a hue is in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::{hue::HueName, ImgFeatures};
+    /// let name = |hue| {
+    ///     let features = ImgFeatures::decode(&format!("00000000000000ff {} 0 0.5 0.2", hue)).unwrap();
+    ///     HueName::of(&features.hue)
+    /// };
+    /// assert_eq!(name(350), HueName::Red);
+    /// assert_eq!(name(10), HueName::Red);
+    /// assert_eq!(name(100), HueName::Green);
+    /// assert_eq!(name(210), HueName::Blue);
+    /// assert_eq!("blue".parse(), Ok(HueName::Blue));
+    /// ```
+    pub fn of(hue: &Hue) -> Self {
+        match hue.degrees() {
+            d if d < 15. => Self::Red,
+            d if d < 45. => Self::Orange,
+            d if d < 75. => Self::Yellow,
+            d if d < 165. => Self::Green,
+            d if d < 195. => Self::Cyan,
+            d if d < 255. => Self::Blue,
+            d if d < 285. => Self::Purple,
+            d if d < 345. => Self::Pink,
+            _ => Self::Red,
+        }
+    }
+
+    /// Name of the hue, as `--filter` and folders of `organize` have it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Red => "red",
+            Self::Orange => "orange",
+            Self::Yellow => "yellow",
+            Self::Green => "green",
+            Self::Cyan => "cyan",
+            Self::Blue => "blue",
+            Self::Purple => "purple",
+            Self::Pink => "pink",
+        }
+    }
+}
+
+impl std::str::FromStr for HueName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|name| name.name() == s)
+            .ok_or_else(|| format!("unknown hue '{}'", s))
+    }
+}
+
+/// Hue of an image, in degrees, normalized.
+///
+/// Can be used as a key for sorting in a regular way.
+/// It is the hue of the HSV color space, see [`Hue::find`].
+///
+/// We make sure to limit the angle with [0, 360) by normalizing
+/// the value on creation.
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct Hue(f64);
+
+impl std::fmt::Display for Hue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+impl Hue {
+    pub fn new(a: angle::Deg<f64>) -> Self {
+        use angle::Angle;
+        Self(a.normalize().scalar())
+    }
+
+    /// The hue in degrees, within [0, 360).
+    pub fn degrees(&self) -> f64 {
+        self.0
+    }
+
+    /// Name of the bucket of the color wheel the hue is in, see [`HueName`].
+    pub fn name(&self) -> HueName {
+        HueName::of(self)
+    }
+
+    /// Hue of a pixel in degrees, `None` if it is too close to gray to have one.
+    fn of_pixel([r, g, b]: [u8; 3]) -> Option<f64> {
+        let max = r.max(g).max(b);
+        let chroma = max - r.min(g).min(b);
+        if chroma < MIN_CHROMA {
+            return None;
+        }
+        let (red, green, blue, chroma) = (r as f64, g as f64, b as f64, chroma as f64);
+        let sector = if max == r {
+            ((green - blue) / chroma).rem_euclid(6.)
+        } else if max == g {
+            (blue - red) / chroma + 2.
+        } else {
+            (red - green) / chroma + 4.
+        };
+        Some(sector * 60.)
+    }
+
+    /// Find [`Hue`] of an image, the one most of its pixels have.
+    ///
+    /// Hues of pixels are counted in buckets [`BUCKET_WIDTH`] degrees wide, and
+    /// the hue is the mean of those in the fullest one. Pixels too close to gray
+    /// are left out, an image with only such pixels has a hue of 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `original` - the image to find [`Hue`] for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::hue::{Hue, HueName};
+    /// let mut image = image::RgbImage::from_pixel(8, 8, image::Rgb([0, 0, 255]));
+    /// assert_eq!(Hue::find(&image).degrees(), 240.);
+    /// // A red corner doesn't make a mostly blue image purple.
+    /// for x in 0..3 {
+    ///     image.put_pixel(x, 0, image::Rgb([255, 0, 0]));
+    /// }
+    /// assert_eq!(Hue::find(&image).name(), HueName::Blue);
+    /// ```
+    pub fn find(original_rgb: &image::RgbImage) -> Self {
+        let mut buckets = [(0usize, 0.); 360 / BUCKET_WIDTH];
+        for pixel in original_rgb.pixels() {
+            if let Some(hue) = Self::of_pixel(pixel.0) {
+                let bucket = (hue as usize / BUCKET_WIDTH).min(buckets.len() - 1);
+                buckets[bucket].0 += 1;
+                buckets[bucket].1 += hue;
+            }
+        }
+        // The first of the fullest buckets, for images with ties to find the same hue.
+        let (count, sum) = buckets
+            .iter()
+            .rev()
+            .max_by_key(|(count, _)| *count)
+            .copied()
+            .unwrap_or_default();
+        match count {
+            0 => Self(0.),
+            count => Hue::new(angle::Deg(sum / count as f64)),
+        }
+    }
+}
+
+/// [`Hue`] as a [`Feature`] named `hue`, compared around the color wheel.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::{hue::HueFeature, set::{Feature, Value}};
+/// let distance = HueFeature.distance(&Value::Number(350.), &Value::Number(10.));
+/// assert_eq!(distance, 20.);
+/// ```
+pub struct HueFeature;
+
+impl Feature for HueFeature {
+    fn name(&self) -> &'static str {
+        "hue"
+    }
+
+    fn compute(&self, img: &Preprocessed) -> Value {
+        Value::Number(Hue::find(img.rgb).degrees())
+    }
+
+    fn distance(&self, a: &Value, b: &Value) -> f64 {
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => {
+                let difference = (a - b).abs() % 360.;
+                difference.min(360. - difference)
+            }
+            _ => f64::INFINITY,
+        }
+    }
+
+    fn max_distance(&self) -> f64 {
+        MAX_DIFFERENCE
+    }
+}
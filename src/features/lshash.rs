@@ -0,0 +1,254 @@
+//! TODO
+
+use super::set::{self, Feature, Preprocessed, Value};
+use std::convert::TryFrom;
+
+/// Hashes that differ in at most this many bits are close by default.
+pub const MAX_DISTANCE: u32 = 6;
+
+/// Locality-sensitive hash of an image.
+///
+/// Can not be used as a key for sorting in a regular way,
+/// as any changes in its bits have equal importance.
+/// Basically, if it was a `u8`, then these two hashes:
+/// `0b01001111` and `0b0001111` would have the same "difference"
+/// between them as these two hashes:
+/// `0b10000001` and `0b10000000`.
+/// Read about Hamming distance if you want to know more.
+///
+/// When used as a key for sorting, will cause "clusters"
+/// to appear in the dataset, each having the same number of enabled bits
+/// in the hashes of its members.
+///
+/// Other properties of the LSH are implementation defined,
+/// it is supposed to be compared with other hashes only
+/// by using Equality or Hamming distance.
+///
+/// PartialOrd for this struct is defined
+/// on the number of bits the hash has.
+///
+/// Ord is not defined, as LsHash does not have a total order.
+///
+/// # Examples
+///
+/// ## PartialEq and Eq
+/// ```
+/// # use libsuccotash::analyze::features::lshash::LsHash;
+/// let just_a = LsHash::new(0b00100000u64);
+/// let also_a = LsHash::new(0b00100000u64);
+/// let just_b = LsHash::new(0b00000001u64);
+/// assert!(just_a == also_a);
+/// assert!(just_a != just_b);
+/// ```
+///
+/// ## PartialOrd
+/// ```
+/// # use libsuccotash::analyze::features::lshash::LsHash;
+/// let a = LsHash::new(0b00100000u64);
+/// let b = LsHash::new(0b00000011u64);
+/// assert!(a < b);
+/// assert!(b > a);
+/// ```
+///
+/// ## Not Ord
+/// ```
+/// # use libsuccotash::analyze::features::lshash::LsHash;
+/// let a = LsHash::new(0b00100000u64);
+/// let b = LsHash::new(0b00000001u64);
+/// assert!(a != b);
+///
+/// assert!(!(a < b));
+/// assert!(!(a > b));
+/// assert!(a.partial_cmp(&b) == None);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LsHash(u64);
+
+/// What pixels of a downscaled image are compared to when finding [`LsHash`].
+///
+/// Pixels at or above the threshold give ones, the others give zeros.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd)]
+pub enum HashThreshold {
+    /// The mean of the pixels.
+    #[default]
+    Mean,
+    /// The median of the pixels, which small very bright regions,
+    /// e.g. flash highlights, don't skew.
+    Median,
+}
+
+impl HashThreshold {
+    /// Name of the threshold, as accepted by [`from_str`](std::str::FromStr::from_str).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Median => "median",
+        }
+    }
+}
+
+impl std::fmt::Display for HashThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for HashThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            _ => Err(format!("unknown hash threshold '{}'", s)),
+        }
+    }
+}
+
+impl LsHash {
+    pub fn new(lshash: u64) -> Self {
+        Self(lshash)
+    }
+
+    /// The bits of the hash.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Hamming distance to another hash, the number of bits they differ in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::lshash::LsHash;
+    /// let a = LsHash::new(0b0110);
+    /// let b = LsHash::new(0b0011);
+    /// assert_eq!(a.distance(&b), 2);
+    /// assert_eq!(a.distance(&a), 0);
+    /// ```
+    pub fn distance(&self, other: &Self) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+
+    /// Find [`LsHash`] of an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `original` - the image to find [`LsHash`] for.
+    pub fn find(original: &image::RgbImage) -> Self {
+        Self::find_luma(&image::imageops::grayscale(original))
+    }
+
+    /// Find [`LsHash`] of a grayscale image.
+    ///
+    /// # Arguments
+    ///
+    /// * `grayscale` - the image to find [`LsHash`] for.
+    pub fn find_luma(grayscale: &image::GrayImage) -> Self {
+        Self::find_luma_with(grayscale, HashThreshold::Mean)
+    }
+
+    /// Find [`LsHash`] of a grayscale image with the given threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `grayscale` - the image to find [`LsHash`] for.
+    /// * `threshold` - what pixels are compared to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::lshash::{HashThreshold, LsHash};
+    /// // A dim gradient with a flash highlight in the corner.
+    /// let flash = image::GrayImage::from_fn(8, 8, |x, y| match (x, y) {
+    ///     (0, 0) => image::Luma([255]),
+    ///     _ => image::Luma([(x * 8 + y) as u8]),
+    /// });
+    /// let mean = LsHash::find_luma_with(&flash, HashThreshold::Mean);
+    /// let median = LsHash::find_luma_with(&flash, HashThreshold::Median);
+    /// assert!(mean.bits().count_ones() < 32);
+    /// assert_eq!(median.bits().count_ones(), 32);
+    /// ```
+    pub fn find_luma_with(grayscale: &image::GrayImage, threshold: HashThreshold) -> Self {
+        // Downscale the picture to 8x8.
+        let grayscale_8x8 =
+            image::imageops::resize(grayscale, 8, 8, image::imageops::FilterType::Triangle);
+
+        let level = match threshold {
+            HashThreshold::Mean => {
+                // Find mean value of the grayscale 8x8 image.
+                let grayscale_8x8_sum = grayscale_8x8
+                    .as_raw()
+                    .iter()
+                    .fold(0u64, |acc, v| acc + u64::from(*v));
+                u8::try_from(grayscale_8x8_sum / 64).expect(
+                    "Mean is supposed to be less or equal to max, and max couldn't be greater than 255",
+                )
+            }
+            HashThreshold::Median => {
+                // The upper one of the two middle values, so that half of the bits are ones.
+                let mut sorted = grayscale_8x8.as_raw().clone();
+                sorted.sort_unstable();
+                sorted[sorted.len() / 2]
+            }
+        };
+
+        // Shift 0 or 1 to some position based on counter, making a "bit vector" that is the "imghash" of the image.
+        let lshash = grayscale_8x8
+            .as_raw()
+            .iter()
+            .fold((0u8, 0u64), |(counter, acc), v| {
+                let bit = *v >= level;
+                let bit_positioned = u64::from(bit) << counter;
+                (counter + 1, acc + bit_positioned)
+            })
+            .1;
+
+        Self::new(lshash)
+    }
+}
+
+/// [`LsHash`] as a [`Feature`] named `lshash`.
+///
+/// Similar images differ in at most [`MAX_DISTANCE`](crate::analyze::group::MAX_DISTANCE)
+/// bits unless told otherwise, see [`FeatureSet::with_max_distance`](super::set::FeatureSet::with_max_distance).
+pub struct LsHashFeature;
+
+impl Feature for LsHashFeature {
+    fn name(&self) -> &'static str {
+        "lshash"
+    }
+
+    fn compute(&self, img: &Preprocessed) -> Value {
+        Value::Bits(LsHash::find_luma(img.luma).bits())
+    }
+
+    fn distance(&self, a: &Value, b: &Value) -> f64 {
+        set::bits_distance(a, b)
+    }
+
+    fn max_distance(&self) -> f64 {
+        f64::from(MAX_DISTANCE)
+    }
+}
+
+impl std::fmt::Display for LsHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+impl PartialOrd for LsHash {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        let self_ones = self.0.count_ones();
+        let other_ones = other.0.count_ones();
+
+        if self_ones < other_ones {
+            Some(std::cmp::Ordering::Less)
+        } else if self_ones > other_ones {
+            Some(std::cmp::Ordering::Greater)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Brightness and contrast of an image.
+//!
+//! Both are found on luminance of pixels, with Rec. 709 coefficients.
+//! They are cheap to compare, so images with wildly different brightness
+//! or contrast can be told apart before comparing anything expensive.
+
+use super::set::{self, Feature, Preprocessed, Value};
+use super::{MAX_BRIGHTNESS_DIFFERENCE, MAX_CONTRAST_DIFFERENCE};
+
+/// Find the mean and the standard deviation of luminance of an image, within [0, 1].
+fn moments(original_rgb: &image::RgbImage) -> (f64, f64) {
+    let luminances = || {
+        original_rgb.pixels().map(|pixel| {
+            let [r, g, b] = pixel.0.map(|c| f64::from(c) / 255.);
+            0.2126 * r + 0.7152 * g + 0.0722 * b
+        })
+    };
+
+    let count = f64::from(original_rgb.width()) * f64::from(original_rgb.height());
+    if count == 0. {
+        return (0., 0.);
+    }
+    // Two passes, the one-pass formula loses precision on low-contrast images.
+    let mean = luminances().sum::<f64>() / count;
+    let variance = luminances().map(|l| (l - mean) * (l - mean)).sum::<f64>() / count;
+    (mean, variance.sqrt())
+}
+
+/// Brightness of an image, as its mean luminance within [0, 1].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::luminance::Brightness;
+/// let black = image::RgbImage::new(16, 16);
+/// let white = image::RgbImage::from_pixel(16, 16, image::Rgb([255, 255, 255]));
+/// assert_eq!(Brightness::find(&black), Brightness::new(0.));
+/// assert!((Brightness::find(&white).mean() - 1.).abs() < 1e-9);
+/// ```
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct Brightness(f64);
+
+impl std::fmt::Display for Brightness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+impl Brightness {
+    pub fn new(mean: f64) -> Self {
+        Self(mean)
+    }
+
+    /// The mean luminance.
+    pub fn mean(&self) -> f64 {
+        self.0
+    }
+
+    /// Find [`Brightness`] of an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `original_rgb` - the image to find [`Brightness`] for.
+    pub fn find(original_rgb: &image::RgbImage) -> Self {
+        Self::new(moments(original_rgb).0)
+    }
+}
+
+/// Contrast of an image, as the standard deviation of its luminance
+/// (RMS contrast) within [0, 0.5].
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::luminance::Contrast;
+/// let flat = image::RgbImage::from_pixel(16, 16, image::Rgb([128, 128, 128]));
+/// let checkers = image::RgbImage::from_fn(16, 16, |x, y| {
+///     image::Rgb([if (x + y) % 2 == 0 { 255 } else { 0 }; 3])
+/// });
+/// assert!(Contrast::find(&flat).rms() < 1e-9);
+/// assert!((Contrast::find(&checkers).rms() - 0.5).abs() < 1e-9);
+/// ```
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct Contrast(f64);
+
+impl std::fmt::Display for Contrast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+impl Contrast {
+    pub fn new(rms: f64) -> Self {
+        Self(rms)
+    }
+
+    /// The standard deviation of luminance.
+    pub fn rms(&self) -> f64 {
+        self.0
+    }
+
+    /// Find [`Contrast`] of an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `original_rgb` - the image to find [`Contrast`] for.
+    pub fn find(original_rgb: &image::RgbImage) -> Self {
+        Self::new(moments(original_rgb).1)
+    }
+}
+
+/// [`Brightness`] as a [`Feature`] named `brightness`.
+pub struct BrightnessFeature;
+
+impl Feature for BrightnessFeature {
+    fn name(&self) -> &'static str {
+        "brightness"
+    }
+
+    fn compute(&self, img: &Preprocessed) -> Value {
+        Value::Number(Brightness::find(img.rgb).mean())
+    }
+
+    fn distance(&self, a: &Value, b: &Value) -> f64 {
+        set::number_distance(a, b)
+    }
+
+    fn max_distance(&self) -> f64 {
+        MAX_BRIGHTNESS_DIFFERENCE
+    }
+}
+
+/// [`Contrast`] as a [`Feature`] named `contrast`.
+pub struct ContrastFeature;
+
+impl Feature for ContrastFeature {
+    fn name(&self) -> &'static str {
+        "contrast"
+    }
+
+    fn compute(&self, img: &Preprocessed) -> Value {
+        Value::Number(Contrast::find(img.rgb).rms())
+    }
+
+    fn distance(&self, a: &Value, b: &Value) -> f64 {
+        set::number_distance(a, b)
+    }
+
+    fn max_distance(&self) -> f64 {
+        MAX_CONTRAST_DIFFERENCE
+    }
+}
@@ -0,0 +1,373 @@
+//! Image features.
+//!
+//! Various features of images that can narrow down a dataset that
+//! a search can be performed on. Some of the features can be used
+//! to sort the dataset, others don't. See documentation to learn.
+//!
+//! Finding features does no IO and needs no runtime, it only takes pixels,
+//! see [`ImgFeatures::of_image`]. The module builds for `wasm32-unknown-unknown`
+//! too, without the rest of the crate, so that e.g. a web page can hash images
+//! the same way before querying a [server](crate::serve) with only their hashes.
+//! It is [`analyze::features`](crate::analyze::features) as well.
+
+pub mod border;
+pub mod dhash;
+#[cfg(feature = "embedding")]
+pub mod embedding;
+pub mod exif;
+pub mod grayscale;
+pub mod hue;
+pub mod lshash;
+pub mod luminance;
+pub mod schema;
+pub mod set;
+pub mod sharpness;
+pub mod tiles;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::analyze::img::ImgRaw;
+use exif::Exif;
+use grayscale::Grayscale;
+use hue::Hue;
+use lshash::{HashThreshold, LsHash};
+use luminance::{Brightness, Contrast};
+use set::{FeatureSet, Preprocessed, Value};
+use sharpness::Sharpness;
+use std::collections::BTreeMap;
+use tiles::Tiles;
+
+/// Names of features that are fields of [`ImgFeatures`], they are always found.
+const FIELDS: [&str; 5] = ["lshash", "hue", "sharpness", "brightness", "contrast"];
+
+/// Images whose [`Brightness`] differs more than this can't be similar.
+pub const MAX_BRIGHTNESS_DIFFERENCE: f64 = 0.25;
+
+/// Images whose [`Contrast`] differs more than this can't be similar.
+pub const MAX_CONTRAST_DIFFERENCE: f64 = 0.15;
+
+/// What to do when finding [`ImgFeatures`].
+#[derive(Clone, Copy, Debug)]
+pub struct FindOptions {
+    /// Whether to find [`Tiles`] too, they take a while.
+    pub tiles: bool,
+    /// Whether to trim uniform borders first, see [`border`].
+    pub trim: bool,
+    /// How to convert images to grayscale for hashing.
+    pub grayscale: Grayscale,
+    /// What pixels are compared to when finding [`LsHash`].
+    pub threshold: HashThreshold,
+    /// Features images are compared by, those that aren't fields
+    /// of [`ImgFeatures`] are found too, see [`set`].
+    pub features: &'static FeatureSet,
+}
+
+impl Default for FindOptions {
+    fn default() -> Self {
+        Self {
+            tiles: false,
+            trim: true,
+            grayscale: Grayscale::default(),
+            threshold: HashThreshold::default(),
+            features: FeatureSet::standard(),
+        }
+    }
+}
+
+/// Features of an image.
+///
+/// Can be used as a key when sorting a number of images
+/// to later use binary search on these images.
+///
+/// Has more than one feature, when sorting,
+/// higher features have higher priority.
+///
+/// # Examples
+///
+#[derive(Clone, PartialEq, PartialOrd)]
+pub struct ImgFeatures {
+    /// Locality-sensitive hash of the image.
+    pub lshash: LsHash,
+    /// Hue characteristic of the image.
+    pub hue: Hue,
+    /// Sharpness of the image.
+    pub sharpness: Sharpness,
+    /// Brightness of the image.
+    pub brightness: Brightness,
+    /// Contrast of the image.
+    pub contrast: Contrast,
+    /// Hashes of parts of the image, if they were asked for.
+    pub tiles: Option<Tiles>,
+    /// How the image was converted to grayscale for the hashes.
+    pub grayscale: Grayscale,
+    /// What pixels were compared to when finding [`LsHash`].
+    pub threshold: HashThreshold,
+    /// Features that aren't fields, by their names, see [`set`].
+    pub custom: BTreeMap<String, Value>,
+    /// EXIF metadata of the file of the image, if it was read, see [`exif`].
+    /// Not found from pixels, [`of_image`](Self::of_image) leaves it out.
+    pub exif: Option<Exif>,
+}
+
+impl ImgFeatures {
+    /// Find ImgFeatures for a given Image, with default [`FindOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `original` - image to find the features for.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::img::ImgRaw;
+    /// # use libsuccotash::analyze::features::ImgFeatures;
+    /// let img_raw = ImgRaw {
+    ///     path: "/home/user/pic.png",
+    ///     data: image::DynamicImage::ImageRgb8(image::RgbImage::new(32, 32)),
+    /// };
+    /// let img_features = ImgFeatures::find(&img_raw);
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn find<P>(original: &ImgRaw<P>) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Self::find_with(original, FindOptions::default())
+    }
+
+    /// Find ImgFeatures for a given Image.
+    ///
+    /// # Arguments
+    ///
+    /// * `original` - image to find the features for.
+    /// * `options` - what to do when finding them.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::img::ImgRaw;
+    /// # use libsuccotash::analyze::features::{FindOptions, ImgFeatures};
+    /// let img_raw = ImgRaw {
+    ///     path: "/home/user/pic.png",
+    ///     data: image::DynamicImage::ImageRgb8(image::RgbImage::new(32, 32)),
+    /// };
+    /// let options = FindOptions {
+    ///     tiles: true,
+    ///     trim: false,
+    ///     ..FindOptions::default()
+    /// };
+    /// let img_features = ImgFeatures::find_with(&img_raw, options);
+    /// assert!(img_features.tiles.is_some());
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn find_with<P>(original: &ImgRaw<P>, options: FindOptions) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Self::of_image(&original.data, options)
+    }
+
+    /// Find ImgFeatures for pixels of an image, decoded in any way.
+    ///
+    /// The same as [`ImgFeatures::find_with`], without a file behind the image.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - the image to find the features for.
+    /// * `options` - what to do when finding them.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use libsuccotash::features::{FindOptions, ImgFeatures};
+    /// let data = image::DynamicImage::ImageRgb8(image::RgbImage::new(32, 32));
+    /// let img_features = ImgFeatures::of_image(&data, FindOptions::default());
+    /// println!("{}", img_features.encode());
+    /// ```
+    pub fn of_image(data: &image::DynamicImage, options: FindOptions) -> Self {
+        let original_rgb = data.to_rgb8();
+        let original_rgb = if options.trim {
+            border::trim(&original_rgb)
+        } else {
+            std::borrow::Cow::Borrowed(&original_rgb)
+        };
+        let grayscale = options.grayscale.convert(&original_rgb);
+        let preprocessed = Preprocessed {
+            rgb: &original_rgb,
+            luma: &grayscale,
+        };
+        let custom = options
+            .features
+            .iter()
+            .filter(|feature| !FIELDS.contains(&feature.name()))
+            .map(|feature| (feature.name().to_owned(), feature.compute(&preprocessed)))
+            .collect();
+
+        Self {
+            lshash: LsHash::find_luma_with(&grayscale, options.threshold),
+            hue: Hue::find(&original_rgb),
+            sharpness: Sharpness::find(&original_rgb),
+            brightness: Brightness::find(&original_rgb),
+            contrast: Contrast::find(&original_rgb),
+            tiles: options.tiles.then(|| Tiles::find_luma(&grayscale)),
+            grayscale: options.grayscale,
+            threshold: options.threshold,
+            custom,
+            exif: None,
+        }
+    }
+
+    /// Value of a feature by its name, whether it is a field or not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::{set::Value, ImgFeatures};
+    /// let features = ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2 dhash=0x0000000000000001").unwrap();
+    /// assert_eq!(features.value("lshash"), Some(Value::Bits(255)));
+    /// assert_eq!(features.value("brightness"), Some(Value::Number(0.5)));
+    /// assert_eq!(features.value("dhash"), Some(Value::Bits(1)));
+    /// assert_eq!(features.value("redness"), None);
+    /// ```
+    pub fn value(&self, name: &str) -> Option<Value> {
+        match name {
+            "lshash" => Some(Value::Bits(self.lshash.bits())),
+            "hue" => Some(Value::Number(self.hue.degrees())),
+            "sharpness" => Some(Value::Number(self.sharpness.variance())),
+            "brightness" => Some(Value::Number(self.brightness.mean())),
+            "contrast" => Some(Value::Number(self.contrast.rms())),
+            _ => self.custom.get(name).cloned(),
+        }
+    }
+
+    /// Whether hashes of two images were found the same way, so they can be compared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::ImgFeatures;
+    /// let mean = ImgFeatures::decode("0000000000000000 0 0 0.5 0.2").unwrap();
+    /// let median = ImgFeatures::decode("0000000000000000:median 0 0 0.5 0.2").unwrap();
+    /// assert!(mean.comparable(&mean));
+    /// assert!(!mean.comparable(&median));
+    /// ```
+    pub fn comparable(&self, other: &Self) -> bool {
+        self.grayscale == other.grayscale && self.threshold == other.threshold
+    }
+
+    /// Whether two images may be similar, judging by cheap features only.
+    ///
+    /// Images with wildly different brightness or contrast can't be similar,
+    /// so there is no need to compare them in an expensive way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::ImgFeatures;
+    /// let dark = ImgFeatures::decode("0000000000000000 0 0 0.1 0.05").unwrap();
+    /// let light = ImgFeatures::decode("0000000000000000 0 0 0.9 0.05").unwrap();
+    /// assert!(dark.may_be_similar(&dark));
+    /// assert!(!dark.may_be_similar(&light));
+    /// ```
+    pub fn may_be_similar(&self, other: &Self) -> bool {
+        (self.brightness.mean() - other.brightness.mean()).abs() <= MAX_BRIGHTNESS_DIFFERENCE
+            && (self.contrast.rms() - other.contrast.rms()).abs() <= MAX_CONTRAST_DIFFERENCE
+    }
+
+    /// Encode the features as a single line of text.
+    ///
+    /// Used to pass features between processes, see [`decode`](Self::decode).
+    /// The [`Grayscale`] conversion and the [`HashThreshold`] follow the hash,
+    /// each after a colon, unless they are the default ones.
+    /// Features that aren't fields come next, as `name=value` words,
+    /// and [EXIF metadata](Exif::encode) last, as `exif.name=value` words.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::ImgFeatures;
+    /// # use libsuccotash::analyze::features::grayscale::Grayscale;
+    /// let features = ImgFeatures::decode("00000000000000ff:rec709:median 0 0 0.5 0.2").unwrap();
+    /// assert_eq!(features.grayscale, Grayscale::Rec709);
+    /// assert_eq!(features.encode(), "00000000000000ff:rec709:median 0 0 0.5 0.2");
+    /// let photo = ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2 exif.orientation=6").unwrap();
+    /// assert_eq!(photo.exif.and_then(|exif| exif.orientation), Some(6));
+    /// ```
+    pub fn encode(&self) -> String {
+        let mut hash = format!("{:016x}", self.lshash.bits());
+        if self.grayscale != Grayscale::default() {
+            hash += &format!(":{}", self.grayscale);
+        }
+        if self.threshold != HashThreshold::default() {
+            hash += &format!(":{}", self.threshold);
+        }
+        let encoded = format!(
+            "{} {} {} {} {}",
+            hash,
+            self.hue.degrees(),
+            self.sharpness.variance(),
+            self.brightness.mean(),
+            self.contrast.rms()
+        );
+        let mut encoded = match &self.tiles {
+            Some(tiles) => format!("{} {}", encoded, tiles.encode()),
+            None => encoded,
+        };
+        for (name, value) in &self.custom {
+            encoded += &format!(" {}={}", name, value.encode());
+        }
+        // Metadata without any of the fields decodes as none.
+        if let Some(words) = self.exif.as_ref().map(Exif::encode) {
+            if !words.is_empty() {
+                encoded += &format!(" {}", words);
+            }
+        }
+        encoded
+    }
+
+    /// Decode features encoded with [`encode`](Self::encode).
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let mut words = encoded.split(' ');
+        let mut hash = words.next()?.split(':');
+        let lshash = u64::from_str_radix(hash.next()?, 16).ok()?;
+        let (mut grayscale, mut threshold) = (Grayscale::default(), HashThreshold::default());
+        for parameter in hash {
+            if let Ok(parsed) = parameter.parse() {
+                grayscale = parsed;
+            } else {
+                threshold = parameter.parse().ok()?;
+            }
+        }
+        let hue = words.next()?.parse().ok()?;
+        let sharpness = words.next()?.parse().ok()?;
+        let brightness = words.next()?.parse().ok()?;
+        let contrast = words.next()?.parse().ok()?;
+        let mut words = words.peekable();
+        let tiles = match words.next_if(|word| !word.contains('=')) {
+            Some(word) => Some(Tiles::decode(word)?),
+            None => None,
+        };
+        let (mut custom, mut exif) = (BTreeMap::new(), None);
+        for word in words {
+            let (name, value) = word.split_once('=')?;
+            if let Some(name) = name.strip_prefix("exif.") {
+                exif.get_or_insert_with(Exif::default)
+                    .decode_word(name, value)?;
+            } else {
+                custom.insert(name.to_owned(), Value::decode(value)?);
+            }
+        }
+
+        Some(Self {
+            lshash: LsHash::new(lshash),
+            hue: Hue::new(angle::Deg(hue)),
+            sharpness: Sharpness::new(sharpness),
+            brightness: Brightness::new(brightness),
+            contrast: Contrast::new(contrast),
+            tiles,
+            grayscale,
+            threshold,
+            custom,
+            exif,
+        })
+    }
+}
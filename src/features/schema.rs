@@ -0,0 +1,133 @@
+//! How features were found, stored next to them.
+//!
+//! Features saved to an [index](crate::analyze::index) are only valid for as
+//! long as they would be found the same way: with the same algorithms and
+//! their parameters. A [`Schema`] records both, and features whose schema
+//! doesn't [satisfy](Schema::satisfies) the current one are found again.
+
+use super::grayscale::Grayscale;
+use super::lshash::HashThreshold;
+use super::FindOptions;
+
+/// Version of the algorithms features are found with.
+///
+/// Bump it whenever any of them changes in a way that changes its results,
+/// so that features found by older versions are found again.
+pub const VERSION: u32 = 3;
+
+/// The version of the algorithms and their parameters features were found with.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::{schema::Schema, FindOptions};
+/// let schema = Schema::of(FindOptions::default());
+/// assert_eq!(schema.encode(), "v3 trim=true tiles=false grayscale=default threshold=mean custom=");
+/// assert_eq!(Schema::decode(&schema.encode()), Some(schema));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schema {
+    /// Version of the algorithms, see [`VERSION`].
+    pub version: u32,
+    /// Whether uniform borders were trimmed.
+    pub trim: bool,
+    /// Whether tiles were found.
+    pub tiles: bool,
+    /// How images were converted to grayscale.
+    pub grayscale: Grayscale,
+    /// What pixels were compared to when hashing.
+    pub threshold: HashThreshold,
+    /// Names of features found in addition to the fields of
+    /// [`ImgFeatures`](super::ImgFeatures), ordered.
+    pub custom: Vec<String>,
+}
+
+impl Schema {
+    /// The schema features are found with now, with `options`.
+    pub fn of(options: FindOptions) -> Self {
+        let mut custom: Vec<String> = options
+            .features
+            .iter()
+            .map(|feature| feature.name())
+            .filter(|name| !super::FIELDS.contains(name))
+            .map(str::to_owned)
+            .collect();
+        custom.sort_unstable();
+        Self {
+            version: VERSION,
+            trim: options.trim,
+            tiles: options.tiles,
+            grayscale: options.grayscale,
+            threshold: options.threshold,
+            custom,
+        }
+    }
+
+    /// Whether features found with this schema can be used in place of
+    /// those that would be found with `wanted`.
+    ///
+    /// They can if everything is the same, except that they may have
+    /// more custom features than wanted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::{schema::{self, Schema}, FindOptions};
+    /// let wanted = Schema::of(FindOptions::default());
+    /// let mut stored = wanted.clone();
+    /// stored.custom.push("dhash".to_owned());
+    /// assert!(stored.satisfies(&wanted));
+    /// assert!(!wanted.satisfies(&stored));
+    /// stored.version = schema::VERSION - 1;
+    /// assert!(!stored.satisfies(&wanted));
+    /// ```
+    pub fn satisfies(&self, wanted: &Self) -> bool {
+        self.version == wanted.version
+            && self.trim == wanted.trim
+            && self.tiles == wanted.tiles
+            && self.grayscale == wanted.grayscale
+            && self.threshold == wanted.threshold
+            && wanted.custom.iter().all(|name| self.custom.contains(name))
+    }
+
+    /// Encode the schema as a line of text without tabs, see [`decode`](Self::decode).
+    ///
+    /// It starts with a `v` and the version, then `name=value` words follow.
+    pub fn encode(&self) -> String {
+        format!(
+            "v{} trim={} tiles={} grayscale={} threshold={} custom={}",
+            self.version,
+            self.trim,
+            self.tiles,
+            self.grayscale,
+            self.threshold,
+            self.custom.join(",")
+        )
+    }
+
+    /// Decode a schema encoded with [`encode`](Self::encode).
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let mut words = encoded.split(' ');
+        let version = words.next()?.strip_prefix('v')?.parse().ok()?;
+        let mut schema = Self {
+            version,
+            trim: true,
+            tiles: false,
+            grayscale: Grayscale::default(),
+            threshold: HashThreshold::default(),
+            custom: Vec::new(),
+        };
+        for word in words {
+            match word.split_once('=')? {
+                ("trim", value) => schema.trim = value.parse().ok()?,
+                ("tiles", value) => schema.tiles = value.parse().ok()?,
+                ("grayscale", value) => schema.grayscale = value.parse().ok()?,
+                ("threshold", value) => schema.threshold = value.parse().ok()?,
+                ("custom", "") => {}
+                ("custom", value) => schema.custom = value.split(',').map(str::to_owned).collect(),
+                _ => return None,
+            }
+        }
+        Some(schema)
+    }
+}
@@ -0,0 +1,356 @@
+//! Pluggable features and sets of them that images are compared by.
+//!
+//! A [`Feature`] is anything found on a preprocessed image that has a distance.
+//! Built-in features are [registered](register) already, library users can add
+//! their own and pick which are used with a [`FeatureSet`]. Two images are
+//! similar if all features of the set are close enough.
+//!
+//! Features that aren't fields of [`ImgFeatures`] are kept in
+//! [`ImgFeatures::custom`]. Worker processes only know built-in features,
+//! so custom ones have to be found in this process, see [`crate::analyze::parallel`].
+
+use super::ImgFeatures;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// What a [`Feature`] finds for an image.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum Value {
+    /// A hash, compared bit by bit.
+    Bits(u64),
+    /// A measure of some kind.
+    Number(f64),
+    /// An embedding of the image, compared by direction, see [`vector_distance`].
+    Vector(Arc<[f32]>),
+}
+
+impl Value {
+    /// Encode the value as a single word, see [`decode`](Self::decode).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::set::Value;
+    /// assert_eq!(Value::Bits(255).encode(), "0x00000000000000ff");
+    /// assert_eq!(Value::Number(0.5).encode(), "0.5");
+    /// assert_eq!(Value::Vector(vec![0.5, -1.].into()).encode(), "[0.5,-1]");
+    /// assert_eq!(Value::decode("0x00000000000000ff"), Some(Value::Bits(255)));
+    /// assert_eq!(Value::decode("0.5"), Some(Value::Number(0.5)));
+    /// assert_eq!(Value::decode("[0.5,-1]"), Some(Value::Vector(vec![0.5, -1.].into())));
+    /// assert_eq!(Value::decode("[]"), Some(Value::Vector(vec![].into())));
+    /// ```
+    pub fn encode(&self) -> String {
+        match self {
+            Value::Bits(bits) => format!("0x{:016x}", bits),
+            Value::Number(number) => number.to_string(),
+            Value::Vector(vector) => {
+                let numbers: Vec<String> = vector.iter().map(f32::to_string).collect();
+                format!("[{}]", numbers.join(","))
+            }
+        }
+    }
+
+    /// Decode a value encoded with [`encode`](Self::encode).
+    pub fn decode(encoded: &str) -> Option<Self> {
+        if let Some(numbers) = encoded.strip_prefix('[') {
+            let numbers = numbers.strip_suffix(']')?;
+            if numbers.is_empty() {
+                return Some(Value::Vector(Arc::new([])));
+            }
+            let vector: Result<Vec<f32>, _> = numbers.split(',').map(str::parse).collect();
+            return vector.ok().map(|vector| Value::Vector(vector.into()));
+        }
+        match encoded.strip_prefix("0x") {
+            Some(bits) => u64::from_str_radix(bits, 16).ok().map(Value::Bits),
+            None => encoded.parse().ok().map(Value::Number),
+        }
+    }
+}
+
+/// An image prepared for finding features on.
+pub struct Preprocessed<'a> {
+    /// The image, with its borders trimmed if asked to.
+    pub rgb: &'a image::RgbImage,
+    /// The image converted to grayscale, see [`super::grayscale`].
+    pub luma: &'a image::GrayImage,
+}
+
+/// A feature of images.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::set::{self, Feature, FeatureSet, Preprocessed, Value};
+/// /// Mean of the red channel.
+/// struct Redness;
+///
+/// impl Feature for Redness {
+///     fn name(&self) -> &'static str {
+///         "redness"
+///     }
+///
+///     fn compute(&self, img: &Preprocessed) -> Value {
+///         let sum: f64 = img.rgb.pixels().map(|pixel| f64::from(pixel[0])).sum();
+///         Value::Number(sum / f64::from(img.rgb.width() * img.rgb.height()) / 255.)
+///     }
+///
+///     fn distance(&self, a: &Value, b: &Value) -> f64 {
+///         set::number_distance(a, b)
+///     }
+///
+///     fn max_distance(&self) -> f64 {
+///         0.2
+///     }
+/// }
+///
+/// set::register(std::sync::Arc::new(Redness)).unwrap();
+/// let features = FeatureSet::select(&["lshash", "redness"]).unwrap();
+/// assert_eq!(features.names(), vec!["lshash", "redness"]);
+/// ```
+pub trait Feature: Send + Sync {
+    /// Name to pick the feature by, unique among registered features.
+    fn name(&self) -> &'static str;
+
+    /// Find the feature of an image.
+    fn compute(&self, img: &Preprocessed) -> Value;
+
+    /// Distance between the feature of two images.
+    fn distance(&self, a: &Value, b: &Value) -> f64;
+
+    /// Images whose features are farther than this can't be similar.
+    fn max_distance(&self) -> f64;
+}
+
+/// Hamming distance between two [`Value::Bits`], infinite for anything else.
+pub fn bits_distance(a: &Value, b: &Value) -> f64 {
+    match (a, b) {
+        (Value::Bits(a), Value::Bits(b)) => f64::from((a ^ b).count_ones()),
+        _ => f64::INFINITY,
+    }
+}
+
+/// Absolute difference between two [`Value::Number`]s, infinite for anything else.
+pub fn number_distance(a: &Value, b: &Value) -> f64 {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => (a - b).abs(),
+        _ => f64::INFINITY,
+    }
+}
+
+/// Angle between two [`Value::Vector`]s, from 0 for the same direction to 1
+/// for opposite ones, infinite for anything else, see [`angular_distance`].
+///
+/// Empty vectors are what couldn't be embedded, they are similar to nothing.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::set::{self, Value};
+/// let a = Value::Vector(vec![1., 0.].into());
+/// let b = Value::Vector(vec![0., 3.].into());
+/// assert!((set::vector_distance(&a, &b) - 0.5).abs() < 1e-6);
+/// assert!(set::vector_distance(&a, &Value::Vector(vec![].into())).is_infinite());
+/// assert!(set::vector_distance(&a, &Value::Number(1.)).is_infinite());
+/// ```
+pub fn vector_distance(a: &Value, b: &Value) -> f64 {
+    match (a, b) {
+        (Value::Vector(a), Value::Vector(b)) if !a.is_empty() && !b.is_empty() => {
+            angular_distance(a, b)
+        }
+        _ => f64::INFINITY,
+    }
+}
+
+/// Angle between two vectors, from 0 for the same direction to 1 for opposite ones.
+///
+/// Vectors are compared by the cosine of the angle between them, turned into
+/// the angle itself, which unlike `1 - cosine` satisfies the triangle inequality.
+/// Zero vectors are in no direction, they are 0.5 away from everything else.
+pub fn angular_distance(a: &[f32], b: &[f32]) -> f64 {
+    let (mut dot, mut norm_a, mut norm_b) = (0., 0., 0.);
+    for (&a, &b) in a.iter().zip(b) {
+        let (a, b) = (f64::from(a), f64::from(b));
+        dot += a * b;
+        norm_a += a * a;
+        norm_b += b * b;
+    }
+    if norm_a == 0. || norm_b == 0. {
+        return if norm_a == norm_b { 0. } else { 0.5 };
+    }
+    let cosine = (dot / (norm_a.sqrt() * norm_b.sqrt())).clamp(-1., 1.);
+    cosine.acos() / std::f64::consts::PI
+}
+
+/// All registered features, the built-in ones first.
+static REGISTRY: LazyLock<RwLock<Vec<Arc<dyn Feature>>>> = LazyLock::new(|| {
+    RwLock::new(vec![
+        Arc::new(super::lshash::LsHashFeature),
+        Arc::new(super::dhash::DHashFeature),
+        Arc::new(super::hue::HueFeature),
+        Arc::new(super::luminance::BrightnessFeature),
+        Arc::new(super::luminance::ContrastFeature),
+    ])
+});
+
+/// Register a feature, so that it can be [selected](FeatureSet::select) by its name.
+///
+/// Fails if there is a feature with this name already.
+pub fn register(feature: Arc<dyn Feature>) -> Result<(), String> {
+    let mut registry = REGISTRY.write().unwrap();
+    if registry.iter().any(|known| known.name() == feature.name()) {
+        return Err(format!(
+            "feature '{}' is registered already",
+            feature.name()
+        ));
+    }
+    registry.push(feature);
+    Ok(())
+}
+
+/// A registered feature with the given name.
+pub fn lookup(name: &str) -> Option<Arc<dyn Feature>> {
+    let registry = REGISTRY.read().unwrap();
+    registry
+        .iter()
+        .find(|feature| feature.name() == name)
+        .cloned()
+}
+
+/// Names of all registered features.
+pub fn names() -> Vec<&'static str> {
+    let registry = REGISTRY.read().unwrap();
+    registry.iter().map(|feature| feature.name()).collect()
+}
+
+/// Features images are compared by, each with how far apart it can be in similar images.
+#[derive(Clone)]
+pub struct FeatureSet {
+    features: Vec<(Arc<dyn Feature>, f64)>,
+}
+
+/// What images are compared by unless told otherwise.
+static STANDARD: LazyLock<FeatureSet> = LazyLock::new(|| {
+    FeatureSet::select(&["lshash", "brightness", "contrast"]).expect("built-in features exist")
+});
+
+impl FeatureSet {
+    /// Hashes, brightness and contrast, what images are compared by unless told otherwise.
+    pub fn standard() -> &'static Self {
+        &STANDARD
+    }
+
+    /// Registered features with the given names, in that order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::set::FeatureSet;
+    /// assert!(FeatureSet::select(&["lshash", "dhash", "hue"]).is_ok());
+    /// assert!(FeatureSet::select(&["lshash", "smell"]).is_err());
+    /// assert!(FeatureSet::select(&[]).is_err());
+    /// ```
+    pub fn select(names: &[&str]) -> Result<Self, String> {
+        if names.is_empty() {
+            return Err("no features selected".to_owned());
+        }
+        let mut features = Vec::new();
+        for name in names {
+            let feature = lookup(name).ok_or_else(|| {
+                format!(
+                    "unknown feature '{}', try one of: {}",
+                    name,
+                    self::names().join(", ")
+                )
+            })?;
+            let max_distance = feature.max_distance();
+            features.push((feature, max_distance));
+        }
+        Ok(Self { features })
+    }
+
+    /// Features from a comma-separated list of names, e.g. `lshash,dhash,hue`.
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let names: Vec<&str> = list.split(',').map(str::trim).collect();
+        Self::select(&names)
+    }
+
+    /// Let the feature `name` be at most `max_distance` apart in similar images,
+    /// if it is in the set.
+    pub fn with_max_distance(mut self, name: &str, max_distance: f64) -> Self {
+        for (feature, max) in &mut self.features {
+            if feature.name() == name {
+                *max = max_distance;
+            }
+        }
+        self
+    }
+
+    /// Keep the set for as long as the program runs, e.g. to put it into
+    /// [`FindOptions`](super::FindOptions).
+    pub fn leak(self) -> &'static Self {
+        Box::leak(Box::new(self))
+    }
+
+    /// How far apart the feature `name` can be in similar images, if it is in the set.
+    pub fn max_distance(&self, name: &str) -> Option<f64> {
+        self.features
+            .iter()
+            .find(|(feature, _)| feature.name() == name)
+            .map(|(_, max_distance)| *max_distance)
+    }
+
+    /// Names of the features, in order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.iter().map(|feature| feature.name()).collect()
+    }
+
+    /// The features, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Feature>> {
+        self.features.iter().map(|(feature, _)| feature)
+    }
+
+    /// Whether two images are close enough in all features of the set.
+    ///
+    /// Features missing from either image don't tell them apart.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::analyze::features::{set::FeatureSet, ImgFeatures};
+    /// let red = ImgFeatures::decode("00000000000000ff 0 0 0.5 0.2").unwrap();
+    /// let blue = ImgFeatures::decode("00000000000000ff 240 0 0.5 0.2").unwrap();
+    /// assert!(FeatureSet::standard().similar(&red, &blue));
+    /// let by_hue = FeatureSet::parse("lshash,hue").unwrap();
+    /// assert!(!by_hue.similar(&red, &blue));
+    /// ```
+    pub fn similar(&self, a: &ImgFeatures, b: &ImgFeatures) -> bool {
+        self.features.iter().all(|(feature, max_distance)| {
+            match (a.value(feature.name()), b.value(feature.name())) {
+                (Some(a), Some(b)) => feature.distance(&a, &b) <= *max_distance,
+                _ => true,
+            }
+        })
+    }
+}
+
+impl PartialEq for FeatureSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.features.len() == other.features.len()
+            && self
+                .features
+                .iter()
+                .zip(&other.features)
+                .all(|((a, max_a), (b, max_b))| a.name() == b.name() && max_a == max_b)
+    }
+}
+
+impl std::fmt::Debug for FeatureSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(
+                self.features
+                    .iter()
+                    .map(|(feature, max)| (feature.name(), max)),
+            )
+            .finish()
+    }
+}
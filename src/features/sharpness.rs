@@ -0,0 +1,87 @@
+//! Sharpness of an image.
+
+/// Images larger than this on any side are scaled down to fit before finding [`Sharpness`].
+pub const SIZE: u32 = 1024;
+
+/// Sharpness of an image, as variance of its Laplacian.
+///
+/// Blurry images have few sharp edges, so their Laplacian is mostly flat
+/// and has a low variance. Use it to pick the sharpest one out of
+/// near-duplicates, e.g. out of a burst: the value itself depends on
+/// the contents of the image, so it is meaningless on its own.
+///
+/// The Laplacian is found on the grayscale image scaled down to fit into
+/// [`SIZE`]x[`SIZE`], so that copies of an image in different resolutions
+/// get similar values.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::sharpness::Sharpness;
+/// let flat = image::RgbImage::from_pixel(16, 16, image::Rgb([128, 128, 128]));
+/// let checkers = image::RgbImage::from_fn(16, 16, |x, y| {
+///     image::Rgb([if (x + y) % 2 == 0 { 255 } else { 0 }; 3])
+/// });
+/// assert_eq!(Sharpness::find(&flat), Sharpness::new(0.));
+/// assert!(Sharpness::find(&checkers) > Sharpness::find(&flat));
+/// ```
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct Sharpness(f64);
+
+impl std::fmt::Display for Sharpness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+impl Sharpness {
+    pub fn new(variance: f64) -> Self {
+        Self(variance)
+    }
+
+    /// The variance of the Laplacian.
+    pub fn variance(&self) -> f64 {
+        self.0
+    }
+
+    /// Find [`Sharpness`] of an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `original_rgb` - the image to find [`Sharpness`] for.
+    pub fn find(original_rgb: &image::RgbImage) -> Self {
+        let grayscale = image::imageops::grayscale(original_rgb);
+        let (width, height) = grayscale.dimensions();
+        let grayscale = if width > SIZE || height > SIZE {
+            let scale = f64::from(SIZE) / f64::from(width.max(height));
+            image::imageops::resize(
+                &grayscale,
+                ((f64::from(width) * scale) as u32).max(1),
+                ((f64::from(height) * scale) as u32).max(1),
+                image::imageops::FilterType::Triangle,
+            )
+        } else {
+            grayscale
+        };
+
+        // Apply the Laplacian kernel to every pixel that has all 4 neighbours.
+        let (width, height) = grayscale.dimensions();
+        let at = |x: u32, y: u32| f64::from(grayscale.get_pixel(x, y)[0]);
+        let (mut sum, mut sum_squares, mut count) = (0., 0., 0.);
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                let laplacian =
+                    at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4. * at(x, y);
+                sum += laplacian;
+                sum_squares += laplacian * laplacian;
+                count += 1.;
+            }
+        }
+
+        if count == 0. {
+            return Self::new(0.);
+        }
+        let mean = sum / count;
+        Self::new(sum_squares / count - mean * mean)
+    }
+}
@@ -0,0 +1,213 @@
+//! Hashes of parts of an image.
+//!
+//! Used to find partial duplicates: crops, collages and screenshots that
+//! contain another image. An image is split into a [`GRID`]x[`GRID`] grid
+//! and every tile gets an [`LsHash`]. Another image contains it if most of
+//! its tiles are found among the windows of the other image: regions of
+//! various sizes, sliding by a quarter of their size.
+
+use super::lshash::LsHash;
+
+/// How many tiles an image is split into along each side.
+pub const GRID: u32 = 4;
+
+/// Windows are found for grids from 1x1 up to this, so tiles of images
+/// down to half of the size of the other image along each side can be found.
+pub const MAX_WINDOW_GRID: u32 = 2 * GRID;
+
+/// Windows slide by this part of their size.
+const STEPS: u32 = 4;
+
+/// Tiles whose hashes differ in at most this many bits match.
+pub const MAX_TILE_DISTANCE: u32 = 8;
+
+/// Images are scaled down to fit into this before splitting, tiles don't need details.
+const SIZE: u32 = 256;
+
+/// Tiles with less difference between their darkest and lightest pixels are flat.
+///
+/// Hashes of flat tiles are noise, so they aren't used.
+const MIN_TILE_RANGE: u8 = 12;
+
+/// Hashes with fewer changes between neighbouring bits are too simple to be used,
+/// e.g. a single straight edge, which can be found in almost any image.
+const MIN_TRANSITIONS: u32 = 20;
+
+/// Count changes between horizontally and vertically neighbouring bits of a hash.
+fn transitions(hash: &LsHash) -> u32 {
+    let bits = hash.bits();
+    // Bits are laid out row by row, 8 per row. Don't compare the last bit
+    // of a row with the first bit of the next one.
+    let horizontal = (bits ^ (bits >> 1)) & 0x7f7f_7f7f_7f7f_7f7f;
+    let vertical = (bits ^ (bits >> 8)) & 0x00ff_ffff_ffff_ffff;
+    horizontal.count_ones() + vertical.count_ones()
+}
+
+/// Hashes of tiles and windows of an image.
+///
+/// Finding them takes a while, so they are only found on demand.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::features::tiles::Tiles;
+/// let noise = |x: u32, y: u32| ((x * 7919 + y * 104729) ^ (x * y)) as u8;
+/// let whole = image::RgbImage::from_fn(256, 256, |x, y| image::Rgb([noise(x / 8, y / 8); 3]));
+/// let crop = image::imageops::crop_imm(&whole, 64, 64, 192, 192).to_image();
+/// let (whole, crop) = (Tiles::find(&whole), Tiles::find(&crop));
+/// assert!(crop.contained_in(&whole) > 0.9);
+/// assert!(whole.contained_in(&crop) < 0.75);
+/// ```
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct Tiles {
+    /// Hashes of the [`GRID`]x[`GRID`] tiles, row by row,
+    /// `None` for flat tiles and tiles that are too simple.
+    pub grid: Vec<Option<LsHash>>,
+    /// Hashes of the windows that aren't flat or too simple, without repetitions.
+    pub windows: Vec<LsHash>,
+}
+
+impl Tiles {
+    /// Find [`Tiles`] of an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `original_rgb` - the image to find [`Tiles`] for.
+    pub fn find(original_rgb: &image::RgbImage) -> Self {
+        Self::find_luma(&image::imageops::grayscale(original_rgb))
+    }
+
+    /// Find [`Tiles`] of a grayscale image.
+    ///
+    /// # Arguments
+    ///
+    /// * `grayscale` - the image to find [`Tiles`] for.
+    pub fn find_luma(grayscale: &image::GrayImage) -> Self {
+        let (width, height) = grayscale.dimensions();
+        let grayscale = if width > SIZE || height > SIZE {
+            let scale = f64::from(SIZE) / f64::from(width.max(height));
+            image::imageops::resize(
+                grayscale,
+                ((f64::from(width) * scale) as u32).max(1),
+                ((f64::from(height) * scale) as u32).max(1),
+                image::imageops::FilterType::Triangle,
+            )
+        } else {
+            grayscale.clone()
+        };
+
+        let (width, height) = grayscale.dimensions();
+        let hash_region = |x: u32, y: u32, w: u32, h: u32| {
+            let region = image::imageops::crop_imm(&grayscale, x, y, w.max(1), h.max(1));
+            let tile = image::imageops::resize(
+                &region.to_image(),
+                8,
+                8,
+                image::imageops::FilterType::Triangle,
+            );
+            let darkest = tile.as_raw().iter().min().copied().unwrap_or(0);
+            let lightest = tile.as_raw().iter().max().copied().unwrap_or(0);
+            if lightest - darkest < MIN_TILE_RANGE {
+                return None;
+            }
+            let hash = LsHash::find_luma(&tile);
+            if transitions(&hash) < MIN_TRANSITIONS {
+                return None;
+            }
+            Some(hash)
+        };
+
+        let grid = (0..GRID * GRID)
+            .map(|i| {
+                let (col, row) = (i % GRID, i / GRID);
+                hash_region(
+                    width * col / GRID,
+                    height * row / GRID,
+                    width / GRID,
+                    height / GRID,
+                )
+            })
+            .collect();
+
+        let mut windows = Vec::new();
+        for n in 1..=MAX_WINDOW_GRID {
+            let (w, h) = (width / n, height / n);
+            // There are STEPS * (n - 1) + 1 windows along a side.
+            for row in 0..STEPS * (n - 1) + 1 {
+                for col in 0..STEPS * (n - 1) + 1 {
+                    windows.extend(hash_region(col * w / STEPS, row * h / STEPS, w, h));
+                }
+            }
+        }
+        windows.sort_by_key(LsHash::bits);
+        windows.dedup();
+
+        Self { grid, windows }
+    }
+
+    /// Which part of the tiles of this image are found among the windows of `other`.
+    ///
+    /// Flat and simple tiles don't count. Images with less than half
+    /// of the tiles left are not contained in anything.
+    pub fn contained_in(&self, other: &Self) -> f64 {
+        let tiles: Vec<&LsHash> = self.grid.iter().flatten().collect();
+        if tiles.len() * 2 < self.grid.len() {
+            return 0.;
+        }
+
+        let found = tiles
+            .iter()
+            .filter(|tile| {
+                other
+                    .windows
+                    .iter()
+                    .any(|window| tile.distance(window) <= MAX_TILE_DISTANCE)
+            })
+            .count();
+        found as f64 / tiles.len() as f64
+    }
+
+    /// Encode the tiles as a single word.
+    ///
+    /// Used to pass tiles between processes, see [`decode`](Self::decode).
+    pub fn encode(&self) -> String {
+        let grid: Vec<String> = self
+            .grid
+            .iter()
+            .map(|tile| match tile {
+                Some(hash) => format!("{:016x}", hash.bits()),
+                None => "-".to_owned(),
+            })
+            .collect();
+        let windows: Vec<String> = self
+            .windows
+            .iter()
+            .map(|hash| format!("{:016x}", hash.bits()))
+            .collect();
+        format!("{};{}", grid.join(","), windows.join(","))
+    }
+
+    /// Decode tiles encoded with [`encode`](Self::encode).
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let hash = |word: &str| u64::from_str_radix(word, 16).ok().map(LsHash::new);
+
+        let (grid, windows) = encoded.split_once(';')?;
+        let grid = grid
+            .split(',')
+            .map(|word| match word {
+                "-" => Some(None),
+                word => hash(word).map(Some),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let windows = windows
+            .split(',')
+            .filter(|word| !word.is_empty())
+            .map(hash)
+            .collect::<Option<Vec<_>>>()?;
+        if grid.len() != (GRID * GRID) as usize {
+            return None;
+        }
+
+        Some(Self { grid, windows })
+    }
+}
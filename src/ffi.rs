@@ -0,0 +1,127 @@
+//! C interface to finding and comparing hashes, with the `ffi` feature.
+//!
+//! Programs in other languages can find the same hashes as the `hash`
+//! subcommand with default options, and compare them the same way.
+//! The functions are declared in `include/succotash.h`, generated from this
+//! module with cbindgen (see `cbindgen.toml`); build the library
+//! to link against with `cargo rustc --lib --release --features ffi --crate-type cdylib`
+//! (or `staticlib`). Panics never cross the interface, they are errors.
+
+use crate::analyze::img::ImgRaw;
+use crate::features::{lshash::LsHash, FindOptions, ImgFeatures};
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+
+/// The hash was found.
+pub const SUCCOTASH_OK: c_int = 0;
+/// A pointer was null or the size of a buffer was wrong.
+pub const SUCCOTASH_INVALID_ARGUMENT: c_int = 1;
+/// The image couldn't be decoded or hashed.
+pub const SUCCOTASH_FAILED: c_int = 2;
+
+/// Hashes that differ in at most this many bits are similar by default.
+#[no_mangle]
+pub static SUCCOTASH_MAX_DISTANCE: u32 = crate::features::lshash::MAX_DISTANCE;
+
+/// Hash `data` into `hash`, catching panics, decoding too.
+///
+/// `data` gives the image, or the error to return.
+fn hash_into(hash: *mut u64, data: impl FnOnce() -> Result<image::DynamicImage, c_int>) -> c_int {
+    if hash.is_null() {
+        return SUCCOTASH_INVALID_ARGUMENT;
+    }
+    let found = panic::catch_unwind(AssertUnwindSafe(|| {
+        let data = data()?;
+        Ok(ImgFeatures::of_image(&data, FindOptions::default()).lshash)
+    }));
+    match found {
+        Ok(Ok(lshash)) => {
+            // SAFETY: the caller gives a valid pointer, it isn't null.
+            unsafe { hash.write(lshash.bits()) };
+            SUCCOTASH_OK
+        }
+        Ok(Err(e)) => e,
+        Err(_) => SUCCOTASH_FAILED,
+    }
+}
+
+/// Hash an image given as pixels, 3 bytes per pixel in RGB order, row after row.
+///
+/// Returns [`SUCCOTASH_OK`] and writes the hash to `hash`, or an error.
+///
+/// # Safety
+///
+/// `pixels` has to point to `width * height * 3` bytes, and `hash` to a `u64`.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::ffi;
+/// let pixels = vec![128; 32 * 16 * 3];
+/// let mut hash = 0;
+/// let result = unsafe { ffi::succotash_hash_rgb8(pixels.as_ptr(), 32, 16, &mut hash) };
+/// assert_eq!(result, ffi::SUCCOTASH_OK);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn succotash_hash_rgb8(
+    pixels: *const u8,
+    width: u32,
+    height: u32,
+    hash: *mut u64,
+) -> c_int {
+    if pixels.is_null() {
+        return SUCCOTASH_INVALID_ARGUMENT;
+    }
+    hash_into(hash, || {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|len| len.checked_mul(3))
+            .ok_or(SUCCOTASH_INVALID_ARGUMENT)?;
+        // SAFETY: the caller gives this many bytes.
+        let pixels = unsafe { std::slice::from_raw_parts(pixels, len) };
+        let data = image::RgbImage::from_raw(width, height, pixels.to_vec())
+            .ok_or(SUCCOTASH_INVALID_ARGUMENT)?;
+        Ok(image::DynamicImage::ImageRgb8(data))
+    })
+}
+
+/// Hash an image given as contents of its file, e.g. a JPEG.
+///
+/// It is decoded the same way the `hash` subcommand decodes files.
+///
+/// Returns [`SUCCOTASH_OK`] and writes the hash to `hash`, or an error.
+///
+/// # Safety
+///
+/// `data` has to point to `len` bytes, and `hash` to a `u64`.
+#[no_mangle]
+pub unsafe extern "C" fn succotash_hash_file_contents(
+    data: *const u8,
+    len: usize,
+    hash: *mut u64,
+) -> c_int {
+    if data.is_null() {
+        return SUCCOTASH_INVALID_ARGUMENT;
+    }
+    // SAFETY: the caller gives this many bytes.
+    let data = unsafe { std::slice::from_raw_parts(data, len) };
+    // Decoded within, a decoder may panic on bytes made to make it.
+    hash_into(hash, || {
+        ImgRaw::decode("", data)
+            .map(|img_raw| img_raw.data)
+            .map_err(|_| SUCCOTASH_FAILED)
+    })
+}
+
+/// How many bits two hashes differ in.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::ffi;
+/// assert_eq!(ffi::succotash_distance(0xff, 0x0f), 4);
+/// ```
+#[no_mangle]
+pub extern "C" fn succotash_distance(a: u64, b: u64) -> u32 {
+    LsHash::new(a).distance(&LsHash::new(b))
+}
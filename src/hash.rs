@@ -0,0 +1,91 @@
+//! Subcommand 'hash' lives here.
+//!
+//! Hash prints features of files without comparing them to anything,
+//! so that succotash can be used as a building block in shell pipelines.
+//! Every image gets a line on stdout or in the output file, see [`line`];
+//! errors go to the log.
+
+use crate::analyze::features::{FindOptions, ImgFeatures};
+use crate::analyze::img::ImgRaw;
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::io::Write;
+
+/// A line for an image: the hash in hex, the hue in degrees and the path, tab-separated.
+///
+/// Files with more than one image, e.g. videos, get a line per image.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::{hash, analyze::features::ImgFeatures};
+/// let features = ImgFeatures::decode("00000000000000ff 120 0 0.5 0.2").unwrap();
+/// let line = hash::line("/home/user/pic.png".as_ref(), &features);
+/// assert_eq!(line, "00000000000000ff\t120\t/home/user/pic.png");
+/// ```
+pub fn line(path: &Path, features: &ImgFeatures) -> String {
+    format!(
+        "{:016x}\t{}\t{}",
+        features.lshash.bits(),
+        features.hue.degrees(),
+        path.to_string_lossy()
+    )
+}
+
+/// Hash the files, do not propagate errors.
+///
+/// You can think of it as of `main` of the `hash` subcommand.
+/// Returns [`Status::Failed`] if any of the files couldn't be hashed.
+///
+/// # Arguments
+///
+/// * `files` - files to hash.
+/// * `options` - how to find the features.
+/// * `output` - the file to write lines to, stdout without it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::hash;
+/// # async_std::task::block_on(async {
+/// hash::run(vec!["/home/user/pic.png".into()], Default::default(), None).await;
+/// # });
+/// ```
+pub async fn run(files: Vec<PathBuf>, options: FindOptions, output: Option<PathBuf>) -> Status {
+    let mut writer = match crate::analyze::output::open(output.as_deref()) {
+        Ok(writer) => writer,
+        Err(e) => {
+            error!("Error during 'hash': {}", e);
+            return Status::Failed;
+        }
+    };
+    let mut failed = 0;
+
+    for file in files {
+        let imgs_raw = match ImgRaw::load_all(file.clone()).await {
+            Ok(imgs_raw) => imgs_raw,
+            Err(e) => {
+                error!("Couldn't hash '{}': {}", file.to_string_lossy(), e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        for img_raw in imgs_raw {
+            let features = ImgFeatures::find_with(&img_raw, options);
+            if let Err(e) = writeln!(writer, "{}", line(&file, &features)) {
+                // E.g. the reader of a pipe has exited, nobody needs the rest.
+                debug!("Stopped writing hashes: {}", e);
+                return Status::failed(failed);
+            }
+        }
+    }
+
+    if let Err(e) = writer.flush() {
+        debug!("Stopped writing hashes: {}", e);
+    }
+    if failed > 0 {
+        warn!("Couldn't hash {} files", failed);
+    }
+    Status::failed(failed)
+}
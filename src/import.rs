@@ -0,0 +1,282 @@
+//! Subcommand 'import' lives here.
+//!
+//! Import copies images from a source, e.g. an SD card or a phone backup,
+//! into a library, leaving out those with a similar image in the library
+//! already, the way `index query` finds them. Features are reused from and
+//! saved to the index, so that importing into the library again is quick.
+//! Files keep their paths relative to the source, or are named by when they
+//! were taken, see [`date_name`], and nothing in the library is overwritten,
+//! see [`organize::destination`]. Similar images within the source are all
+//! copied, e.g. photos of a burst.
+
+use crate::analyze::features::{schema::Schema, ImgFeatures};
+use crate::analyze::index::{self, query, store};
+use crate::analyze::{self, AnalyzeOptions};
+use crate::journal::{Journal, Op};
+use crate::organize;
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::collections::HashSet;
+use std::io::Write;
+
+/// How to import files, besides what files and how to find their features.
+#[derive(Clone, Debug, Default)]
+pub struct ImportOptions {
+    /// Name files by when they were taken, see [`date_name`].
+    pub rename: bool,
+    /// Only print where files would go, without copying them.
+    pub dry_run: bool,
+    /// Where to write what files were skipped and why, see [`write_skipped`].
+    pub summary: Option<PathBuf>,
+    /// Where to record what is done to be undone, see [`journal`](crate::journal).
+    pub journal: Option<std::path::PathBuf>,
+}
+
+/// Where a file goes in the library, relative to it, named by when it was taken.
+///
+/// Files go into folders by year and month, like `organize --by date`
+/// sorts them, and keep their extension. Returns `None` for images without
+/// the time in their EXIF metadata.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::import;
+/// # use libsuccotash::analyze::features::ImgFeatures;
+/// let features = ImgFeatures::decode("00000000000000ff 100 0 0.5 0.2 exif.taken=1688212800").unwrap();
+/// let name = import::date_name("DCIM/IMG_0001.JPG".as_ref(), &features).unwrap();
+/// assert_eq!(name.to_string_lossy(), "2023/07/2023-07-01 12.00.00.JPG");
+/// let features = ImgFeatures::decode("00000000000000ff 100 0 0.5 0.2").unwrap();
+/// assert_eq!(import::date_name("DCIM/IMG_0001.JPG".as_ref(), &features), None);
+/// ```
+pub fn date_name(path: &Path, features: &ImgFeatures) -> Option<PathBuf> {
+    let folder = organize::folder(organize::By::Date, features)?;
+    let date_time = features.exif.as_ref()?.date_time()?;
+    // Without a fraction of a second, and with no colons, which some filesystems refuse.
+    let mut name = date_time[..19].replace(':', ".");
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    Some(folder.join(name))
+}
+
+/// What importing would do, see [`plan`].
+#[derive(Debug, Default)]
+pub struct Plan {
+    /// Files to copy, and where to.
+    pub copies: Vec<(PathBuf, PathBuf)>,
+    /// Files to skip, and the closest file of the library with a similar image.
+    pub skipped: Vec<(PathBuf, query::Match)>,
+}
+
+/// Plan what files to copy where, and what files to skip, see [`run`].
+///
+/// # Arguments
+///
+/// * `src` - the directory to import files from.
+/// * `library` - the directory to import them into.
+/// * `rename` - whether to name files by when they were taken, see [`date_name`].
+/// * `options` - what files to pick, how to find their features and how close
+///   similar images are.
+pub async fn plan(
+    src: &str,
+    library: &str,
+    rename: bool,
+    options: &AnalyzeOptions,
+) -> Result<Plan, Box<dyn std::error::Error>> {
+    let known = analyze::walker::Source::Walk(vec![library.to_owned()])
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+    let known = analyze::find_features(known.paths, options).await?;
+    let snapshot = store::Snapshot::of(known, Schema::of(options.find_options()));
+    debug!("Library '{}' has {} files", library, snapshot.len());
+    let query = query::Query::new(&snapshot, options.search);
+    let set = options
+        .features
+        .clone()
+        .with_max_distance("lshash", f64::from(options.max_distance));
+
+    let files = analyze::walker::Source::Walk(vec![src.to_owned()])
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+    let imgs = analyze::find_features(files.paths, options).await?;
+    let mut plan = Plan::default();
+    let mut taken = HashSet::new();
+    // Images of a file, e.g. keyframes of a video, are one after another.
+    let mut start = 0;
+    while start < imgs.len() {
+        let path = &imgs[start].path;
+        let end = start
+            + imgs[start..]
+                .iter()
+                .take_while(|img| img.path == *path)
+                .count();
+        let features: Vec<ImgFeatures> = imgs[start..end]
+            .iter()
+            .map(|img| img.features.clone())
+            .collect();
+        start = end;
+
+        let similar = index::similar_to_any(&query, path, &features, &set);
+        if let Some(closest) = similar.into_iter().next() {
+            plan.skipped.push((path.clone(), closest));
+            continue;
+        }
+        let relative = path
+            .strip_prefix(src)
+            .ok()
+            .map(Path::to_path_buf)
+            .or_else(|| path.file_name().map(Into::into))
+            .ok_or_else(|| format!("'{}' has no file name", path.to_string_lossy()))?;
+        let relative = match rename {
+            true => date_name(path, &features[0]).unwrap_or(relative),
+            false => relative,
+        };
+        let folder = Path::new(library).join(relative.parent().unwrap_or_else(|| "".as_ref()));
+        let name = relative.file_name().unwrap_or_default();
+        let to = organize::destination(name.as_ref(), &folder, &taken)
+            .ok_or_else(|| format!("'{}' has no file name", path.to_string_lossy()))?;
+        taken.insert(to.clone());
+        plan.copies.push((path.clone(), to));
+    }
+    Ok(plan)
+}
+
+/// Write skipped files, one per line: the path, the distance to the closest
+/// image of the library and its path, separated by tabs.
+pub fn write_skipped(
+    skipped: &[(PathBuf, query::Match)],
+    output: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = analyze::output::open(Some(output))?;
+    for (path, closest) in skipped {
+        writeln!(
+            writer,
+            "{}\t{}\t{}",
+            path.to_string_lossy(),
+            closest.distance,
+            closest.path.to_string_lossy()
+        )?;
+    }
+    writer.flush()?;
+    info!(
+        "Wrote {} skipped files to '{}'",
+        skipped.len(),
+        output.to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Copy a file where it goes, creating the folders on the way.
+fn copy(from: &Path, to: &Path, journal: &mut Journal) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    journal.record(Op::Copy, from.as_ref(), to.as_ref())?;
+    std::fs::copy(from, to).map(drop)
+}
+
+/// Import files, see [`run`].
+///
+/// Returns how many files couldn't be copied.
+async fn try_run(
+    src: &str,
+    library: &str,
+    import: ImportOptions,
+    options: &AnalyzeOptions,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let plan = plan(src, library, import.rename, options).await?;
+    for (path, closest) in &plan.skipped {
+        debug!(
+            "Skipping '{}', '{}' is similar",
+            path.to_string_lossy(),
+            closest.path.to_string_lossy()
+        );
+    }
+    if let Some(summary) = &import.summary {
+        write_skipped(&plan.skipped, summary)?;
+    }
+
+    if import.dry_run {
+        let mut writer = analyze::output::open(None)?;
+        for (from, to) in &plan.copies {
+            writeln!(
+                writer,
+                "{} -> {}",
+                from.to_string_lossy(),
+                to.to_string_lossy()
+            )?;
+        }
+        writer.flush()?;
+        return Ok(0);
+    }
+
+    let mut failed = 0;
+    let mut journal = Journal::new(import.journal);
+    for (from, to) in &plan.copies {
+        match copy(from, to, &mut journal) {
+            Ok(()) => debug!(
+                "Copied '{}' to '{}'",
+                from.to_string_lossy(),
+                to.to_string_lossy()
+            ),
+            Err(e) => {
+                error!(
+                    "Couldn't copy '{}' to '{}': {}",
+                    from.to_string_lossy(),
+                    to.to_string_lossy(),
+                    e
+                );
+                failed += 1;
+            }
+        }
+    }
+    journal.close();
+    info!(
+        "Imported {} files into '{}', skipped {} with similar images there already",
+        plan.copies.len() as u64 - failed,
+        library,
+        plan.skipped.len()
+    );
+    Ok(failed)
+}
+
+/// Copy files with no similar image in a library into it, do not propagate errors.
+///
+/// You can think of it as of `main` of the `import` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Arguments
+///
+/// * `src` - the directory to import files from.
+/// * `library` - the directory to import them into.
+/// * `import` - how to import them.
+/// * `options` - what files to pick, how to find their features and how close
+///   similar images are.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::import::{self, ImportOptions};
+/// # async_std::task::block_on(async {
+/// let import = ImportOptions {
+///     rename: true,
+///     ..Default::default()
+/// };
+/// import::run("/media/sdcard", "/home/user/Pictures", import, Default::default()).await;
+/// # });
+/// ```
+pub async fn run(
+    src: &str,
+    library: &str,
+    import: ImportOptions,
+    options: AnalyzeOptions,
+) -> Status {
+    match try_run(src, library, import, &options).await {
+        Ok(failed) => Status::failed(failed),
+        Err(e) => {
+            error!("Error during 'import': {}", e);
+            Status::Failed
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Subcommand 'init' lives here.
+//!
+//! Init asks a few questions on the terminal, writes the answers
+//! to the configuration file (see [`config`]) and, if asked to,
+//! registers a job that runs the analysis on a schedule.
+
+use crate::config::{self, Config, Schedule};
+use crate::status::Status;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Ask a question, return the answer or `default` if the answer is empty.
+///
+/// Fails on the end of input, so that a closed terminal doesn't loop forever.
+fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: &str,
+) -> io::Result<String> {
+    if default.is_empty() {
+        write!(output, "{}: ", question)?;
+    } else {
+        write!(output, "{} [{}]: ", question, default)?;
+    }
+    output.flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "no answer to the question",
+        ));
+    }
+    match answer.trim() {
+        "" => Ok(default.to_owned()),
+        answer => Ok(answer.to_owned()),
+    }
+}
+
+/// Ask a question until the answer parses.
+fn ask_parsed<R, W, T>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: &str,
+) -> io::Result<T>
+where
+    R: BufRead,
+    W: Write,
+    T: std::str::FromStr<Err = String>,
+{
+    loop {
+        match ask(input, output, question, default)?.parse() {
+            Ok(value) => return Ok(value),
+            Err(e) => writeln!(output, "Sorry, {}", e)?,
+        }
+    }
+}
+
+/// Ask a yes/no question, "no" by default.
+fn confirm<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+) -> io::Result<bool> {
+    let answer = ask(input, output, &format!("{} (y/n)", question), "n")?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Ask for a configuration.
+///
+/// # Arguments
+///
+/// * `input` - where the answers come from, e.g. stdin.
+/// * `output` - where the questions go to, e.g. stdout.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::{init, config::Schedule, analyze::preset::Preset};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let answers = "/home/user/Pictures\n/mnt/nas/Photos\n\n/tmp/index\nloose\nweekly\n";
+/// let config = init::ask_config(&mut answers.as_bytes(), &mut std::io::sink())?;
+/// assert_eq!(config.roots.len(), 2);
+/// assert_eq!(config.preset, Preset::Loose);
+/// assert_eq!(config.schedule, Schedule::Weekly);
+/// # Ok(())
+/// # }
+/// ```
+pub fn ask_config<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> io::Result<Config> {
+    let mut config = Config::default();
+
+    writeln!(
+        output,
+        "Which directories have your pictures? One per line, an empty line to finish."
+    )?;
+    loop {
+        let root = ask(input, output, "Directory", "")?;
+        if !root.is_empty() {
+            config.roots.push(root.into());
+        } else if config.roots.is_empty() {
+            writeln!(output, "Sorry, at least one directory is needed")?;
+        } else {
+            break;
+        }
+    }
+
+    let index = config::default_index_path().unwrap_or_default();
+    let index = ask(
+        input,
+        output,
+        "Where to keep the index",
+        &index.to_string_lossy(),
+    )?;
+    config.index = (!index.is_empty()).then(|| index.into());
+
+    config.preset = ask_parsed(
+        input,
+        output,
+        "How strict should the analysis be (strict, balanced, loose)",
+        config.preset.name(),
+    )?;
+    config.schedule = ask_parsed(
+        input,
+        output,
+        "How often to run the analysis on its own (never, daily, weekly)",
+        config.schedule.name(),
+    )?;
+
+    Ok(config)
+}
+
+/// A crontab line that runs the analysis of a root on the schedule.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::{init, config::Schedule, analyze::preset::Preset};
+/// let line = init::cron_line(
+///     Schedule::Daily,
+///     "/usr/bin/succotash".as_ref(),
+///     Preset::Strict,
+///     "/home/user/Pictures".as_ref(),
+/// );
+/// assert_eq!(
+///     line.unwrap(),
+///     "@daily '/usr/bin/succotash' analyze --preset strict '/home/user/Pictures'"
+/// );
+/// ```
+pub fn cron_line(
+    schedule: Schedule,
+    program: &Path,
+    preset: crate::analyze::preset::Preset,
+    root: &Path,
+) -> Option<String> {
+    // Single quotes keep everything but themselves as is in sh.
+    let quote = |path: &Path| format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"));
+    let when = match schedule {
+        Schedule::Never => return None,
+        Schedule::Daily => "@daily",
+        Schedule::Weekly => "@weekly",
+    };
+    Some(format!(
+        "{} {} analyze --preset {} {}",
+        when,
+        quote(program),
+        preset,
+        quote(root)
+    ))
+}
+
+/// Add lines to the crontab of the user.
+fn register_cron(lines: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use std::process::{Command, Stdio};
+
+    // Listing fails if there is no crontab yet, start with an empty one then.
+    let current = Command::new("crontab")
+        .arg("-l")
+        .stderr(Stdio::null())
+        .output()?;
+    let mut crontab = if current.status.success() {
+        String::from_utf8(current.stdout)?
+    } else {
+        String::new()
+    };
+    if !crontab.is_empty() && !crontab.ends_with('\n') {
+        crontab.push('\n');
+    }
+    for line in lines {
+        crontab += line;
+        crontab.push('\n');
+    }
+
+    let mut install = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()?;
+    install
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(crontab.as_bytes())?;
+    if !install.wait()?.success() {
+        return Err("crontab refused the new jobs".into());
+    }
+    Ok(())
+}
+
+/// Run the wizard on the terminal.
+///
+/// # Arguments
+///
+/// * `path` - where to write the configuration, [`config::default_path`] if not set.
+fn try_run(path: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path
+        .or_else(config::default_path)
+        .ok_or("don't know where to write the configuration, give a path")?;
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    let (mut input, mut output) = (stdin.lock(), stdout.lock());
+
+    if path.exists()
+        && !confirm(
+            &mut input,
+            &mut output,
+            &format!("'{}' already exists. Overwrite it?", path.to_string_lossy()),
+        )?
+    {
+        info!("Keeping '{}' as it is", path.to_string_lossy());
+        return Ok(());
+    }
+
+    let config = ask_config(&mut input, &mut output)?;
+    config.save(&path)?;
+    info!("Wrote the configuration to '{}'", path.to_string_lossy());
+
+    let program = std::env::current_exe()?;
+    let lines: Vec<String> = config
+        .roots
+        .iter()
+        .filter_map(|root| cron_line(config.schedule, &program, config.preset, root))
+        .collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    if cfg!(unix)
+        && confirm(
+            &mut input,
+            &mut output,
+            "Register the scheduled job with crontab?",
+        )?
+    {
+        register_cron(&lines)?;
+        info!("Registered {} scheduled jobs", lines.len());
+    } else {
+        info!("To run the analysis on schedule, add these lines to your crontab:");
+        lines.iter().for_each(|line| info!("    {}", line));
+    }
+    Ok(())
+}
+
+/// Run the wizard on the terminal, do not propagate errors.
+///
+/// You can think of it as of `main` of the `init` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::init;
+/// init::run(None);
+/// ```
+pub fn run(path: Option<PathBuf>) -> Status {
+    match try_run(path) {
+        Ok(_) => {
+            debug!("Done 'init'");
+            Status::Clean
+        }
+        Err(e) => {
+            error!("Error during 'init': {}", e);
+            Status::Failed
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Subcommand 'verify' lives here.
+//!
+//! Verify finds corrupted files, e.g. to check a NAS for bit rot: every image
+//! is decoded in full, not at the reduced scale 'analyze' decodes JPEGs at,
+//! and what goes wrong is reported as a [`Problem`]. Files are picked the way
+//! 'analyze' picks them (see [`walker`](crate::analyze::walker)), and are
+//! decoded on all cores.
+
+use crate::analyze::img::{ImgRaw, Probe};
+use crate::analyze::progress::{Progress, Stage};
+use crate::analyze::{self, cancel::CancelToken, walker};
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use serde_json::json;
+use std::io::Write;
+
+/// How many files are decoded between reports of progress.
+const CHUNK: usize = 64;
+
+/// What is wrong with a file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    /// The file couldn't be read at all.
+    Unreadable(String),
+    /// The image couldn't be decoded.
+    Decode(String),
+    /// The image ends before its end marker, e.g. a file that wasn't copied in full.
+    Truncated,
+    /// The image decoded to other dimensions than its header says.
+    Dimensions {
+        /// Width and height in the header.
+        header: (u32, u32),
+        /// Width and height of the decoded image.
+        decoded: (u32, u32),
+    },
+}
+
+impl Problem {
+    /// Name of the kind of the problem, in JSON.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Unreadable(_) => "unreadable",
+            Self::Decode(_) => "decode",
+            Self::Truncated => "truncated",
+            Self::Dimensions { .. } => "dimensions",
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unreadable(e) => write!(f, "can't be read: {}", e),
+            Self::Decode(e) => write!(f, "can't be decoded: {}", e),
+            Self::Truncated => write!(f, "is truncated"),
+            Self::Dimensions { header, decoded } => write!(
+                f,
+                "is {}x{} in its header but decodes to {}x{}",
+                header.0, header.1, decoded.0, decoded.1
+            ),
+        }
+    }
+}
+
+/// How to write the report.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// A line per corrupted file, for people.
+    #[default]
+    Text,
+    /// A JSON document, for other programs.
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/// Whether contents of a file end where the format says, if the format tells.
+///
+/// A JPEG ends with an end-of-image marker after its last scan, a PNG
+/// with an `IEND` chunk and a GIF with a trailer byte. Data appended after
+/// them, e.g. by some cameras, is fine.
+fn complete(data: &[u8], format: image::ImageFormat) -> bool {
+    let last = |pattern: &[u8]| data.windows(pattern.len()).rposition(|w| w == pattern);
+    match format {
+        image::ImageFormat::Jpeg => match (last(&[0xff, 0xda]), last(&[0xff, 0xd9])) {
+            (Some(scan), Some(end)) => end > scan,
+            _ => false,
+        },
+        image::ImageFormat::Png => last(b"IEND").is_some(),
+        image::ImageFormat::Gif => data.contains(&0x3b),
+        _ => true,
+    }
+}
+
+/// Check contents of a file by decoding them in full.
+///
+/// # Arguments
+///
+/// * `path` - the file, to tell formats with no header of their own, e.g. RAW.
+/// * `data` - its contents.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::integrity::{self, Problem};
+/// let mut jpeg = Vec::new();
+/// image::DynamicImage::new_rgb8(64, 64)
+///     .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90))
+///     .unwrap();
+/// let path = std::path::Path::new("a.jpg");
+/// assert_eq!(integrity::check(path, &jpeg), None);
+/// assert_eq!(integrity::check(path, &jpeg[..jpeg.len() - 100]), Some(Problem::Truncated));
+/// // Bit rot in the header.
+/// jpeg[20..40].fill(0);
+/// assert!(matches!(integrity::check(path, &jpeg), Some(Problem::Decode(_))));
+/// ```
+pub fn check(path: &std::path::Path, data: &[u8]) -> Option<Problem> {
+    let probe = Probe::of_bytes(data);
+    let format = match probe.format {
+        Some(format) => format,
+        // Decoders of their own, e.g. of RAW files, check what they can.
+        None => {
+            return ImgRaw::decode(path, data)
+                .err()
+                .map(|e| Problem::Decode(e.to_string()))
+        }
+    };
+    if !complete(data, format) {
+        return Some(Problem::Truncated);
+    }
+    let decoded = match image::load_from_memory_with_format(data, format) {
+        Ok(decoded) => decoded,
+        Err(e) => return Some(Problem::Decode(e.to_string())),
+    };
+    let decoded = image::GenericImageView::dimensions(&decoded);
+    match probe.dimensions {
+        Some(header) if header != decoded => Some(Problem::Dimensions { header, decoded }),
+        _ => None,
+    }
+}
+
+/// Check a file, see [`check`].
+fn check_file(path: &Path) -> Option<Problem> {
+    match std::fs::read(path) {
+        Ok(data) => check(path.as_ref(), &data),
+        Err(e) => Some(Problem::Unreadable(e.to_string())),
+    }
+}
+
+/// What checking files found.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// How many files were checked.
+    pub checked: usize,
+    /// Files with problems, in the order they were found.
+    pub problems: Vec<(PathBuf, Problem)>,
+    /// Files that can't be decoded by this build, and the decoders they need.
+    pub unsupported: Vec<(PathBuf, &'static analyze::decoders::Decoder)>,
+}
+
+impl Report {
+    /// The report as a JSON document.
+    pub fn json(&self) -> serde_json::Value {
+        let problems: Vec<_> = self
+            .problems
+            .iter()
+            .map(|(path, problem)| {
+                json!({
+                    "path": path.to_string_lossy(),
+                    "problem": problem.name(),
+                    "message": problem.to_string(),
+                })
+            })
+            .collect();
+        let unsupported: Vec<_> = self
+            .unsupported
+            .iter()
+            .map(|(path, decoder)| {
+                json!({
+                    "path": path.to_string_lossy(),
+                    "format": decoder.format,
+                    "reason": decoder.reason(),
+                })
+            })
+            .collect();
+        json!({
+            "checked": self.checked,
+            "problems": problems,
+            "unsupported": unsupported,
+        })
+    }
+}
+
+/// Check files, see [`run`].
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `walk` - what files to pick.
+/// * `threads` - how many threads to decode on, one per core if zero.
+/// * `cancel` - stops checking, what was found so far is returned.
+/// * `progress` - where to report files checked, see [`Stage::Decode`].
+pub async fn collect(
+    dirs: Vec<String>,
+    walk: &walker::WalkOptions,
+    threads: usize,
+    cancel: &CancelToken,
+    progress: &Progress,
+) -> Result<Report, Box<dyn std::error::Error>> {
+    let files = walker::Source::Walk(dirs)
+        .files(walk, cancel, progress)
+        .await?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()?;
+    let pool = std::sync::Arc::new(pool);
+
+    let tracker = progress.stage(Stage::Decode, Some(files.paths.len() as u64));
+    let mut report = Report {
+        unsupported: files.unsupported,
+        ..Report::default()
+    };
+    for chunk in files.paths.chunks(CHUNK) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let (chunk, pool) = (chunk.to_vec(), pool.clone());
+        let checked: Vec<(PathBuf, Option<Problem>)> = analyze::io::unblock(move || {
+            pool.install(|| {
+                chunk
+                    .into_par_iter()
+                    .map(|path| {
+                        let problem = check_file(&path);
+                        (path, problem)
+                    })
+                    .collect()
+            })
+        })
+        .await;
+        report.checked += checked.len();
+        tracker.advance(
+            checked.len() as u64,
+            checked.last().map(|(path, _)| path.as_path()),
+        );
+        for (path, problem) in checked {
+            if let Some(problem) = problem {
+                debug!("'{}' {}", path.to_string_lossy(), problem);
+                report.problems.push((path, problem));
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Check files and write what is wrong with them, do not propagate errors.
+///
+/// You can think of it as of `main` of the `verify` subcommand.
+/// Returns [`Status::Found`] if there are corrupted files, and
+/// [`Status::Failed`] if some couldn't be read or there were other errors.
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `options` - what files to pick, on how many threads to decode them,
+///   and where to report progress.
+/// * `format` - how to write the report.
+/// * `output` - the file to write the report to, stdout without it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::integrity;
+/// # async_std::task::block_on(async {
+/// let dirs = vec!["/mnt/nas/Photos".into()];
+/// integrity::run(dirs, Default::default(), integrity::Format::Json, None).await;
+/// # });
+/// ```
+pub async fn run(
+    dirs: Vec<String>,
+    options: analyze::AnalyzeOptions,
+    format: Format,
+    output: Option<PathBuf>,
+) -> Status {
+    let threads = options.threads;
+    let collected = collect(
+        dirs,
+        &options.walk,
+        threads,
+        &options.cancel,
+        &options.progress,
+    )
+    .await;
+    let written = collected.and_then(|report| {
+        let mut writer = analyze::output::open(output.as_deref())?;
+        match format {
+            Format::Text => {
+                for (path, problem) in &report.problems {
+                    writeln!(writer, "{}: {}", path.to_string_lossy(), problem)?;
+                }
+            }
+            Format::Json => writeln!(writer, "{}", report.json())?,
+        }
+        writer.flush()?;
+        Ok(report)
+    });
+    let report = match written {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Error during 'verify': {}", e);
+            return Status::Failed;
+        }
+    };
+
+    for (decoder, count) in analyze::decoders::counts(&report.unsupported) {
+        warn!(
+            "Skipped {} {} files: {}",
+            count,
+            decoder.format,
+            decoder.reason()
+        );
+    }
+    info!(
+        "Checked {} files, {} are corrupted",
+        report.checked,
+        report.problems.len()
+    );
+    let unreadable = report
+        .problems
+        .iter()
+        .filter(|(_, problem)| matches!(problem, Problem::Unreadable(_)))
+        .count();
+    Status::found(report.problems.len()).max(Status::failed(unreadable as u64))
+}
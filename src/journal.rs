@@ -0,0 +1,599 @@
+//! Subcommand 'undo' lives here.
+//!
+//! Whatever touches files, [`--action`](crate::analyze::action) of 'analyze',
+//! 'organize' and 'import', first appends what it is about to do to a
+//! [`Journal`], with a hash of the contents of the file. Undo reads the journal
+//! back and undoes its entries last to first: moved files are moved back,
+//! copies and links are removed, hard links are made files of their own again, trashed files are
+//! restored from the trash, and files deleted for good are copied back from
+//! copies the journal keeps of them, see [`stash`]. Files that are made
+//! again get the permissions and modification time they had.
+//!
+//! # Format
+//!
+//! The journal is a text file. The first line is [`HEADER`], every other
+//! line is an [`Entry`]: tab-separated name of the [`Op`], [escaped](escape)
+//! path of the file, path of the other file, SHA-256 of the contents
+//! of the file before it was touched, and its mode in octal and modification
+//! time in nanoseconds since the Unix epoch, or `-` for either if it isn't
+//! known. Entries of the previous version end with the hash.
+
+use crate::analyze::index::store::{escape, unescape};
+use crate::status::Status;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// The first line of a journal, holds the version of the format.
+pub const HEADER: &str = "succotash-journal 2";
+
+/// First lines of journals of older versions, which can still be read.
+pub const OLD_HEADERS: [&str; 1] = ["succotash-journal 1"];
+
+/// What was done to a file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    /// Deleted for good, the other file is a copy of it, see [`stash`].
+    Delete,
+    /// Moved to the trash, the other file is the kept one.
+    Trash,
+    /// Replaced with a reflink to the other file.
+    Reflink,
+    /// Replaced with a hard link to the other file.
+    Hardlink,
+    /// Moved to the other path.
+    Move,
+    /// Linked to from the other path, with a symbolic link.
+    Symlink,
+    /// Copied to the other path.
+    Copy,
+}
+
+impl Op {
+    /// Name of the operation in the journal.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Trash => "trash",
+            Self::Reflink => "reflink",
+            Self::Hardlink => "hardlink",
+            Self::Move => "move",
+            Self::Symlink => "symlink",
+            Self::Copy => "copy",
+        }
+    }
+}
+
+impl std::str::FromStr for Op {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(Self::Delete),
+            "trash" => Ok(Self::Trash),
+            "reflink" => Ok(Self::Reflink),
+            "hardlink" => Ok(Self::Hardlink),
+            "move" => Ok(Self::Move),
+            "symlink" => Ok(Self::Symlink),
+            "copy" => Ok(Self::Copy),
+            _ => Err(format!("unknown operation '{}'", s)),
+        }
+    }
+}
+
+/// A line of a journal, see the [module](self).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// What was done.
+    pub op: Op,
+    /// The file it was done to, absolute when [recorded](Journal::record).
+    pub path: PathBuf,
+    /// The kept file, the source of a reflink, or where the file went,
+    /// absolute when recorded too.
+    pub other: PathBuf,
+    /// SHA-256 of the contents of `path` before, in hex, see [`digest`].
+    pub hash: String,
+    /// Permissions of `path` before, as its mode on Unix, if they are known.
+    pub mode: Option<u32>,
+    /// When `path` was modified before, in nanoseconds since the Unix epoch, if it is known.
+    pub modified: Option<u64>,
+}
+
+impl Entry {
+    /// The entry as a line of a journal, without the line break.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::journal::{Entry, Op};
+    /// let entry = Entry {
+    ///     op: Op::Move,
+    ///     path: "/photos/a.jpg".into(),
+    ///     other: "/sorted/2023/07/a.jpg".into(),
+    ///     hash: "9f86d0".into(),
+    ///     mode: Some(0o644),
+    ///     modified: None,
+    /// };
+    /// assert_eq!(entry.encode(), "move\t/photos/a.jpg\t/sorted/2023/07/a.jpg\t9f86d0\t644\t-");
+    /// assert_eq!(Entry::decode(&entry.encode()), Some(entry));
+    /// // Entries of the previous version have neither.
+    /// let old = Entry::decode("move\t/photos/a.jpg\t/sorted/2023/07/a.jpg\t9f86d0").unwrap();
+    /// assert_eq!((old.mode, old.modified), (None, None));
+    /// ```
+    pub fn encode(&self) -> String {
+        let or_dash = |field: Option<String>| field.unwrap_or_else(|| "-".to_owned());
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.op.name(),
+            escape(&self.path.to_string_lossy()),
+            escape(&self.other.to_string_lossy()),
+            self.hash,
+            or_dash(self.mode.map(|mode| format!("{:o}", mode))),
+            or_dash(self.modified.map(|modified| modified.to_string())),
+        )
+    }
+
+    /// Read an entry from a line of a journal.
+    pub fn decode(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let mut entry = Self {
+            op: fields.next()?.parse().ok()?,
+            path: unescape(fields.next()?).into(),
+            other: unescape(fields.next()?).into(),
+            hash: fields.next()?.to_owned(),
+            mode: None,
+            modified: None,
+        };
+        if let Some(mode) = fields.next() {
+            entry.mode = match mode {
+                "-" => None,
+                mode => Some(u32::from_str_radix(mode, 8).ok()?),
+            };
+            entry.modified = match fields.next()? {
+                "-" => None,
+                modified => Some(modified.parse().ok()?),
+            };
+        }
+        fields.next().is_none().then_some(entry)
+    }
+}
+
+/// SHA-256 of the contents of a file, in hex.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::journal;
+/// let path = std::env::temp_dir().join("succotash-journal-digest");
+/// std::fs::write(&path, b"test").unwrap();
+/// assert_eq!(
+///     journal::digest(&path).unwrap(),
+///     "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+/// );
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn digest(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Mode and modification time of a file, as an [`Entry`] has them.
+fn metadata_of(path: &Path) -> io::Result<(Option<u32>, Option<u64>)> {
+    let metadata = std::fs::metadata(path)?;
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+    #[cfg(not(unix))]
+    let mode = None;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .and_then(|since| u64::try_from(since.as_nanos()).ok());
+    Ok((mode, modified))
+}
+
+/// Give a file the mode and modification time an entry recorded, those that it did.
+fn restore_metadata(path: &Path, mode: Option<u32>, modified: Option<u64>) -> io::Result<()> {
+    // The time first, the permissions may not let the file be opened for writing.
+    if let Some(modified) = modified {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_nanos(modified))?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+/// Where a journal keeps copies of files deleted for good: a folder next to
+/// it, named like it with `.files` after, e.g. `journal-20240301-120000.files`.
+///
+/// Copies are named by their [`digest`], so identical files are kept once.
+/// They take the space deleting the files reclaimed until the folder is
+/// removed, and undoing needs them.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::journal;
+/// let stash = journal::stash("/home/user/.local/state/succotash/journal-20240301-120000".as_ref());
+/// assert_eq!(stash.to_string_lossy(), "/home/user/.local/state/succotash/journal-20240301-120000.files");
+/// ```
+pub fn stash(journal: &Path) -> PathBuf {
+    let mut name = journal.file_name().unwrap_or_default().to_os_string();
+    name.push(".files");
+    journal.with_file_name(name)
+}
+
+/// Keep a copy of a file in the [`stash`] of a journal, if there isn't one
+/// already, and return where it is.
+fn keep_copy(journal: &Path, path: &Path, hash: &str) -> io::Result<PathBuf> {
+    let dir = std::path::absolute(stash(journal))?;
+    std::fs::create_dir_all(&dir)?;
+    let copy = dir.join(hash);
+    if !copy.is_file() {
+        // Renamed once whole, so that an interrupted copy isn't taken for one.
+        let partial = dir.join(format!("{}.partial", hash));
+        std::fs::copy(path, &partial)?;
+        std::fs::rename(&partial, &copy)?;
+    }
+    Ok(copy)
+}
+
+/// Where entries are appended, see the [module](self).
+///
+/// The file, and the directories on the way to it, are created with the
+/// first entry, so nothing is written if nothing is done. A journal
+/// without a path records nothing.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::journal::{Journal, Op};
+/// # fn main() -> std::io::Result<()> {
+/// let mut journal = Journal::new(Some("/home/user/succotash.journal".into()));
+/// journal.record(Op::Move, "/photos/a.jpg".as_ref(), "/sorted/2023/07/a.jpg".as_ref())?;
+/// std::fs::rename("/photos/a.jpg", "/sorted/2023/07/a.jpg")?;
+/// journal.close();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Journal {
+    /// Where the journal is, if anywhere.
+    path: Option<PathBuf>,
+    /// The journal, once something was recorded.
+    file: Option<File>,
+    /// How many entries were recorded.
+    recorded: usize,
+}
+
+impl Journal {
+    /// A journal at `path`, appended to if it is there already.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// Record that `op` is about to be done to `path`, before it is.
+    ///
+    /// Nothing should be done if recording fails, as it couldn't be undone.
+    /// A file about to be deleted for good is copied to the [`stash`], and
+    /// the copy is the other file of the entry instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - what is about to be done.
+    /// * `path` - the file it is done to, which is hashed.
+    /// * `other` - the other file of the entry, see [`Entry::other`].
+    pub fn record(&mut self, op: Op, path: &Path, other: &Path) -> io::Result<()> {
+        let Some(journal) = &self.path else {
+            return Ok(());
+        };
+        if self.file.is_none() {
+            if let Some(dir) = journal.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)?;
+            }
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(journal)?;
+            if file.metadata()?.len() == 0 {
+                writeln!(file, "{}", HEADER)?;
+            }
+            self.file = Some(file);
+        }
+        let hash = digest(path)?;
+        let (mode, modified) = metadata_of(path)?;
+        // Absolute, so that the trash is searched for what it has, and undo
+        // works from any directory.
+        let other = match op {
+            Op::Delete => keep_copy(journal, path, &hash)?,
+            _ => std::path::absolute(other)?,
+        };
+        let entry = Entry {
+            op,
+            path: std::path::absolute(path)?,
+            other,
+            hash,
+            mode,
+            modified,
+        };
+        // A line at a time, so that the journal is whole up to the last action.
+        let file = self.file.as_mut().expect("the journal is open");
+        file.write_all(format!("{}\n", entry.encode()).as_bytes())?;
+        self.recorded += 1;
+        Ok(())
+    }
+
+    /// Close the journal, telling how to undo what it recorded.
+    pub fn close(self) {
+        if let (Some(path), true) = (&self.path, self.recorded > 0) {
+            info!(
+                "Recorded {} actions in '{}', 'succotash undo' it to undo them",
+                self.recorded,
+                path.to_string_lossy()
+            );
+        }
+    }
+}
+
+/// Read the entries of a journal, in the order they were recorded.
+pub fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let mut lines = io::BufReader::new(File::open(path)?).lines();
+    match lines.next().transpose()? {
+        Some(header) if header == HEADER || OLD_HEADERS.contains(&header.as_str()) => {}
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("'{}' is not a journal", path.to_string_lossy()),
+            ))
+        }
+    }
+    let mut entries = Vec::new();
+    for line in lines {
+        let line = line?;
+        match Entry::decode(&line) {
+            Some(entry) => entries.push(entry),
+            None => warn!("Skipping an invalid entry: '{}'", line),
+        }
+    }
+    Ok(entries)
+}
+
+/// What undoing an entry did.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Undone {
+    /// The file is as it was before the entry.
+    Restored,
+    /// Nothing was done, for this reason.
+    Skipped(String),
+    /// The entry couldn't be undone, for this reason.
+    Failed(String),
+}
+
+/// Whether a file has the contents an entry recorded.
+fn unchanged(path: &Path, hash: &str) -> Result<bool, String> {
+    digest(path)
+        .map(|digest| digest == hash)
+        .map_err(|e| format!("'{}' {}", path.to_string_lossy(), e))
+}
+
+/// Move a file, or copy and remove it if it is on another filesystem.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Make a hard link a file of its own, with a copy of the data it shares,
+/// and the mode and modification time the file had before it was linked,
+/// or those the link has if they weren't recorded.
+fn separate(path: &Path, mode: Option<u32>, modified: Option<u64>) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.succotash-undo", name));
+    let copied = metadata_of(path).and_then(|(linked_mode, linked_modified)| {
+        std::fs::copy(path, &temporary)?;
+        restore_metadata(
+            &temporary,
+            mode.or(linked_mode),
+            modified.or(linked_modified),
+        )?;
+        std::fs::rename(&temporary, path)
+    });
+    if copied.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    copied
+}
+
+/// Restore a file from the trash, the one that was put there last.
+#[cfg(any(
+    windows,
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn restore_from_trash(path: &Path) -> Result<(), String> {
+    use trash::os_limited;
+
+    let item = os_limited::list()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or("is not in the trash anymore")?;
+    os_limited::restore_all([item]).map_err(|e| e.to_string())
+}
+
+/// Restore a file from the trash, which can't be done here.
+#[cfg(not(any(
+    windows,
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore_from_trash(_: &Path) -> Result<(), String> {
+    Err("can't be restored from the trash on this system, restore it by hand".to_owned())
+}
+
+/// Undo an entry, if the files are still as it left them.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::journal::{self, Entry, Op, Undone};
+/// let dir = std::env::temp_dir().join("succotash-journal-undo");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("b.jpg"), b"photo").unwrap();
+/// let entry = Entry {
+///     op: Op::Move,
+///     path: dir.join("a.jpg"),
+///     other: dir.join("b.jpg"),
+///     hash: journal::digest(&dir.join("b.jpg")).unwrap(),
+///     mode: None,
+///     modified: None,
+/// };
+/// assert_eq!(journal::undo(&entry), Undone::Restored);
+/// assert!(dir.join("a.jpg").is_file());
+/// assert!(matches!(journal::undo(&entry), Undone::Skipped(_)));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn undo(entry: &Entry) -> Undone {
+    let Entry {
+        op,
+        path,
+        other,
+        hash,
+        mode,
+        modified,
+    } = entry;
+    let there = |path: &Path| std::fs::symlink_metadata(path).is_ok();
+    let other_name = other.to_string_lossy();
+    let undone = match op {
+        Op::Delete | Op::Trash | Op::Move if there(path) => {
+            return Undone::Skipped("is there already".to_owned());
+        }
+        Op::Delete => match unchanged(other, hash) {
+            Ok(true) => std::fs::copy(other, path)
+                .and_then(|_| restore_metadata(path, *mode, *modified))
+                .map_err(|e| e.to_string()),
+            Ok(false) => Err(format!(
+                "was deleted for good, and '{}' has other contents",
+                other_name
+            )),
+            Err(e) => Err(format!("was deleted for good, and {}", e)),
+        },
+        Op::Trash => restore_from_trash(path),
+        Op::Reflink => {
+            return match unchanged(path, hash) {
+                Ok(true) => Undone::Skipped("has the contents it had".to_owned()),
+                Ok(false) => Undone::Failed("has changed since".to_owned()),
+                Err(e) => Undone::Failed(e),
+            };
+        }
+        Op::Hardlink => match unchanged(path, hash) {
+            Ok(true) => separate(path, *mode, *modified).map_err(|e| e.to_string()),
+            Ok(false) => Err("has changed since".to_owned()),
+            Err(e) => Err(e),
+        },
+        Op::Move if !there(other) => Err(format!("'{}' is not there anymore", other_name)),
+        Op::Move => match unchanged(other, hash) {
+            Ok(true) => move_file(other, path).map_err(|e| e.to_string()),
+            Ok(false) => Err(format!("'{}' has changed since", other_name)),
+            Err(e) => Err(e),
+        },
+        Op::Symlink => match std::fs::symlink_metadata(other) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                std::fs::remove_file(other).map_err(|e| e.to_string())
+            }
+            Ok(_) => return Undone::Skipped(format!("'{}' is not a link anymore", other_name)),
+            Err(_) => return Undone::Skipped(format!("'{}' is not there anymore", other_name)),
+        },
+        Op::Copy if !there(other) => {
+            return Undone::Skipped(format!("'{}' is not there anymore", other_name));
+        }
+        Op::Copy => match unchanged(other, hash) {
+            Ok(true) => std::fs::remove_file(other).map_err(|e| e.to_string()),
+            Ok(false) => Err(format!("'{}' has changed since", other_name)),
+            Err(e) => Err(e),
+        },
+    };
+    match undone {
+        Ok(()) => Undone::Restored,
+        Err(e) => Undone::Failed(e),
+    }
+}
+
+/// Undo what a journal recorded, last to first, do not propagate errors.
+///
+/// You can think of it as of `main` of the `undo` subcommand.
+/// Returns [`Status::Failed`] if there were errors. The journal is left
+/// as it is, undoing it again skips what was undone.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::journal;
+/// journal::run("/home/user/.local/state/succotash/journal-20240301-120000".as_ref());
+/// ```
+pub fn run(journal: &Path) -> Status {
+    let entries = match read(journal) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Error during 'undo': {}", e);
+            return Status::Failed;
+        }
+    };
+    let (mut restored, mut failed) = (0, 0);
+    for entry in entries.iter().rev() {
+        let path = entry.path.to_string_lossy();
+        match undo(entry) {
+            Undone::Restored => {
+                debug!("Undid '{}' of '{}'", entry.op.name(), path);
+                restored += 1;
+            }
+            Undone::Skipped(reason) => debug!("Left '{}' alone: {}", path, reason),
+            Undone::Failed(e) => {
+                error!("Couldn't undo '{}' of '{}': {}", entry.op.name(), path, e);
+                failed += 1;
+            }
+        }
+    }
+    info!(
+        "Undid {} of {} actions in '{}'",
+        restored,
+        entries.len(),
+        journal.to_string_lossy()
+    );
+    Status::failed(failed)
+}
@@ -1,6 +1,46 @@
+#[cfg(not(target_arch = "wasm32"))]
 #[macro_use]
 extern crate log;
 
+// Only finding features builds for the web, everything else does IO.
+pub mod features;
+
+#[cfg(all(feature = "bin", not(target_arch = "wasm32")))]
 pub mod bin_util;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub mod analyze;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compare;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod db;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hash;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod import;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod init;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod integrity;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod journal;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod montage;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod organize;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod serve;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod status;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod units;
@@ -0,0 +1,241 @@
+//! Subcommand 'montage' lives here.
+//!
+//! Montage renders contact sheets, JPEGs with thumbnails of many images side
+//! by side, to skim a library quickly: a sheet for every group of similar
+//! images, or for every bucket of hue. Files are picked, their features are
+//! found and similar images are grouped the way 'analyze' does (see
+//! [`analyze::find_features`] and [`group::candidates`]). Thumbnails are made
+//! the way the [web page](crate::serve::ui) makes them, from decoded images.
+
+use crate::analyze::{self, group, img::ImgRaw, AnalyzeOptions};
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+/// Thumbnails fit into a square with sides of this many pixels by default.
+pub const TILE_SIZE: u32 = 160;
+
+/// Pixels between thumbnails and around them.
+pub const GAP: u32 = 4;
+
+/// A sheet has at most this many thumbnails in a row.
+pub const MAX_COLUMNS: usize = 8;
+
+/// A sheet has at most this many thumbnails, more images take more sheets.
+pub const MAX_TILES: usize = 64;
+
+/// Color of the background around thumbnails.
+const BACKGROUND: image::Rgb<u8> = image::Rgb([32, 32, 32]);
+
+/// What images get a sheet together.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum By {
+    /// Every group of similar images, see [`group`].
+    #[default]
+    Groups,
+    /// Every bucket of hue, [`HUE_STEP`](crate::stats::HUE_STEP) degrees wide.
+    Hue,
+}
+
+impl std::str::FromStr for By {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "groups" => Ok(Self::Groups),
+            "hue" => Ok(Self::Hue),
+            _ => Err(format!("unknown way to make sheets by '{}'", s)),
+        }
+    }
+}
+
+/// How to render sheets, besides what files and how to find their features.
+#[derive(Clone, Debug)]
+pub struct MontageOptions {
+    /// What images get a sheet together.
+    pub by: By,
+    /// The directory to write sheets to.
+    pub output_dir: PathBuf,
+    /// Thumbnails fit into a square with sides of this many pixels.
+    pub tile_size: u32,
+}
+
+impl Default for MontageOptions {
+    fn default() -> Self {
+        Self {
+            by: By::default(),
+            output_dir: ".".into(),
+            tile_size: TILE_SIZE,
+        }
+    }
+}
+
+/// Render a sheet of thumbnails, at most [`MAX_COLUMNS`] in a row.
+///
+/// Every image is scaled to fit into a square of `tile_size` pixels,
+/// keeping its aspect ratio, and centered in it.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::montage;
+/// let img = image::DynamicImage::ImageRgb8(image::RgbImage::new(320, 160));
+/// let sheet = montage::render(&vec![img; 10], 100);
+/// // 8 thumbnails in the first row and 2 in the second, with gaps around them.
+/// assert_eq!(sheet.dimensions(), (8 * 104 + 4, 2 * 104 + 4));
+/// assert_eq!(sheet.get_pixel(54, 4 + 25), &image::Rgb([0, 0, 0]));
+/// assert_ne!(sheet.get_pixel(54, 4 + 24), &image::Rgb([0, 0, 0]));
+/// ```
+pub fn render(imgs: &[image::DynamicImage], tile_size: u32) -> image::RgbImage {
+    let columns = imgs.len().clamp(1, MAX_COLUMNS) as u32;
+    let rows = imgs.len().div_ceil(MAX_COLUMNS).max(1) as u32;
+    let step = tile_size + GAP;
+    let mut sheet =
+        image::RgbImage::from_pixel(columns * step + GAP, rows * step + GAP, BACKGROUND);
+    for (i, img) in imgs.iter().enumerate() {
+        let thumbnail = img.thumbnail(tile_size, tile_size).to_rgb8();
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        let x = GAP + column * step + (tile_size - thumbnail.width()) / 2;
+        let y = GAP + row * step + (tile_size - thumbnail.height()) / 2;
+        image::imageops::replace(&mut sheet, &thumbnail, x, y);
+    }
+    sheet
+}
+
+/// Images that get a sheet together, by the name of the sheet without an extension.
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `by` - what images get a sheet together.
+/// * `options` - what files to pick, how to find their features and
+///   how similar images in a group are.
+pub async fn sheets(
+    dirs: Vec<String>,
+    by: By,
+    options: &AnalyzeOptions,
+) -> Result<BTreeMap<String, Vec<PathBuf>>, Box<dyn std::error::Error>> {
+    let files = analyze::walker::Source::Walk(dirs)
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+    let imgs = analyze::find_features(files.paths, options).await?;
+
+    let mut sheets: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    match by {
+        By::Groups => {
+            let features = options
+                .features
+                .clone()
+                .with_max_distance("lshash", f64::from(options.max_distance));
+            let pairs = group::candidates(&imgs, &features, options.search, &options.cancel);
+            for (i, group) in group::group_pairs(imgs, &pairs).iter().enumerate() {
+                let paths = group.files().iter().map(|img| img.path.clone()).collect();
+                sheets.insert(format!("group-{:04}", i + 1), paths);
+            }
+        }
+        By::Hue => {
+            let step = crate::stats::HUE_STEP;
+            for img in imgs {
+                let bucket = (img.features.hue.degrees() as usize / step).min(360 / step - 1);
+                let name = format!("hue-{:03}-{:03}", bucket * step, (bucket + 1) * step);
+                let paths = sheets.entry(name).or_default();
+                if paths.last() != Some(&img.path) {
+                    paths.push(img.path);
+                }
+            }
+        }
+    }
+    Ok(sheets)
+}
+
+/// Render a sheet of files and write it as a JPEG, see [`render`].
+///
+/// Files that can't be decoded are left out. Returns how many were.
+async fn write_sheet(
+    paths: &[PathBuf],
+    tile_size: u32,
+    output: &Path,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut imgs = Vec::with_capacity(paths.len());
+    let mut failed = 0;
+    for path in paths {
+        // The first image of a file stands for all of them, e.g. of a video.
+        match ImgRaw::load_all(path.clone()).await {
+            Ok(imgs_raw) => imgs.extend(imgs_raw.into_iter().next().map(|img_raw| img_raw.data)),
+            Err(e) => {
+                error!("Couldn't render '{}': {}", path.to_string_lossy(), e);
+                failed += 1;
+            }
+        }
+    }
+    let sheet = render(&imgs, tile_size);
+    sheet.save_with_format(output, image::ImageFormat::Jpeg)?;
+    Ok(failed)
+}
+
+/// Render contact sheets of the files, do not propagate errors.
+///
+/// You can think of it as of `main` of the `montage` subcommand.
+/// Sheets are named after what they are of, e.g. `group-0001.jpg` or
+/// `hue-090-120.jpg`, and those of more than [`MAX_TILES`] images are split
+/// into `-2`, `-3` and so on. Returns [`Status::Failed`] if there were errors.
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `montage` - how to render sheets and where to write them.
+/// * `options` - what files to pick and how to find their features.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::montage;
+/// # async_std::task::block_on(async {
+/// montage::run(vec!["/home/user/Pictures".into()], Default::default(), Default::default()).await;
+/// # });
+/// ```
+pub async fn run(dirs: Vec<String>, montage: MontageOptions, options: AnalyzeOptions) -> Status {
+    let sheets = match sheets(dirs, montage.by, &options).await {
+        Ok(sheets) => sheets,
+        Err(e) => {
+            error!("Error during 'montage': {}", e);
+            return Status::Failed;
+        }
+    };
+    if let Err(e) = async_std::fs::create_dir_all(&montage.output_dir).await {
+        error!(
+            "Couldn't create '{}': {}",
+            montage.output_dir.to_string_lossy(),
+            e
+        );
+        return Status::Failed;
+    }
+
+    let (mut written, mut failed) = (0, 0);
+    for (name, paths) in &sheets {
+        for (i, chunk) in paths.chunks(MAX_TILES).enumerate() {
+            let name = match i {
+                0 => format!("{}.jpg", name),
+                _ => format!("{}-{}.jpg", name, i + 1),
+            };
+            let output = montage.output_dir.join(name);
+            match write_sheet(chunk, montage.tile_size, &output).await {
+                Ok(skipped) => {
+                    debug!("Wrote '{}'", output.to_string_lossy());
+                    written += 1;
+                    failed += skipped;
+                }
+                Err(e) => {
+                    error!("Couldn't write '{}': {}", output.to_string_lossy(), e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    info!(
+        "Wrote {} contact sheets to '{}'",
+        written,
+        montage.output_dir.to_string_lossy()
+    );
+    Status::failed(failed)
+}
@@ -0,0 +1,308 @@
+//! Subcommand 'organize' lives here.
+//!
+//! Organize sorts a library into folders by what its images are: when they
+//! were taken (`2023/07`), their hue (`090-120`), the name of their hue
+//! (`blue`) or the camera they were taken with. Files are picked and their features are found the way
+//! 'analyze' does (see [`analyze::find_features`]), reused from and saved to
+//! the index, and then moved or linked into a target directory. Files the
+//! folder can't be told of, e.g. images without EXIF metadata when sorting
+//! by date, go to [`UNSORTED`].
+
+use crate::analyze::{self, features::ImgFeatures, AnalyzeOptions};
+use crate::journal::{Journal, Op};
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Folder of files the folder can't be told of, see [`folder`].
+pub const UNSORTED: &str = "unsorted";
+
+/// What folders files are sorted into.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum By {
+    /// Year and month the photo was taken, by its EXIF metadata, e.g. `2023/07`.
+    #[default]
+    Date,
+    /// Hue, in buckets [`HUE_STEP`](crate::stats::HUE_STEP) degrees wide, e.g. `090-120`.
+    Hue,
+    /// Name of the hue, see [`HueName`](crate::features::hue::HueName), e.g. `blue`.
+    HueName,
+    /// Make and model of the camera, by its EXIF metadata, e.g. `Canon EOS R6`.
+    Camera,
+}
+
+impl std::str::FromStr for By {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "date" => Ok(Self::Date),
+            "hue" => Ok(Self::Hue),
+            "hue-name" => Ok(Self::HueName),
+            "camera" => Ok(Self::Camera),
+            _ => Err(format!("unknown way to organize by '{}'", s)),
+        }
+    }
+}
+
+/// What to do with a file to put it into its folder.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Action {
+    /// Move the file, or copy and remove it if it is on another filesystem.
+    #[default]
+    Move,
+    /// Leave the file where it is and make a symbolic link to it.
+    Symlink,
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "move" => Ok(Self::Move),
+            "symlink" => Ok(Self::Symlink),
+            _ => Err(format!("unknown action '{}'", s)),
+        }
+    }
+}
+
+/// How to organize files, besides what files and how to find their features.
+#[derive(Clone, Debug, Default)]
+pub struct OrganizeOptions {
+    /// What folders files are sorted into.
+    pub by: By,
+    /// What to do with the files.
+    pub action: Action,
+    /// Only print where files would go, without touching them.
+    pub dry_run: bool,
+    /// Where to record what is done to be undone, see [`journal`](crate::journal).
+    pub journal: Option<std::path::PathBuf>,
+}
+
+/// Folder of an image, relative to the target directory, if it can be told.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::organize::{self, By};
+/// # use libsuccotash::analyze::features::ImgFeatures;
+/// let features = ImgFeatures::decode(
+///     "00000000000000ff 100 0 0.5 0.2 exif.taken=1688212800 exif.camera=Canon%20EOS%20R6",
+/// )
+/// .unwrap();
+/// let folder = |by| organize::folder(by, &features).map(|folder| folder.to_string_lossy().into_owned());
+/// assert_eq!(folder(By::Date).as_deref(), Some("2023/07"));
+/// assert_eq!(folder(By::Hue).as_deref(), Some("090-120"));
+/// assert_eq!(folder(By::HueName).as_deref(), Some("green"));
+/// assert_eq!(folder(By::Camera).as_deref(), Some("Canon EOS R6"));
+/// assert_eq!(organize::folder(By::Date, &ImgFeatures::decode("00000000000000ff 100 0 0.5 0.2").unwrap()), None);
+/// ```
+pub fn folder(by: By, features: &ImgFeatures) -> Option<PathBuf> {
+    let exif = features.exif.as_ref();
+    match by {
+        By::Date => {
+            let date_time = exif?.date_time()?;
+            Some(Path::new(&date_time[..4]).join(&date_time[5..7]))
+        }
+        By::Hue => {
+            let step = crate::stats::HUE_STEP;
+            let bucket = (features.hue.degrees() as usize / step).min(360 / step - 1);
+            Some(format!("{:03}-{:03}", bucket * step, (bucket + 1) * step).into())
+        }
+        By::HueName => Some(features.hue.name().name().into()),
+        By::Camera => {
+            // Names of cameras are free text, they must not escape the target.
+            let camera: String = exif?
+                .camera
+                .as_deref()?
+                .chars()
+                .map(|c| match c {
+                    '/' | '\\' | ':' => '_',
+                    c if c.is_control() => '_',
+                    c => c,
+                })
+                .collect();
+            let camera = camera.trim().trim_start_matches('.');
+            (!camera.is_empty()).then(|| camera.into())
+        }
+    }
+}
+
+/// Where to put a file in `folder` so that it doesn't overwrite another one:
+/// under its own name, or with ` (2)`, ` (3)` and so on before the extension.
+///
+/// `path` itself counts as free, paths in `taken`, planned for other files, don't.
+pub fn destination(path: &Path, folder: &Path, taken: &HashSet<PathBuf>) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let stem = Path::new(name).file_stem()?.to_string_lossy();
+    let extension = Path::new(name).extension();
+    (1..).find_map(|i| {
+        let candidate = match (i, extension) {
+            (1, _) => folder.join(name),
+            (_, Some(extension)) => {
+                folder.join(format!("{} ({}).{}", stem, i, extension.to_string_lossy()))
+            }
+            (_, None) => folder.join(format!("{} ({})", stem, i)),
+        };
+        let free = candidate.as_path() == path
+            || (!taken.contains(&candidate) && std::fs::symlink_metadata(&candidate).is_err());
+        free.then_some(candidate)
+    })
+}
+
+/// Plan where every file goes, as `(from, to)`, see [`run`].
+///
+/// Files already where they would go are left out. A file with more than one image,
+/// e.g. a video, goes where its first image would.
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `into` - the directory to sort files into.
+/// * `by` - what folders files are sorted into.
+/// * `options` - what files to pick and how to find their features.
+pub async fn plan(
+    dirs: Vec<String>,
+    into: &Path,
+    by: By,
+    options: &AnalyzeOptions,
+) -> Result<Vec<(PathBuf, PathBuf)>, Box<dyn std::error::Error>> {
+    let files = analyze::walker::Source::Walk(dirs)
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+
+    let mut seen = HashSet::new();
+    let mut taken = HashSet::new();
+    let mut moves = Vec::new();
+    for img in analyze::find_features(files.paths, options).await? {
+        if !seen.insert(img.path.clone()) {
+            continue;
+        }
+        let folder = folder(by, &img.features).unwrap_or_else(|| UNSORTED.into());
+        let to = destination(&img.path, &into.join(folder), &taken)
+            .ok_or_else(|| format!("'{}' has no file name", img.path.to_string_lossy()))?;
+        if to != img.path {
+            taken.insert(to.clone());
+            moves.push((img.path, to));
+        }
+    }
+    Ok(moves)
+}
+
+/// Put a file where it goes, creating the folders on the way.
+fn apply(from: &Path, to: &Path, action: Action, journal: &mut Journal) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let op = match action {
+        Action::Move => Op::Move,
+        Action::Symlink => Op::Symlink,
+    };
+    journal.record(op, from.as_ref(), to.as_ref())?;
+    match action {
+        Action::Move => {
+            if std::fs::rename(from, to).is_err() {
+                // E.g. the target is on another filesystem.
+                std::fs::copy(from, to)?;
+                std::fs::remove_file(from)?;
+            }
+            Ok(())
+        }
+        Action::Symlink => {
+            let from = std::fs::canonicalize(from)?;
+            #[cfg(unix)]
+            return std::os::unix::fs::symlink(from, to);
+            #[cfg(windows)]
+            return std::os::windows::fs::symlink_file(from, to);
+        }
+    }
+}
+
+/// Sort files into folders, do not propagate errors.
+///
+/// You can think of it as of `main` of the `organize` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `into` - the directory to sort files into.
+/// * `organize` - how to sort them.
+/// * `options` - what files to pick and how to find their features.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::organize;
+/// # async_std::task::block_on(async {
+/// organize::run(
+///     vec!["/home/user/Pictures".into()],
+///     "/home/user/Sorted".into(),
+///     Default::default(),
+///     Default::default(),
+/// )
+/// .await;
+/// # });
+/// ```
+pub async fn run(
+    dirs: Vec<String>,
+    into: PathBuf,
+    organize: OrganizeOptions,
+    options: AnalyzeOptions,
+) -> Status {
+    let moves = match plan(dirs, &into, organize.by, &options).await {
+        Ok(moves) => moves,
+        Err(e) => {
+            error!("Error during 'organize': {}", e);
+            return Status::Failed;
+        }
+    };
+
+    if organize.dry_run {
+        let mut writer = match analyze::output::open(None) {
+            Ok(writer) => writer,
+            Err(e) => {
+                error!("Error during 'organize': {}", e);
+                return Status::Failed;
+            }
+        };
+        for (from, to) in &moves {
+            let line = format!("{} -> {}", from.to_string_lossy(), to.to_string_lossy());
+            if writeln!(writer, "{}", line).is_err() {
+                return Status::Clean;
+            }
+        }
+        let _ = writer.flush();
+        return Status::Clean;
+    }
+
+    let mut failed = 0;
+    let mut journal = Journal::new(organize.journal);
+    for (from, to) in &moves {
+        match apply(from, to, organize.action, &mut journal) {
+            Ok(()) => debug!(
+                "Put '{}' at '{}'",
+                from.to_string_lossy(),
+                to.to_string_lossy()
+            ),
+            Err(e) => {
+                error!(
+                    "Couldn't put '{}' at '{}': {}",
+                    from.to_string_lossy(),
+                    to.to_string_lossy(),
+                    e
+                );
+                failed += 1;
+            }
+        }
+    }
+    journal.close();
+    info!(
+        "Organized {} files into '{}'",
+        moves.len() as u64 - failed,
+        into.to_string_lossy()
+    );
+    Status::failed(failed)
+}
@@ -0,0 +1,198 @@
+//! Python bindings, with the `python` feature.
+//!
+//! The `succotash` module finds hashes exactly as the `hash` subcommand does,
+//! with the [configuration](crate::config) applied, and finds similar images
+//! as `analyze` does with the configuration of the directory, reusing and filling
+//! the configured index:
+//!
+//! ```python
+//! import succotash
+//! a = succotash.hash_file("/home/user/Pictures/a.jpg")  # "00000000000000ff"
+//! succotash.distance(a, succotash.hash_file("/home/user/Pictures/b.jpg"))
+//! for group in succotash.find_duplicates("/home/user/Pictures"):
+//!     print(group)  # ["/home/user/Pictures/a.jpg", "/home/user/Pictures/copy.jpg"]
+//! ```
+//!
+//! Build the module with `cargo rustc --lib --release --features python --crate-type cdylib`
+//! and put `target/release/liblibsuccotash.so` next to the notebook as `succotash.so`.
+
+// Code generated for `#[pyfunction]`s converts their errors even when they are `PyErr`s.
+#![allow(clippy::useless_conversion)]
+
+use crate::analyze::img::ImgRaw;
+use crate::analyze::{find_features, group, score, walker, AnalyzeOptions};
+use crate::bin_util::{self, cli::AnalyzeArgs};
+use crate::config::{self, Config};
+use async_std::task::block_on;
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+/// Turn an error of the library into one of Python.
+fn os_error(e: impl std::fmt::Display) -> PyErr {
+    PyOSError::new_err(e.to_string())
+}
+
+/// Parse a hash in hex, as `hash` prints it.
+fn parse_hash(hash: &str) -> PyResult<u64> {
+    u64::from_str_radix(hash, 16)
+        .map_err(|e| PyValueError::new_err(format!("malformed hash '{}': {}", hash, e)))
+}
+
+/// The hash of the image in a file, in hex as `hash` prints it.
+///
+/// Of a video, the hash of its first keyframe.
+#[pyfunction]
+fn hash_file(py: Python<'_>, path: std::path::PathBuf) -> PyResult<String> {
+    let (config, _) = config::load(None).map_err(os_error)?;
+    let options = config.find_options().map_err(os_error)?;
+    py.allow_threads(|| {
+        let imgs_raw = block_on(ImgRaw::load_all(path)).map_err(os_error)?;
+        let img_raw = imgs_raw
+            .first()
+            .ok_or_else(|| os_error("no images in the file"))?;
+        let features = crate::analyze::features::ImgFeatures::find_with(img_raw, options);
+        Ok(format!("{:016x}", features.lshash.bits()))
+    })
+}
+
+/// How many bits two hashes in hex differ in.
+#[pyfunction]
+fn distance(a: &str, b: &str) -> PyResult<u32> {
+    Ok((parse_hash(a)? ^ parse_hash(b)?).count_ones())
+}
+
+/// Groups of similar images, each a list of paths, see [`find_duplicates`].
+#[pyclass]
+struct Duplicates {
+    groups: std::vec::IntoIter<Vec<String>>,
+}
+
+#[pymethods]
+impl Duplicates {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<Vec<String>> {
+        slf.groups.next()
+    }
+}
+
+/// Options of `analyze` of a directory, with keyword arguments of
+/// [`find_duplicates`] given as its arguments.
+///
+/// They go through [`AnalyzeArgs`], so the configuration is applied and
+/// overridden by them exactly as on the command line.
+fn analyze_options(
+    config: &Config,
+    dir: &str,
+    max_distance: Option<u32>,
+    partial: bool,
+    features: Option<Vec<String>>,
+    search: Option<String>,
+) -> PyResult<AnalyzeOptions> {
+    let mut args = vec!["succotash".to_owned(), "analyze".to_owned()];
+    if let Some(max_distance) = max_distance {
+        args.extend(["--max-distance".to_owned(), max_distance.to_string()]);
+    }
+    if partial {
+        args.push("--partial".to_owned());
+    }
+    if let Some(features) = features {
+        args.extend(["--features".to_owned(), features.join(",")]);
+    }
+    if let Some(search) = search {
+        args.extend(["--search".to_owned(), search]);
+    }
+    // The directory can't be taken for an argument then.
+    args.extend(["--".to_owned(), dir.to_owned()]);
+
+    let matches = bin_util::app().get_matches_from_safe(args).map_err(|e| {
+        PyValueError::new_err(e.message.lines().next().unwrap_or_default().to_owned())
+    })?;
+    let matches = matches
+        .subcommand_matches("analyze")
+        .expect("analyze is parsed");
+    let args =
+        AnalyzeArgs::from_matches(matches).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let mut options = args
+        .options(config)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    options.resume = options.index.is_some();
+    Ok(options)
+}
+
+/// Find groups of similar images in a directory, the most similar first.
+///
+/// Images are compared as `analyze` compares them with the configuration
+/// of the directory, keyword arguments override it like arguments of `analyze` do:
+/// `features` is a list of names and `search` is a name, e.g. `"vp-tree"`.
+/// Features are reused from and saved to the configured index.
+/// The whole directory is analyzed before the first group is given.
+#[pyfunction]
+#[pyo3(signature = (dir, max_distance = None, partial = false, features = None, search = None))]
+fn find_duplicates(
+    py: Python<'_>,
+    dir: String,
+    max_distance: Option<u32>,
+    partial: bool,
+    features: Option<Vec<String>>,
+    search: Option<String>,
+) -> PyResult<Duplicates> {
+    let (config, _) = config::load(Some(dir.as_ref())).map_err(os_error)?;
+    let options = analyze_options(&config, &dir, max_distance, partial, features, search)?;
+    let groups = py.allow_threads(|| {
+        block_on(async {
+            let source = walker::Source::Walk(vec![dir]);
+            let files = source
+                .files(&options.walk, &options.cancel, &options.progress)
+                .await?;
+            find_features(files.paths, &options).await
+        })
+        .map(|imgs| {
+            let features = options
+                .features
+                .clone()
+                .with_max_distance("lshash", f64::from(options.max_distance));
+            let mut pairs = group::candidates(&imgs, &features, options.search, &options.cancel);
+            if options.partial {
+                let contained = group::contained(&imgs, group::MIN_CONTAINED, &options.cancel);
+                pairs.extend(
+                    contained
+                        .into_iter()
+                        .map(|(inner, outer, _)| (inner, outer)),
+                );
+            }
+            let mut groups = group::group_pairs(imgs, &pairs);
+            score::sort(&mut groups, &options.weights, &features);
+            groups
+        })
+        .map_err(os_error)
+    })?;
+    let groups: Vec<Vec<String>> = groups
+        .iter()
+        .map(|group| {
+            let mut paths: Vec<String> = group
+                .imgs
+                .iter()
+                .map(|img| img.path.to_string_lossy().into_owned())
+                .collect();
+            // Keyframes of a video are one file.
+            paths.dedup();
+            paths
+        })
+        .collect();
+    Ok(Duplicates {
+        groups: groups.into_iter(),
+    })
+}
+
+/// The `succotash` Python module.
+#[pymodule]
+fn succotash(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(hash_file, m)?)?;
+    m.add_function(wrap_pyfunction!(distance, m)?)?;
+    m.add_function(wrap_pyfunction!(find_duplicates, m)?)?;
+    m.add_class::<Duplicates>()?;
+    Ok(())
+}
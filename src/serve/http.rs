@@ -0,0 +1,212 @@
+//! Just enough HTTP/1.1 for [`serve`](super).
+//!
+//! Every connection carries one request, the response closes it.
+//! Bodies have to come with `Content-Length`, chunked ones are refused.
+
+use async_std::io::{BufReader, Read, Write};
+use async_std::prelude::*;
+use serde_json::{json, Value};
+
+/// Bodies of requests larger than this are refused, in bytes.
+pub const MAX_BODY: usize = 64 << 20;
+
+/// A request to the server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    /// `GET`, `POST` and so on.
+    pub method: String,
+    /// The path, without the query.
+    pub path: String,
+    /// Parameters of the query, percent-decoded.
+    pub query: Vec<(String, String)>,
+    /// Contents of the body, empty without one.
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The query parameter `name`, if given.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Decode a percent-encoded component of a query, `+` stands for a space.
+///
+/// Malformed escapes are kept as they are.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::serve::http;
+/// assert_eq!(http::decode_component("%2Fhome%2Fuser%2Fa+b.jpg"), "/home/user/a b.jpg");
+/// assert_eq!(http::decode_component("100%"), "100%");
+/// ```
+pub fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Read a request from `reader`.
+///
+/// Clients that wait for `Expect: 100-continue` are told to go on through `writer`.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::serve::http;
+/// # async_std::task::block_on(async {
+/// let sent = b"POST /match?max-distance=4 HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc";
+/// let request = http::read_request(&mut &sent[..], &mut Vec::new()).await?;
+/// assert_eq!(request.path, "/match");
+/// assert_eq!(request.param("max-distance"), Some("4"));
+/// assert_eq!(request.body, b"abc");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// # });
+/// ```
+pub async fn read_request<R: Read + Unpin, W: Write + Unpin>(
+    reader: &mut R,
+    writer: &mut W,
+) -> Result<Request, Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_owned(), target.to_owned()),
+        _ => return Err(format!("malformed request line '{}'", line.trim_end()).into()),
+    };
+
+    let mut length = 0;
+    let mut expects = false;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err("connection closed in the headers".into());
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').unwrap_or((header, ""));
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse()?;
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Err(format!("'Transfer-Encoding: {}' is not supported", value).into());
+        } else if name.eq_ignore_ascii_case("expect") {
+            expects = value.eq_ignore_ascii_case("100-continue");
+        }
+    }
+    if length > MAX_BODY {
+        return Err(format!("the body of {} bytes is too large", length).into());
+    }
+    if expects && length > 0 {
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+        writer.flush().await?;
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            (decode_component(key), decode_component(value))
+        })
+        .collect();
+    Ok(Request {
+        method,
+        path: path.to_owned(),
+        query,
+        body,
+    })
+}
+
+/// A response of the server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    /// Status code, e.g. 200.
+    pub status: u16,
+    /// `Content-Type` of the body.
+    pub content_type: &'static str,
+    /// What is sent back.
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// A successful response with a JSON document.
+    pub fn ok(body: Value) -> Self {
+        Self::bytes("application/json", body.to_string().into_bytes())
+    }
+
+    /// A successful response with a body of any type.
+    pub fn bytes(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    /// A failed response, a JSON document with the message in the `error` field.
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            ..Self::ok(json!({ "error": message }))
+        }
+    }
+
+    /// The response as it is sent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::serve::http::Response;
+    /// let response = Response::error(404, "no such endpoint").to_bytes();
+    /// let response = String::from_utf8(response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    /// assert!(response.ends_with("\r\n\r\n{\"error\":\"no such endpoint\"}"));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        let mut bytes = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason,
+            self.content_type,
+            self.body.len()
+        )
+        .into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
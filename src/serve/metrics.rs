@@ -0,0 +1,70 @@
+//! Metrics of a running [`serve`](super) or [`daemon`](crate::daemon),
+//! for Prometheus to scrape.
+//!
+//! Both count what they do in [`METRICS`], next to what finding features
+//! counts there, and expose all of them at `/metrics` in the
+//! [text format](https://prometheus.io/docs/instrumenting/exposition_formats/)
+//! of Prometheus, see [`render`].
+
+use crate::analyze::metrics::{Histogram, BUCKETS, METRICS};
+use std::fmt::Write;
+
+/// `Content-Type` of rendered metrics.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Write a histogram in the text format, named with `_seconds` after its name.
+fn render_histogram(out: &mut String, histogram: &Histogram) {
+    let name = format!("succotash_{}_seconds", histogram.name);
+    let _ = writeln!(out, "# HELP {} {}.", name, histogram.help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    let mut count = 0;
+    for (i, bucket) in histogram.buckets().into_iter().enumerate() {
+        count += bucket;
+        let bound = match BUCKETS.get(i) {
+            Some(&bound) => (bound as f64 / 1e6).to_string(),
+            None => "+Inf".to_owned(),
+        };
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+    }
+    let _ = writeln!(out, "{}_sum {}", name, histogram.sum().as_secs_f64());
+    let _ = writeln!(out, "{}_count {}", name, count);
+}
+
+/// All [`METRICS`] in the text format of Prometheus: counters named with
+/// `_total` after their names, and histograms with `_seconds`.
+///
+/// # Arguments
+///
+/// * `files` - how many files the index has.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::analyze::metrics::METRICS;
+/// # use libsuccotash::serve::metrics;
+/// METRICS.queries.inc();
+/// METRICS.hashing.observe(std::time::Duration::from_millis(2));
+/// let rendered = metrics::render(120);
+/// assert!(rendered.contains("\nsuccotash_indexed_files 120\n"));
+/// assert!(rendered.contains("\nsuccotash_queries_total 1\n"));
+/// assert!(rendered.contains("\nsuccotash_files_analyzed_total 0\n"));
+/// assert!(rendered.contains("\nsuccotash_hashing_seconds_bucket{le=\"0.001\"} 0\n"));
+/// assert!(rendered.contains("\nsuccotash_hashing_seconds_bucket{le=\"0.004\"} 1\n"));
+/// assert!(rendered.contains("\nsuccotash_hashing_seconds_count 1\n"));
+/// ```
+pub fn render(files: usize) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# HELP succotash_indexed_files Files in the index.");
+    let _ = writeln!(out, "# TYPE succotash_indexed_files gauge");
+    let _ = writeln!(out, "succotash_indexed_files {}", files);
+    for counter in METRICS.counters() {
+        let name = format!("succotash_{}_total", counter.name);
+        let _ = writeln!(out, "# HELP {} {}.", name, counter.help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, counter.get());
+    }
+    for histogram in METRICS.histograms() {
+        render_histogram(&mut out, histogram);
+    }
+    out
+}
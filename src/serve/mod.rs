@@ -0,0 +1,354 @@
+//! Subcommand 'serve' lives here.
+//!
+//! Serve answers queries to an index over HTTP, turning succotash into
+//! a reverse image lookup service. The index is read once when the server
+//! starts, build it again and restart the server to pick up new files.
+//! Every endpoint responds with JSON, errors have an `error` field:
+//!
+//! * `POST /match` with an image as the body: files with similar images,
+//!   `{"matches": [{"path": "...", "distance": 3}, ...]}`, the closest first.
+//! * `GET /hash/HASH`, with a hash as [`hash`](crate::hash) prints it:
+//!   files with images whose hashes are close, like `/match`.
+//! * `GET /groups`: groups of similar images within the index,
+//!   `{"groups": [...], "savings": {...}}` like [`json`](crate::analyze::output::json)
+//!   writes them.
+//!
+//! `/match` and `/hash` take `?max-distance=BITS` to override how many bits
+//! hashes of similar images can differ in.
+//!
+//! The index can be that of a [database](crate::db), given by its directory,
+//! and then images in `/groups` have the tags of their files.
+//!
+//! `GET /metrics` has [metrics](metrics) for Prometheus, in its text format.
+//!
+//! The [web page](ui) to review groups in is at `/`, and uses these too:
+//!
+//! * `GET /image?path=PATH`: a PNG thumbnail of a file of the index.
+//! * `GET /plan`: files [planned](crate::analyze::plan) to be kept and deleted,
+//!   `{"keep": [...], "delete": [...]}`.
+//! * `POST /plan` with `{"delete": [...]}`: write files someone has picked
+//!   for deletion to the delete list, see [`AnalyzeOptions::delete_list`].
+
+pub mod http;
+pub mod metrics;
+pub mod ui;
+
+use crate::analyze::features::{set::FeatureSet, ImgFeatures};
+use crate::analyze::group::Group;
+use crate::analyze::img::ImgRaw;
+use crate::analyze::index::{self, query, store};
+use crate::analyze::metrics::METRICS;
+use crate::analyze::plan::{self, Plan};
+use crate::analyze::{output, walker, AnalyzeOptions};
+use crate::status::Status;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::path::{Path, PathBuf};
+use async_std::prelude::*;
+use http::{Request, Response};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// What the server answers queries with.
+struct Server {
+    /// Images of the index, ready to be queried.
+    query: query::Query<'static>,
+    /// Images of the index, to be grouped.
+    snapshot: &'static store::Snapshot,
+    /// Tags of files, if the index is that of a database.
+    files: walker::Files,
+    /// How to find features of uploaded images, how to search the index
+    /// and how close similar images are.
+    options: AnalyzeOptions,
+    /// Groups of similar images in the index, found on the first request for them.
+    groups: OnceLock<Vec<Group>>,
+}
+
+impl Server {
+    /// How many bits hashes can differ in, `max-distance` of the request if given.
+    fn max_distance(&self, request: &Request) -> Result<f64, Box<dyn std::error::Error>> {
+        let max_distance: u32 = match request.param("max-distance") {
+            Some(max_distance) => max_distance.parse()?,
+            None => self.options.max_distance,
+        };
+        Ok(f64::from(max_distance))
+    }
+
+    /// Files with images similar to an image with `features`, the closest first.
+    fn matches(&self, features: &ImgFeatures, set: &FeatureSet) -> Value {
+        let started = Instant::now();
+        let found = self.query.similar(features, set);
+        METRICS.querying.observe(started.elapsed());
+        let matches: Vec<Value> = found
+            .iter()
+            .map(|found| {
+                json!({
+                    "path": found.path.to_string_lossy(),
+                    "distance": found.distance,
+                })
+            })
+            .collect();
+        json!({ "matches": matches })
+    }
+
+    /// `POST /match`: files with images similar to the uploaded one.
+    async fn match_image(
+        &'static self,
+        request: Request,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let max_distance = self.max_distance(&request)?;
+        let set = self
+            .options
+            .features
+            .clone()
+            .with_max_distance("lshash", max_distance);
+        let find_options = self.options.find_options();
+        METRICS.queries.inc();
+        // Decoding is CPU-bound, it doesn't hold up other connections.
+        let features = async_std::task::spawn_blocking(move || {
+            let started = Instant::now();
+            let features = ImgRaw::decode(PathBuf::from("upload"), &request.body)
+                .map(|img_raw| ImgFeatures::find_with(&img_raw, find_options));
+            METRICS.hashing.observe(started.elapsed());
+            features.map_err(|e| {
+                METRICS.decode_failures.inc();
+                e.to_string()
+            })
+        })
+        .await?;
+        Ok(self.matches(&features, &set))
+    }
+
+    /// `GET /hash/HASH`: files with images whose hashes are close to `hash`.
+    fn match_hash(
+        &self,
+        request: &Request,
+        hash: &str,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        METRICS.queries.inc();
+        // Only hashes are compared, the other features are placeholders.
+        let features = ImgFeatures::decode(&format!("{} 0 0 0.5 0.2", hash))
+            .ok_or_else(|| format!("malformed hash '{}'", hash))?;
+        let set = FeatureSet::select(&["lshash"])?;
+        let set = set.with_max_distance("lshash", self.max_distance(request)?);
+        Ok(self.matches(&features, &set))
+    }
+
+    /// Groups of similar images within the index.
+    fn groups(&self) -> &[Group] {
+        if let Some(groups) = self.groups.get() {
+            METRICS.cache_hits.inc();
+            return groups;
+        }
+        METRICS.cache_misses.inc();
+        self.groups.get_or_init(|| {
+            let groups = index::groups(self.snapshot, &self.options);
+            info!(
+                "Found {} groups of similar images in the index",
+                groups.len()
+            );
+            groups
+        })
+    }
+
+    /// `GET /groups`: groups of similar images within the index.
+    async fn groups_document(&'static self) -> Value {
+        // Grouping the whole index takes a while, it doesn't hold up other connections.
+        let groups = async_std::task::spawn_blocking(move || self.groups()).await;
+        let mut document = output::json::document(groups, &self.files);
+        json!({ "groups": document["groups"].take(), "savings": document["savings"].take() })
+    }
+
+    /// `GET /image?path=PATH`: a thumbnail of a file of the index.
+    async fn image(&self, request: &Request) -> Result<Response, Box<dyn std::error::Error>> {
+        let path = request.param("path").ok_or("no 'path' to show")?;
+        // Only files of the index are shown, not whatever else the server can read.
+        if self.snapshot.get(Path::new(path)).is_none() {
+            return Ok(Response::error(404, "no such file in the index"));
+        }
+        let imgs_raw = ImgRaw::load_all(PathBuf::from(path))
+            .await
+            .inspect_err(|_| METRICS.decode_failures.inc())?;
+        let img_raw = imgs_raw.first().ok_or("no images in the file")?;
+        Ok(Response::bytes("image/png", ui::thumbnail(img_raw)?))
+    }
+
+    /// `GET /plan`: files planned to be kept and deleted.
+    async fn plan(&'static self) -> Value {
+        let plan = async_std::task::spawn_blocking(move || {
+            Plan::weighing(self.groups(), &self.options.keep, &self.options.priorities)
+        })
+        .await;
+        let paths = |paths: &[PathBuf]| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        };
+        json!({ "keep": paths(&plan.keep), "delete": paths(&plan.delete) })
+    }
+
+    /// `POST /plan`: write files picked for deletion to the delete list.
+    async fn save_plan(
+        &'static self,
+        request: &Request,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let delete_list =
+            self.options.delete_list.as_ref().ok_or(
+                "there is no delete list to write, start the server with --emit-delete-list",
+            )?;
+        let decided: Value = serde_json::from_slice(&request.body)?;
+        let delete: Vec<PathBuf> = decided["delete"]
+            .as_array()
+            .ok_or("no 'delete' array of paths")?
+            .iter()
+            .map(|path| {
+                path.as_str()
+                    .map(PathBuf::from)
+                    .ok_or("paths have to be strings")
+            })
+            .collect::<Result<_, _>>()?;
+        let groups = async_std::task::spawn_blocking(move || self.groups()).await;
+        let plan = Plan::decided(groups, &delete)?;
+        plan::write_delete_list(&plan, delete_list)?;
+        info!(
+            "Wrote {} files picked for deletion to '{}'",
+            plan.delete.len(),
+            delete_list.to_string_lossy()
+        );
+        Ok(json!({ "delete": plan.delete.len(), "path": delete_list.to_string_lossy() }))
+    }
+
+    /// Answer a request.
+    async fn respond(&'static self, request: Request) -> Response {
+        let answer = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/") => return Response::bytes("text/html; charset=utf-8", ui::PAGE.into()),
+            ("POST", "/match") => self.match_image(request).await,
+            ("GET", "/groups") => Ok(self.groups_document().await),
+            ("GET", path) if path.starts_with("/hash/") => {
+                self.match_hash(&request, &path["/hash/".len()..])
+            }
+            ("GET", "/image") => {
+                let response = self.image(&request).await;
+                return response.unwrap_or_else(|e| Response::error(400, &e.to_string()));
+            }
+            ("GET", "/plan") => Ok(self.plan().await),
+            ("GET", "/metrics") => {
+                let metrics = metrics::render(self.snapshot.len());
+                return Response::bytes(metrics::CONTENT_TYPE, metrics.into_bytes());
+            }
+            ("POST", "/plan") => self.save_plan(&request).await,
+            (_, "/" | "/match" | "/groups" | "/image" | "/plan" | "/metrics") => {
+                return Response::error(405, "method not allowed")
+            }
+            (_, path) if path.starts_with("/hash/") => {
+                return Response::error(405, "method not allowed")
+            }
+            _ => return Response::error(404, "no such endpoint"),
+        };
+        match answer {
+            Ok(body) => Response::ok(body),
+            Err(e) => Response::error(400, &e.to_string()),
+        }
+    }
+
+    /// Read a request from `stream` and answer it.
+    async fn handle(&'static self, stream: TcpStream) -> std::io::Result<()> {
+        let request = http::read_request(&mut &stream, &mut &stream).await;
+        let response = match request.map_err(|e| e.to_string()) {
+            Ok(request) => {
+                debug!("{} {}", request.method, request.path);
+                self.respond(request).await
+            }
+            Err(e) => Response::error(400, &e),
+        };
+        if response.status != 200 {
+            let body = String::from_utf8_lossy(&response.body);
+            debug!("Responding with {}: {}", response.status, body);
+        }
+        (&stream).write_all(&response.to_bytes()).await?;
+        (&stream).flush().await
+    }
+}
+
+/// Serve queries to an index over HTTP until the process is stopped.
+///
+/// # Arguments
+///
+/// * `listen` - the address to listen on, e.g. `127.0.0.1:8080`.
+/// * `index` - where the index file is, or the directory of a database.
+/// * `options` - how to find features of uploaded images, how to search
+///   the index and [how close](AnalyzeOptions::max_distance) similar images are.
+pub async fn try_run(
+    listen: &str,
+    index: &str,
+    options: AnalyzeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (snapshot, files) = if Path::new(index).is_dir().await {
+        let db = crate::db::Db::open(index.as_ref())?;
+        let mut files = walker::Files::default();
+        files.tags = db.tags();
+        (db.snapshot(&[])?, files)
+    } else {
+        (store::Snapshot::open(index)?, walker::Files::default())
+    };
+    // The server runs until the process exits, so the index is kept for as long.
+    let snapshot: &'static store::Snapshot = Box::leak(Box::new(snapshot));
+    crate::analyze::index::warn_stale(snapshot, index, &options);
+    let server: &'static Server = Box::leak(Box::new(Server {
+        query: query::Query::new(snapshot, options.search),
+        snapshot,
+        files,
+        options,
+        groups: OnceLock::new(),
+    }));
+
+    let listener = TcpListener::bind(listen).await?;
+    info!(
+        "Serving {} files of '{}' on http://{}",
+        snapshot.len(),
+        index,
+        listener.local_addr()?
+    );
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Couldn't accept a connection: {}", e);
+                continue;
+            }
+        };
+        async_std::task::spawn(async move {
+            if let Err(e) = server.handle(stream).await {
+                debug!("Couldn't respond: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Serve queries to an index over HTTP, do not propagate errors.
+///
+/// You can think of it as of `main` of the `serve` subcommand.
+/// Returns [`Status::Failed`] if the server couldn't start or stopped serving.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::serve;
+/// async_std::task::block_on(serve::run(
+///     "127.0.0.1:8080",
+///     "/home/user/photos.index",
+///     Default::default(),
+/// ));
+/// ```
+pub async fn run(listen: &str, index: &str, options: AnalyzeOptions) -> Status {
+    match try_run(listen, index, options).await {
+        Ok(()) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'serve': {}", e);
+            Status::Failed
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! A web page to review groups of similar images in.
+//!
+//! The page is embedded into the executable and served at `/`. It shows
+//! groups of similar images of the index with thumbnails, with the files
+//! [planned](crate::analyze::plan) to be kept marked, and every file can be
+//! marked to keep or to delete. Saving writes the files marked for deletion
+//! as a delete list, like `analyze --emit-delete-list` does, see
+//! [`Plan::decided`](crate::analyze::plan::Plan::decided). The server deletes nothing.
+
+use crate::analyze::img::ImgRaw;
+use std::path::Path;
+
+/// The page, HTML with the script and styles in it.
+pub const PAGE: &str = include_str!("ui.html");
+
+/// Thumbnails fit into a square with sides of this many pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// A thumbnail of an image as a PNG, keeping its aspect ratio.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::{analyze::img::ImgRaw, serve::ui};
+/// let img_raw = ImgRaw {
+///     path: "/a.png",
+///     data: image::DynamicImage::new_rgb8(1024, 512),
+/// };
+/// let png = ui::thumbnail(&img_raw).unwrap();
+/// let thumbnail = image::load_from_memory(&png).unwrap();
+/// assert_eq!(image::GenericImageView::dimensions(&thumbnail), (256, 128));
+/// ```
+pub fn thumbnail<P: AsRef<Path>>(img_raw: &ImgRaw<P>) -> image::ImageResult<Vec<u8>> {
+    let thumbnail = img_raw.data.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let mut png = Vec::new();
+    thumbnail.write_to(&mut png, image::ImageOutputFormat::Png)?;
+    Ok(png)
+}
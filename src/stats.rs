@@ -0,0 +1,233 @@
+//! Subcommand 'stats' lives here.
+//!
+//! Stats gives an overview of a library before it is analyzed, e.g. to pick
+//! the options of the analysis: what formats, sizes and dimensions its files
+//! are, and how hues and hashes of its images are distributed, each as a
+//! histogram with a bar for every bucket. Features are found the way
+//! 'analyze' finds them (see [`analyze::find_features`]), reused from and
+//! saved to the index.
+
+use crate::analyze::{self, decoders, features::ImgFeatures, img, AnalyzeOptions};
+use crate::status::Status;
+use async_std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Upper bounds of buckets of sizes of files, in bytes.
+/// The last bucket has no bound.
+pub const SIZES: [u64; 5] = [100 << 10, 1 << 20, 5 << 20, 20 << 20, 100 << 20];
+
+/// Upper bounds of buckets of longer sides of images, in pixels.
+/// The last bucket has no bound.
+pub const SIDES: [u32; 5] = [256, 1024, 2048, 4096, 8192];
+
+/// How many degrees of hue a bucket covers.
+pub const HUE_STEP: usize = 30;
+
+/// How many bits set in a hash a bucket covers.
+pub const BITS_STEP: usize = 4;
+
+/// Counts that describe a library.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// How many files there are of each format, by its name.
+    pub formats: BTreeMap<String, usize>,
+    /// Sum of sizes of the files, in bytes.
+    pub bytes: u64,
+    /// How many files have their size in each bucket, see [`SIZES`].
+    pub sizes: [usize; SIZES.len() + 1],
+    /// How many images have their longer side in each bucket, see [`SIDES`].
+    pub sides: [usize; SIDES.len() + 1],
+    /// How many images have dimensions that can't be read from the header.
+    pub unknown_sides: usize,
+    /// How many images have their hue in each bucket, see [`HUE_STEP`].
+    pub hues: [usize; 360 / HUE_STEP],
+    /// How many hashes have a number of bits set in each bucket, see [`BITS_STEP`].
+    pub bits: [usize; 64 / BITS_STEP + 1],
+}
+
+impl Stats {
+    /// Count a file by its format, size and dimensions.
+    ///
+    /// Only the header of the file is read.
+    pub async fn add_file(&mut self, path: &Path) {
+        let size = async_std::fs::metadata(path)
+            .await
+            .map_or(0, |metadata| metadata.len());
+        self.bytes += size;
+        let bucket = SIZES.iter().position(|&bound| size <= bound);
+        self.sizes[bucket.unwrap_or(SIZES.len())] += 1;
+
+        let probe = img::probe(path).await.ok();
+        let format = match probe.and_then(|probe| probe.format) {
+            Some(format) => format!("{:?}", format).to_lowercase(),
+            None => match decoders::OPTIONAL
+                .iter()
+                .find(|decoder| decoder.matches(path))
+            {
+                Some(decoder) => decoder.format.to_lowercase(),
+                None => "unknown".to_owned(),
+            },
+        };
+        *self.formats.entry(format).or_default() += 1;
+
+        match probe.and_then(|probe| probe.dimensions) {
+            Some((width, height)) => {
+                let side = width.max(height);
+                let bucket = SIDES.iter().position(|&bound| side <= bound);
+                self.sides[bucket.unwrap_or(SIDES.len())] += 1;
+            }
+            None => self.unknown_sides += 1,
+        }
+    }
+
+    /// Count an image by its features.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use libsuccotash::{stats::Stats, analyze::features::ImgFeatures};
+    /// let mut stats = Stats::default();
+    /// stats.add_features(&ImgFeatures::decode("00000000000000ff 100 0 0.5 0.2").unwrap());
+    /// assert_eq!(stats.hues[3], 1);
+    /// assert_eq!(stats.bits[2], 1);
+    /// ```
+    pub fn add_features(&mut self, features: &ImgFeatures) {
+        let hue = features.hue.degrees() as usize / HUE_STEP;
+        self.hues[hue.min(self.hues.len() - 1)] += 1;
+        let bits = features.lshash.bits().count_ones() as usize;
+        self.bits[bits / BITS_STEP] += 1;
+    }
+
+    /// Lines of a report for people.
+    pub fn lines(&self) -> Vec<String> {
+        let files: usize = self.formats.values().sum();
+        let mut lines = vec![format!(
+            "{} files, {}",
+            files,
+            crate::units::format_size(self.bytes)
+        )];
+
+        lines.push("Formats:".to_owned());
+        for (format, count) in &self.formats {
+            lines.push(row(format, *count, files));
+        }
+
+        lines.push("File size:".to_owned());
+        for (i, count) in self.sizes.iter().enumerate() {
+            // Bounds are whole, e.g. `1 MiB` rather than `1.0 MiB`.
+            let size = |bytes| crate::units::format_size(bytes).replace(".0 ", " ");
+            let label = match (i.checked_sub(1).map(|i| SIZES[i]), SIZES.get(i)) {
+                (None, Some(&upper)) => format!("up to {}", size(upper)),
+                (Some(lower), Some(&upper)) => format!("{}-{}", size(lower), size(upper)),
+                (Some(lower), None) => format!("over {}", size(lower)),
+                (None, None) => unreachable!("there are bounds"),
+            };
+            lines.push(row(&label, *count, files));
+        }
+
+        lines.push("Longer side, pixels:".to_owned());
+        for (i, count) in self.sides.iter().enumerate() {
+            let label = match (i.checked_sub(1).map(|i| SIDES[i]), SIDES.get(i)) {
+                (None, Some(upper)) => format!("up to {}", upper),
+                (Some(lower), Some(upper)) => format!("{}-{}", lower + 1, upper),
+                (Some(lower), None) => format!("over {}", lower),
+                (None, None) => unreachable!("there are bounds"),
+            };
+            lines.push(row(&label, *count, files));
+        }
+        if self.unknown_sides > 0 {
+            lines.push(row("unknown", self.unknown_sides, files));
+        }
+
+        let images: usize = self.hues.iter().sum();
+        lines.push("Hue, degrees:".to_owned());
+        for (i, count) in self.hues.iter().enumerate() {
+            let label = format!("{}-{}", i * HUE_STEP, (i + 1) * HUE_STEP);
+            lines.push(row(&label, *count, images));
+        }
+
+        lines.push("Bits set in hashes:".to_owned());
+        for (i, count) in self.bits.iter().enumerate() {
+            let label = match i * BITS_STEP {
+                64 => "64".to_owned(),
+                lower => format!("{}-{}", lower, lower + BITS_STEP - 1),
+            };
+            lines.push(row(&label, *count, images));
+        }
+        lines
+    }
+}
+
+/// A line of a histogram: a label, a count and a bar as long as its part of `total`.
+fn row(label: &str, count: usize, total: usize) -> String {
+    const WIDTH: usize = 40;
+    let bar = (count * WIDTH).checked_div(total).unwrap_or(0);
+    let row = format!("  {:<14} {:>8} {}", label, count, "#".repeat(bar));
+    row.trim_end().to_owned()
+}
+
+/// Collect stats of the files, see [`run`].
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `options` - what files to pick and how to find their features.
+pub async fn collect(
+    dirs: Vec<String>,
+    options: &AnalyzeOptions,
+) -> Result<Stats, Box<dyn std::error::Error>> {
+    let files = analyze::walker::Source::Walk(dirs)
+        .files(&options.walk, &options.cancel, &options.progress)
+        .await?;
+
+    let mut stats = Stats::default();
+    for path in files
+        .paths
+        .iter()
+        .chain(files.unsupported.iter().map(|(path, _)| path))
+    {
+        stats.add_file(path).await;
+    }
+    for img in analyze::find_features(files.paths, options).await? {
+        stats.add_features(&img.features);
+    }
+    Ok(stats)
+}
+
+/// Print stats of the files, do not propagate errors.
+///
+/// You can think of it as of `main` of the `stats` subcommand.
+/// Returns [`Status::Failed`] if there were errors.
+///
+/// # Arguments
+///
+/// * `dirs` - directories to walk.
+/// * `options` - what files to pick and how to find their features.
+/// * `output` - the file to write the stats to, stdout without it.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use libsuccotash::stats;
+/// # async_std::task::block_on(async {
+/// stats::run(vec!["/home/user/Pictures".into()], Default::default(), None).await;
+/// # });
+/// ```
+pub async fn run(dirs: Vec<String>, options: AnalyzeOptions, output: Option<PathBuf>) -> Status {
+    let written = collect(dirs, &options).await.and_then(|stats| {
+        let mut writer = analyze::output::open(output.as_deref())?;
+        for line in stats.lines() {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+        Ok(())
+    });
+    match written {
+        Ok(()) => Status::Clean,
+        Err(e) => {
+            error!("Error during 'stats': {}", e);
+            Status::Failed
+        }
+    }
+}
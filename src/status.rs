@@ -0,0 +1,55 @@
+//! How subcommands end.
+//!
+//! Every subcommand returns a [`Status`], which the executable exits with,
+//! so that scripts can tell whether anything was found without reading
+//! the output: 0 if nothing was, 1 if something was, 2 if there were errors.
+
+/// How a subcommand ended, see [`Status::code`].
+///
+/// Statuses are ordered by how much they matter: combining two of them,
+/// e.g. of a few steps of a subcommand, gives the greater one.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::status::Status;
+/// assert_eq!(Status::found(3), Status::Found);
+/// assert_eq!(Status::found(0).max(Status::Failed).code(), 2);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    /// Ran fine and found nothing, e.g. no similar images.
+    #[default]
+    Clean,
+    /// Ran fine and found something, e.g. similar images.
+    Found,
+    /// Errors occurred, whatever was found.
+    Failed,
+}
+
+impl Status {
+    /// [`Status::Found`] if `count` things were found, [`Status::Clean`] if none were.
+    pub fn found(count: usize) -> Self {
+        match count {
+            0 => Self::Clean,
+            _ => Self::Found,
+        }
+    }
+
+    /// [`Status::Failed`] if `count` things failed, [`Status::Clean`] if none did.
+    pub fn failed(count: u64) -> Self {
+        match count {
+            0 => Self::Clean,
+            _ => Self::Failed,
+        }
+    }
+
+    /// The exit code of the executable.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Clean => 0,
+            Self::Found => 1,
+            Self::Failed => 2,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Parsing values with units.
+//!
+//! Arguments that are sizes, pixel counts or durations are given with
+//! their units, e.g. `10KiB`, `50MP` or `90m`, rather than as bare numbers
+//! in some implied unit. Errors are strings, so the parsers can be used as
+//! validators of arguments too.
+
+use std::time::Duration;
+
+/// Split a value into a non-negative number and the unit after it.
+fn split_number<'a>(s: &'a str, what: &str) -> Result<(f64, &'a str), String> {
+    let s = s.trim();
+    let unit_start = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(unit_start);
+    match number.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok((number, unit.trim())),
+        _ => Err(format!("'{}' is not a {}", s, what)),
+    }
+}
+
+/// Parse a size in bytes.
+///
+/// Accepts a number followed by a unit: none or `B` for bytes, `K`, `M`, `G`
+/// and `T` (or `KB` and so on) for powers of 1000, `KiB`, `MiB`, `GiB` and `TiB`
+/// for powers of 1024. Units are case-insensitive, numbers can have a fraction.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::units::parse_size;
+/// assert_eq!(parse_size("512"), Ok(512));
+/// assert_eq!(parse_size("10KiB"), Ok(10 * 1024));
+/// assert_eq!(parse_size("1.5MB"), Ok(1_500_000));
+/// assert_eq!(parse_size("2 gib"), Ok(2 << 30));
+/// assert!(parse_size("10 parsecs").is_err());
+/// assert!(parse_size("-1K").is_err());
+/// ```
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let (number, unit) = split_number(s, "size")?;
+    let multiplier: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000_u64.pow(2),
+        "g" | "gb" => 1000_u64.pow(3),
+        "t" | "tb" => 1000_u64.pow(4),
+        "ki" | "kib" => 1 << 10,
+        "mi" | "mib" => 1 << 20,
+        "gi" | "gib" => 1 << 30,
+        "ti" | "tib" => 1 << 40,
+        _ => return Err(format!("unknown size unit '{}' in '{}'", unit, s)),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Format a size in bytes for people, in powers of 1024,
+/// so that [`parse_size`] reads it back (almost) the same.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::units::format_size;
+/// assert_eq!(format_size(512), "512 B");
+/// assert_eq!(format_size(1536), "1.5 KiB");
+/// assert_eq!(format_size(5 << 30), "5.0 GiB");
+/// ```
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.;
+    let mut unit = 0;
+    while size >= 1024. && unit + 1 < UNITS.len() {
+        size /= 1024.;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Parse dimensions of an image, `WIDTHxHEIGHT`.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::units::parse_dimensions;
+/// assert_eq!(parse_dimensions("64x48"), Ok((64, 48)));
+/// assert_eq!(parse_dimensions("64 X 48"), Ok((64, 48)));
+/// assert!(parse_dimensions("64").is_err());
+/// assert!(parse_dimensions("64x").is_err());
+/// ```
+pub fn parse_dimensions(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("'{}' are not dimensions", s);
+    let (width, height) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let side = |side: &str| side.trim().parse::<u32>().map_err(|_| invalid());
+    Ok((side(width)?, side(height)?))
+}
+
+/// Parse a number of pixels.
+///
+/// Accepts dimensions (`4000x3000`) or a number followed by a unit:
+/// none for pixels, `KP` for thousands and `MP` for millions of pixels.
+/// Units are case-insensitive, numbers can have a fraction.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::units::parse_pixels;
+/// assert_eq!(parse_pixels("50MP"), Ok(50_000_000));
+/// assert_eq!(parse_pixels("0.5mp"), Ok(500_000));
+/// assert_eq!(parse_pixels("4000x3000"), Ok(12_000_000));
+/// assert_eq!(parse_pixels("640"), Ok(640));
+/// assert!(parse_pixels("4000x").is_err());
+/// ```
+pub fn parse_pixels(s: &str) -> Result<u64, String> {
+    if s.contains(['x', 'X']) {
+        let (width, height) = parse_dimensions(s)?;
+        return Ok(u64::from(width) * u64::from(height));
+    }
+
+    let (number, unit) = split_number(s, "number of pixels")?;
+    let multiplier = match unit.to_ascii_lowercase().as_str() {
+        "" | "p" | "px" => 1.,
+        "kp" => 1e3,
+        "mp" => 1e6,
+        _ => return Err(format!("unknown pixel unit '{}' in '{}'", unit, s)),
+    };
+    Ok((number * multiplier).round() as u64)
+}
+
+/// Parse a duration.
+///
+/// Accepts one or more numbers, each followed by a unit: `ms`, `s`, `m`, `h`,
+/// `d`, `w` or `y` (365 days), e.g. `90m`, `1h30m` or `1.5s`.
+///
+/// # Examples
+///
+/// ```
+/// # use libsuccotash::units::parse_duration;
+/// # use std::time::Duration;
+/// assert_eq!(parse_duration("3s"), Ok(Duration::from_secs(3)));
+/// assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+/// assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+/// assert!(parse_duration("90").is_err());
+/// assert!(parse_duration("").is_err());
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("'{}' is not a duration", s);
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let unit_start = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let unit_end = rest[unit_start..]
+            .find(|c: char| c.is_ascii_digit())
+            .map_or(rest.len(), |end| unit_start + end);
+        let number: f64 = rest[..unit_start].parse().map_err(|_| invalid())?;
+        let seconds = match &rest[unit_start..unit_end] {
+            "ms" => 0.001,
+            "s" => 1.,
+            "m" => 60.,
+            "h" => 60. * 60.,
+            "d" => 24. * 60. * 60.,
+            "w" => 7. * 24. * 60. * 60.,
+            "y" => 365. * 24. * 60. * 60.,
+            _ => return Err(invalid()),
+        };
+        total += Duration::try_from_secs_f64(number * seconds).map_err(|_| invalid())?;
+        rest = &rest[unit_end..];
+    }
+    Ok(total)
+}
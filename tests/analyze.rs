@@ -0,0 +1,935 @@
+//! The whole analysis, from walking directories to writing reports.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::cancel::CancelToken;
+use libsuccotash::analyze::filter::Filters;
+use libsuccotash::analyze::index::{self, sidecar::Mode};
+use libsuccotash::analyze::output::{matrix, Format};
+use libsuccotash::analyze::walker::{glob::Pattern, links::LinkPolicy, Source};
+use libsuccotash::analyze::{self, AnalyzeOptions};
+
+#[test]
+fn groups_similar_images() {
+    let tree = Tree::new("groups");
+    tree.image("a.png", Kind::Horizontal)
+        .image("copies/a.jpg", Kind::Horizontal)
+        .image("b.png", Kind::Vertical);
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), AnalyzeOptions::default());
+    assert_eq!(
+        common::groups(&tree, &report),
+        vec![vec!["a.png", "copies/a.jpg"]]
+    );
+    for img in report["groups"][0]["images"].as_array().unwrap() {
+        assert_eq!(img["width"], common::SIZE);
+        assert_eq!(img["height"], common::SIZE);
+    }
+}
+
+#[test]
+fn estimates_savings_of_every_strategy() {
+    let tree = Tree::new("savings");
+    tree.image("a.png", Kind::Horizontal)
+        .image("copies/a.jpg", Kind::Horizontal);
+    let size = |name| std::fs::metadata(tree.path(name)).unwrap().len();
+    let (png, jpg) = (size("a.png"), size("copies/a.jpg"));
+    assert_ne!(png, jpg);
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), AnalyzeOptions::default());
+    let savings = &report["groups"][0]["savings"];
+    // Keeping the largest file deletes the smallest one, and the other way around.
+    assert_eq!(savings["largest"], png.min(jpg));
+    assert_eq!(savings["smallest"], png.max(jpg));
+    assert!(savings["sharpest"] == jpg || savings["sharpest"] == png);
+    assert_eq!(report["savings"], *savings);
+}
+
+#[test]
+fn skips_ignored_files() {
+    let tree = Tree::new("ignore");
+    tree.image("a.png", Kind::Horizontal)
+        .image("sprites/a.png", Kind::Horizontal)
+        .image("art/sprites/a.png", Kind::Horizontal)
+        .image("art/keep/a.png", Kind::Horizontal)
+        .file(".succotashignore", b"sprites/\n")
+        .file("art/.gitignore", b"keep\n");
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), AnalyzeOptions::default());
+    assert_eq!(
+        common::groups(&tree, &report),
+        vec![vec!["a.png", "art/keep/a.png"]]
+    );
+
+    let mut options = AnalyzeOptions::default();
+    options.walk.gitignore = true;
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+}
+
+#[cfg(unix)]
+#[test]
+fn picks_linked_files_once() {
+    let tree = Tree::new("links");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal)
+        .symlink("a.png", "link.png")
+        .symlink("", "loop");
+    std::fs::hard_link(tree.path("a.png"), tree.path("hard.png")).unwrap();
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), AnalyzeOptions::default());
+    let groups = common::groups(&tree, &report);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(
+        groups[0].len(),
+        2,
+        "a.png, link.png and hard.png are one file: {:?}",
+        groups
+    );
+    // Only hard links are reported, symbolic ones are the file itself.
+    let hardlinks = report["hardlinks"].as_array().unwrap();
+    assert_eq!(hardlinks.len(), 1);
+    assert_eq!(
+        common::relative(&tree, hardlinks[0]["path"].as_str().unwrap()),
+        "hard.png"
+    );
+    assert_eq!(
+        common::relative(&tree, hardlinks[0]["links_to"].as_str().unwrap()),
+        "a.png"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn follows_linked_directories_if_asked() {
+    let tree = Tree::new("follow");
+    tree.image("photos/a.png", Kind::Horizontal)
+        .image("elsewhere/b.png", Kind::Horizontal)
+        .symlink("elsewhere", "photos/more")
+        .symlink("photos", "photos/loop");
+
+    let report = common::analyze(
+        &tree,
+        common::walk(&tree, &["photos"]),
+        AnalyzeOptions::default(),
+    );
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+
+    let mut options = AnalyzeOptions::default();
+    options.walk.symlinks = LinkPolicy::Follow;
+    let report = common::analyze(&tree, common::walk(&tree, &["photos"]), options);
+    assert_eq!(
+        common::groups(&tree, &report),
+        vec![vec!["photos/a.png", "photos/more/b.png"]]
+    );
+}
+
+#[test]
+fn reports_roots_of_files() {
+    let tree = Tree::new("roots");
+    tree.image("internal/a.png", Kind::Horizontal)
+        .image("nas/a.png", Kind::Horizontal)
+        .image("nas/b.png", Kind::Vertical);
+
+    let source = common::walk(&tree, &["internal", "nas"]);
+    let report = common::analyze(&tree, source, AnalyzeOptions::default());
+    assert_eq!(report["roots"].as_array().unwrap().len(), 2);
+    for img in report["groups"][0]["images"].as_array().unwrap() {
+        let path = img["path"].as_str().unwrap();
+        let root = img["root"].as_str().unwrap();
+        assert!(path.starts_with(root), "'{}' isn't in '{}'", path, root);
+    }
+}
+
+#[test]
+fn reports_unsupported_files() {
+    let tree = Tree::new("unsupported");
+    tree.image("a.png", Kind::Horizontal)
+        .file("b.heic", b"not really");
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), AnalyzeOptions::default());
+    let unsupported = &report["unsupported"];
+    assert_eq!(unsupported["counts"][0]["format"], "HEIC");
+    assert_eq!(unsupported["counts"][0]["files"], 1);
+    assert_eq!(
+        common::relative(&tree, unsupported["files"][0]["path"].as_str().unwrap()),
+        "b.heic"
+    );
+}
+
+#[test]
+fn analyzes_listed_files() {
+    let tree = Tree::new("list");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal)
+        .image("c.png", Kind::Horizontal);
+    let list = format!("{}\n{}\n", tree.string("a.png"), tree.string("c.png"));
+    tree.file("list.txt", list.as_bytes());
+
+    let source = Source::List(tree.path("list.txt").into());
+    let report = common::analyze(&tree, source, AnalyzeOptions::default());
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "c.png"]]);
+}
+
+#[test]
+fn plans_to_keep_the_sharpest_image() {
+    let tree = Tree::new("plan");
+    tree.image("blurry.png", Kind::Horizontal)
+        .image("sharp.png", Kind::HorizontalNoisy)
+        .image("other.png", Kind::Vertical);
+    let delete_list = tree.root().with_extension("delete");
+
+    let options = AnalyzeOptions {
+        delete_list: Some(delete_list.clone().into()),
+        ..Default::default()
+    };
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(
+        common::groups(&tree, &report),
+        vec![vec!["blurry.png", "sharp.png"]]
+    );
+
+    let listed = std::fs::read(&delete_list).unwrap();
+    std::fs::remove_file(&delete_list).unwrap();
+    let listed: Vec<String> = listed
+        .split(|&b| b == b'\0')
+        .filter(|path| !path.is_empty())
+        .map(|path| common::relative(&tree, &String::from_utf8_lossy(path)))
+        .collect();
+    assert_eq!(listed, vec!["blurry.png"]);
+}
+
+#[test]
+fn plans_to_keep_the_file_criteria_weigh_highest() {
+    let tree = Tree::new("keep");
+    tree.image("exports/a.jpg", Kind::HorizontalNoisy)
+        .image("originals/a.png", Kind::Horizontal);
+    let delete_list = tree.root().with_extension("delete");
+
+    let planned = |keep: &[&str]| {
+        let options = AnalyzeOptions {
+            delete_list: Some(delete_list.clone().into()),
+            keep: libsuccotash::analyze::keep::Criteria::parse(keep.iter().copied()).unwrap(),
+            ..Default::default()
+        };
+        common::analyze(&tree, common::walk(&tree, &[""]), options);
+        let listed = std::fs::read(&delete_list).unwrap();
+        std::fs::remove_file(&delete_list).unwrap();
+        common::relative(
+            &tree,
+            String::from_utf8_lossy(&listed).trim_end_matches('\0'),
+        )
+    };
+    assert_eq!(planned(&["path=1"]), "exports/a.jpg");
+    assert_eq!(planned(&["format=1", "size=0.5"]), "exports/a.jpg");
+    // Of files that score the same, the first one by path is kept.
+    assert_eq!(planned(&["path=0"]), "originals/a.png");
+}
+
+#[test]
+fn plans_to_keep_preferred_files() {
+    let tree = Tree::new("prefer");
+    tree.image("Camera/a.jpg", Kind::HorizontalNoisy)
+        .image("WhatsApp/a.jpg", Kind::Horizontal);
+    let delete_list = tree.root().with_extension("delete");
+
+    let planned = |prefer: &[&str], deprioritize: &[&str]| {
+        let globs = |globs: &[&str]| -> Vec<Pattern> {
+            globs
+                .iter()
+                .map(|glob| Pattern::new(glob).unwrap())
+                .collect()
+        };
+        let options = AnalyzeOptions {
+            delete_list: Some(delete_list.clone().into()),
+            priorities: libsuccotash::analyze::keep::Priorities {
+                prefer: globs(prefer),
+                deprioritize: globs(deprioritize),
+            },
+            ..Default::default()
+        };
+        common::analyze(&tree, common::walk(&tree, &[""]), options);
+        let listed = std::fs::read(&delete_list).unwrap();
+        std::fs::remove_file(&delete_list).unwrap();
+        common::relative(
+            &tree,
+            String::from_utf8_lossy(&listed).trim_end_matches('\0'),
+        )
+    };
+    assert_eq!(planned(&["**/Camera/**"], &[]), "WhatsApp/a.jpg");
+    assert_eq!(planned(&[], &["**/Camera/**"]), "Camera/a.jpg");
+    assert_eq!(
+        planned(&["**/WhatsApp/*"], &["**/Camera/**"]),
+        "Camera/a.jpg"
+    );
+    assert_eq!(planned(&["*.jpg"], &["**/WhatsApp/**"]), "WhatsApp/a.jpg");
+}
+
+#[test]
+fn groups_bursts_and_plans_to_keep_the_best_photo() {
+    let tree = Tree::new("bursts");
+    tree.photo("burst/1.jpg", Kind::Horizontal, "2021:06:01 12:00:00.1")
+        .photo(
+            "burst/2.jpg",
+            Kind::HorizontalNoisy,
+            "2021:06:01 12:00:00.5",
+        )
+        .photo("burst/3.jpg", Kind::Horizontal, "2021:06:01 12:00:00.9")
+        .photo("later.jpg", Kind::Horizontal, "2021:06:01 12:05:00")
+        .image("undated.png", Kind::Horizontal);
+    let delete_list = tree.root().with_extension("delete");
+
+    let options = AnalyzeOptions {
+        bursts: Some(std::time::Duration::from_secs(2)),
+        delete_list: Some(delete_list.clone().into()),
+        ..Default::default()
+    };
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(
+        common::groups(&tree, &report),
+        vec![vec!["burst/1.jpg", "burst/2.jpg", "burst/3.jpg"]]
+    );
+
+    let listed = std::fs::read(&delete_list).unwrap();
+    std::fs::remove_file(&delete_list).unwrap();
+    let listed: Vec<String> = listed
+        .split(|&b| b == b'\0')
+        .filter(|path| !path.is_empty())
+        .map(|path| common::relative(&tree, &String::from_utf8_lossy(path)))
+        .collect();
+    assert_eq!(listed, vec!["burst/1.jpg", "burst/3.jpg"]);
+}
+
+#[test]
+fn skips_small_files_and_images() {
+    let tree = Tree::new("size");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal);
+    let size = std::fs::metadata(tree.path("a.png")).unwrap().len();
+
+    let mut options = AnalyzeOptions::default();
+    options.walk.size.max_size = Some(size - 1);
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+
+    let mut options = AnalyzeOptions::default();
+    options.walk.size.min_dimensions = Some((common::SIZE + 1, 1));
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+
+    let mut options = AnalyzeOptions::default();
+    options.walk.size.min_dimensions = Some((1, common::SIZE));
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "b.png"]]);
+}
+
+#[test]
+fn reports_oversized_files_without_reading_them() {
+    let tree = Tree::new("oversized");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal)
+        .file("huge.tif", &[0; 1 << 16]);
+
+    let output = tree.root().with_extension("json");
+    let options = AnalyzeOptions {
+        max_file_size: Some(1 << 15),
+        decode_timeout: Some(std::time::Duration::from_secs(60)),
+        format: Format::Json,
+        output: Some(output.clone().into()),
+        ..Default::default()
+    };
+    let source = common::walk(&tree, &[""]);
+    let status = async_std::task::block_on(libsuccotash::analyze::run(source, options));
+    // The oversized file failed, the others were analyzed.
+    assert_eq!(status.code(), 2);
+    let report = serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "b.png"]]);
+}
+
+#[test]
+fn analyzes_one_file_at_a_time_under_a_tight_memory_limit() {
+    let tree = Tree::new("memory");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.jpg", Kind::Horizontal)
+        .image("c.png", Kind::Vertical);
+
+    let options = AnalyzeOptions {
+        threads: 2,
+        max_memory: Some(1),
+        ..Default::default()
+    };
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "b.jpg"]]);
+}
+
+#[test]
+fn reports_in_the_same_order_every_time() {
+    let tree = Tree::new("order");
+    tree.image("z.png", Kind::Horizontal)
+        .image("y.png", Kind::Horizontal)
+        .image("b/a.png", Kind::Vertical)
+        .image("a.png", Kind::Vertical);
+    let options = || AnalyzeOptions {
+        threads: 4,
+        ..Default::default()
+    };
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options());
+    assert_eq!(
+        common::groups(&tree, &report),
+        vec![vec!["a.png", "b/a.png"], vec!["y.png", "z.png"]]
+    );
+    for _ in 0..3 {
+        assert_eq!(
+            common::analyze(&tree, common::walk(&tree, &[""]), options()),
+            report
+        );
+    }
+}
+
+#[test]
+fn resumes_from_saved_features() {
+    let tree = Tree::new("resume");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal);
+    let index = tree.root().with_extension("index");
+    let options = |resume| AnalyzeOptions {
+        index: Some(index.clone().into()),
+        resume,
+        ..Default::default()
+    };
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options(false));
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "b.png"]]);
+
+    // Features of the changed file come from the index, so it is still similar.
+    tree.image("b.png", Kind::Vertical);
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options(true));
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "b.png"]]);
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options(false));
+    std::fs::remove_file(&index).unwrap();
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+}
+
+#[test]
+fn finds_features_again_if_found_otherwise() {
+    let tree = Tree::new("schema");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal);
+    let index = tree.root().with_extension("index");
+    let options = |trim| AnalyzeOptions {
+        index: Some(index.clone().into()),
+        resume: true,
+        trim,
+        ..Default::default()
+    };
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options(true));
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "b.png"]]);
+
+    // Saved features were found with trimming, so those of the changed file are found again.
+    tree.image("b.png", Kind::Vertical);
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options(false));
+    std::fs::remove_file(&index).unwrap();
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+}
+
+#[test]
+fn reuses_features_in_sidecars() {
+    let tree = Tree::new("sidecars");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal);
+    let options = |sidecars| AnalyzeOptions {
+        sidecars,
+        ..Default::default()
+    };
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options(Mode::Write));
+    assert!(tree.root().join("b.png.xmp").exists());
+
+    // Features of the changed file come from its sidecar.
+    tree.image("b.png", Kind::Vertical);
+    let report_again = common::analyze(&tree, common::walk(&tree, &[""]), options(Mode::Read));
+    assert_eq!(common::groups(&tree, &report), vec![vec!["a.png", "b.png"]]);
+    assert_eq!(
+        common::groups(&tree, &report_again),
+        vec![vec!["a.png", "b.png"]]
+    );
+}
+
+#[test]
+fn writes_results_when_cancelled() {
+    let tree = Tree::new("cancel");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal);
+
+    let token = CancelToken::new();
+    token.cancel();
+    let options = AnalyzeOptions::default().cancel_token(token);
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+}
+
+#[test]
+fn skips_quarantined_files_until_retried() {
+    let tree = Tree::new("quarantine");
+    tree.image("a.png", Kind::Horizontal)
+        .file("broken.png", b"not an image");
+    let index = tree.root().with_extension("index");
+    let quarantine: std::path::PathBuf = index::quarantine::path_of(index.as_path().into()).into();
+    let analyze = |retry_quarantined| {
+        let options = AnalyzeOptions {
+            index: Some(index.clone().into()),
+            retry_quarantined,
+            output: Some(tree.root().with_extension("json").into()),
+            ..Default::default()
+        };
+        let source = common::walk(&tree, &[""]);
+        async_std::task::block_on(libsuccotash::analyze::run(source, options)).code()
+    };
+
+    assert_eq!(analyze(false), 2);
+    assert!(std::fs::read_to_string(&quarantine)
+        .unwrap()
+        .contains(&tree.string("broken.png")));
+    // The broken file isn't tried again, so the analysis doesn't fail.
+    assert_eq!(analyze(false), 0);
+
+    tree.image("broken.png", Kind::Vertical);
+    assert_eq!(analyze(true), 0);
+    assert!(!quarantine.exists());
+    std::fs::remove_file(&index).unwrap();
+    std::fs::remove_file(tree.root().with_extension("json")).unwrap();
+}
+
+#[test]
+fn exports_distances_between_all_images() {
+    let tree = Tree::new("matrix");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal)
+        .image("c.png", Kind::Vertical);
+    let export = |format, max_distance| {
+        let path = tree.root().with_extension(format!("{:?}", format));
+        let options = AnalyzeOptions {
+            matrix: Some(matrix::Export {
+                path: path.clone().into(),
+                format,
+                max_distance,
+            }),
+            ..Default::default()
+        };
+        common::analyze(&tree, common::walk(&tree, &[""]), options);
+        let exported = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        exported
+    };
+
+    let full = String::from_utf8(export(matrix::Format::Csv, None)).unwrap();
+    let rows: Vec<Vec<&str>> = full.lines().map(|row| row.split(',').collect()).collect();
+    assert_eq!(rows.len(), 4);
+    assert!(rows[0][3].ends_with("c.png"));
+    assert_eq!(rows[1][1..3], ["0", "0"]);
+    assert_eq!(rows[3][3], "0");
+    assert_ne!(rows[3][1], "0");
+
+    let sparse = String::from_utf8(export(matrix::Format::Csv, Some(0))).unwrap();
+    let rows: Vec<&str> = sparse.lines().collect();
+    assert_eq!(rows.len(), 2);
+    assert!(rows[1].ends_with("b.png,0"), "{}", sparse);
+
+    let binary = export(matrix::Format::Binary, None);
+    assert_eq!(&binary[..8], matrix::MAGIC);
+    assert_eq!(binary[8], 0);
+    assert_eq!(binary[9..13], 3u32.to_le_bytes());
+    // A byte for each of the nine pairs ends the file.
+    let distances = &binary[binary.len() - 9..];
+    assert_eq!(distances[..3], [0, 0, distances[2]]);
+    assert_ne!(distances[2], 0);
+}
+
+#[test]
+fn exports_pairs_of_similar_images_as_a_graph() {
+    let tree = Tree::new("graph");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal)
+        .image("c.png", Kind::Vertical);
+    let graph = tree.root().with_extension("dot");
+    let options = AnalyzeOptions {
+        graph: Some(graph.clone().into()),
+        ..Default::default()
+    };
+    common::analyze(&tree, common::walk(&tree, &[""]), options);
+    let dot = std::fs::read_to_string(&graph).unwrap();
+    std::fs::remove_file(&graph).unwrap();
+    assert!(dot.starts_with("graph similar {"), "{}", dot);
+    assert!(dot.contains("n0 [label=\"a.png\""), "{}", dot);
+    assert!(dot.contains("n0 -- n1 [label=0];"), "{}", dot);
+    assert!(!dot.contains("c.png"), "{}", dot);
+}
+
+#[cfg(feature = "archives")]
+#[test]
+fn groups_images_inside_archives() {
+    use std::io::Write;
+
+    let png = |kind| {
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgb8(common::image(kind))
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        png
+    };
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, kind) in [
+        ("pages/001.png", Kind::Horizontal),
+        ("pages/002.png", Kind::Vertical),
+    ] {
+        zip.start_file(name, stored).unwrap();
+        zip.write_all(&png(kind)).unwrap();
+    }
+    zip.start_file("notes.txt", stored).unwrap();
+    let zip = zip.finish().unwrap().into_inner();
+
+    let mut tar = tar::Builder::new(Vec::new());
+    let cover = png(Kind::Vertical);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(cover.len() as u64);
+    header.set_mode(0o644);
+    tar.append_data(&mut header, "cover.png", cover.as_slice())
+        .unwrap();
+    let tar = tar.into_inner().unwrap();
+
+    let tree = Tree::new("archives");
+    tree.image("a.png", Kind::Horizontal)
+        .file("comics/issue-1.cbz", &zip)
+        .file("scans.tar", &tar);
+
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), AnalyzeOptions::default());
+    assert_eq!(common::groups(&tree, &report), Vec::<Vec<String>>::new());
+
+    let mut options = AnalyzeOptions::default();
+    options.walk.archives = true;
+    let report = common::analyze(&tree, common::walk(&tree, &[""]), options);
+    assert_eq!(
+        common::groups(&tree, &report),
+        vec![
+            vec!["a.png", "comics/issue-1.cbz!/pages/001.png"],
+            vec!["comics/issue-1.cbz!/pages/002.png", "scans.tar!/cover.png"],
+        ]
+    );
+}
+
+#[test]
+fn reflinks_only_exact_duplicates() {
+    use libsuccotash::analyze::action::{self, Action, Outcome};
+    use libsuccotash::analyze::{features::ImgFeatures, group, img::Img, plan::Plan};
+
+    let tree = Tree::new("reflink");
+    tree.image("a.png", Kind::Horizontal)
+        .file("b.png", &std::fs::read(tree.path("a.png")).unwrap())
+        .image("c.png", Kind::HorizontalNoisy);
+    // The same features for all, so that `a.png` is kept, the first by path.
+    let imgs = ["a.png", "b.png", "c.png"]
+        .iter()
+        .map(|name| Img {
+            path: tree.path(name).into(),
+            features: ImgFeatures::decode("00000000000000ff 0 90 0.5 0.2").unwrap(),
+        })
+        .collect();
+    let groups = group::group(imgs, group::MAX_DISTANCE);
+    let plan = Plan::new(&groups);
+
+    let report = action::apply(Action::Reflink, &groups, &plan, &Default::default());
+    let outcomes: Vec<_> = report
+        .outcomes
+        .iter()
+        .map(|(path, outcome)| (common::relative(&tree, &path.to_string_lossy()), outcome))
+        .collect();
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0].0, "b.png");
+    // Temporary directories are rarely on btrfs or XFS.
+    match outcomes[0].1 {
+        Outcome::Done(source) => assert_eq!(source.to_string_lossy(), tree.string("a.png")),
+        Outcome::Skipped(reason) => assert_eq!(reason, "the filesystem doesn't support reflinks"),
+        Outcome::Elsewhere(source) => panic!("'{}' is elsewhere", source.to_string_lossy()),
+        Outcome::Failed(e) => panic!("{}", e),
+    }
+    assert_eq!(outcomes[1].0, "c.png");
+    assert!(
+        matches!(outcomes[1].1, Outcome::Skipped(reason) if reason.starts_with("is not the same"))
+    );
+    assert_eq!(report.failed(), 0);
+
+    assert_eq!(
+        std::fs::read(tree.path("a.png")).unwrap(),
+        std::fs::read(tree.path("b.png")).unwrap()
+    );
+    let names: Vec<_> = std::fs::read_dir(tree.root())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names.len(), 3, "no temporary files are left: {:?}", names);
+}
+
+#[cfg(unix)]
+#[test]
+fn hardlinks_exact_duplicates_on_the_same_filesystem() {
+    use libsuccotash::analyze::action::{self, Action, ActionOptions, Outcome};
+    use libsuccotash::analyze::{features::ImgFeatures, group, img::Img, plan::Plan};
+    use libsuccotash::journal;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let tree = Tree::new("hardlink");
+    tree.image("a.png", Kind::Horizontal)
+        .file("b.png", &std::fs::read(tree.path("a.png")).unwrap())
+        .image("c.png", Kind::HorizontalNoisy);
+    // The kept file and the one linked to it have their own permissions and times.
+    let at = |secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    let set = |name: &str, mode, modified| {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(tree.path(name))
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        std::fs::set_permissions(tree.path(name), PermissionsExt::from_mode(mode)).unwrap();
+    };
+    set("b.png", 0o600, at(1_700_000_000));
+    set("a.png", 0o444, at(1_600_000_000));
+    let mut names = vec![tree.path("a.png"), tree.path("b.png"), tree.path("c.png")];
+    // Another filesystem, if the system has one at hand.
+    let shm = std::path::Path::new("/dev/shm");
+    let elsewhere = shm.join(format!("succotash-hardlink-{}", std::process::id()));
+    let dev = |path: &std::path::Path| std::fs::metadata(path).map(|m| m.dev()).ok();
+    let across = dev(shm).is_some() && dev(shm) != dev(tree.root());
+    if across {
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::fs::copy(tree.path("a.png"), elsewhere.join("d.png")).unwrap();
+        names.push(elsewhere.join("d.png"));
+    }
+    // The same features for all but the copy elsewhere, which is blurrier,
+    // so that `a.png` is kept, the first by path.
+    let imgs = names
+        .iter()
+        .map(|path| Img {
+            path: path.clone().into(),
+            features: ImgFeatures::decode(if path.starts_with(&elsewhere) {
+                "00000000000000ff 0 60 0.5 0.2"
+            } else {
+                "00000000000000ff 0 90 0.5 0.2"
+            })
+            .unwrap(),
+        })
+        .collect();
+    let groups = group::group(imgs, group::MAX_DISTANCE);
+    let journal = tree.path("journal");
+    let options = ActionOptions {
+        journal: Some(journal.clone()),
+        ..Default::default()
+    };
+    let report = action::apply(Action::Hardlink, &groups, &Plan::new(&groups), &options);
+    let outcome = |path: std::path::PathBuf| {
+        let path: async_std::path::PathBuf = path.into();
+        let (_, outcome) = report.outcomes.iter().find(|(p, _)| *p == path).unwrap();
+        outcome.clone()
+    };
+    assert_eq!(
+        outcome(tree.path("b.png")),
+        Outcome::Done(tree.path("a.png").into())
+    );
+    assert!(
+        matches!(outcome(tree.path("c.png")), Outcome::Skipped(reason) if reason.starts_with("is not the same"))
+    );
+    assert_eq!(report.failed(), 0);
+    let inode = |path: std::path::PathBuf| std::fs::metadata(path).unwrap().ino();
+    assert_eq!(inode(tree.path("a.png")), inode(tree.path("b.png")));
+    if across {
+        assert_eq!(
+            outcome(elsewhere.join("d.png")),
+            Outcome::Elsewhere(tree.path("a.png").into())
+        );
+        assert_eq!(report.elsewhere(), 1);
+        std::fs::remove_dir_all(&elsewhere).unwrap();
+    }
+
+    // Undoing makes it a file of its own again, with the same contents,
+    // and the permissions and modification time it had.
+    assert_eq!(journal::run(&journal).code(), 0);
+    assert_ne!(inode(tree.path("a.png")), inode(tree.path("b.png")));
+    assert_eq!(
+        std::fs::read(tree.path("a.png")).unwrap(),
+        std::fs::read(tree.path("b.png")).unwrap()
+    );
+    let metadata = std::fs::metadata(tree.path("b.png")).unwrap();
+    assert_eq!(metadata.mode() & 0o777, 0o600);
+    assert_eq!(metadata.modified().unwrap(), at(1_700_000_000));
+    let metadata = std::fs::metadata(tree.path("a.png")).unwrap();
+    assert_eq!(metadata.mode() & 0o777, 0o444);
+    assert_eq!(metadata.modified().unwrap(), at(1_600_000_000));
+}
+
+#[test]
+fn deletes_duplicates_or_moves_them_to_the_trash() {
+    use libsuccotash::analyze::action::{self, Action, ActionOptions, Outcome};
+    use libsuccotash::analyze::{features::ImgFeatures, group, img::Img, plan::Plan};
+
+    let tree = Tree::new("delete");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal)
+        .image("c.png", Kind::HorizontalNoisy);
+    // Freedesktop trash lives in the data directory, keep it in the tree.
+    std::env::set_var("XDG_DATA_HOME", tree.path("data"));
+    let groups = |names: &[&str]| {
+        let imgs = names
+            .iter()
+            .map(|name| Img {
+                path: tree.path(name).into(),
+                features: ImgFeatures::decode("00000000000000ff 0 90 0.5 0.2").unwrap(),
+            })
+            .collect();
+        group::group(imgs, group::MAX_DISTANCE)
+    };
+
+    let b = groups(&["a.png", "b.png"]);
+    let report = action::apply(Action::Delete, &b, &Plan::new(&b), &Default::default());
+    assert!(matches!(report.outcomes[0].1, Outcome::Done(_)));
+    assert!(report.reclaimed > 0);
+    assert!(!tree.path("b.png").exists());
+
+    let c = groups(&["a.png", "c.png"]);
+    let journal = tree.path("journal");
+    let options = ActionOptions {
+        trash: true,
+        journal: Some(journal.clone()),
+    };
+    let report = action::apply(Action::Delete, &c, &Plan::new(&c), &options);
+    assert!(matches!(report.outcomes[0].1, Outcome::Done(_)));
+    assert_eq!(report.reclaimed, 0);
+    assert!(!tree.path("c.png").exists());
+    assert!(tree.path("data/Trash/files/c.png").exists());
+    assert!(tree.path("a.png").exists());
+
+    // Nothing is deleted once no kept file is there.
+    std::fs::remove_file(tree.path("a.png")).unwrap();
+    tree.image("d.png", Kind::Horizontal);
+    let d = groups(&["a.png", "d.png"]);
+    let report = action::apply(Action::Delete, &d, &Plan::new(&d), &options);
+    assert!(matches!(report.outcomes[0].1, Outcome::Skipped(_)));
+    assert!(tree.path("d.png").exists());
+
+    // Only what was done is recorded, and undone.
+    assert_eq!(libsuccotash::journal::read(&journal).unwrap().len(), 1);
+    assert_eq!(libsuccotash::journal::run(&journal).code(), 0);
+    assert!(tree.path("c.png").exists());
+    assert!(!tree.path("data/Trash/files/c.png").exists());
+}
+
+#[test]
+fn undoes_deletions_recorded_in_the_journal() {
+    use libsuccotash::analyze::action::{self, Action, ActionOptions};
+    use libsuccotash::analyze::{features::ImgFeatures, group, img::Img, plan::Plan};
+    use libsuccotash::journal::{self, Op, Undone};
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree = Tree::new("undo");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.png", Kind::Horizontal)
+        .image("c.png", Kind::HorizontalNoisy);
+    std::fs::set_permissions(tree.path("b.png"), PermissionsExt::from_mode(0o640)).unwrap();
+    let b = std::fs::read(tree.path("b.png")).unwrap();
+    let c = std::fs::read(tree.path("c.png")).unwrap();
+    let imgs = ["a.png", "b.png", "c.png"]
+        .iter()
+        .map(|name| Img {
+            path: tree.path(name).into(),
+            features: ImgFeatures::decode("00000000000000ff 0 90 0.5 0.2").unwrap(),
+        })
+        .collect();
+    let groups = group::group(imgs, group::MAX_DISTANCE);
+    let journal = tree.path("journal");
+    let options = ActionOptions {
+        journal: Some(journal.clone()),
+        ..Default::default()
+    };
+    let report = action::apply(Action::Delete, &groups, &Plan::new(&groups), &options);
+    assert_eq!(report.done(), 2);
+
+    let entries = journal::read(&journal).unwrap();
+    let ops: Vec<_> = entries.iter().map(|entry| entry.op).collect();
+    assert_eq!(ops, [Op::Delete, Op::Delete]);
+    // Copies of the deleted files are kept next to the journal.
+    for entry in &entries {
+        assert!(entry.other.starts_with(journal::stash(&journal)));
+    }
+
+    // A deleted file comes back as it was, even if only similar to the kept one,
+    // unless its copy has changed since.
+    assert_eq!(journal::undo(&entries[0]), Undone::Restored);
+    assert_eq!(std::fs::read(tree.path("b.png")).unwrap(), b);
+    assert_eq!(
+        std::fs::metadata(tree.path("b.png"))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777,
+        0o640
+    );
+    let copy = std::fs::read(&entries[1].other).unwrap();
+    std::fs::write(&entries[1].other, b"changed").unwrap();
+    assert!(matches!(journal::undo(&entries[1]), Undone::Failed(_)));
+    assert!(!tree.path("c.png").exists());
+    assert_ne!(journal::run(&journal).code(), 0);
+    std::fs::write(&entries[1].other, copy).unwrap();
+    assert_eq!(journal::run(&journal).code(), 0);
+    assert_eq!(std::fs::read(tree.path("c.png")).unwrap(), c);
+}
+
+#[test]
+fn filters_images_by_the_name_of_their_hue() {
+    let tree = Tree::new("filter");
+    let colors = [
+        ("red", [200, 0, 0]),
+        ("green", [0, 200, 0]),
+        ("blue", [0, 0, 200]),
+    ];
+    for (name, color) in colors {
+        tree.image(&format!("{}.png", name), Kind::Solid(color))
+            .image(&format!("copies/{}.png", name), Kind::Solid(color));
+    }
+
+    let options = AnalyzeOptions::default();
+    let paths = colors
+        .iter()
+        .map(|(name, _)| tree.path(&format!("{}.png", name)).into())
+        .collect();
+    let imgs = async_std::task::block_on(analyze::find_features(paths, &options)).unwrap();
+    assert_eq!(imgs.len(), colors.len());
+    for img in imgs {
+        let name = img.path.file_stem().unwrap().to_string_lossy().into_owned();
+        assert_eq!(img.features.hue.name().name(), name);
+    }
+
+    let filtered = |filter: &str| {
+        let options = AnalyzeOptions {
+            filters: Filters::parse([filter]).unwrap(),
+            ..Default::default()
+        };
+        common::groups(
+            &tree,
+            &common::analyze(&tree, common::walk(&tree, &[""]), options),
+        )
+    };
+    assert_eq!(
+        filtered("hue=blue"),
+        vec![vec!["blue.png", "copies/blue.png"]]
+    );
+    let groups = filtered("hue=red,green");
+    assert!(!groups.is_empty());
+    assert!(groups.iter().flatten().all(|path| !path.contains("blue")));
+    assert!(filtered("hue=purple").is_empty());
+}
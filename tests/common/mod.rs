@@ -0,0 +1,199 @@
+//! Helpers of the integration tests.
+//!
+//! A [`Tree`] is a temporary directory that tests fill with generated
+//! images and other files, run the analysis on and look at the results of.
+
+#![allow(dead_code)]
+
+use libsuccotash::analyze::{self, output, walker::Source, AnalyzeOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Images are this many pixels wide and high.
+pub const SIZE: u32 = 64;
+
+/// How an image looks, images of the same kind are similar.
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    /// Dark on the left, light on the right.
+    Horizontal,
+    /// Dark at the top, light at the bottom.
+    Vertical,
+    /// Like [`Kind::Horizontal`], with fine noise that makes it sharper.
+    HorizontalNoisy,
+    /// A single color all over.
+    Solid([u8; 3]),
+}
+
+/// Pixels of an image of a kind.
+pub fn image(kind: Kind) -> image::RgbImage {
+    image::RgbImage::from_fn(SIZE, SIZE, |x, y| {
+        let level = match kind {
+            Kind::Solid(color) => return image::Rgb(color),
+            Kind::Horizontal => x * 255 / SIZE,
+            Kind::Vertical => y * 255 / SIZE,
+            Kind::HorizontalNoisy => {
+                let noise = if (x + y) % 2 == 0 { 8 } else { 0 };
+                (x * 247 / SIZE) + noise
+            }
+        } as u8;
+        image::Rgb([level, level / 2, 255 - level])
+    })
+}
+
+/// A temporary directory, removed when dropped.
+pub struct Tree {
+    root: PathBuf,
+}
+
+impl Tree {
+    /// Create an empty directory, unique to the test.
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "succotash-test-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    /// The directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of something in the directory.
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// Path of something in the directory, as a string.
+    pub fn string(&self, relative: &str) -> String {
+        self.path(relative).to_string_lossy().into_owned()
+    }
+
+    /// Write a file, creating directories it is in.
+    pub fn file(&self, relative: &str, contents: &[u8]) -> &Self {
+        let path = self.path(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+        self
+    }
+
+    /// Write an image, in the format its extension tells.
+    pub fn image(&self, relative: &str, kind: Kind) -> &Self {
+        let path = self.path(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image(kind).save(path).unwrap();
+        self
+    }
+
+    /// Write a JPEG photo with EXIF metadata saying it was taken at `taken`,
+    /// e.g. `2021:06:01 12:00:00.25`.
+    pub fn photo(&self, relative: &str, kind: Kind, taken: &str) -> &Self {
+        use exif::{experimental::Writer, Field, In, Tag, Value};
+
+        let (date_time, subsec) = taken.split_once('.').unwrap_or((taken, "0"));
+        let field = |tag, value: &str| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        };
+        let fields = [
+            field(Tag::Make, "Canon"),
+            field(Tag::Model, "EOS R6"),
+            field(Tag::DateTimeOriginal, date_time),
+            field(Tag::SubSecTimeOriginal, subsec),
+        ];
+        let mut writer = Writer::new();
+        fields.iter().for_each(|field| writer.push_field(field));
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let app1 = [b"Exif\0\0".as_ref(), &tiff.into_inner()].concat();
+
+        let mut jpeg = Vec::new();
+        image::DynamicImage::ImageRgb8(image(kind))
+            .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90))
+            .unwrap();
+        // EXIF goes right after the start of the image, as an APP1 segment.
+        let length = (app1.len() + 2) as u16;
+        let mut contents = jpeg[..2].to_vec();
+        contents.extend_from_slice(&[0xff, 0xe1]);
+        contents.extend_from_slice(&length.to_be_bytes());
+        contents.extend_from_slice(&app1);
+        contents.extend_from_slice(&jpeg[2..]);
+        self.file(relative, &contents)
+    }
+
+    /// Make a symbolic link at `relative` pointing to `target`.
+    #[cfg(unix)]
+    pub fn symlink(&self, target: &str, relative: &str) -> &Self {
+        std::os::unix::fs::symlink(self.path(target), self.path(relative)).unwrap();
+        self
+    }
+}
+
+impl Drop for Tree {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Run the analysis and read its JSON report.
+///
+/// # Arguments
+///
+/// * `tree` - where to write the report, next to (not in) the analyzed files.
+/// * `source` - what to analyze.
+/// * `options` - how, the format and the output are set here.
+pub fn analyze(tree: &Tree, source: Source, mut options: AnalyzeOptions) -> serde_json::Value {
+    let report = tree.root().with_extension("json");
+    options.format = output::Format::Json;
+    options.output = Some(report.clone().into());
+    async_std::task::block_on(analyze::run(source, options));
+
+    let json = std::fs::read_to_string(&report).expect("the report was written");
+    std::fs::remove_file(&report).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+/// Walk directories of a tree.
+pub fn walk(tree: &Tree, dirs: &[&str]) -> Source {
+    Source::Walk(dirs.iter().map(|dir| tree.string(dir)).collect())
+}
+
+/// Paths of images in every group of a report, relative to the tree, sorted.
+pub fn groups(tree: &Tree, report: &serde_json::Value) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = report["groups"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| {
+            let mut paths: Vec<String> = group["images"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|img| relative(tree, img["path"].as_str().unwrap()))
+                .collect();
+            paths.sort();
+            paths
+        })
+        .collect();
+    groups.sort();
+    groups
+}
+
+/// A path relative to the tree, with `/` separators.
+pub fn relative(tree: &Tree, path: &str) -> String {
+    Path::new(path)
+        .strip_prefix(tree.root())
+        .unwrap()
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,165 @@
+//! Keeping a library indexed in a database.
+
+mod common;
+
+use async_std::path::PathBuf;
+use async_std::task::block_on;
+use common::{Kind, Tree};
+use libsuccotash::analyze::index::store::Snapshot;
+use libsuccotash::analyze::output::Format;
+use libsuccotash::analyze::walker::glob::Pattern;
+use libsuccotash::analyze::AnalyzeOptions;
+use libsuccotash::db::{self, Db};
+use libsuccotash::status::Status;
+
+#[test]
+fn keeps_files_groups_and_features_between_runs() {
+    let tree = Tree::new("db");
+    tree.image("library/a.png", Kind::Horizontal)
+        .image("library/b.png", Kind::Horizontal)
+        .image("library/c.png", Kind::Vertical);
+    let dir: PathBuf = tree.path("db").into();
+    let add = || {
+        let dirs = vec![tree.string("library")];
+        block_on(db::try_add(&dir, dirs, AnalyzeOptions::default())).unwrap()
+    };
+    let dedupe = || {
+        let options = AnalyzeOptions {
+            output: Some(tree.path("groups.txt").into()),
+            ..Default::default()
+        };
+        db::try_dedupe(&dir, &[], &options).unwrap()
+    };
+    let groups = || -> Vec<(String, Option<usize>)> {
+        let db = Db::open(&dir).unwrap();
+        db.files
+            .iter()
+            .map(|(path, record)| {
+                (
+                    common::relative(&tree, &path.to_string_lossy()),
+                    record.group,
+                )
+            })
+            .collect()
+    };
+
+    assert_eq!(db::init(&dir), Status::Clean);
+    assert_eq!(add(), 3);
+    // Files that haven't changed aren't analyzed again.
+    assert_eq!(add(), 0);
+    assert_eq!(dedupe(), 1);
+    assert_eq!(
+        groups(),
+        vec![
+            ("library/a.png".to_owned(), Some(1)),
+            ("library/b.png".to_owned(), Some(1)),
+            ("library/c.png".to_owned(), None),
+        ]
+    );
+
+    tree.image("library/c.png", Kind::HorizontalNoisy);
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+    let file = std::fs::File::options()
+        .write(true)
+        .open(tree.path("library/c.png"))
+        .unwrap();
+    file.set_modified(modified).unwrap();
+    assert_eq!(add(), 1);
+    assert_eq!(dedupe(), 1);
+    assert!(groups().iter().all(|(_, group)| *group == Some(1)));
+
+    let patterns = [Pattern::new("**/library/b.png").unwrap()];
+    assert_eq!(db::try_remove(&dir, &patterns).unwrap(), 1);
+    let files: Vec<String> = groups().into_iter().map(|(path, _)| path).collect();
+    assert_eq!(files, vec!["library/a.png", "library/c.png"]);
+    let snapshot = Snapshot::open(Db::open(&dir).unwrap().index()).unwrap();
+    assert_eq!(snapshot.len(), 2);
+
+    let found = tree.path("found.txt");
+    let status = block_on(db::search(
+        &dir,
+        tree.path("library/a.png").into(),
+        &[],
+        AnalyzeOptions::default(),
+        Some(found.clone().into()),
+    ));
+    assert_eq!(status, Status::Found);
+    let found = std::fs::read_to_string(found).unwrap();
+    assert!(found.ends_with("library/c.png\n"), "{}", found);
+}
+
+#[test]
+fn searches_and_dedupes_files_with_tags() {
+    let tree = Tree::new("db-tags");
+    tree.image("library/rome/a.png", Kind::Horizontal)
+        .image("library/rome/b.png", Kind::Horizontal)
+        .image("library/home/c.png", Kind::Horizontal);
+    let dir: PathBuf = tree.path("db").into();
+    assert_eq!(db::init(&dir), Status::Clean);
+    let dirs = vec![tree.string("library")];
+    block_on(db::try_add(&dir, dirs, AnalyzeOptions::default())).unwrap();
+
+    let tags = vec!["vacation2022".to_owned(), "italy".to_owned()];
+    let rome = Pattern::new("**/rome/*").unwrap();
+    assert_eq!(db::try_tag(&dir, &rome, &tags, false).unwrap(), 2);
+    let untag = Pattern::new("b.png").unwrap();
+    assert_eq!(db::try_tag(&dir, &untag, &tags[1..], true).unwrap(), 1);
+    let bad = ["cats,dogs".to_owned()];
+    assert!(db::try_tag(&dir, &rome, &bad, false).is_err());
+
+    let found = |tags: &[String]| -> Vec<String> {
+        let image = tree.path("library/rome/a.png").into();
+        let found = block_on(db::try_search(
+            &dir,
+            image,
+            tags,
+            &AnalyzeOptions::default(),
+        ));
+        let found = found.unwrap().into_iter();
+        found
+            .map(|found| common::relative(&tree, &found.path.to_string_lossy()))
+            .collect()
+    };
+    assert_eq!(found(&[]).len(), 2);
+    assert_eq!(found(&tags[..1]), vec!["library/rome/b.png"]);
+    assert!(found(&tags).is_empty());
+
+    let output = tree.path("groups.json");
+    let options = AnalyzeOptions {
+        format: Format::Json,
+        output: Some(output.clone().into()),
+        ..Default::default()
+    };
+    assert_eq!(db::try_dedupe(&dir, &tags[..1], &options).unwrap(), 1);
+    let db = Db::open(&dir).unwrap();
+    let groups: Vec<Option<usize>> = db.files.values().map(|record| record.group).collect();
+    // Ordered by path, the file at home has no tags.
+    assert_eq!(groups, vec![None, Some(1), Some(1)]);
+
+    let document: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(output).unwrap()).unwrap();
+    let images = document["groups"][0]["images"].as_array().unwrap();
+    let tags_of = |name: &str| {
+        let image = images.iter().find(|image| {
+            let path = image["path"].as_str().unwrap();
+            path.ends_with(name)
+        });
+        image.unwrap()["tags"].clone()
+    };
+    assert_eq!(
+        tags_of("a.png"),
+        serde_json::json!(["italy", "vacation2022"])
+    );
+    assert_eq!(tags_of("b.png"), serde_json::json!(["vacation2022"]));
+}
+
+#[test]
+fn refuses_to_init_over_a_database_or_to_add_to_none() {
+    let tree = Tree::new("db-init");
+    let dir: PathBuf = tree.path("db").into();
+    assert_eq!(db::init(&dir), Status::Clean);
+    assert_eq!(db::init(&dir), Status::Failed);
+    let elsewhere: PathBuf = tree.path("elsewhere").into();
+    let added = block_on(db::try_add(&elsewhere, vec![], AnalyzeOptions::default()));
+    assert!(added.is_err());
+}
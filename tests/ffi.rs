@@ -0,0 +1,69 @@
+//! The C interface, with the `ffi` feature.
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use common::Kind;
+use libsuccotash::analyze::features::{FindOptions, ImgFeatures};
+use libsuccotash::ffi;
+
+#[test]
+fn hashes_like_the_library() {
+    let img = common::image(Kind::Horizontal);
+    let expected = ImgFeatures::of_image(
+        &image::DynamicImage::ImageRgb8(img.clone()),
+        FindOptions::default(),
+    )
+    .lshash
+    .bits();
+
+    let mut hash = 0;
+    let result =
+        unsafe { ffi::succotash_hash_rgb8(img.as_ptr(), img.width(), img.height(), &mut hash) };
+    assert_eq!(result, ffi::SUCCOTASH_OK);
+    assert_eq!(hash, expected);
+
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgb8(img)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let mut from_file = 0;
+    let result =
+        unsafe { ffi::succotash_hash_file_contents(png.as_ptr(), png.len(), &mut from_file) };
+    assert_eq!(result, ffi::SUCCOTASH_OK);
+    assert_eq!(ffi::succotash_distance(from_file, hash), 0);
+}
+
+#[test]
+fn refuses_bad_arguments() {
+    let mut hash = 0;
+    let result = unsafe { ffi::succotash_hash_rgb8(std::ptr::null(), 4, 4, &mut hash) };
+    assert_eq!(result, ffi::SUCCOTASH_INVALID_ARGUMENT);
+    let result =
+        unsafe { ffi::succotash_hash_file_contents(b"not an image".as_ptr(), 12, &mut hash) };
+    assert_eq!(result, ffi::SUCCOTASH_FAILED);
+}
+
+#[test]
+fn header_is_generated_from_the_interface() {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(root.join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(root.join("src/ffi.rs"))
+        .generate()
+        .unwrap()
+        .write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let path = root.join("include/succotash.h");
+    if std::env::var_os("SUCCOTASH_UPDATE_HEADER").is_some() {
+        std::fs::write(&path, &generated).unwrap();
+    }
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        generated,
+        "the header is out of date, regenerate it with SUCCOTASH_UPDATE_HEADER=1"
+    );
+}
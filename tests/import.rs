@@ -0,0 +1,71 @@
+//! Importing new images into libraries.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::AnalyzeOptions;
+use libsuccotash::import::{self, ImportOptions};
+
+#[test]
+fn copies_only_images_new_to_the_library() {
+    let tree = Tree::new("import");
+    tree.image("library/old.png", Kind::Horizontal)
+        .image("sd/DCIM/copy.png", Kind::HorizontalNoisy)
+        .image("sd/DCIM/new.png", Kind::Vertical)
+        .photo(
+            "sd/DCIM/IMG_0001.jpg",
+            Kind::Vertical,
+            "2023:07:14 08:30:00",
+        );
+    let summary = tree.path("skipped.txt");
+    let journal = tree.path("journal");
+    let import = ImportOptions {
+        rename: true,
+        summary: Some(summary.clone().into()),
+        journal: Some(journal.clone()),
+        ..Default::default()
+    };
+    let status = async_std::task::block_on(import::run(
+        &tree.string("sd"),
+        &tree.string("library"),
+        import,
+        AnalyzeOptions::default(),
+    ));
+    assert_eq!(status.code(), 0);
+
+    // Without the time it was taken, a file keeps its path.
+    assert!(tree.path("library/DCIM/new.png").is_file());
+    assert!(tree
+        .path("library/2023/07/2023-07-14 08.30.00.jpg")
+        .is_file());
+    assert!(!tree.path("library/DCIM/copy.png").exists());
+    assert!(tree.path("sd/DCIM/copy.png").is_file());
+    let summary = std::fs::read_to_string(summary).unwrap();
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(lines.len(), 1, "{}", summary);
+    assert!(lines[0].starts_with(&tree.string("sd/DCIM/copy.png")));
+    assert!(lines[0].ends_with("library/old.png"));
+
+    assert_eq!(libsuccotash::journal::run(&journal).code(), 0);
+    assert!(!tree.path("library/DCIM/new.png").exists());
+    assert!(tree.path("library/old.png").is_file());
+    assert!(tree.path("sd/DCIM/new.png").is_file());
+}
+
+#[test]
+fn plans_around_files_in_the_library() {
+    let tree = Tree::new("import-plan");
+    tree.image("library/a.png", Kind::Vertical)
+        .image("sd/a.png", Kind::Horizontal);
+    let plan = async_std::task::block_on(import::plan(
+        &tree.string("sd"),
+        &tree.string("library"),
+        false,
+        &AnalyzeOptions::default(),
+    ))
+    .unwrap();
+    assert!(plan.skipped.is_empty());
+    let to: std::path::PathBuf = plan.copies[0].1.clone().into();
+    assert_eq!(to, tree.path("library/a (2).png"));
+    assert!(!tree.path("library/a (2).png").exists());
+}
@@ -0,0 +1,269 @@
+//! Building an index of a library and querying it.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::index::{self, Search};
+use libsuccotash::analyze::AnalyzeOptions;
+
+#[test]
+fn finds_similar_images_in_a_built_index() {
+    let tree = Tree::new("index");
+    tree.image("library/a.png", Kind::Horizontal)
+        .image("library/b.png", Kind::Vertical)
+        .image("query.jpg", Kind::Horizontal);
+    let index_path = tree.root().with_extension("index");
+
+    let options = AnalyzeOptions {
+        index: Some(index_path.clone().into()),
+        resume: true,
+        ..Default::default()
+    };
+    let dirs = vec![tree.string("library")];
+    let count = async_std::task::block_on(index::try_build(dirs, &options)).unwrap();
+    assert_eq!(count, 2);
+
+    for search in [
+        Search::Brute,
+        Search::VpTree,
+        Search::Bands(8),
+        Search::Hnsw,
+    ] {
+        let options = AnalyzeOptions {
+            search,
+            ..Default::default()
+        };
+        let found = async_std::task::block_on(index::try_query(
+            tree.path("query.jpg").into(),
+            index_path.to_str().unwrap(),
+            &options,
+        ))
+        .unwrap();
+        let found: Vec<String> = found
+            .iter()
+            .map(|found| common::relative(&tree, &found.path.to_string_lossy()))
+            .collect();
+        assert_eq!(found, vec!["library/a.png"], "with {:?}", search);
+    }
+    std::fs::remove_file(&index_path).unwrap();
+}
+
+#[test]
+fn queries_a_directory_without_an_index() {
+    let tree = Tree::new("index-directory");
+    tree.image("library/a.png", Kind::Horizontal)
+        .image("library/b.png", Kind::Vertical)
+        .image("query.jpg", Kind::Horizontal);
+
+    let found = async_std::task::block_on(index::try_query(
+        tree.path("query.jpg").into(),
+        &tree.string("library"),
+        &AnalyzeOptions::default(),
+    ))
+    .unwrap();
+    let found: Vec<String> = found
+        .iter()
+        .map(|found| common::relative(&tree, &found.path.to_string_lossy()))
+        .collect();
+    assert_eq!(found, vec!["library/a.png"]);
+}
+
+#[cfg(feature = "urls")]
+#[test]
+fn queries_images_by_their_urls() {
+    use std::io::{BufRead, BufReader, Write};
+
+    let tree = Tree::new("index-urls");
+    tree.image("library/a.png", Kind::Horizontal)
+        .image("query.jpg", Kind::Horizontal);
+    let image = std::fs::read(tree.path("query.jpg")).unwrap();
+
+    // Serves the image at `/query.jpg` and nothing else.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = String::new();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            reader.read_line(&mut request).unwrap();
+            while reader.read_line(&mut String::new()).unwrap() > 2 {}
+            let (status, body): (_, &[u8]) = if request.starts_with("GET /query.jpg ") {
+                ("200 OK", &image)
+            } else {
+                ("404 Not Found", b"")
+            };
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n",
+                status,
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+
+    let query = |path: &str| {
+        async_std::task::block_on(index::try_query(
+            format!("{}{}", url, path).into(),
+            &tree.string("library"),
+            &AnalyzeOptions::default(),
+        ))
+    };
+    let found = query("/query.jpg").unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(query("/missing.jpg").is_err());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn queries_without_an_executor() {
+    use libsuccotash::analyze::blocking;
+    use libsuccotash::status::Status;
+
+    let tree = Tree::new("index-blocking");
+    tree.image("library/a.png", Kind::Horizontal)
+        .image("query.jpg", Kind::Horizontal);
+    let index_path = tree.root().with_extension("index");
+
+    let options = AnalyzeOptions {
+        index: Some(index_path.clone().into()),
+        resume: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        blocking::build(vec![tree.string("library")], options),
+        Status::Clean
+    );
+    let found = blocking::query(
+        tree.path("query.jpg").into(),
+        index_path.to_str().unwrap(),
+        &AnalyzeOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(found.len(), 1);
+    std::fs::remove_file(&index_path).unwrap();
+}
+
+#[test]
+fn queries_vectors_by_angle() {
+    use libsuccotash::analyze::features::set::{self, Feature, FeatureSet, Preprocessed, Value};
+    use libsuccotash::analyze::features::{schema::Schema, FindOptions, ImgFeatures};
+    use libsuccotash::analyze::index::{query::Query, store};
+
+    /// Mean color of the image, as a direction.
+    struct Tint;
+
+    impl Feature for Tint {
+        fn name(&self) -> &'static str {
+            "tint"
+        }
+
+        fn compute(&self, img: &Preprocessed) -> Value {
+            let mut sum = [0f32; 3];
+            for pixel in img.rgb.pixels() {
+                for (sum, &channel) in sum.iter_mut().zip(&pixel.0) {
+                    *sum += f32::from(channel);
+                }
+            }
+            Value::Vector(sum.to_vec().into())
+        }
+
+        fn distance(&self, a: &Value, b: &Value) -> f64 {
+            set::vector_distance(a, b)
+        }
+
+        fn max_distance(&self) -> f64 {
+            0.1
+        }
+    }
+
+    set::register(std::sync::Arc::new(Tint)).unwrap();
+    let tree = Tree::new("index-vectors");
+    let index_path = tree.root().with_extension("index");
+    let tinted = |tint: &str| {
+        let encoded = format!("0000000000000000 0 0 0.5 0.2 tint={}", tint);
+        vec![ImgFeatures::decode(&encoded).unwrap()]
+    };
+    let schema = Schema::of(FindOptions::default());
+    let mut index = store::Store::open(&index_path).unwrap();
+    index
+        .put("/red.png".as_ref(), &schema, tinted("[1,0,0]"))
+        .unwrap();
+    index
+        .put("/dark-red.png".as_ref(), &schema, tinted("[0.2,0.01,0]"))
+        .unwrap();
+    index
+        .put("/green.png".as_ref(), &schema, tinted("[0,1,0]"))
+        .unwrap();
+    index
+        .put("/blank.png".as_ref(), &schema, tinted("[]"))
+        .unwrap();
+    drop(index);
+
+    let snapshot = store::Snapshot::open(&index_path).unwrap();
+    let by_tint = FeatureSet::select(&["tint"]).unwrap();
+    for search in [Search::Brute, Search::VpTree, Search::Hnsw] {
+        let query = Query::new(&snapshot, search);
+        let found: Vec<String> = query
+            .similar(&tinted("[0.9,0.05,0]")[0], &by_tint)
+            .iter()
+            .map(|found| found.path.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            found,
+            vec!["/dark-red.png", "/red.png"],
+            "with {:?}",
+            search
+        );
+    }
+    std::fs::remove_file(&index_path).unwrap();
+}
+
+#[test]
+fn keeps_writes_of_stores_opened_during_compaction() {
+    use libsuccotash::analyze::features::{schema::Schema, FindOptions, ImgFeatures};
+    use libsuccotash::analyze::index::store;
+
+    let tree = Tree::new("index-compact");
+    let index_path = tree.path("index");
+    drop(store::Store::open(&index_path).unwrap());
+
+    // One writer keeps compacting, the others only put files of their own,
+    // so that some of them open the file while it is being replaced.
+    let write = |writer: usize| {
+        let schema = Schema::of(FindOptions::default());
+        let features = vec![ImgFeatures::decode("0000000000000000 0 0 0.5 0.2").unwrap()];
+        let mut written = Vec::new();
+        for i in 0..500 {
+            let mut store = match store::Store::open(&index_path) {
+                Ok(store) => store,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                Err(e) => panic!("{}", e),
+            };
+            let path = format!("/{}-{}.png", writer, i);
+            store.put(path.as_ref(), &schema, features.clone()).unwrap();
+            if writer == 0 {
+                store.compact().unwrap();
+            }
+            written.push(path);
+        }
+        written
+    };
+    let written: Vec<String> = std::thread::scope(|scope| {
+        let writers: Vec<_> = (0..4)
+            .map(|writer| scope.spawn(move || write(writer)))
+            .collect();
+        writers
+            .into_iter()
+            .flat_map(|writer| writer.join().unwrap())
+            .collect()
+    });
+    assert!(!written.is_empty());
+
+    let snapshot = store::Snapshot::open(&index_path).unwrap();
+    for path in &written {
+        assert!(snapshot.get(path.as_ref()).is_some(), "'{}' is lost", path);
+    }
+    assert_eq!(snapshot.len(), written.len());
+}
@@ -0,0 +1,45 @@
+//! Undoing what was recorded in journals.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::action::{Action, ActionOptions};
+use libsuccotash::analyze::walker::Source;
+use libsuccotash::analyze::AnalyzeOptions;
+use libsuccotash::journal;
+
+#[test]
+fn undoes_trashing_of_relative_paths_from_another_directory() {
+    let tree = Tree::new("journal-relative");
+    tree.image("photos/a.png", Kind::Horizontal)
+        .file(
+            "photos/b.png",
+            &std::fs::read(tree.path("photos/a.png")).unwrap(),
+        )
+        .file("elsewhere/.keep", b"");
+    // Freedesktop trash lives in the data directory, keep it in the tree.
+    std::env::set_var("XDG_DATA_HOME", tree.path("data"));
+    let journal = tree.path("journal");
+    let options = AnalyzeOptions {
+        action: Some(Action::Delete),
+        action_options: ActionOptions {
+            trash: true,
+            journal: Some(journal.clone()),
+        },
+        ..Default::default()
+    };
+    std::env::set_current_dir(tree.root()).unwrap();
+    common::analyze(&tree, Source::Walk(vec!["photos".into()]), options);
+    assert!(!tree.path("photos/b.png").exists());
+    assert!(tree.path("data/Trash/files/b.png").exists());
+
+    let entries = journal::read(&journal).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path, tree.path("photos/b.png"));
+    assert_eq!(entries[0].other, tree.path("photos/a.png"));
+
+    std::env::set_current_dir(tree.path("elsewhere")).unwrap();
+    assert_eq!(journal::run(&journal).code(), 0);
+    assert!(tree.path("photos/b.png").is_file());
+    assert!(!tree.path("data/Trash/files/b.png").exists());
+}
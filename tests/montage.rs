@@ -0,0 +1,36 @@
+//! Contact sheets of libraries.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::AnalyzeOptions;
+use libsuccotash::montage::{self, MontageOptions, GAP};
+
+#[test]
+fn renders_a_sheet_per_group() {
+    let tree = Tree::new("montage");
+    tree.image("in/a.png", Kind::Horizontal)
+        .image("in/b.png", Kind::HorizontalNoisy)
+        .image("in/c.png", Kind::Vertical);
+
+    let montage = MontageOptions {
+        output_dir: tree.path("sheets").into(),
+        tile_size: 32,
+        ..MontageOptions::default()
+    };
+    let status = async_std::task::block_on(montage::run(
+        vec![tree.string("in")],
+        montage,
+        AnalyzeOptions::default(),
+    ));
+    assert_eq!(status.code(), 0);
+
+    let sheets: Vec<_> = std::fs::read_dir(tree.path("sheets"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(sheets, vec!["group-0001.jpg"]);
+    let sheet = image::open(tree.path("sheets/group-0001.jpg")).unwrap();
+    let dimensions = image::GenericImageView::dimensions(&sheet);
+    assert_eq!(dimensions, (2 * (32 + GAP) + GAP, 32 + 2 * GAP));
+}
@@ -0,0 +1,87 @@
+//! Sorting libraries into folders.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::AnalyzeOptions;
+use libsuccotash::organize::{self, By, OrganizeOptions};
+
+#[test]
+fn moves_photos_into_folders_by_date() {
+    let tree = Tree::new("organize");
+    tree.photo("in/a.jpg", Kind::Horizontal, "2021:06:01 12:00:00")
+        .photo("in/nested/a.jpg", Kind::Vertical, "2021:06:02 12:00:00")
+        .photo("in/b.jpg", Kind::Vertical, "2023:07:14 08:30:00")
+        .image("in/c.png", Kind::Horizontal);
+
+    let organize = OrganizeOptions {
+        by: By::Date,
+        ..OrganizeOptions::default()
+    };
+    let status = async_std::task::block_on(organize::run(
+        vec![tree.string("in")],
+        tree.path("out").into(),
+        organize,
+        AnalyzeOptions::default(),
+    ));
+    assert_eq!(status.code(), 0);
+
+    assert!(tree.path("out/2021/06/a.jpg").is_file());
+    assert!(tree.path("out/2021/06/a (2).jpg").is_file());
+    assert!(tree.path("out/2023/07/b.jpg").is_file());
+    assert!(tree.path("out/unsorted/c.png").is_file());
+    assert!(!tree.path("in/a.jpg").exists());
+    assert!(!tree.path("in/c.png").exists());
+}
+
+#[test]
+fn plans_without_touching_files() {
+    let tree = Tree::new("organize-plan");
+    tree.photo("in/a.jpg", Kind::Horizontal, "2021:06:01 12:00:00");
+
+    let moves = async_std::task::block_on(organize::plan(
+        vec![tree.string("in")],
+        tree.path("out").as_path().into(),
+        By::Camera,
+        &AnalyzeOptions::default(),
+    ))
+    .unwrap();
+    let to: std::path::PathBuf = moves[0].1.clone().into();
+    assert_eq!(to, tree.path("out/Canon EOS R6/a.jpg"));
+    assert!(tree.path("in/a.jpg").is_file());
+}
+
+#[test]
+fn undoes_moves_and_links_recorded_in_the_journal() {
+    let tree = Tree::new("organize-undo");
+    tree.photo("in/a.jpg", Kind::Horizontal, "2021:06:01 12:00:00")
+        .photo("in/b.jpg", Kind::Vertical, "2023:07:14 08:30:00");
+    let journal = tree.path("journal");
+    let run = |action| {
+        let organize = OrganizeOptions {
+            action,
+            journal: Some(journal.clone()),
+            ..OrganizeOptions::default()
+        };
+        async_std::task::block_on(organize::run(
+            vec![tree.string("in")],
+            tree.path("out").into(),
+            organize,
+            AnalyzeOptions::default(),
+        ))
+    };
+
+    assert_eq!(run(organize::Action::Symlink).code(), 0);
+    assert!(tree.path("out/2021/06/a.jpg").is_symlink());
+    assert_eq!(libsuccotash::journal::run(&journal).code(), 0);
+    assert!(!tree.path("out/2021/06/a.jpg").exists());
+    assert!(tree.path("in/a.jpg").is_file());
+
+    std::fs::remove_file(&journal).unwrap();
+    assert_eq!(run(organize::Action::Move).code(), 0);
+    assert!(!tree.path("in/b.jpg").exists());
+    assert_eq!(libsuccotash::journal::run(&journal).code(), 0);
+    assert!(tree.path("in/a.jpg").is_file());
+    assert!(tree.path("in/b.jpg").is_file());
+    assert!(!tree.path("out/2023/07/b.jpg").exists());
+}
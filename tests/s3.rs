@@ -0,0 +1,119 @@
+//! Walking a bucket of S3-compatible storage, served by a fake one.
+
+#![cfg(feature = "s3")]
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::AnalyzeOptions;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+/// Serve objects like S3 does, listing them two at a time, and fail
+/// requests that aren't signed.
+fn serve(objects: HashMap<String, Vec<u8>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let mut keys: Vec<_> = objects.keys().cloned().collect();
+        keys.sort();
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            let mut signed = false;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                signed |= header
+                    .to_lowercase()
+                    .starts_with("authorization: aws4-hmac-sha256 credential=key/");
+            }
+            let target = request.split(' ').nth(1).unwrap();
+            let (status, body) = match target.split_once('?') {
+                _ if !signed => (
+                    "403 Forbidden",
+                    b"<Error><Code>AccessDenied</Code></Error>".to_vec(),
+                ),
+                Some(("/bucket", query)) => {
+                    let next = query.contains("continuation-token=page%2F2");
+                    let start = if next { 2 } else { 0 };
+                    let page = &keys[start..keys.len().min(start + 2)];
+                    let mut xml = String::from("<ListBucketResult>");
+                    for key in page {
+                        xml += &format!(
+                            "<Contents><Key>{}</Key><Size>{}</Size></Contents>",
+                            key.replace('&', "&amp;"),
+                            objects[key].len()
+                        );
+                    }
+                    if start == 0 && keys.len() > 2 {
+                        xml += "<IsTruncated>true</IsTruncated>";
+                        xml += "<NextContinuationToken>page/2</NextContinuationToken>";
+                    }
+                    ("200 OK", (xml + "</ListBucketResult>").into_bytes())
+                }
+                _ => match target
+                    .strip_prefix("/bucket/")
+                    .map(|key| key.replace("%20", " "))
+                    .and_then(|key| objects.get(&key))
+                {
+                    Some(data) => ("200 OK", data.clone()),
+                    None => (
+                        "404 Not Found",
+                        b"<Error><Code>NoSuchKey</Code></Error>".to_vec(),
+                    ),
+                },
+            };
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+    endpoint
+}
+
+#[test]
+fn groups_similar_objects() {
+    let tree = Tree::new("s3");
+    tree.image("a.png", Kind::Horizontal)
+        .image("a.jpg", Kind::Horizontal)
+        .image("b.png", Kind::Vertical);
+    let read = |name| std::fs::read(tree.path(name)).unwrap();
+    let objects = HashMap::from([
+        ("photos/a.png".to_owned(), read("a.png")),
+        ("photos/summer copies/a.jpg".to_owned(), read("a.jpg")),
+        ("photos/b.png".to_owned(), read("b.png")),
+        ("photos/empty/".to_owned(), Vec::new()),
+    ]);
+    std::env::set_var("AWS_ENDPOINT_URL", serve(objects));
+    std::env::set_var("AWS_ACCESS_KEY_ID", "key");
+    std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+
+    let source = libsuccotash::analyze::walker::Source::Walk(vec!["s3://bucket/photos".into()]);
+    let report = common::analyze(&tree, source, AnalyzeOptions::default());
+    let paths: Vec<_> = report["groups"][0]["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|img| img["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        paths.len(),
+        2,
+        "one group of two images in {}",
+        report["groups"]
+    );
+    assert!(paths.contains(&"s3://bucket/photos/a.png"));
+    assert!(paths.contains(&"s3://bucket/photos/summer copies/a.jpg"));
+    assert_eq!(report["groups"].as_array().unwrap().len(), 1);
+}
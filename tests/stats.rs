@@ -0,0 +1,43 @@
+//! Stats of whole libraries.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::AnalyzeOptions;
+use libsuccotash::stats;
+
+#[test]
+fn counts_formats_sizes_and_features() {
+    let tree = Tree::new("stats");
+    tree.image("a.png", Kind::Horizontal)
+        .image("b.jpg", Kind::Vertical)
+        .image("c.jpg", Kind::Vertical)
+        .file("d.heic", b"not really");
+
+    let dirs = vec![tree.string("")];
+    let stats =
+        async_std::task::block_on(stats::collect(dirs, &AnalyzeOptions::default())).unwrap();
+    assert_eq!(stats.formats["png"], 1);
+    assert_eq!(stats.formats["jpeg"], 2);
+    assert_eq!(stats.formats["heic"], 1);
+    assert_eq!(stats.sizes[0], 4);
+    assert_eq!(stats.sides[0], 3);
+    assert_eq!(stats.unknown_sides, 1);
+    assert_eq!(stats.hues.iter().sum::<usize>(), 3);
+    assert_eq!(stats.bits.iter().sum::<usize>(), 3);
+    let bytes: u64 = ["a.png", "b.jpg", "c.jpg", "d.heic"]
+        .iter()
+        .map(|name| std::fs::metadata(tree.path(name)).unwrap().len())
+        .sum();
+    assert_eq!(stats.bytes, bytes);
+
+    let lines = stats.lines();
+    let sizes = lines.iter().position(|line| line == "File size:").unwrap();
+    assert!(
+        lines[sizes + 1].starts_with("  up to 100 KiB"),
+        "{:?}",
+        lines
+    );
+    assert!(lines[sizes + 1].ends_with(&"#".repeat(40)), "{:?}", lines);
+    assert_eq!(lines[sizes + 2].trim_end(), "  100 KiB-1 MiB         0");
+}
@@ -0,0 +1,37 @@
+//! Finding corrupted files.
+
+mod common;
+
+use common::{Kind, Tree};
+use libsuccotash::analyze::AnalyzeOptions;
+use libsuccotash::integrity;
+
+#[test]
+fn reports_truncated_files_as_json() {
+    let tree = Tree::new("verify");
+    tree.image("nas/good.png", Kind::Horizontal)
+        .photo("nas/good.jpg", Kind::Vertical, "2021:06:01 12:00:00")
+        .photo("nas/cut.jpg", Kind::Horizontal, "2021:06:01 12:00:01");
+    let jpeg = std::fs::read(tree.path("nas/cut.jpg")).unwrap();
+    tree.file("nas/cut.jpg", &jpeg[..jpeg.len() / 2]);
+
+    let output = tree.path("report.json");
+    let status = async_std::task::block_on(integrity::run(
+        vec![tree.string("nas")],
+        AnalyzeOptions::default(),
+        integrity::Format::Json,
+        Some(output.clone().into()),
+    ));
+    assert_eq!(status.code(), 1);
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(output).unwrap()).unwrap();
+    assert_eq!(report["checked"], 3);
+    let problems = report["problems"].as_array().unwrap();
+    assert_eq!(problems.len(), 1);
+    assert_eq!(
+        common::relative(&tree, problems[0]["path"].as_str().unwrap()),
+        "nas/cut.jpg"
+    );
+    assert_eq!(problems[0]["problem"], "truncated");
+}